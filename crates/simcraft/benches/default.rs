@@ -4,10 +4,14 @@ use std::time::Duration;
 
 use simcraft::dsl::*;
 use simcraft::model::nodes::{Action, TriggerMode};
-use simcraft::simulator::Simulate;
+use simcraft::simulator::{BinaryHeapScheduler, CalendarScheduler, Scheduler, Simulate};
 use simcraft::utils::errors::SimulationError;
 
 fn simulation_benchmark(steps: u64) -> Result<(), SimulationError> {
+    scheduler_benchmark(steps, Box::new(BinaryHeapScheduler::default()))
+}
+
+fn scheduler_benchmark(steps: u64, scheduler: Box<dyn Scheduler>) -> Result<(), SimulationError> {
     let mut sim = simulation! {
         processes {
             source "source1" {
@@ -56,6 +60,7 @@ fn simulation_benchmark(steps: u64) -> Result<(), SimulationError> {
             }
         }
     }?;
+    sim.set_scheduler(scheduler);
 
     sim.step_n(steps as usize)?;
     Ok(())
@@ -66,13 +71,33 @@ fn criterion_benchmark(c: &mut Criterion) {
     group.sample_size(10);
 
     for steps in [1_000, 5_000, 10_000, 50_000, 100_000, 500_000].iter() {
-        group.throughput(Throughput::Elements(*steps as u64));
+        group.throughput(Throughput::Elements(*steps));
         group.bench_with_input(format!("Simulate {} steps", steps), steps, |b, s| {
             b.iter(|| simulation_benchmark(black_box(*s)));
         });
     }
 
     group.finish();
+
+    let mut group = c.benchmark_group("scheduler");
+    group.sample_size(10);
+
+    for steps in [10_000, 100_000].iter() {
+        group.throughput(Throughput::Elements(*steps));
+        group.bench_with_input(format!("Binary heap {} steps", steps), steps, |b, s| {
+            b.iter(|| scheduler_benchmark(black_box(*s), Box::new(BinaryHeapScheduler::default())));
+        });
+        group.bench_with_input(format!("Calendar queue {} steps", steps), steps, |b, s| {
+            b.iter(|| {
+                scheduler_benchmark(
+                    black_box(*s),
+                    Box::new(CalendarScheduler::new(1.0).unwrap()),
+                )
+            });
+        });
+    }
+
+    group.finish();
 }
 
 criterion_group! {
@@ -0,0 +1,57 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::model::ProcessState;
+use crate::simulator::SimulationState;
+
+/// Resources moved from one process to another during a step
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Flow {
+    pub source: String,
+    pub target: String,
+    pub amount: f64,
+}
+
+/// Everything an animation player needs to draw one step: where resources sit afterwards
+/// and what moved to get them there
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Frame {
+    pub step: u64,
+    pub time: f64,
+    pub process_states: HashMap<String, ProcessState>,
+    pub flows: Vec<Flow>,
+}
+
+/// Pairs each state with the flow matrix of the step that produced it.
+///
+/// `states[i]` should be the state after the step whose events gave `flow_matrices[i]`, as
+/// from `step_flow_matrix`. Extra entries on either side are ignored. Flows are sorted by
+/// source then target so frames serialize identically across runs.
+pub fn frames(
+    states: &[SimulationState],
+    flow_matrices: &[HashMap<(String, String), f64>],
+) -> Vec<Frame> {
+    states
+        .iter()
+        .zip(flow_matrices)
+        .map(|(state, matrix)| {
+            let mut flows: Vec<Flow> = matrix
+                .iter()
+                .map(|((source, target), amount)| Flow {
+                    source: source.clone(),
+                    target: target.clone(),
+                    amount: *amount,
+                })
+                .collect();
+            flows.sort_by(|a, b| (&a.source, &a.target).cmp(&(&b.source, &b.target)));
+
+            Frame {
+                step: state.step,
+                time: state.time,
+                process_states: state.process_states.clone(),
+                flows,
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,91 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use super::summary::{held_resources, CONSERVATION_TOLERANCE};
+use crate::model::ProcessState;
+use crate::simulator::{Simulation, StatefulSimulation};
+
+/// How one process's books fail to balance
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProcessLeak {
+    pub process: String,
+    /// Resources unaccounted for: positive when the process lost some, negative when it made some
+    pub leaked: f64,
+    pub inflow: f64,
+    pub outflow: f64,
+    pub held_before: f64,
+    pub held_after: f64,
+}
+
+/// Processes whose accounting does not balance, largest leak first
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LeakReport {
+    pub leaked: f64,
+    pub processes: Vec<ProcessLeak>,
+}
+
+/// Checks every process's holdings against what its connections delivered to and took from it.
+///
+/// A process balances when what it held at the start, plus what it accepted and produced, less
+/// what it handed on and consumed, equals what it holds now. Sources produce exactly what they
+/// hand on and drains consume exactly what they accept, so a process reporting no holdings yet
+/// keeping what it accepts, like a custom node that forgets to count its stock, shows as a leak.
+pub fn locate_leak(sim: &Simulation) -> Option<LeakReport> {
+    let mut inflows: HashMap<&str, f64> = HashMap::new();
+    let mut outflows: HashMap<&str, f64> = HashMap::new();
+    let connections = sim.connections();
+    for conn in &connections {
+        let transferred = sim.connection_stats(&conn.id).transferred;
+        *inflows.entry(&conn.target_id).or_default() += transferred;
+        *outflows.entry(&conn.source_id).or_default() += transferred;
+    }
+
+    let state = sim.get_simulation_state();
+    let mut processes = Vec::new();
+
+    for (id, process_state) in &state.process_states {
+        let inflow = inflows.get(id.as_str()).copied().unwrap_or(0.0);
+        let outflow = outflows.get(id.as_str()).copied().unwrap_or(0.0);
+
+        let (produced, consumed) = match process_state {
+            ProcessState::Source(source) => (source.resources_produced, 0.0),
+            ProcessState::Drain(drain) => (0.0, drain.resources_consumed),
+            ProcessState::Converter(converter) => {
+                (converter.resources_produced, converter.resources_consumed)
+            }
+            ProcessState::Merger(merger) => (merger.resources_produced, merger.resources_consumed),
+            _ => (0.0, 0.0),
+        };
+        let held_before = sim.initial_holding(id);
+        let held_after = held_resources(process_state);
+
+        let leaked = held_before + inflow + produced - outflow - consumed - held_after;
+        let scale = (held_before + inflow + produced).abs().max(1.0);
+        if leaked.abs() > CONSERVATION_TOLERANCE * scale {
+            processes.push(ProcessLeak {
+                process: id.clone(),
+                leaked,
+                inflow,
+                outflow,
+                held_before,
+                held_after,
+            });
+        }
+    }
+
+    if processes.is_empty() {
+        return None;
+    }
+    processes.sort_by(|a, b| {
+        b.leaked
+            .abs()
+            .total_cmp(&a.leaked.abs())
+            .then_with(|| a.process.cmp(&b.process))
+    });
+    Some(LeakReport {
+        leaked: processes.iter().map(|leak| leak.leaked).sum(),
+        processes,
+    })
+}
@@ -0,0 +1,23 @@
+use std::collections::HashMap;
+
+use crate::simulator::event::{Event, EventPayload};
+
+/// Sums the resources accepted between each `(source id, target id)` pair in a batch of events.
+///
+/// Transfers are counted from their `ResourceAccepted` acknowledgements, so partially
+/// accepted and rejected transfers only contribute what actually arrived. Summing the
+/// matrices of every step gives the flow network of a whole run.
+pub fn step_flow_matrix(events: &[Event]) -> HashMap<(String, String), f64> {
+    let mut matrix: HashMap<(String, String), f64> = HashMap::new();
+
+    for event in events {
+        if let EventPayload::ResourceAccepted(amount) = event.payload {
+            // Acknowledgements travel from the receiver back to the sender
+            *matrix
+                .entry((event.target_id.clone(), event.source_id.clone()))
+                .or_default() += amount;
+        }
+    }
+
+    matrix
+}
@@ -0,0 +1,159 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::model::{Processor, SerializableProcess};
+use crate::simulator::Simulation;
+
+/// A process in a `ModelGraph`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GraphNode {
+    pub id: String,
+    pub node_type: &'static str,
+}
+
+/// A connection in a `ModelGraph`, between node indices
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GraphEdge {
+    pub id: String,
+    pub source: usize,
+    pub target: usize,
+    /// The connection's effective flow rate when the graph was taken, never negative
+    pub capacity: f64,
+}
+
+/// The process/connection topology of a model, indexed for graph algorithms.
+///
+/// Nodes are ordered by id and edges by connection id, so indices are stable for a given
+/// model. Connections touching an unknown process are left out.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelGraph {
+    nodes: Vec<GraphNode>,
+    edges: Vec<GraphEdge>,
+    /// Outgoing edge indices per node
+    outgoing: Vec<Vec<usize>>,
+    /// Incoming edge indices per node
+    incoming: Vec<Vec<usize>>,
+    #[serde(skip)]
+    index: HashMap<String, usize>,
+}
+
+impl ModelGraph {
+    pub fn from_simulation(sim: &Simulation) -> Self {
+        let mut nodes: Vec<GraphNode> = sim
+            .processes()
+            .values()
+            .map(|process| GraphNode {
+                id: process.id().to_string(),
+                node_type: process.get_type(),
+            })
+            .collect();
+        nodes.sort_by(|a, b| a.id.cmp(&b.id));
+        let index: HashMap<String, usize> = nodes
+            .iter()
+            .enumerate()
+            .map(|(i, node)| (node.id.clone(), i))
+            .collect();
+
+        let mut connections = sim.connections();
+        connections.sort_by(|a, b| a.id.cmp(&b.id));
+        let edges: Vec<GraphEdge> = connections
+            .into_iter()
+            .filter_map(|conn| {
+                Some(GraphEdge {
+                    id: conn.id.clone(),
+                    source: *index.get(&conn.source_id)?,
+                    target: *index.get(&conn.target_id)?,
+                    capacity: conn.effective_flow_rate(sim.current_time()).max(0.0),
+                })
+            })
+            .collect();
+
+        let mut outgoing = vec![Vec::new(); nodes.len()];
+        let mut incoming = vec![Vec::new(); nodes.len()];
+        for (i, edge) in edges.iter().enumerate() {
+            outgoing[edge.source].push(i);
+            incoming[edge.target].push(i);
+        }
+
+        Self {
+            nodes,
+            edges,
+            outgoing,
+            incoming,
+            index,
+        }
+    }
+
+    pub fn nodes(&self) -> &[GraphNode] {
+        &self.nodes
+    }
+
+    pub fn edges(&self) -> &[GraphEdge] {
+        &self.edges
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn index_of(&self, id: &str) -> Option<usize> {
+        self.index.get(id).copied()
+    }
+
+    /// Edges leaving the node at `node`
+    pub fn outgoing(&self, node: usize) -> impl Iterator<Item = &GraphEdge> {
+        self.outgoing[node].iter().map(|&edge| &self.edges[edge])
+    }
+
+    /// Edges arriving at the node at `node`
+    pub fn incoming(&self, node: usize) -> impl Iterator<Item = &GraphEdge> {
+        self.incoming[node].iter().map(|&edge| &self.edges[edge])
+    }
+
+    /// Ids of the processes `id` sends to, in edge order, each listed once
+    pub fn successors(&self, id: &str) -> Vec<&str> {
+        let Some(node) = self.index_of(id) else {
+            return vec![];
+        };
+        let mut ids: Vec<&str> = Vec::new();
+        for edge in self.outgoing(node) {
+            let target = self.nodes[edge.target].id.as_str();
+            if !ids.contains(&target) {
+                ids.push(target);
+            }
+        }
+        ids
+    }
+
+    /// Ids of the processes sending to `id`, in edge order, each listed once
+    pub fn predecessors(&self, id: &str) -> Vec<&str> {
+        let Some(node) = self.index_of(id) else {
+            return vec![];
+        };
+        let mut ids: Vec<&str> = Vec::new();
+        for edge in self.incoming(node) {
+            let source = self.nodes[edge.source].id.as_str();
+            if !ids.contains(&source) {
+                ids.push(source);
+            }
+        }
+        ids
+    }
+
+    /// Capacity between every ordered pair of nodes, parallel edges added together
+    pub fn capacity_matrix(&self) -> Vec<Vec<f64>> {
+        let mut capacity = vec![vec![0.0; self.len()]; self.len()];
+        for edge in &self.edges {
+            capacity[edge.source][edge.target] += edge.capacity;
+        }
+        capacity
+    }
+}
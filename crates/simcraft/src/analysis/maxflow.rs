@@ -0,0 +1,56 @@
+use std::collections::VecDeque;
+
+use crate::simulator::Simulation;
+
+/// Maximum steady-state flow from `source_id` to `sink_id`, treating each connection's
+/// current effective flow rate as the capacity of an edge between its processes.
+///
+/// Parallel connections add their capacities. Returns 0 when either process is unknown
+/// or the sink cannot be reached.
+pub fn max_flow(sim: &Simulation, source_id: &str, sink_id: &str) -> f64 {
+    let graph = sim.graph();
+    let (Some(source), Some(sink)) = (graph.index_of(source_id), graph.index_of(sink_id)) else {
+        return 0.0;
+    };
+    if source == sink {
+        return 0.0;
+    }
+
+    let mut capacity = graph.capacity_matrix();
+
+    // Edmonds-Karp: augment along shortest residual paths until the sink is cut off
+    let mut total = 0.0;
+    loop {
+        let mut parent = vec![None; graph.len()];
+        parent[source] = Some(source);
+        let mut queue = VecDeque::from([source]);
+        while let Some(node) = queue.pop_front() {
+            for next in 0..graph.len() {
+                if parent[next].is_none() && capacity[node][next] > f64::EPSILON {
+                    parent[next] = Some(node);
+                    queue.push_back(next);
+                }
+            }
+        }
+        if parent[sink].is_none() {
+            return total;
+        }
+
+        let mut bottleneck = f64::INFINITY;
+        let mut node = sink;
+        while node != source {
+            let prev = parent[node].unwrap();
+            bottleneck = bottleneck.min(capacity[prev][node]);
+            node = prev;
+        }
+
+        let mut node = sink;
+        while node != source {
+            let prev = parent[node].unwrap();
+            capacity[prev][node] -= bottleneck;
+            capacity[node][prev] += bottleneck;
+            node = prev;
+        }
+        total += bottleneck;
+    }
+}
@@ -0,0 +1,42 @@
+use crate::analysis::summary::held_resources;
+use crate::simulator::SimulationState;
+
+/// Per-process statistics over a series of states, such as those kept by
+/// `Simulation::recorded_states`
+#[derive(Debug, Clone, Copy)]
+pub struct Metrics<'a> {
+    states: &'a [SimulationState],
+}
+
+impl<'a> Metrics<'a> {
+    pub fn new(states: &'a [SimulationState]) -> Self {
+        Self { states }
+    }
+
+    /// Resources the process held on average over time, integrating between states with the
+    /// trapezoidal rule. States without the process are skipped. A single state gives its
+    /// level, and a process that never appears gives 0.
+    pub fn time_weighted_occupancy(&self, process_id: &str) -> f64 {
+        let samples: Vec<(f64, f64)> = self
+            .states
+            .iter()
+            .filter_map(|state| {
+                let process_state = state.process_states.get(process_id)?;
+                Some((state.time, held_resources(process_state)))
+            })
+            .collect();
+        let (Some(first), Some(last)) = (samples.first(), samples.last()) else {
+            return 0.0;
+        };
+
+        let duration = last.0 - first.0;
+        if duration <= 0.0 {
+            return last.1;
+        }
+        let area: f64 = samples
+            .windows(2)
+            .map(|pair| (pair[1].0 - pair[0].0) * (pair[0].1 + pair[1].1) / 2.0)
+            .sum();
+        area / duration
+    }
+}
@@ -1 +1,13 @@
+pub mod animation;
+pub mod conservation;
+pub mod flow_matrix;
+pub mod graph;
+pub mod maxflow;
+pub mod metrics;
+pub mod summary;
+pub mod timeseries;
 pub mod utils;
+
+pub use graph::ModelGraph;
+pub use metrics::Metrics;
+pub use summary::RunSummary;
@@ -0,0 +1,44 @@
+use serde_json::Value;
+use std::collections::HashMap;
+
+use crate::simulator::SimulationState;
+
+/// Collects every scalar process metric across a series of states.
+///
+/// Keys take the form `"<process id>.<field>"` (e.g. `"pool1.resources"`) and each
+/// value is a `(time, value)` series in the order the states were given.
+pub fn collect(states: &[SimulationState]) -> HashMap<String, Vec<(f64, f64)>> {
+    let mut series: HashMap<String, Vec<(f64, f64)>> = HashMap::new();
+
+    for state in states {
+        for (process_id, process_state) in &state.process_states {
+            for (field, value) in scalar_fields(process_state) {
+                series
+                    .entry(format!("{}.{}", process_id, field))
+                    .or_default()
+                    .push((state.time, value));
+            }
+        }
+    }
+
+    series
+}
+
+/// Extracts the numeric fields of a process state, ignoring its variant tag.
+pub(crate) fn scalar_fields<T: serde::Serialize>(process_state: &T) -> Vec<(String, f64)> {
+    let value = serde_json::to_value(process_state).unwrap_or(Value::Null);
+
+    // Externally tagged enum variants serialize as `{ "Variant": { ... } }`
+    let fields = match value {
+        Value::Object(map) if map.len() == 1 => match map.into_iter().next() {
+            Some((_, Value::Object(fields))) => fields,
+            _ => return vec![],
+        },
+        _ => return vec![],
+    };
+
+    fields
+        .into_iter()
+        .filter_map(|(field, value)| value.as_f64().map(|number| (field, number)))
+        .collect()
+}
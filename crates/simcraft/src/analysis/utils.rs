@@ -1,6 +1,9 @@
 use crate::simulator::event::{Event, EventPayload};
 use std::collections::HashMap;
 
+/// (source id, source port, target id, target port, formatted amount)
+type TransferKey = (String, String, String, String, String);
+
 /// Creates a visual representation of resource transfers from a batch of events
 pub fn visualise_resource_transfers(events: &[Event]) -> String {
     let mut transfers = String::new();
@@ -8,8 +11,7 @@ pub fn visualise_resource_transfers(events: &[Event]) -> String {
     transfers.push_str("Resource Transfers:\n");
 
     // Create a map of resource transfers and their outcomes
-    let mut transfer_outcomes: HashMap<(String, String, String, String, String), (bool, f64)> =
-        HashMap::new();
+    let mut transfer_outcomes: HashMap<TransferKey, (bool, f64)> = HashMap::new();
 
     // Track all resource events to determine outcomes
     for event in events {
@@ -0,0 +1,195 @@
+//! Operations the host bindings offer on a simulation, shared so the desktop, web and
+//! command interfaces behave alike and only differ in how they hand results over.
+
+use thiserror::Error;
+
+use crate::model::{Connection, Process, ProcessState, Processor};
+use crate::simulator::{
+    Event, RunConfiguration, Simulate, Simulation, SimulationState, StatefulSimulation,
+};
+use crate::utils::errors::SimulationError;
+
+/// An operation that failed, with the engine error behind it
+#[derive(Error, Debug, Clone, PartialEq)]
+#[error("Failed to {action}: {description}")]
+pub struct ApiError {
+    /// What was being attempted, e.g. "step simulation"
+    pub action: String,
+    /// Boxed to keep results small
+    pub error: Box<SimulationError>,
+    /// The error described with the type and settings of the processes involved
+    pub description: String,
+}
+
+impl ApiError {
+    fn new(simulation: &Simulation, action: impl Into<String>, error: SimulationError) -> Self {
+        Self {
+            action: action.into(),
+            description: simulation.describe_error(&error),
+            error: Box::new(error),
+        }
+    }
+}
+
+/// Bindings that report errors as plain messages can pass them on with `?`
+impl From<ApiError> for String {
+    fn from(error: ApiError) -> Self {
+        error.to_string()
+    }
+}
+
+pub fn create(
+    processes: Vec<Process>,
+    connections: Vec<Connection>,
+    config: RunConfiguration,
+) -> Result<Simulation, ApiError> {
+    Simulation::with_config(processes, connections, config).map_err(|error| ApiError {
+        action: "create simulation".to_string(),
+        description: error.to_string(),
+        error: Box::new(error),
+    })
+}
+
+/// Builds a simulation from a JSON model, which may leave out connection ids and flow rates.
+/// Every problem with the model is described, not just the first.
+pub fn load(model: &str) -> Result<Simulation, ApiError> {
+    Simulation::load_and_validate(model).map_err(|errors| {
+        let problems: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+        ApiError {
+            action: "load simulation".to_string(),
+            description: problems.join("; "),
+            error: Box::new(
+                errors
+                    .into_iter()
+                    .next()
+                    .unwrap_or_else(|| SimulationError::Other("model is not valid".to_string())),
+            ),
+        }
+    })
+}
+
+pub fn step(simulation: &mut Simulation) -> Result<Vec<Event>, ApiError> {
+    simulation
+        .step()
+        .map_err(|e| ApiError::new(simulation, "step simulation", e))
+}
+
+pub fn step_n(simulation: &mut Simulation, n: usize) -> Result<Vec<Event>, ApiError> {
+    simulation
+        .step_n(n)
+        .map_err(|e| ApiError::new(simulation, format!("step simulation {} times", n), e))
+}
+
+pub fn step_until(simulation: &mut Simulation, until: f64) -> Result<Vec<Event>, ApiError> {
+    simulation
+        .step_until(until)
+        .map_err(|e| ApiError::new(simulation, format!("step simulation until {}", until), e))
+}
+
+pub fn reset(simulation: &mut Simulation) -> Result<(), ApiError> {
+    simulation
+        .reset()
+        .map_err(|e| ApiError::new(simulation, "reset simulation", e))
+}
+
+pub fn state(simulation: &Simulation) -> SimulationState {
+    simulation.get_simulation_state()
+}
+
+pub fn process_state(simulation: &Simulation, process_id: &str) -> Result<ProcessState, ApiError> {
+    simulation
+        .get_process_state(process_id)
+        .map_err(|e| ApiError::new(simulation, "get process state", e))
+}
+
+/// Every process, ordered by id
+pub fn processes(simulation: &Simulation) -> Vec<Process> {
+    let mut processes: Vec<Process> = simulation.processes().values().cloned().collect();
+    processes.sort_by(|a, b| a.id().cmp(b.id()));
+    processes
+}
+
+/// Every connection, in the order they were added
+pub fn connections(simulation: &Simulation) -> Vec<Connection> {
+    simulation.connections()
+}
+
+pub fn add_process(simulation: &mut Simulation, process: Process) -> Result<(), ApiError> {
+    simulation
+        .add_process(process)
+        .map_err(|e| ApiError::new(simulation, "add process", e))
+}
+
+pub fn update_process(
+    simulation: &mut Simulation,
+    process_id: &str,
+    process: Process,
+) -> Result<(), ApiError> {
+    simulation
+        .update_process(process_id, process)
+        .map_err(|e| ApiError::new(simulation, "update process", e))
+}
+
+pub fn remove_process(simulation: &mut Simulation, process_id: &str) -> Result<(), ApiError> {
+    simulation
+        .remove_process(process_id)
+        .map(|_| ())
+        .map_err(|e| ApiError::new(simulation, "remove process", e))
+}
+
+pub fn add_connection(simulation: &mut Simulation, connection: Connection) -> Result<(), ApiError> {
+    simulation
+        .add_connection(connection)
+        .map_err(|e| ApiError::new(simulation, "add connection", e))
+}
+
+pub fn update_connection(
+    simulation: &mut Simulation,
+    connection_id: &str,
+    connection: Connection,
+) -> Result<(), ApiError> {
+    simulation
+        .update_connection(connection_id, connection)
+        .map_err(|e| ApiError::new(simulation, "update connection", e))
+}
+
+pub fn remove_connection(simulation: &mut Simulation, connection_id: &str) -> Result<(), ApiError> {
+    simulation
+        .remove_connection(connection_id)
+        .map_err(|e| ApiError::new(simulation, "remove connection", e))
+}
+
+pub fn set_connection_flow_rate(
+    simulation: &mut Simulation,
+    connection_id: &str,
+    flow_rate: Option<f64>,
+) -> Result<(), ApiError> {
+    simulation
+        .set_connection_flow_rate(connection_id, flow_rate)
+        .map_err(|e| ApiError::new(simulation, "set connection flow rate", e))
+}
+
+/// Keeps engine log messages at `min_level` ("ERROR", "WARN", "INFO", "DEBUG" or "TRACE")
+/// and above
+pub fn enable_log_capture(simulation: &mut Simulation, min_level: &str) -> Result<(), ApiError> {
+    let level: tracing::Level = min_level.parse().map_err(|e| {
+        let error = SimulationError::Other(format!("Invalid log level '{}': {}", min_level, e));
+        ApiError::new(simulation, "capture logs", error)
+    })?;
+    simulation.enable_log_capture(level);
+    Ok(())
+}
+
+pub fn export(simulation: &Simulation) -> Result<Vec<u8>, ApiError> {
+    simulation
+        .snapshot()
+        .map_err(|e| ApiError::new(simulation, "export simulation", e))
+}
+
+pub fn import(blob: &[u8]) -> Result<Simulation, ApiError> {
+    Simulation::restore(blob).map_err(|error| ApiError {
+        action: "import simulation".to_string(),
+        description: error.to_string(),
+        error: Box::new(error),
+    })
+}
@@ -36,12 +36,17 @@ macro_rules! simulation {
     ) => {{
         let mut processes = vec![];
 
-        // Add a stepper process by default
-        processes.push($crate::dsl::create_stepper());
-
         // Add user-defined processes
         processes_internal!(processes, $($process_def)*);
 
+        // Add a stepper process by default, unless one was declared
+        if !processes
+            .iter()
+            .any(|process| process.downcast_ref::<$crate::model::nodes::Stepper>().is_some())
+        {
+            processes.insert(0, $crate::dsl::create_stepper());
+        }
+
         // Create connections
         let connections = connections_internal!($($connection_def)*);
 
@@ -76,6 +81,9 @@ macro_rules! processes_internal {
         $(
             action: $action:expr,
         )?
+        $(
+            output_ports: $output_ports:expr,
+        )?
         $(,)?
     } $($rest:tt)*) => {
         {
@@ -87,6 +95,9 @@ macro_rules! processes_internal {
             $(
                 let builder = builder.action($action);
             )*
+            $(
+                let builder = builder.output_ports($output_ports);
+            )*
             $processes.push($crate::model::process::Process::new(Box::new(builder.build().unwrap())));
         }
         processes_internal!($processes, $($rest)*);
@@ -113,6 +124,9 @@ macro_rules! processes_internal {
         $(
             capacity: $capacity:expr,
         )?
+        $(
+            capacity_schedule: $capacity_schedule:expr,
+        )?
         $(
             resources: $resources:expr,
         )?
@@ -130,6 +144,9 @@ macro_rules! processes_internal {
             $(
                 let builder = builder.capacity($capacity);
             )*
+            $(
+                let builder = builder.capacity_schedule($capacity_schedule);
+            )*
             $(
                 let builder = builder.state($crate::model::process_state::PoolState{resources: $resources, pending_outgoing_resources: 0.0});
             )*
@@ -193,6 +210,9 @@ macro_rules! processes_internal {
         $(
             release_amount: $release_amount:expr,
         )?
+        $(
+            release_partial: $release_partial:expr,
+        )?
         $(,)?
     } $($rest:tt)*) => {
         {
@@ -207,6 +227,9 @@ macro_rules! processes_internal {
             $(
                 let builder = builder.release_amount($release_amount);
             )*
+            $(
+                let builder = builder.release_partial($release_partial);
+            )*
             $processes.push($crate::model::process::Process::new(Box::new(builder.build().unwrap())));
         }
         processes_internal!($processes, $($rest)*);
@@ -224,12 +247,25 @@ macro_rules! processes_internal {
 
     // Stepper process with attributes
     ($processes:ident, stepper $id:tt {
-        $($attr_name:ident: $attr_value:expr),* $(,)?
+        $(
+            trigger_mode: $trigger_mode:expr,
+        )?
+        $(
+            dt: $dt:expr
+        )?
+        $(,)?
     } $($rest:tt)*) => {
         {
             let mut builder = $crate::model::nodes::Stepper::builder();
-            builder.id($id);
-            $processes.push($crate::model::process::Process::new(Box::new(builder.build().unwrap())));
+            let builder = builder.id($id);
+            $(
+                let builder = builder.trigger_mode($trigger_mode);
+            )*
+            let mut stepper = builder.build().unwrap();
+            $(
+                stepper.set_dt($dt).unwrap();
+            )*
+            $processes.push($crate::model::process::Process::new(Box::new(stepper)));
         }
         processes_internal!($processes, $($rest)*);
     };
@@ -339,7 +375,7 @@ macro_rules! run_simulation {
         let mut all_events = Vec::new();
 
         // Run simulation step by step until target time
-        while sim.get_simulation_state().time < $time + f64.EPSILON {
+        while sim.get_simulation_state().time < $time - f64::EPSILON {
             let events = sim.step()?;
             if events.is_empty() {
                 break;
@@ -4,4 +4,6 @@ pub mod simulator;
 pub mod utils;
 pub mod prelude;
 pub mod analysis;
+pub mod api;
+pub mod rpc;
 pub use dsl::*;
@@ -1,17 +1,83 @@
+use derive_builder::Builder;
 use serde::{Deserialize, Serialize};
 
-#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+use crate::dsl::parse_endpoint;
+
+/// Rate used by nodes for connections without a configured `flow_rate`
+pub const DEFAULT_FLOW_RATE: f64 = 1.0;
+
+/// Output port every process has for links to observers such as a probe. Nothing is pushed
+/// through it, so an observer never takes a share of what its source sends on.
+pub const OBSERVE_PORT: &str = "observe";
+
+/// When a connection lets resources through, judged on what its source holds at the moment
+/// it pushes. A source's supply is unlimited, so it counts as above any threshold.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum FlowCondition {
+    #[default]
+    Always,
+    /// Only while the source holds more than the threshold
+    SourceAbove(f64),
+    /// Only while the source holds less than the threshold
+    SourceBelow(f64),
+}
+
+impl FlowCondition {
+    /// Whether a source holding `level` may push through the connection
+    pub fn allows(&self, level: f64) -> bool {
+        match self {
+            FlowCondition::Always => true,
+            FlowCondition::SourceAbove(threshold) => level > *threshold,
+            FlowCondition::SourceBelow(threshold) => level < *threshold,
+        }
+    }
+
+    fn is_always(&self) -> bool {
+        *self == FlowCondition::Always
+    }
+}
+
+#[derive(Builder, Default, Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
+#[builder(default)]
 pub struct Connection {
+    #[serde(default)]
+    #[builder(setter(into))]
     pub id: String,
     #[serde(rename = "sourceID")]
+    #[builder(setter(into))]
     pub source_id: String,
+    #[builder(setter(into, strip_option))]
     pub source_port: Option<String>,
     #[serde(rename = "targetID")]
+    #[builder(setter(into))]
     pub target_id: String,
+    #[builder(setter(into, strip_option))]
     pub target_port: Option<String>,
+    #[builder(setter(strip_option))]
     pub flow_rate: Option<f64>,
+    /// Piecewise-constant rate changes as `(start time, rate)` pairs, overriding `flow_rate`
+    /// from each start time onward
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[builder(setter(custom))]
+    pub schedule: Vec<(f64, f64)>,
+    /// Scales whichever rate currently applies
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[builder(setter(strip_option))]
+    pub multiplier: Option<f64>,
+    /// Indivisible quantum resources cross in: only whole multiples are transferred
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[builder(setter(strip_option))]
+    pub packet_size: Option<f64>,
+    /// Pushes only go through while this holds
+    #[serde(default, skip_serializing_if = "FlowCondition::is_always")]
+    pub condition: FlowCondition,
+    /// Most the connection may carry over the whole run; transfers beyond it are rejected
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[builder(setter(strip_option))]
+    pub total_capacity: Option<f64>,
     #[serde(default)]
+    #[builder(setter(skip))]
     pub sequence_number: u64,
 }
 
@@ -31,10 +97,67 @@ impl Connection {
             target_id,
             target_port,
             flow_rate,
+            schedule: vec![],
+            multiplier: None,
+            packet_size: None,
+            condition: FlowCondition::Always,
+            total_capacity: None,
             sequence_number: 0,
         }
     }
 
+    pub fn builder() -> ConnectionBuilder {
+        ConnectionBuilder::default()
+    }
+
+    pub fn with_schedule(mut self, schedule: Vec<(f64, f64)>) -> Self {
+        self.schedule = schedule;
+        self.schedule.sort_by(|a, b| a.0.total_cmp(&b.0));
+        self
+    }
+
+    pub fn with_multiplier(mut self, multiplier: f64) -> Self {
+        self.multiplier = Some(multiplier);
+        self
+    }
+
+    pub fn with_condition(mut self, condition: FlowCondition) -> Self {
+        self.condition = condition;
+        self
+    }
+
+    pub fn with_packet_size(mut self, packet_size: f64) -> Self {
+        self.packet_size = Some(packet_size);
+        self
+    }
+
+    /// Largest amount up to `amount` made of whole packets, or `amount` itself when the
+    /// connection has no packet size
+    pub fn whole_packets(&self, amount: f64) -> f64 {
+        match self.packet_size {
+            Some(size) if size > 0.0 => ((amount / size) + 1e-9).floor() * size,
+            _ => amount,
+        }
+    }
+
+    /// Configured rate at `time` after applying the schedule and multiplier,
+    /// or `None` when no rate has been set
+    pub fn flow_rate_at(&self, time: f64) -> Option<f64> {
+        self.schedule
+            .iter()
+            .rev()
+            .find(|(start, _)| *start <= time)
+            .map(|(_, rate)| *rate)
+            .or(self.flow_rate)
+            .map(|rate| rate * self.multiplier.unwrap_or(1.0))
+    }
+
+    /// Rate nodes move resources at, at `time`, falling back to the default of 1.0 when unset
+    pub fn effective_flow_rate(&self, time: f64) -> f64 {
+        self.flow_rate_at(time)
+            .unwrap_or(DEFAULT_FLOW_RATE * self.multiplier.unwrap_or(1.0))
+    }
+
     pub fn source_id(&self) -> &str {
         &self.source_id
     }
@@ -51,3 +174,27 @@ impl Connection {
         self.target_port.as_deref()
     }
 }
+
+impl ConnectionBuilder {
+    /// Sets the source from an endpoint such as `"source1.out"`, where the port is optional
+    pub fn source(&mut self, endpoint: &str) -> &mut Self {
+        let (id, port) = parse_endpoint(endpoint);
+        self.source_id = Some(id.to_string());
+        self.source_port = Some(port.map(|p| p.to_string()));
+        self
+    }
+
+    /// Sets the target from an endpoint such as `"pool1.in"`, where the port is optional
+    pub fn target(&mut self, endpoint: &str) -> &mut Self {
+        let (id, port) = parse_endpoint(endpoint);
+        self.target_id = Some(id.to_string());
+        self.target_port = Some(port.map(|p| p.to_string()));
+        self
+    }
+
+    pub fn schedule(&mut self, mut schedule: Vec<(f64, f64)>) -> &mut Self {
+        schedule.sort_by(|a, b| a.0.total_cmp(&b.0));
+        self.schedule = Some(schedule);
+        self
+    }
+}
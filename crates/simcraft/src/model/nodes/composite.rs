@@ -0,0 +1,381 @@
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::warn;
+
+use crate::{
+    model::{
+        process_state::{CompositeState, ProcessState},
+        Connection, Process, ProcessContext, Processor, SerializableProcess,
+    },
+    simulator::{
+        event::{Event, EventPayload},
+        Simulate, Simulation,
+    },
+    utils::errors::SimulationError,
+};
+
+/// Id of the internal process that feeds resources received on the composite's `in` port
+const INPUT_ID: &str = "__composite_input";
+/// Id of the internal process that collects resources leaving through the composite's `out` port
+const OUTPUT_ID: &str = "__composite_output";
+/// Query sent to the output boundary to take the resources it has collected
+const COLLECT: &str = "collect";
+
+/// A subgraph of processes exposed as a single process with `in` and `out` ports.
+///
+/// Resources received on `in` are fed into a bound internal port, and resources sent to
+/// the bound internal output are pushed out through `out`. The inner simulation is advanced
+/// to the outer time whenever the composite handles events, and outer `Step`s are broadcast
+/// inside, so the inner model should not contain its own Stepper.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, SerializableProcess)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Composite {
+    id: String,
+    state: CompositeState,
+    inner: Simulation,
+}
+
+impl Composite {
+    /// Wraps `inner`, binding the composite's `in` port to `input` and its `out` port to
+    /// `output`, each given as a `(process id, port)` pair.
+    pub fn new(
+        id: &str,
+        mut inner: Simulation,
+        input: (&str, &str),
+        output: (&str, &str),
+    ) -> Result<Composite, SimulationError> {
+        inner.add_process(Process::new(Box::new(CompositeInput {
+            id: INPUT_ID.to_string(),
+            ..Default::default()
+        })))?;
+        inner.add_process(Process::new(Box::new(CompositeOutput {
+            id: OUTPUT_ID.to_string(),
+            ..Default::default()
+        })))?;
+
+        inner.add_connection(Connection::new(
+            format!("{}.in", id),
+            INPUT_ID.to_string(),
+            Some("out".to_string()),
+            input.0.to_string(),
+            Some(input.1.to_string()),
+            None,
+        ))?;
+        inner.add_connection(Connection::new(
+            format!("{}.out", id),
+            output.0.to_string(),
+            Some(output.1.to_string()),
+            OUTPUT_ID.to_string(),
+            Some("in".to_string()),
+            None,
+        ))?;
+
+        Ok(Composite {
+            id: id.to_string(),
+            state: CompositeState::default(),
+            inner,
+        })
+    }
+
+    /// The wrapped simulation, e.g. to inspect the state of internal processes
+    pub fn inner(&self) -> &Simulation {
+        &self.inner
+    }
+
+    /// Processes every inner event scheduled up to and including `time`
+    fn advance_inner(&mut self, time: f64) -> Result<(), SimulationError> {
+        while let Some(next_time) = self.inner.next_event_time() {
+            if next_time > time + f64::EPSILON {
+                break;
+            }
+            self.inner.step()?;
+        }
+        Ok(())
+    }
+
+    /// Takes whatever the output boundary has collected into the outgoing buffer
+    fn collect_outputs(&mut self, time: f64) -> Result<(), SimulationError> {
+        let query = Event::new(
+            "simulation",
+            OUTPUT_ID,
+            time,
+            EventPayload::Custom(COLLECT.into()),
+        );
+        for event in self.inner.process_event(&query)? {
+            if let EventPayload::Resource(amount) = event.payload {
+                self.state.outgoing_resources += amount;
+            }
+        }
+        Ok(())
+    }
+
+    fn push_outputs(&mut self, context: &ProcessContext) -> Vec<Event> {
+        let mut new_events = Vec::new();
+
+        for conn in context.outputs_for_port(Some("out")) {
+            let available = self.state.available_resources();
+            let amount = conn
+                .flow_rate_at(context.current_time())
+                .map_or(available, |rate| rate.min(available));
+            if amount <= 0.0 {
+                break;
+            }
+
+            self.state.pending_outgoing_resources += amount;
+            new_events.push(
+                Event::new(
+                    self.id(),
+                    &conn.target_id,
+                    context.current_time(),
+                    EventPayload::Resource(amount),
+                )
+                .with_source_port("out")
+                .with_target_port(conn.target_port.clone().unwrap_or("in".to_string())),
+            );
+        }
+
+        new_events
+    }
+}
+
+impl Processor for Composite {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn on_event(
+        &mut self,
+        event: &Event,
+        context: &ProcessContext,
+    ) -> Result<Vec<Event>, SimulationError> {
+        self.on_events(std::slice::from_ref(event), context)
+    }
+
+    fn on_events(
+        &mut self,
+        events: &[Event],
+        context: &ProcessContext,
+    ) -> Result<Vec<Event>, SimulationError> {
+        let time = context.current_time();
+        let mut new_events = Vec::new();
+
+        for event in events {
+            match &event.payload {
+                EventPayload::SimulationStart | EventPayload::SimulationEnd => {}
+                EventPayload::Step | EventPayload::Trigger => {
+                    self.inner.schedule_event(Event::new(
+                        "simulation",
+                        "broadcast",
+                        time,
+                        EventPayload::Step,
+                    ))?;
+                }
+                EventPayload::Resource(amount) => {
+                    // Incoming resources are buffered by the input boundary, so always accepted
+                    self.state.resources_received += amount;
+                    self.inner.schedule_event(Event::new(
+                        "simulation",
+                        INPUT_ID,
+                        time,
+                        EventPayload::Resource(*amount),
+                    ))?;
+                    new_events.push(Event::new(
+                        self.id(),
+                        &event.source_id,
+                        time,
+                        EventPayload::ResourceAccepted(*amount),
+                    ));
+                }
+                EventPayload::ResourceAccepted(amount) => {
+                    self.state.pending_outgoing_resources -= amount;
+                    self.state.outgoing_resources -= amount;
+                    self.state.resources_released += amount;
+                }
+                EventPayload::ResourceRejected(amount) => {
+                    self.state.pending_outgoing_resources -= amount;
+                }
+                // Incoming resources are always accepted, so there is always room
+                EventPayload::CapacityQuery => new_events.push(Event::new(
+                    self.id(),
+                    &event.source_id,
+                    time,
+                    EventPayload::CapacityResponse(f64::MAX),
+                )),
+                EventPayload::PullRequest | EventPayload::PullAllRequest => {}
+                event_payload => {
+                    warn!("Unhandled event payload: {:?}", event_payload);
+                }
+            }
+        }
+
+        self.advance_inner(time)?;
+        self.collect_outputs(time)?;
+        new_events.extend(self.push_outputs(context));
+
+        Ok(new_events)
+    }
+
+    fn get_state(&self) -> ProcessState {
+        ProcessState::Composite(self.state.clone())
+    }
+
+    fn get_input_ports(&self) -> &[&'static str] {
+        &["in"]
+    }
+
+    fn get_output_ports(&self) -> &[&'static str] {
+        &["out"]
+    }
+
+    fn reset(&mut self) {
+        self.state = CompositeState::default();
+        // Resetting a simulation can't fail; the result type is kept for API symmetry
+        let _ = crate::simulator::StatefulSimulation::reset(&mut self.inner);
+    }
+}
+
+/// Internal boundary process buffering resources that enter a [`Composite`]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, SerializableProcess)]
+#[serde(default, rename_all = "camelCase")]
+pub struct CompositeInput {
+    id: String,
+    buffered: f64,
+    pending_outgoing_resources: f64,
+}
+
+impl CompositeInput {
+    fn push(&mut self, context: &ProcessContext) -> Vec<Event> {
+        let mut new_events = Vec::new();
+
+        for conn in context.outputs_for_port(Some("out")) {
+            let available = (self.buffered - self.pending_outgoing_resources).max(0.0);
+            if available <= 0.0 {
+                break;
+            }
+
+            self.pending_outgoing_resources += available;
+            new_events.push(
+                Event::new(
+                    &self.id,
+                    &conn.target_id,
+                    context.current_time(),
+                    EventPayload::Resource(available),
+                )
+                .with_source_port("out")
+                .with_target_port(conn.target_port.clone().unwrap_or("in".to_string())),
+            );
+        }
+
+        new_events
+    }
+}
+
+impl Processor for CompositeInput {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn on_event(
+        &mut self,
+        event: &Event,
+        context: &ProcessContext,
+    ) -> Result<Vec<Event>, SimulationError> {
+        let new_events = match &event.payload {
+            EventPayload::Resource(amount) => {
+                self.buffered += amount;
+                self.push(context)
+            }
+            EventPayload::Step => self.push(context),
+            EventPayload::ResourceAccepted(amount) => {
+                self.buffered -= amount;
+                self.pending_outgoing_resources -= amount;
+                vec![]
+            }
+            EventPayload::ResourceRejected(amount) => {
+                self.pending_outgoing_resources -= amount;
+                vec![]
+            }
+            _ => vec![],
+        };
+
+        Ok(new_events)
+    }
+
+    fn get_state(&self) -> ProcessState {
+        ProcessState::Custom(json!({ "buffered": self.buffered }))
+    }
+
+    fn get_input_ports(&self) -> &[&'static str] {
+        &[]
+    }
+
+    fn get_output_ports(&self) -> &[&'static str] {
+        &["out"]
+    }
+
+    fn reset(&mut self) {
+        self.buffered = 0.0;
+        self.pending_outgoing_resources = 0.0;
+    }
+}
+
+/// Internal boundary process collecting resources that leave a [`Composite`]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, SerializableProcess)]
+#[serde(default, rename_all = "camelCase")]
+pub struct CompositeOutput {
+    id: String,
+    collected: f64,
+}
+
+impl Processor for CompositeOutput {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn on_event(
+        &mut self,
+        event: &Event,
+        context: &ProcessContext,
+    ) -> Result<Vec<Event>, SimulationError> {
+        let new_events = match &event.payload {
+            EventPayload::Resource(amount) => {
+                self.collected += amount;
+                vec![Event::new(
+                    &self.id,
+                    &event.source_id,
+                    context.current_time(),
+                    EventPayload::ResourceAccepted(*amount),
+                )]
+            }
+            // Answered directly to the owning composite rather than scheduled
+            EventPayload::Custom(query) if query == COLLECT => {
+                let collected = std::mem::take(&mut self.collected);
+                vec![Event::new(
+                    &self.id,
+                    &event.source_id,
+                    context.current_time(),
+                    EventPayload::Resource(collected),
+                )]
+            }
+            _ => vec![],
+        };
+
+        Ok(new_events)
+    }
+
+    fn get_state(&self) -> ProcessState {
+        ProcessState::Custom(json!({ "collected": self.collected }))
+    }
+
+    fn get_input_ports(&self) -> &[&'static str] {
+        &["in"]
+    }
+
+    fn get_output_ports(&self) -> &[&'static str] {
+        &[]
+    }
+
+    fn reset(&mut self) {
+        self.collected = 0.0;
+    }
+}
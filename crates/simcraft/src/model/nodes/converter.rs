@@ -0,0 +1,374 @@
+use std::collections::BTreeMap;
+
+use derive_builder::Builder;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use super::{process_events_with_priority, TriggerMode};
+use crate::{
+    model::{
+        process_state::{ConverterState, ProcessState},
+        ProcessContext, Processor, SerializableProcess,
+    },
+    simulator::event::{Event, EventPayload},
+    utils::{errors::SimulationError, ports::intern_port},
+};
+
+/// Amounts a converter takes from each input port to make one batch, and the amounts one batch
+/// yields on each output port. Serialized as `{"inputs": {port: amount}, "outputs": {...}}`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(from = "RecipeAmounts", into = "RecipeAmounts")]
+pub struct Recipe {
+    inputs: Vec<(&'static str, f64)>,
+    outputs: Vec<(&'static str, f64)>,
+    input_ports: Vec<&'static str>,
+    output_ports: Vec<&'static str>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RecipeAmounts {
+    #[serde(default)]
+    inputs: BTreeMap<String, f64>,
+    #[serde(default)]
+    outputs: BTreeMap<String, f64>,
+}
+
+impl Recipe {
+    pub fn new(inputs: &[(&str, f64)], outputs: &[(&str, f64)]) -> Self {
+        let intern = |amounts: &[(&str, f64)]| -> Vec<(&'static str, f64)> {
+            amounts
+                .iter()
+                .map(|(port, amount)| (intern_port(port), *amount))
+                .collect()
+        };
+        let inputs = intern(inputs);
+        let outputs = intern(outputs);
+        Self {
+            input_ports: inputs.iter().map(|(port, _)| *port).collect(),
+            output_ports: outputs.iter().map(|(port, _)| *port).collect(),
+            inputs,
+            outputs,
+        }
+    }
+
+    pub fn inputs(&self) -> &[(&'static str, f64)] {
+        &self.inputs
+    }
+
+    pub fn outputs(&self) -> &[(&'static str, f64)] {
+        &self.outputs
+    }
+
+    /// Whole batches `buffered` inputs are enough for
+    fn batches(&self, buffered: &BTreeMap<String, f64>) -> u64 {
+        self.inputs
+            .iter()
+            .filter(|(_, amount)| *amount > 0.0)
+            .map(|(port, amount)| {
+                let held = buffered.get(*port).copied().unwrap_or(0.0);
+                ((held / amount) + 1e-9).floor() as u64
+            })
+            .min()
+            .unwrap_or(0)
+    }
+}
+
+impl From<RecipeAmounts> for Recipe {
+    fn from(amounts: RecipeAmounts) -> Self {
+        let inputs: Vec<(&str, f64)> = amounts
+            .inputs
+            .iter()
+            .map(|(port, amount)| (port.as_str(), *amount))
+            .collect();
+        let outputs: Vec<(&str, f64)> = amounts
+            .outputs
+            .iter()
+            .map(|(port, amount)| (port.as_str(), *amount))
+            .collect();
+        Recipe::new(&inputs, &outputs)
+    }
+}
+
+impl From<Recipe> for RecipeAmounts {
+    fn from(recipe: Recipe) -> Self {
+        let amounts = |entries: Vec<(&'static str, f64)>| {
+            entries
+                .into_iter()
+                .map(|(port, amount)| (port.to_string(), amount))
+                .collect()
+        };
+        Self {
+            inputs: amounts(recipe.inputs),
+            outputs: amounts(recipe.outputs),
+        }
+    }
+}
+
+/// Turns resources arriving on its input ports into resources on its output ports, a batch at
+/// a time, following its `Recipe`.
+///
+/// Arrivals are buffered per port and converted as soon as every input of the recipe is covered,
+/// as many batches at once as the buffers allow; whatever is left over stays buffered for the
+/// next batch. The trigger mode decides when the converter pulls from its inputs, so a passive
+/// converter only converts what is pushed into it. Each batch's output on a port is split evenly
+/// across that port's connections.
+#[derive(Builder, Debug, Clone, Serialize, Deserialize, SerializableProcess)]
+#[serde(default, rename_all = "camelCase")]
+#[builder(default)]
+pub struct Converter {
+    #[builder(setter(into))]
+    id: String,
+    state: ConverterState,
+    /// State the first run started from, restored on reset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[builder(setter(skip))]
+    initial_state: Option<ConverterState>,
+    trigger_mode: TriggerMode,
+    recipe: Recipe,
+    /// Order among processes acting at the same time, highest first
+    priority: i64,
+}
+
+impl Default for Converter {
+    fn default() -> Self {
+        Self {
+            id: String::new(),
+            state: ConverterState::default(),
+            initial_state: None,
+            trigger_mode: TriggerMode::Automatic,
+            recipe: Recipe::default(),
+            priority: 0,
+        }
+    }
+}
+
+impl Converter {
+    pub fn new(id: &str, recipe: Recipe) -> Converter {
+        Converter::builder()
+            .id(id.to_string())
+            .recipe(recipe)
+            .build()
+            .unwrap()
+    }
+
+    pub fn builder() -> ConverterBuilder {
+        ConverterBuilder::default()
+    }
+
+    pub fn recipe(&self) -> &Recipe {
+        &self.recipe
+    }
+
+    fn handle_pull(&mut self, context: &ProcessContext) -> Vec<Event> {
+        self.recipe
+            .input_ports
+            .iter()
+            .flat_map(|port| context.inputs_for_port(Some(port)))
+            .map(|conn| {
+                Event::new(
+                    self.id(),
+                    &conn.source_id,
+                    context.current_time(),
+                    EventPayload::PullRequest,
+                )
+            })
+            .collect()
+    }
+
+    fn handle_resource(
+        &mut self,
+        event: &Event,
+        context: &ProcessContext,
+        amount: f64,
+    ) -> Result<Vec<Event>, SimulationError> {
+        assert!(amount >= 0.0);
+
+        let port = event.target_port.as_deref().unwrap_or("in");
+        if !self.recipe.input_ports.contains(&port) {
+            warn!(
+                "Converter '{}' has no recipe input '{}' - rejecting resources",
+                self.id, port
+            );
+            return Ok(vec![Event::new(
+                self.id(),
+                &event.source_id,
+                context.current_time(),
+                EventPayload::ResourceRejected(amount),
+            )]);
+        }
+
+        *self.state.buffered.entry(port.to_string()).or_default() += amount;
+        let mut new_events = vec![Event::new(
+            self.id(),
+            &event.source_id,
+            context.current_time(),
+            EventPayload::ResourceAccepted(amount),
+        )];
+        new_events.extend(self.convert(context));
+
+        Ok(new_events)
+    }
+
+    /// Converts every whole batch the buffers allow, sending the products on
+    fn convert(&mut self, context: &ProcessContext) -> Vec<Event> {
+        let batches = self.recipe.batches(&self.state.buffered);
+        if batches == 0 {
+            return vec![];
+        }
+
+        for (port, amount) in &self.recipe.inputs {
+            let used = amount * batches as f64;
+            if let Some(held) = self.state.buffered.get_mut(*port) {
+                *held = (*held - used).max(0.0);
+            }
+            self.state.resources_consumed += used;
+        }
+        self.state.batches += batches;
+
+        let mut new_events = Vec::new();
+        for (port, amount) in &self.recipe.outputs {
+            let connections: Vec<_> = context.outputs_for_port(Some(port)).collect();
+            if connections.is_empty() {
+                warn!(
+                    "Converter '{}' has no connection on output '{}' - discarding its products",
+                    self.id, port
+                );
+                continue;
+            }
+            let share = amount * batches as f64 / connections.len() as f64;
+            for conn in connections {
+                new_events.push(
+                    Event::new(
+                        self.id(),
+                        &conn.target_id,
+                        context.current_time(),
+                        EventPayload::Resource(share),
+                    )
+                    .with_source_port(*port)
+                    .with_target_port(conn.target_port.clone().unwrap_or("in".to_string())),
+                );
+            }
+        }
+
+        new_events
+    }
+}
+
+impl Processor for Converter {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn priority(&self) -> i64 {
+        self.priority
+    }
+
+    fn describe_settings(&self) -> Option<String> {
+        let describe = |amounts: &[(&str, f64)]| {
+            amounts
+                .iter()
+                .map(|(port, amount)| format!("{} {}", amount, port))
+                .collect::<Vec<_>>()
+                .join(" + ")
+        };
+        Some(format!(
+            "{} -> {}",
+            describe(self.recipe.inputs()),
+            describe(self.recipe.outputs())
+        ))
+    }
+
+    fn on_events(
+        &mut self,
+        events: &[Event],
+        context: &ProcessContext,
+    ) -> Result<Vec<Event>, SimulationError> {
+        process_events_with_priority(events, context, |event, ctx| self.on_event(event, ctx))
+    }
+
+    fn on_event(
+        &mut self,
+        event: &Event,
+        context: &ProcessContext,
+    ) -> Result<Vec<Event>, SimulationError> {
+        let new_events: Vec<Event> = match &event.payload {
+            EventPayload::SimulationStart => {
+                self.initial_state.get_or_insert_with(|| self.state.clone());
+                vec![]
+            }
+            EventPayload::SimulationEnd => vec![],
+            EventPayload::Step => match self.trigger_mode {
+                TriggerMode::Passive => vec![],
+                TriggerMode::Interactive => unimplemented!(),
+                TriggerMode::Automatic => self.handle_pull(context),
+                TriggerMode::Enabling => {
+                    if context.current_step() == 1 {
+                        self.handle_pull(context)
+                    } else {
+                        vec![]
+                    }
+                }
+            },
+            EventPayload::Trigger => self.handle_pull(context),
+            EventPayload::Resource(amount) => self.handle_resource(event, context, *amount)?,
+            EventPayload::ResourceAccepted(amount) => {
+                self.state.resources_produced += amount;
+                vec![]
+            }
+            // Products nobody takes are lost, as with a source
+            EventPayload::ResourceRejected(_) => vec![],
+            // Recipe inputs buffer whatever they are sent
+            EventPayload::CapacityQuery => vec![Event::new(
+                self.id(),
+                &event.source_id,
+                context.current_time(),
+                EventPayload::CapacityResponse(
+                    if self
+                        .recipe
+                        .input_ports
+                        .contains(&event.target_port.as_deref().unwrap_or("in"))
+                    {
+                        f64::MAX
+                    } else {
+                        0.0
+                    },
+                ),
+            )],
+            event_payload => {
+                warn!("Unhandled event payload: {:?}", event_payload);
+                vec![]
+            }
+        };
+
+        assert!(self.state.resources_consumed >= 0.0);
+        assert!(self.state.resources_produced >= 0.0);
+
+        Ok(new_events)
+    }
+
+    fn get_state(&self) -> ProcessState {
+        ProcessState::Converter(self.state.clone())
+    }
+
+    fn get_input_ports(&self) -> &[&'static str] {
+        &self.recipe.input_ports
+    }
+
+    fn get_output_ports(&self) -> &[&'static str] {
+        &self.recipe.output_ports
+    }
+
+    fn get_required_input_ports(&self) -> &[&'static str] {
+        &self.recipe.input_ports
+    }
+
+    fn get_required_output_ports(&self) -> &[&'static str] {
+        &self.recipe.output_ports
+    }
+
+    fn reset(&mut self) {
+        if let Some(initial_state) = &self.initial_state {
+            self.state = initial_state.clone();
+        }
+    }
+}
@@ -9,7 +9,7 @@ use crate::{
         ProcessContext, Processor, SerializableProcess,
     },
     simulator::event::{Event, EventPayload},
-    utils::errors::SimulationError,
+    utils::{errors::SimulationError, rng::Rng},
 };
 
 #[derive(Builder, Debug, Clone, Serialize, Deserialize, SerializableProcess)]
@@ -19,11 +19,45 @@ pub struct Delay {
     #[builder(setter(into))]
     id: String,
     state: DelayState,
+    /// State the first run started from, restored on reset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[builder(setter(skip))]
+    initial_state: Option<DelayState>,
     trigger_mode: TriggerMode,
     action: DelayAction,
     release_amount: f64, // Only used in Queue mode
+    /// In Queue mode, let out a final chunk smaller than `release_amount` instead of holding it
+    release_partial: bool,
+    /// Order among processes acting at the same time, highest first
+    priority: i64,
+    /// In Delay mode, spread each transfer's delay uniformly within `delay ± jitter`,
+    /// so resources may overtake one another
+    jitter: f64,
+    /// Pins the jitter to a seed of its own, independent of the simulation seed
+    #[builder(setter(strip_option))]
+    seed: Option<u64>,
     #[builder(setter(skip))]
     next_release_time: f64, // When the next release is allowed
+    /// In Queue mode, when the resource now at the head of the queue arrived
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[builder(setter(skip))]
+    queued_since: Option<f64>,
+    /// In Queue mode, when the last chunk was released
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[builder(setter(skip))]
+    last_release_time: Option<f64>,
+    /// Transfers sent so far, used to stamp each with its dispatch index
+    #[builder(setter(skip))]
+    dispatched: u64,
+    /// Dispatch index and delivery time of transfers that may not have arrived yet, in
+    /// dispatch order, used to stamp each transfer with its dispatch floor
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[builder(setter(skip))]
+    undelivered: Vec<(u64, f64)>,
+    /// Random stream for the jitter, kept so a saved run draws on where it left off
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[builder(setter(skip))]
+    rng: Option<Rng>,
 }
 
 impl Default for Delay {
@@ -31,10 +65,20 @@ impl Default for Delay {
         Self {
             id: String::new(),
             state: DelayState::default(),
+            initial_state: None,
             trigger_mode: TriggerMode::Automatic,
             action: DelayAction::Delay,
             release_amount: 1.0,
+            release_partial: false,
+            priority: 0,
+            jitter: 0.0,
+            seed: None,
             next_release_time: 0.0,
+            queued_since: None,
+            last_release_time: None,
+            dispatched: 0,
+            undelivered: vec![],
+            rng: None,
         }
     }
 }
@@ -48,22 +92,196 @@ impl Delay {
         DelayBuilder::default()
     }
 
-    fn can_release_from_queue(&self, current_time: f64) -> bool {
-        self.state.pending_outgoing_resources < self.release_amount
-            && self.state.available_resources() >= self.release_amount
-            && current_time >= self.next_release_time
+    /// Resources on their way through the delay, as `(amount, release time)` pairs ordered by
+    /// release time, including any backlog not yet scheduled
+    pub fn contents(&self) -> Vec<(f64, f64)> {
+        let mut contents: Vec<(f64, f64)> = self
+            .state
+            .backlog
+            .iter()
+            .chain(&self.state.in_flight)
+            .copied()
+            .collect();
+        contents.sort_by(|a, b| a.1.total_cmp(&b.1));
+        contents
+    }
+
+    /// Lets everything go at the current time: transfers already under way are marked as due
+    /// now, the backlog is released and a queue sends on all it holds in one transfer. The
+    /// simulation brings the matching scheduled transfers forward; see `Simulation::flush`.
+    pub fn flush(&mut self, context: &ProcessContext) -> Vec<Event> {
+        let now = context.current_time();
+        for (_, release_time) in self
+            .state
+            .in_flight
+            .iter_mut()
+            .chain(self.state.backlog.iter_mut())
+        {
+            *release_time = release_time.min(now);
+        }
+
+        let mut new_events = self.release_backlog(context);
+        if self.action == DelayAction::Queue {
+            let mut outputs = context.outputs_for_port(Some("out"));
+            let available = self.state.available_resources();
+            if let (Some(conn), None) = (outputs.next(), outputs.next()) {
+                if available > 0.0 {
+                    self.state.pending_outgoing_resources += available;
+                    self.queued_since = None;
+                    self.last_release_time = Some(now);
+                    new_events.push(self.create_transfer_event(
+                        conn.target_id.clone(),
+                        conn.target_port.clone(),
+                        available,
+                        now,
+                        now,
+                    ));
+                }
+            }
+        }
+        new_events
+    }
+
+    /// Schedules every backlogged release along the single output connection
+    fn release_backlog(&mut self, context: &ProcessContext) -> Vec<Event> {
+        if self.state.backlog.is_empty() {
+            return vec![];
+        }
+
+        let mut outputs = context.outputs_for_port(Some("out"));
+        let (Some(conn), None) = (outputs.next(), outputs.next()) else {
+            warn!(
+                "Delay '{}' needs exactly one output connection to release its backlog",
+                self.id
+            );
+            return vec![];
+        };
+
+        std::mem::take(&mut self.state.backlog)
+            .into_iter()
+            .map(|(amount, release_time)| {
+                self.state.pending_outgoing_resources += amount;
+                self.create_transfer_event(
+                    conn.target_id.clone(),
+                    conn.target_port.clone(),
+                    amount,
+                    release_time.max(context.current_time()),
+                    context.current_time(),
+                )
+            })
+            .collect()
+    }
+
+    /// Releases the next chunk of the queue if one is due
+    fn handle_queue_release(&mut self, context: &ProcessContext) -> Vec<Event> {
+        let mut outputs = context.outputs_for_port(Some("out"));
+        let (Some(conn), None) = (outputs.next(), outputs.next()) else {
+            return vec![];
+        };
+
+        let delay = conn.flow_rate.unwrap_or(1.0);
+        self.update_next_release_time(delay);
+        match self.queue_release_amount(context.current_time()) {
+            Some(release) => {
+                self.record_queue_release(release, delay, context.current_time());
+                vec![self.create_transfer_event(
+                    conn.target_id.clone(),
+                    conn.target_port.clone(),
+                    release,
+                    context.current_time(),
+                    context.current_time(),
+                )]
+            }
+            None => vec![],
+        }
+    }
+
+    /// Derives when the queue may next release from when its head arrived and when it last
+    /// released, each of which holds it back a full `delay`. Recomputing it this way, rather
+    /// than adjusting it as events come in, keeps the cadence whatever order arrivals and
+    /// steps interleave in.
+    fn update_next_release_time(&mut self, delay: f64) {
+        if let Some(anchor) = self.queue_anchor() {
+            self.next_release_time = anchor + delay;
+        }
+    }
+
+    /// The later of the queue head's arrival and the last release, if either is known
+    fn queue_anchor(&self) -> Option<f64> {
+        match (self.queued_since, self.last_release_time) {
+            (Some(arrival), Some(release)) => Some(arrival.max(release)),
+            (arrival, release) => arrival.or(release),
+        }
+    }
+
+    /// Books a queue release of `amount` at `time`, emptying the queue head if nothing
+    /// releasable is left behind it
+    fn record_queue_release(&mut self, amount: f64, delay: f64, time: f64) {
+        self.state.pending_outgoing_resources += amount;
+        self.last_release_time = Some(time);
+        if self.state.available_resources() <= 1e-9 {
+            self.queued_since = None;
+        }
+        self.update_next_release_time(delay);
+    }
+
+    /// Amount the queue may release at `current_time`, if any
+    fn queue_release_amount(&self, current_time: f64) -> Option<f64> {
+        if self.state.pending_outgoing_resources >= self.release_amount
+            || current_time < self.next_release_time
+        {
+            return None;
+        }
+
+        let available = self.state.available_resources();
+        if available >= self.release_amount {
+            Some(self.release_amount)
+        } else if self.release_partial && available > 0.0 {
+            Some(available)
+        } else {
+            None
+        }
     }
 
     fn create_transfer_event(
-        &self,
+        &mut self,
         target_id: String,
         target_port: Option<String>,
         amount: f64,
         time: f64,
+        now: f64,
     ) -> Event {
+        let dispatch_index = self.dispatched;
+        self.dispatched += 1;
+        self.state.in_flight.push((amount, time));
+
+        // Transfers due before the current time have arrived; of the rest, the earliest
+        // dispatched that is due after this one may still be on its way when it lands
+        self.undelivered
+            .retain(|(_, delivery_time)| *delivery_time >= now);
+        let dispatch_floor = self
+            .undelivered
+            .iter()
+            .find(|(_, delivery_time)| *delivery_time > time)
+            .map_or(dispatch_index, |(index, _)| *index);
+        self.undelivered.push((dispatch_index, time));
+
         Event::new(&self.id, &target_id, time, EventPayload::Resource(amount))
             .with_source_port("out")
             .with_target_port(target_port.unwrap_or("in".to_string()))
+            .with_dispatch_index(dispatch_index)
+            .with_dispatch_floor(dispatch_floor)
+    }
+
+    /// Delay for the next transfer, drawn around `delay` when jitter is set
+    fn sample_delay(&mut self, delay: f64, context: &ProcessContext) -> f64 {
+        if self.jitter <= 0.0 {
+            return delay;
+        }
+        let seed = self.seed.unwrap_or(context.seed());
+        let rng = self.rng.get_or_insert_with(|| Rng::new(seed));
+        let offset = (rng.next_f64() * 2.0 - 1.0) * self.jitter;
+        (delay + offset).max(0.0)
     }
 
     fn handle_resource(
@@ -111,28 +329,31 @@ impl Delay {
         match self.action {
             DelayAction::Delay => {
                 // In Delay mode, schedule resource transfer after delay
+                let delay = self.sample_delay(delay, context);
                 self.state.pending_outgoing_resources += amount;
                 new_events.push(self.create_transfer_event(
                     conn.target_id.clone(),
                     conn.target_port.clone(),
                     amount,
                     context.current_time() + delay,
+                    context.current_time(),
                 ));
             }
             DelayAction::Queue => {
                 // If queue was empty, resources need to wait the full delay
-                if self.state.available_resources() == amount {
-                    self.next_release_time = context.current_time() + delay;
+                if self.state.available_resources() - amount <= 1e-9 {
+                    self.queued_since = Some(context.current_time());
                 }
+                self.update_next_release_time(delay);
 
                 // Check if we can release immediately
-                if self.can_release_from_queue(context.current_time()) {
-                    self.state.pending_outgoing_resources += self.release_amount;
-                    self.next_release_time = context.current_time() + delay;
+                if let Some(release) = self.queue_release_amount(context.current_time()) {
+                    self.record_queue_release(release, delay, context.current_time());
                     new_events.push(self.create_transfer_event(
                         conn.target_id.clone(),
                         conn.target_port.clone(),
-                        self.release_amount,
+                        release,
+                        context.current_time(),
                         context.current_time(),
                     ));
                 }
@@ -160,11 +381,29 @@ impl Delay {
     }
 }
 
+impl DelayBuilder {
+    /// Pre-loads the delay with resources already in flight, as `(amount, release time)` pairs
+    pub fn pending(&mut self, entries: Vec<(f64, f64)>) -> &mut Self {
+        let state = self.state.get_or_insert_with(DelayState::default);
+        state.resources_received += entries.iter().map(|(amount, _)| amount).sum::<f64>();
+        state.backlog.extend(entries);
+        self
+    }
+}
+
 impl Processor for Delay {
     fn id(&self) -> &str {
         &self.id
     }
 
+    fn priority(&self) -> i64 {
+        self.priority
+    }
+
+    fn describe_settings(&self) -> Option<String> {
+        Some(format!("{:?} mode", self.action))
+    }
+
     fn on_events(
         &mut self,
         events: &[Event],
@@ -179,32 +418,16 @@ impl Processor for Delay {
         context: &ProcessContext,
     ) -> Result<Vec<Event>, SimulationError> {
         let new_events: Vec<Event> = match &event.payload {
-            EventPayload::SimulationStart | EventPayload::SimulationEnd => vec![],
-            EventPayload::Step => match self.action {
-                DelayAction::Delay => vec![], // Delays in Delay mode don't respond to Step events
-                DelayAction::Queue => {
-                    // In Queue mode, check if we can release on every step
-                    let mut outputs = context.outputs_for_port(Some("out"));
-                    if let Some(conn) = outputs.next() {
-                        let delay = conn.flow_rate.unwrap_or(1.0);
-                        if outputs.next().is_none()
-                            && self.can_release_from_queue(context.current_time())
-                        {
-                            self.state.pending_outgoing_resources += self.release_amount;
-                            self.next_release_time = context.current_time() + delay;
-                            vec![self.create_transfer_event(
-                                conn.target_id.clone(),
-                                conn.target_port.clone(),
-                                self.release_amount,
-                                context.current_time(),
-                            )]
-                        } else {
-                            vec![]
-                        }
-                    } else {
-                        vec![]
-                    }
-                }
+            EventPayload::SimulationStart => {
+                self.initial_state.get_or_insert_with(|| self.state.clone());
+                self.release_backlog(context)
+            }
+            EventPayload::SimulationEnd => vec![],
+            // Delays in Delay mode don't respond to Step or Trigger events,
+            // while queues check whether they can release
+            EventPayload::Step | EventPayload::Trigger => match self.action {
+                DelayAction::Delay => vec![],
+                DelayAction::Queue => self.handle_queue_release(context),
             },
             EventPayload::PullRequest | EventPayload::PullAllRequest => {
                 self.handle_pull_request(context)?
@@ -213,12 +436,28 @@ impl Processor for Delay {
             EventPayload::ResourceAccepted(amount) => {
                 self.state.pending_outgoing_resources -= amount;
                 self.state.resources_released += amount;
+                self.state.settle_in_flight(*amount, context.current_time());
                 vec![]
             }
             EventPayload::ResourceRejected(amount) => {
                 self.state.pending_outgoing_resources -= amount;
+                self.state.settle_in_flight(*amount, context.current_time());
                 vec![]
             }
+            // Anything is accepted as long as there is a single output to pass it on to
+            EventPayload::CapacityQuery => {
+                let room = if context.outputs_for_port(Some("out")).count() == 1 {
+                    f64::MAX
+                } else {
+                    0.0
+                };
+                vec![Event::new(
+                    &self.id,
+                    &event.source_id,
+                    context.current_time(),
+                    EventPayload::CapacityResponse(room),
+                )]
+            }
             event_payload => {
                 warn!("Unhandled event payload: {:?}", event_payload);
                 vec![]
@@ -230,6 +469,12 @@ impl Processor for Delay {
         assert!(self.state.pending_outgoing_resources >= 0.0);
         assert!(self.state.current_resources() >= 0.0);
         assert!(self.state.available_resources() >= 0.0);
+        // A queue never releases before a full delay has passed since its head arrived
+        // or its last release went out
+        if let Some(anchor) = self.queue_anchor() {
+            assert!(anchor <= context.current_time());
+            assert!(self.next_release_time >= anchor);
+        }
 
         Ok(new_events)
     }
@@ -246,8 +491,23 @@ impl Processor for Delay {
         &["out"]
     }
 
+    fn get_required_input_ports(&self) -> &[&'static str] {
+        &["in"]
+    }
+
+    fn get_required_output_ports(&self) -> &[&'static str] {
+        &["out"]
+    }
+
     fn reset(&mut self) {
-        self.state = DelayState::default();
+        if let Some(initial_state) = &self.initial_state {
+            self.state = initial_state.clone();
+        }
         self.next_release_time = 0.0;
+        self.queued_since = None;
+        self.last_release_time = None;
+        self.dispatched = 0;
+        self.undelivered.clear();
+        self.rng = None;
     }
 }
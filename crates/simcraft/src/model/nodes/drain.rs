@@ -2,7 +2,7 @@ use derive_builder::Builder;
 use serde::{Deserialize, Serialize};
 use tracing::warn;
 
-use super::{process_events_with_priority, Action, TriggerMode};
+use super::{check_action, process_events_with_priority, Action, TriggerMode};
 use crate::{
     model::{
         process_state::{DrainState, ProcessState},
@@ -19,8 +19,23 @@ pub struct Drain {
     #[builder(setter(into))]
     id: String,
     state: DrainState,
+    /// State the first run started from, restored on reset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[builder(setter(skip))]
+    initial_state: Option<DrainState>,
     trigger_mode: TriggerMode,
     action: Action,
+    /// Order among processes acting at the same time, highest first
+    priority: i64,
+    /// Class of demand this node's pulls belong to; suppliers serve higher classes first
+    priority_class: i64,
+    /// Rotate which input is pulled from first on each pull, rather than always starting
+    /// from the earliest connection
+    round_robin: bool,
+    /// Most the drain consumes per step; anything beyond it is rejected
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[builder(setter(strip_option))]
+    max_rate: Option<f64>,
 }
 
 impl Default for Drain {
@@ -28,13 +43,21 @@ impl Default for Drain {
         Self {
             id: String::new(),
             state: DrainState::default(),
+            initial_state: None,
             trigger_mode: TriggerMode::Automatic,
             action: Action::PullAny,
+            priority: 0,
+            priority_class: 0,
+            round_robin: false,
+            max_rate: None,
         }
     }
 }
 
 impl Drain {
+    /// Actions a drain can perform; any other is rejected when the drain is added
+    const ACTIONS: [Action; 2] = [Action::PullAny, Action::PullAll];
+
     pub fn new(id: &str) -> Drain {
         Drain::builder().id(id.to_string()).build().unwrap()
     }
@@ -50,9 +73,15 @@ impl Drain {
         let new_events = match self.action {
             Action::PullAny => self.handle_pull_any(context)?,
             Action::PullAll => self.handle_pull_all(context)?,
-            // TODO Handle invalid actions at compile time
-            Action::PushAny => unimplemented!(),
-            Action::PushAll => unimplemented!(),
+            // Rejected by `validate`, which a restored snapshot skips
+            Action::PushAny
+            | Action::PushAll
+            | Action::PushLottery
+            | Action::PushProportional
+            | Action::PushIfRoom => {
+                warn!("Drain '{}' can't perform {:?}", self.id, self.action);
+                vec![]
+            }
         };
 
         Ok(new_events)
@@ -61,8 +90,15 @@ impl Drain {
     fn handle_pull_any(&mut self, context: &ProcessContext) -> Result<Vec<Event>, SimulationError> {
         let mut new_events = Vec::new();
 
+        let mut inputs: Vec<&Connection> = context.inputs_for_port(Some("in")).collect();
+        let count = inputs.len();
+        if self.round_robin && count > 0 {
+            inputs.rotate_left(self.state.rotation % count);
+            self.state.rotation = (self.state.rotation + 1) % count;
+        }
+
         // Pull whatever is available up to flow rates from each input
-        for conn in context.inputs_for_port(Some("in")) {
+        for conn in inputs {
             new_events.push(Event::new(
                 self.id(),
                 &conn.source_id,
@@ -78,7 +114,7 @@ impl Drain {
         let mut new_events = Vec::new();
 
         // Request all resources - will only receive if all are available
-        let inputs: Vec<&Connection> = context.outputs_for_port(Some("in")).collect();
+        let inputs: Vec<&Connection> = context.inputs_for_port(Some("in")).collect();
         for conn in inputs {
             new_events.push(Event::new(
                 self.id(),
@@ -91,6 +127,44 @@ impl Drain {
         Ok(new_events)
     }
 
+    /// Under `Action::PullAll`, turns back every delivery in `events` unless each input has
+    /// delivered, so nothing is consumed from some inputs while others come up short
+    fn reject_incomplete_pull_all(
+        &self,
+        events: &[Event],
+        context: &ProcessContext,
+    ) -> (Vec<Event>, Vec<Event>) {
+        let is_delivery = |event: &Event| matches!(event.payload, EventPayload::Resource(_));
+        if self.action != Action::PullAll || !events.iter().any(is_delivery) {
+            return (events.to_vec(), vec![]);
+        }
+
+        let complete = context.inputs_for_port(Some("in")).all(|conn| {
+            events
+                .iter()
+                .any(|event| is_delivery(event) && event.source_id == conn.source_id)
+        });
+        if complete {
+            return (events.to_vec(), vec![]);
+        }
+
+        let (deliveries, others): (Vec<Event>, Vec<Event>) =
+            events.iter().cloned().partition(is_delivery);
+        let rejections = deliveries
+            .iter()
+            .filter_map(|event| match event.payload {
+                EventPayload::Resource(amount) => Some(Event::new(
+                    self.id(),
+                    &event.source_id,
+                    context.current_time(),
+                    EventPayload::ResourceRejected(amount),
+                )),
+                _ => None,
+            })
+            .collect();
+        (others, rejections)
+    }
+
     fn handle_resource(
         &mut self,
         event: &Event,
@@ -99,14 +173,34 @@ impl Drain {
     ) -> Result<Vec<Event>, SimulationError> {
         assert!(amount >= 0.0);
 
-        self.state.resources_consumed += amount;
+        let accepted = amount.min(self.remaining_rate());
+        let rejected = amount - accepted;
+        self.state.resources_consumed += accepted;
+        self.state.consumed_this_step += accepted;
 
-        Ok(vec![Event::new(
+        let mut new_events = vec![Event::new(
             self.id(),
             &event.source_id,
             context.current_time(),
-            EventPayload::ResourceAccepted(amount),
-        )])
+            EventPayload::ResourceAccepted(accepted),
+        )];
+        if rejected > 0.0 {
+            new_events.push(Event::new(
+                self.id(),
+                &event.source_id,
+                context.current_time(),
+                EventPayload::ResourceRejected(rejected),
+            ));
+        }
+
+        Ok(new_events)
+    }
+
+    /// What the drain may still consume this step
+    fn remaining_rate(&self) -> f64 {
+        self.max_rate.map_or(f64::MAX, |max_rate| {
+            (max_rate - self.state.consumed_this_step).max(0.0)
+        })
     }
 }
 
@@ -115,12 +209,30 @@ impl Processor for Drain {
         &self.id
     }
 
+    fn priority(&self) -> i64 {
+        self.priority
+    }
+
+    fn priority_class(&self) -> i64 {
+        self.priority_class
+    }
+
+    fn validate(&self) -> Result<(), SimulationError> {
+        check_action(self, self.action, &Self::ACTIONS)
+    }
+
     fn on_events(
         &mut self,
         events: &[Event],
         context: &ProcessContext,
     ) -> Result<Vec<Event>, SimulationError> {
-        process_events_with_priority(events, context, |event, ctx| self.on_event(event, ctx))
+        let (events, mut new_events) = self.reject_incomplete_pull_all(events, context);
+        new_events.extend(process_events_with_priority(
+            &events,
+            context,
+            |event, ctx| self.on_event(event, ctx),
+        )?);
+        Ok(new_events)
     }
 
     fn on_event(
@@ -128,8 +240,16 @@ impl Processor for Drain {
         event: &Event,
         context: &ProcessContext,
     ) -> Result<Vec<Event>, SimulationError> {
+        if matches!(event.payload, EventPayload::Step) {
+            self.state.consumed_this_step = 0.0;
+        }
+
         let new_events: Vec<Event> = match &event.payload {
-            EventPayload::SimulationStart | EventPayload::SimulationEnd => vec![],
+            EventPayload::SimulationStart => {
+                self.initial_state.get_or_insert_with(|| self.state.clone());
+                vec![]
+            }
+            EventPayload::SimulationEnd => vec![],
             EventPayload::Step => match self.trigger_mode {
                 TriggerMode::Passive => vec![],
                 TriggerMode::Interactive => unimplemented!(),
@@ -142,7 +262,15 @@ impl Processor for Drain {
                     }
                 }
             },
+            EventPayload::Trigger => self.handle_automatic_action(context)?,
             EventPayload::Resource(amount) => self.handle_resource(event, context, *amount)?,
+            // A drain absorbs anything it is sent, up to its rate limit
+            EventPayload::CapacityQuery => vec![Event::new(
+                self.id(),
+                &event.source_id,
+                context.current_time(),
+                EventPayload::CapacityResponse(self.remaining_rate()),
+            )],
             event_payload => {
                 warn!("Unhandled event payload: {:?}", event_payload);
                 vec![]
@@ -166,7 +294,13 @@ impl Processor for Drain {
         &[] // Drain has no outputs
     }
 
+    fn get_required_input_ports(&self) -> &[&'static str] {
+        &["in"]
+    }
+
     fn reset(&mut self) {
-        self.state = DrainState::default();
+        if let Some(initial_state) = &self.initial_state {
+            self.state = initial_state.clone();
+        }
     }
 }
@@ -1,3 +1,5 @@
+use std::cmp::Reverse;
+
 use crate::model::ProcessContext;
 use crate::simulator::event::{Event, EventPayload};
 use crate::utils::errors::SimulationError;
@@ -33,31 +35,45 @@ where
         }
     }
 
-    // Sort pull request events by connection sequence number
+    // Sort pull request events by requester class, then priority, then connection sequence number
     pull_request_events.sort_by_key(|event| {
-        context
+        let sequence_number = context
             .outputs_for_port(Some("out"))
             .find(|conn| conn.target_id == event.source_id)
             .map(|conn| conn.sequence_number)
-            .unwrap_or(u64::MAX)
+            .unwrap_or(u64::MAX);
+        (
+            Reverse(context.priority_class_of(&event.source_id)),
+            Reverse(context.priority_of(&event.source_id)),
+            sequence_number,
+        )
     });
 
-    // Sort pull all request events by connection sequence number
+    // Sort pull all request events by requester class, then priority, then connection sequence number
     pull_all_request_events.sort_by_key(|event| {
-        context
+        let sequence_number = context
             .outputs_for_port(Some("out"))
             .find(|conn| conn.target_id == event.source_id)
             .map(|conn| conn.sequence_number)
-            .unwrap_or(u64::MAX)
+            .unwrap_or(u64::MAX);
+        (
+            Reverse(context.priority_class_of(&event.source_id)),
+            Reverse(context.priority_of(&event.source_id)),
+            sequence_number,
+        )
     });
 
-    // Sort resource events by connection sequence number
+    // Sort resource events by sender priority, then connection sequence number
     resource_events.sort_by_key(|event| {
-        context
+        let sequence_number = context
             .inputs_for_port(Some("in"))
             .find(|conn| conn.source_id == event.source_id)
             .map(|conn| conn.sequence_number)
-            .unwrap_or(u64::MAX)
+            .unwrap_or(u64::MAX);
+        (
+            Reverse(context.priority_of(&event.source_id)),
+            sequence_number,
+        )
     });
 
     // Process events in priority order
@@ -0,0 +1,87 @@
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use crate::{
+    model::{process_state::ProcessState, ProcessContext, Processor, SerializableProcess},
+    simulator::Event,
+    utils::errors::SimulationError,
+};
+
+type Handler = dyn FnMut(&[Event], &ProcessContext) -> Result<Vec<Event>, SimulationError> + Send;
+
+/// Process whose behaviour is a closure, for prototyping custom logic inline before writing a
+/// dedicated node.
+///
+/// The closure receives each batch of simultaneous events addressed to the process and returns
+/// the events it emits, through an `in` and an `out` port. Such processes can't be serialized
+/// back into a model: they are written out with type `FnProcess`, which no factory builds.
+/// Clones share the one closure, captured state included.
+#[derive(Clone)]
+pub struct FnProcess {
+    id: String,
+    handler: Arc<Mutex<Handler>>,
+}
+
+impl FnProcess {
+    pub fn new<F>(id: &str, handler: F) -> Self
+    where
+        F: FnMut(&[Event], &ProcessContext) -> Result<Vec<Event>, SimulationError> + Send + 'static,
+    {
+        Self {
+            id: id.to_string(),
+            handler: Arc::new(Mutex::new(handler)),
+        }
+    }
+}
+
+impl fmt::Debug for FnProcess {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FnProcess").field("id", &self.id).finish()
+    }
+}
+
+impl SerializableProcess for FnProcess {
+    fn get_type(&self) -> &'static str {
+        "FnProcess"
+    }
+}
+
+impl Processor for FnProcess {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn on_event(
+        &mut self,
+        event: &Event,
+        context: &ProcessContext,
+    ) -> Result<Vec<Event>, SimulationError> {
+        self.on_events(std::slice::from_ref(event), context)
+    }
+
+    fn on_events(
+        &mut self,
+        events: &[Event],
+        context: &ProcessContext,
+    ) -> Result<Vec<Event>, SimulationError> {
+        let mut handler = self
+            .handler
+            .lock()
+            .map_err(|e| SimulationError::Other(e.to_string()))?;
+        (*handler)(events, context)
+    }
+
+    fn get_state(&self) -> ProcessState {
+        ProcessState::Custom(serde_json::Value::Null)
+    }
+
+    fn get_input_ports(&self) -> &[&'static str] {
+        &["in"]
+    }
+
+    fn get_output_ports(&self) -> &[&'static str] {
+        &["out"]
+    }
+
+    fn reset(&mut self) {}
+}
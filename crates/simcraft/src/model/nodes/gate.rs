@@ -0,0 +1,250 @@
+use derive_builder::Builder;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use super::{process_events_with_priority, TriggerMode};
+use crate::{
+    model::{
+        connection::Connection,
+        process_state::{GateState, ProcessState},
+        ProcessContext, Processor, SerializableProcess,
+    },
+    simulator::event::{Event, EventPayload},
+    utils::errors::SimulationError,
+};
+
+/// Splits what arrives on its input across its outputs in proportion to their weights,
+/// taken from each output connection's flow rate (1.0 when unset).
+///
+/// Resources an output rejects stay in the gate and are split again with the next arrival,
+/// or on every step when the gate is automatic.
+#[derive(Builder, Debug, Clone, Serialize, Deserialize, SerializableProcess)]
+#[serde(default, rename_all = "camelCase")]
+#[builder(default)]
+pub struct Gate {
+    #[builder(setter(into))]
+    id: String,
+    state: GateState,
+    /// State the first run started from, restored on reset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[builder(setter(skip))]
+    initial_state: Option<GateState>,
+    trigger_mode: TriggerMode,
+    /// Order among processes acting at the same time, highest first
+    priority: i64,
+}
+
+impl Default for Gate {
+    fn default() -> Self {
+        Self {
+            id: String::new(),
+            state: GateState::default(),
+            initial_state: None,
+            trigger_mode: TriggerMode::Passive,
+            priority: 0,
+        }
+    }
+}
+
+/// Shares of `amount` in proportion to `weights`. The last weighted share takes whatever the
+/// others leave, so the shares always add back up to `amount` exactly.
+fn split_by_weight(amount: f64, weights: &[f64]) -> Vec<f64> {
+    let total: f64 = weights.iter().map(|weight| weight.max(0.0)).sum();
+    if total <= 0.0 {
+        return vec![0.0; weights.len()];
+    }
+
+    let last = weights.iter().rposition(|weight| *weight > 0.0);
+    let mut remaining = amount;
+    weights
+        .iter()
+        .enumerate()
+        .map(|(index, weight)| {
+            if Some(index) == last {
+                return remaining.max(0.0);
+            }
+            let share = amount * weight.max(0.0) / total;
+            remaining -= share;
+            share
+        })
+        .collect()
+}
+
+impl Gate {
+    pub fn new(id: &str) -> Gate {
+        Gate::builder().id(id.to_string()).build().unwrap()
+    }
+
+    pub fn builder() -> GateBuilder {
+        GateBuilder::default()
+    }
+
+    /// Pushes everything the gate holds to its outputs, split by weight
+    fn distribute(&mut self, context: &ProcessContext) -> Vec<Event> {
+        let amount = self.state.available_resources();
+        if amount <= 0.0 {
+            return vec![];
+        }
+
+        let outputs: Vec<&Connection> = context.outputs_for_port(Some("out")).collect();
+        let weights: Vec<f64> = outputs
+            .iter()
+            .map(|conn| conn.effective_flow_rate(context.current_time()))
+            .collect();
+
+        let mut new_events = Vec::new();
+        for (conn, share) in outputs.into_iter().zip(split_by_weight(amount, &weights)) {
+            if share <= 0.0 {
+                continue;
+            }
+            self.state.pending_outgoing_resources += share;
+            new_events.push(
+                Event::new(
+                    self.id(),
+                    &conn.target_id,
+                    context.current_time(),
+                    EventPayload::Resource(share),
+                )
+                .with_source_port("out")
+                .with_target_port(conn.target_port.clone().unwrap_or("in".to_string())),
+            );
+        }
+
+        new_events
+    }
+
+    fn handle_resource(
+        &mut self,
+        event: &Event,
+        context: &ProcessContext,
+        amount: f64,
+    ) -> Result<Vec<Event>, SimulationError> {
+        assert!(amount >= 0.0);
+
+        if context.outputs_for_port(Some("out")).next().is_none() {
+            warn!("No output connection - rejecting resources");
+            return Ok(vec![Event::new(
+                self.id(),
+                &event.source_id,
+                context.current_time(),
+                EventPayload::ResourceRejected(amount),
+            )]);
+        }
+
+        self.state.resources_received += amount;
+        let mut new_events = vec![Event::new(
+            self.id(),
+            &event.source_id,
+            context.current_time(),
+            EventPayload::ResourceAccepted(amount),
+        )];
+        new_events.extend(self.distribute(context));
+
+        Ok(new_events)
+    }
+}
+
+impl Processor for Gate {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn priority(&self) -> i64 {
+        self.priority
+    }
+
+    fn on_events(
+        &mut self,
+        events: &[Event],
+        context: &ProcessContext,
+    ) -> Result<Vec<Event>, SimulationError> {
+        process_events_with_priority(events, context, |event, ctx| self.on_event(event, ctx))
+    }
+
+    fn on_event(
+        &mut self,
+        event: &Event,
+        context: &ProcessContext,
+    ) -> Result<Vec<Event>, SimulationError> {
+        let new_events: Vec<Event> = match &event.payload {
+            EventPayload::SimulationStart => {
+                self.initial_state.get_or_insert_with(|| self.state.clone());
+                vec![]
+            }
+            EventPayload::SimulationEnd => vec![],
+            EventPayload::Step => match self.trigger_mode {
+                TriggerMode::Passive => vec![],
+                TriggerMode::Interactive => unimplemented!(),
+                TriggerMode::Automatic => self.distribute(context),
+                TriggerMode::Enabling => {
+                    if context.current_step() == 1 {
+                        self.distribute(context)
+                    } else {
+                        vec![]
+                    }
+                }
+            },
+            EventPayload::Trigger => self.distribute(context),
+            EventPayload::Resource(amount) => self.handle_resource(event, context, *amount)?,
+            EventPayload::ResourceAccepted(amount) => {
+                self.state.pending_outgoing_resources -= amount;
+                self.state.resources_released += amount;
+                vec![]
+            }
+            EventPayload::ResourceRejected(amount) => {
+                self.state.pending_outgoing_resources -= amount;
+                vec![]
+            }
+            // Anything is accepted while there is an output to pass it on to
+            EventPayload::CapacityQuery => vec![Event::new(
+                self.id(),
+                &event.source_id,
+                context.current_time(),
+                EventPayload::CapacityResponse(
+                    if context.outputs_for_port(Some("out")).next().is_some() {
+                        f64::MAX
+                    } else {
+                        0.0
+                    },
+                ),
+            )],
+            event_payload => {
+                warn!("Unhandled event payload: {:?}", event_payload);
+                vec![]
+            }
+        };
+
+        assert!(self.state.resources_received >= 0.0);
+        assert!(self.state.resources_released >= 0.0);
+        assert!(self.state.pending_outgoing_resources >= -1e-9);
+        assert!(self.state.current_resources() >= -1e-9);
+
+        Ok(new_events)
+    }
+
+    fn get_state(&self) -> ProcessState {
+        ProcessState::Gate(self.state.clone())
+    }
+
+    fn get_input_ports(&self) -> &[&'static str] {
+        &["in"]
+    }
+
+    fn get_output_ports(&self) -> &[&'static str] {
+        &["out"]
+    }
+
+    fn get_required_input_ports(&self) -> &[&'static str] {
+        &["in"]
+    }
+
+    fn get_required_output_ports(&self) -> &[&'static str] {
+        &["out"]
+    }
+
+    fn reset(&mut self) {
+        if let Some(initial_state) = &self.initial_state {
+            self.state = initial_state.clone();
+        }
+    }
+}
@@ -0,0 +1,252 @@
+use derive_builder::Builder;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use super::{process_events_with_priority, TriggerMode};
+use crate::{
+    model::{
+        connection::Connection,
+        process_state::{MergerState, ProcessState},
+        ProcessContext, Processor, SerializableProcess,
+    },
+    simulator::event::{Event, EventPayload},
+    utils::errors::SimulationError,
+};
+
+/// Joins its inputs: waits until every input connection has delivered its flow rate, then
+/// takes that much from each and emits `output_amount` as one combined unit.
+///
+/// Arrivals are buffered per source, so surplus on a fast input carries over to later
+/// merges. A merger without input connections never emits. Each merge's output is split
+/// evenly across the output connections.
+#[derive(Builder, Debug, Clone, Serialize, Deserialize, SerializableProcess)]
+#[serde(default, rename_all = "camelCase")]
+#[builder(default)]
+pub struct Merger {
+    #[builder(setter(into))]
+    id: String,
+    state: MergerState,
+    /// State the first run started from, restored on reset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[builder(setter(skip))]
+    initial_state: Option<MergerState>,
+    trigger_mode: TriggerMode,
+    /// Amount one merge emits
+    output_amount: f64,
+    /// Order among processes acting at the same time, highest first
+    priority: i64,
+}
+
+impl Default for Merger {
+    fn default() -> Self {
+        Self {
+            id: String::new(),
+            state: MergerState::default(),
+            initial_state: None,
+            trigger_mode: TriggerMode::Automatic,
+            output_amount: 1.0,
+            priority: 0,
+        }
+    }
+}
+
+impl Merger {
+    pub fn new(id: &str) -> Merger {
+        Merger::builder().id(id.to_string()).build().unwrap()
+    }
+
+    pub fn builder() -> MergerBuilder {
+        MergerBuilder::default()
+    }
+
+    fn handle_pull(&mut self, context: &ProcessContext) -> Vec<Event> {
+        context
+            .inputs_for_port(Some("in"))
+            .map(|conn| {
+                Event::new(
+                    self.id(),
+                    &conn.source_id,
+                    context.current_time(),
+                    EventPayload::PullRequest,
+                )
+            })
+            .collect()
+    }
+
+    fn handle_resource(
+        &mut self,
+        event: &Event,
+        context: &ProcessContext,
+        amount: f64,
+    ) -> Result<Vec<Event>, SimulationError> {
+        assert!(amount >= 0.0);
+
+        *self
+            .state
+            .buffered
+            .entry(event.source_id.clone())
+            .or_default() += amount;
+        let mut new_events = vec![Event::new(
+            self.id(),
+            &event.source_id,
+            context.current_time(),
+            EventPayload::ResourceAccepted(amount),
+        )];
+        new_events.extend(self.merge(context));
+
+        Ok(new_events)
+    }
+
+    /// Whole merges the buffers cover, each input needing its flow rate
+    fn merges_available(&self, inputs: &[&Connection], time: f64) -> u64 {
+        inputs
+            .iter()
+            .map(|conn| {
+                let required = conn.effective_flow_rate(time);
+                if required <= 0.0 {
+                    return u64::MAX;
+                }
+                let held = self.state.buffered.get(&conn.source_id).copied();
+                ((held.unwrap_or(0.0) / required) + 1e-9).floor() as u64
+            })
+            .min()
+            .unwrap_or(0)
+    }
+
+    /// Merges as many times as every input allows, sending the combined units on
+    fn merge(&mut self, context: &ProcessContext) -> Vec<Event> {
+        let time = context.current_time();
+        let inputs: Vec<&Connection> = context.inputs_for_port(Some("in")).collect();
+        let merges = self.merges_available(&inputs, time);
+        if merges == 0 || merges == u64::MAX {
+            return vec![];
+        }
+
+        for conn in inputs {
+            let used = conn.effective_flow_rate(time).max(0.0) * merges as f64;
+            if let Some(held) = self.state.buffered.get_mut(&conn.source_id) {
+                let taken = used.min(*held);
+                *held -= taken;
+                self.state.resources_consumed += taken;
+            }
+        }
+        self.state.merges += merges;
+
+        let outputs: Vec<&Connection> = context.outputs_for_port(Some("out")).collect();
+        if outputs.is_empty() {
+            warn!(
+                "Merger '{}' has no output connection - discarding its output",
+                self.id
+            );
+            return vec![];
+        }
+        let share = self.output_amount * merges as f64 / outputs.len() as f64;
+        outputs
+            .into_iter()
+            .map(|conn| {
+                Event::new(
+                    self.id(),
+                    &conn.target_id,
+                    time,
+                    EventPayload::Resource(share),
+                )
+                .with_source_port("out")
+                .with_target_port(conn.target_port.clone().unwrap_or("in".to_string()))
+            })
+            .collect()
+    }
+}
+
+impl Processor for Merger {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn priority(&self) -> i64 {
+        self.priority
+    }
+
+    fn on_events(
+        &mut self,
+        events: &[Event],
+        context: &ProcessContext,
+    ) -> Result<Vec<Event>, SimulationError> {
+        process_events_with_priority(events, context, |event, ctx| self.on_event(event, ctx))
+    }
+
+    fn on_event(
+        &mut self,
+        event: &Event,
+        context: &ProcessContext,
+    ) -> Result<Vec<Event>, SimulationError> {
+        let new_events: Vec<Event> = match &event.payload {
+            EventPayload::SimulationStart => {
+                self.initial_state.get_or_insert_with(|| self.state.clone());
+                vec![]
+            }
+            EventPayload::SimulationEnd => vec![],
+            EventPayload::Step => match self.trigger_mode {
+                TriggerMode::Passive => vec![],
+                TriggerMode::Interactive => unimplemented!(),
+                TriggerMode::Automatic => self.handle_pull(context),
+                TriggerMode::Enabling => {
+                    if context.current_step() == 1 {
+                        self.handle_pull(context)
+                    } else {
+                        vec![]
+                    }
+                }
+            },
+            EventPayload::Trigger => self.handle_pull(context),
+            EventPayload::Resource(amount) => self.handle_resource(event, context, *amount)?,
+            EventPayload::ResourceAccepted(amount) => {
+                self.state.resources_produced += amount;
+                vec![]
+            }
+            // Merged units nobody takes are lost, as with a converter
+            EventPayload::ResourceRejected(_) => vec![],
+            // Inputs buffer whatever they are sent
+            EventPayload::CapacityQuery => vec![Event::new(
+                self.id(),
+                &event.source_id,
+                context.current_time(),
+                EventPayload::CapacityResponse(f64::MAX),
+            )],
+            event_payload => {
+                warn!("Unhandled event payload: {:?}", event_payload);
+                vec![]
+            }
+        };
+
+        assert!(self.state.resources_consumed >= 0.0);
+        assert!(self.state.resources_produced >= 0.0);
+
+        Ok(new_events)
+    }
+
+    fn get_state(&self) -> ProcessState {
+        ProcessState::Merger(self.state.clone())
+    }
+
+    fn get_input_ports(&self) -> &[&'static str] {
+        &["in"]
+    }
+
+    fn get_output_ports(&self) -> &[&'static str] {
+        &["out"]
+    }
+
+    fn get_required_input_ports(&self) -> &[&'static str] {
+        &["in"]
+    }
+
+    fn get_required_output_ports(&self) -> &[&'static str] {
+        &["out"]
+    }
+
+    fn reset(&mut self) {
+        if let Some(initial_state) = &self.initial_state {
+            self.state = initial_state.clone();
+        }
+    }
+}
@@ -1,18 +1,37 @@
 use serde::{Deserialize, Serialize};
 
+use crate::model::Processor;
+use crate::utils::errors::SimulationError;
+
+pub mod composite;
+pub mod converter;
 pub mod delay;
 pub mod drain;
 pub mod event_priority;
+pub mod fn_process;
+pub mod gate;
+pub mod merger;
 pub mod pool;
+pub mod probe;
+pub mod reorder;
 pub mod resource;
 pub mod source;
+pub mod splitter;
 pub mod stepper;
 
+pub use self::composite::Composite;
+pub use self::converter::{Converter, Recipe};
 pub use self::delay::Delay;
 pub use self::drain::Drain;
 pub use self::event_priority::process_events_with_priority;
+pub use self::fn_process::FnProcess;
+pub use self::gate::Gate;
+pub use self::merger::Merger;
 pub use self::pool::Pool;
+pub use self::probe::Probe;
+pub use self::reorder::ReorderBuffer;
 pub use self::source::Source;
+pub use self::splitter::{SplitStrategy, Splitter};
 pub use self::stepper::Stepper;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -35,6 +54,14 @@ pub enum Action {
     PullAll,
     PushAny,
     PushAll,
+    /// Push a single unit to one output chosen at random, weighted by flow rate
+    PushLottery,
+    /// Share available resources across outputs in proportion to their flow rates,
+    /// each capped at its own rate
+    PushProportional,
+    /// Ask each output how much room it has and push only what fits, so a full receiver
+    /// costs a query instead of a rejected transfer. Receivers that never answer get nothing.
+    PushIfRoom,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -42,3 +69,29 @@ pub enum Overflow {
     Block,
     Drain,
 }
+
+/// How a `PullAny` node spreads its pull requests over its inputs in one step
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum PullPolicy {
+    /// Ask every input for its full flow rate, rejecting whatever arrives past capacity
+    #[default]
+    EveryInput,
+    /// Ask inputs in turn only for the room left, stopping once the capacity is spoken for
+    WithinCapacity,
+}
+
+/// Rejects `action` unless it is one of the `supported` actions of the process's node type
+pub(crate) fn check_action(
+    process: &dyn Processor,
+    action: Action,
+    supported: &[Action],
+) -> Result<(), SimulationError> {
+    if supported.contains(&action) {
+        return Ok(());
+    }
+    Err(SimulationError::UnsupportedAction {
+        process: process.id().to_string(),
+        process_type: process.get_type().to_string(),
+        action: format!("{:?}", action),
+    })
+}
@@ -1,15 +1,19 @@
+use std::collections::VecDeque;
+
 use derive_builder::Builder;
 use serde::{Deserialize, Serialize};
 use tracing::warn;
 
-use super::{process_events_with_priority, Action, Overflow, TriggerMode};
+use super::{
+    process_events_with_priority, Action, Overflow, PullPolicy, ReorderBuffer, TriggerMode,
+};
 use crate::{
     model::{
         process_state::{PoolState, ProcessState},
         Connection, ProcessContext, Processor, SerializableProcess,
     },
     simulator::event::{Event, EventPayload},
-    utils::errors::SimulationError,
+    utils::{errors::SimulationError, rng::Rng},
 };
 
 #[derive(Builder, Debug, Clone, Serialize, Deserialize, SerializableProcess)]
@@ -19,10 +23,47 @@ pub struct Pool {
     #[builder(setter(into))]
     id: String,
     state: PoolState,
+    /// State the first run started from, restored on reset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[builder(setter(skip))]
+    initial_state: Option<PoolState>,
     trigger_mode: TriggerMode,
     action: Action,
     overflow: Overflow,
+    /// How `PullAny` shares the pool's room among its inputs
+    pull_policy: PullPolicy,
     capacity: f64,
+    /// Capacity changes as `(start time, capacity)` pairs, overriding `capacity` from each
+    /// start time onward. Excess left by a drop is discarded under `Overflow::Drain` and kept,
+    /// blocking further inflow, under `Overflow::Block`.
+    #[builder(setter(strip_option))]
+    capacity_schedule: Option<Vec<(f64, f64)>>,
+    /// Order among processes acting at the same time, highest first
+    priority: i64,
+    /// Class of demand this node's pulls belong to; suppliers serve higher classes first
+    priority_class: i64,
+    /// Fixed seed for this pool's random draws, in place of the one derived from the
+    /// simulation seed
+    #[builder(setter(strip_option))]
+    seed: Option<u64>,
+    /// Time resources must spend in the pool after arriving before they may leave
+    #[builder(setter(strip_option))]
+    min_dwell: Option<f64>,
+    /// Arrivals still tracked for `min_dwell`, oldest first, as `(arrival time, amount)`
+    #[builder(setter(skip))]
+    cohorts: VecDeque<(f64, f64)>,
+    /// Deliver resources in the order their sender dispatched them, holding early arrivals
+    reorder: bool,
+    #[builder(setter(skip))]
+    reorder_buffer: ReorderBuffer,
+    /// Random stream for `PushLottery`, kept so a saved run draws on where it left off
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[builder(setter(skip))]
+    rng: Option<Rng>,
+    /// Whether the pool was last seen `(empty, full)`, to signal only on reaching either
+    #[serde(skip)]
+    #[builder(setter(skip))]
+    boundary: Option<(bool, bool)>,
 }
 
 impl Default for Pool {
@@ -30,10 +71,22 @@ impl Default for Pool {
         Self {
             id: String::new(),
             state: PoolState::default(),
+            initial_state: None,
             trigger_mode: TriggerMode::Passive,
             action: Action::PullAny,
             overflow: Overflow::Block,
+            pull_policy: PullPolicy::EveryInput,
             capacity: -1.0,
+            capacity_schedule: None,
+            priority: 0,
+            priority_class: 0,
+            seed: None,
+            min_dwell: None,
+            cohorts: VecDeque::new(),
+            reorder: false,
+            reorder_buffer: ReorderBuffer::default(),
+            rng: None,
+            boundary: None,
         }
     }
 }
@@ -47,8 +100,105 @@ impl Pool {
         PoolBuilder::default()
     }
 
-    fn available_resources(&self) -> f64 {
-        self.state.available_resources()
+    /// Configured capacity, or `None` when the pool is unbounded
+    pub fn capacity(&self) -> Option<f64> {
+        (self.capacity >= 0.0).then_some(self.capacity)
+    }
+
+    /// Sends `Custom("empty")` or `Custom("full")` through the matching output port when the
+    /// pool has just become empty or reached capacity. The level at the start of the run only
+    /// sets the baseline.
+    fn boundary_signals(&mut self, capacity: f64, context: &ProcessContext) -> Vec<Event> {
+        let empty = self.state.resources <= 1e-9;
+        let full = capacity >= 0.0 && self.state.resources >= capacity - 1e-9;
+        let Some((was_empty, was_full)) = self.boundary.replace((empty, full)) else {
+            return vec![];
+        };
+
+        let mut signals = Vec::new();
+        for (port, reached) in [("empty", empty && !was_empty), ("full", full && !was_full)] {
+            if !reached {
+                continue;
+            }
+            for conn in context.outputs_for_port(Some(port)) {
+                let mut signal = Event::new(
+                    self.id(),
+                    &conn.target_id,
+                    context.current_time(),
+                    EventPayload::Custom(port.to_string()),
+                );
+                signal.source_port = Some(port.to_string());
+                signal.target_port = conn.target_port.clone();
+                signals.push(signal);
+            }
+        }
+        signals
+    }
+
+    /// Capacity in force at `time`, negative when unbounded
+    fn capacity_at(&self, time: f64) -> f64 {
+        self.capacity_schedule
+            .iter()
+            .flatten()
+            .filter(|(start, _)| *start <= time)
+            .max_by(|a, b| a.0.total_cmp(&b.0))
+            .map_or(self.capacity, |(_, capacity)| *capacity)
+    }
+
+    /// Discards resources above a reduced capacity when overflow drains
+    fn drain_excess(&mut self, capacity: f64, time: f64) {
+        if self.overflow != Overflow::Drain || capacity < 0.0 {
+            return;
+        }
+
+        // Resources already promised downstream stay until acknowledged
+        let excess = (self.state.resources - capacity).min(self.available_resources(time));
+        if excess > 0.0 {
+            warn!(
+                "Pool '{}' discarding {} resources above its capacity of {}",
+                self.id, excess, capacity
+            );
+            self.state.resources -= excess;
+            self.trim_cohorts();
+        }
+    }
+
+    /// Resources free to leave at `time`: neither promised downstream nor still dwelling
+    fn available_resources(&self, time: f64) -> f64 {
+        let Some(min_dwell) = self.min_dwell else {
+            return self.state.available_resources();
+        };
+        let dwelling: f64 = self
+            .cohorts
+            .iter()
+            .filter(|(arrival, _)| time - arrival < min_dwell)
+            .map(|(_, amount)| amount)
+            .sum();
+        (self.state.available_resources() - dwelling).max(0.0)
+    }
+
+    fn record_arrival(&mut self, time: f64, amount: f64) {
+        if self.min_dwell.is_some() && amount > 0.0 {
+            self.cohorts.push_back((time, amount));
+        }
+    }
+
+    /// Resources leave oldest first, so departures use up the earliest cohorts
+    fn trim_cohorts(&mut self) {
+        let mut excess =
+            self.cohorts.iter().map(|(_, amount)| amount).sum::<f64>() - self.state.resources;
+        while excess > 0.0 {
+            let Some((_, amount)) = self.cohorts.front_mut() else {
+                break;
+            };
+            if *amount <= excess {
+                excess -= *amount;
+                self.cohorts.pop_front();
+            } else {
+                *amount -= excess;
+                excess = 0.0;
+            }
+        }
     }
 
     fn handle_automatic_action(
@@ -56,14 +206,22 @@ impl Pool {
         context: &ProcessContext,
     ) -> Result<Vec<Event>, SimulationError> {
         let mut new_events = Vec::new();
+        // Conditions are judged on the level before this round of pushes
+        let level = self.state.resources;
+        let open_outputs = || {
+            context
+                .outputs_for_port(Some("out"))
+                .filter(move |conn| conn.condition.allows(level))
+        };
 
         match self.action {
             Action::PushAny => {
                 // Push up to available resources through each connection
-                for conn in context.outputs_for_port(Some("out")) {
-                    let flow_rate = conn.flow_rate.unwrap_or(1.0);
-                    let available_resources = self.available_resources();
-                    let push_amount = available_resources.min(flow_rate);
+                for conn in open_outputs() {
+                    let flow_rate = conn.effective_flow_rate(context.current_time());
+                    let available_resources = self.available_resources(context.current_time());
+                    // Partial packets stay buffered until a whole one is available
+                    let push_amount = conn.whole_packets(available_resources.min(flow_rate));
 
                     if push_amount > 0.0 {
                         new_events.push(
@@ -83,17 +241,23 @@ impl Pool {
             }
             Action::PushAll => {
                 // Calculate total required resources
-                let outputs: Vec<&Connection> = context.outputs_for_port(Some("out")).collect();
+                let outputs: Vec<&Connection> = open_outputs().collect();
                 let total_required: f64 = outputs
                     .iter()
-                    .map(|conn| conn.flow_rate.unwrap_or(1.0))
+                    .map(|conn| {
+                        conn.whole_packets(conn.effective_flow_rate(context.current_time()))
+                    })
                     .sum();
 
                 // Push only if we have enough available resources for all outputs
-                let available_resources = self.available_resources();
+                let available_resources = self.available_resources(context.current_time());
                 if available_resources >= total_required {
                     for conn in outputs {
-                        let flow_rate = conn.flow_rate.unwrap_or(1.0);
+                        let flow_rate =
+                            conn.whole_packets(conn.effective_flow_rate(context.current_time()));
+                        if flow_rate <= 0.0 {
+                            continue;
+                        }
                         new_events.push(
                             Event::new(
                                 self.id(),
@@ -109,25 +273,117 @@ impl Pool {
                     }
                 }
             }
+            Action::PushProportional => {
+                // Split what is available by flow rate, so outputs never claim more in total
+                let outputs: Vec<&Connection> = open_outputs().collect();
+                let flow_rates: Vec<f64> = outputs
+                    .iter()
+                    .map(|conn| conn.effective_flow_rate(context.current_time()))
+                    .collect();
+                let total_rate: f64 = flow_rates.iter().sum();
+                let available_resources = self.available_resources(context.current_time());
+
+                if total_rate > 0.0 {
+                    for (conn, flow_rate) in outputs.into_iter().zip(flow_rates) {
+                        let share = available_resources * flow_rate / total_rate;
+                        let push_amount = conn.whole_packets(share.min(flow_rate));
+                        if push_amount <= 0.0 {
+                            continue;
+                        }
+                        new_events.push(
+                            Event::new(
+                                self.id(),
+                                &conn.target_id,
+                                context.current_time(),
+                                EventPayload::Resource(push_amount),
+                            )
+                            .with_source_port("out")
+                            .with_target_port(conn.target_port.clone().unwrap_or("in".to_string())),
+                        );
+
+                        self.state.pending_outgoing_resources += push_amount;
+                    }
+                }
+            }
+            Action::PushIfRoom => {
+                // Transfers follow once each receiver reports its room
+                for conn in open_outputs() {
+                    if conn.effective_flow_rate(context.current_time()) <= 0.0 {
+                        continue;
+                    }
+                    new_events.push(
+                        Event::new(
+                            self.id(),
+                            &conn.target_id,
+                            context.current_time(),
+                            EventPayload::CapacityQuery,
+                        )
+                        .with_source_port("out")
+                        .with_target_port(conn.target_port.clone().unwrap_or("in".to_string())),
+                    );
+                }
+            }
+            Action::PushLottery => {
+                // Push a single unit to one output, drawn with probability proportional to flow rate
+                let outputs: Vec<&Connection> = open_outputs().collect();
+                let weights: Vec<f64> = outputs
+                    .iter()
+                    .map(|conn| conn.effective_flow_rate(context.current_time()))
+                    .collect();
+
+                if self.available_resources(context.current_time()) >= 1.0 {
+                    let seed = self.seed.unwrap_or(context.seed());
+                    let rng = self.rng.get_or_insert_with(|| Rng::new(seed));
+                    if let Some(conn) = rng.choose_weighted(&weights).map(|index| outputs[index]) {
+                        new_events.push(
+                            Event::new(
+                                self.id(),
+                                &conn.target_id,
+                                context.current_time(),
+                                EventPayload::Resource(1.0),
+                            )
+                            .with_source_port("out")
+                            .with_target_port(conn.target_port.clone().unwrap_or("in".to_string())),
+                        );
+
+                        self.state.pending_outgoing_resources += 1.0;
+                    }
+                }
+            }
             Action::PullAny => {
                 // Pull whatever is available up to flow rates
+                let mut room = match self.pull_policy {
+                    PullPolicy::EveryInput => f64::MAX,
+                    PullPolicy::WithinCapacity => self.remaining_capacity(context),
+                };
                 for conn in context.inputs_for_port(Some("in")) {
+                    if room <= 0.0 {
+                        break;
+                    }
                     // Request resources - actual amount will be determined by Source/Pool
-                    new_events.push(Event::new(
+                    let request = Event::new(
                         self.id(),
                         &conn.source_id,
                         context.current_time(),
                         EventPayload::PullRequest,
-                    ));
+                    );
+                    let rate = conn.effective_flow_rate(context.current_time());
+                    if rate > room {
+                        new_events.push(request.with_pull_limit(room));
+                        room = 0.0;
+                    } else {
+                        new_events.push(request);
+                        room -= rate;
+                    }
                 }
             }
             Action::PullAll => {
                 // Request all - will only receive if flow rate resources are available
-                let inputs: Vec<&Connection> = context.outputs_for_port(Some("in")).collect();
+                let inputs: Vec<&Connection> = context.inputs_for_port(Some("in")).collect();
                 for conn in inputs {
                     new_events.push(Event::new(
-                        &conn.source_id,
                         self.id(),
+                        &conn.source_id,
                         context.current_time(),
                         EventPayload::PullAllRequest,
                     ));
@@ -138,15 +394,63 @@ impl Pool {
         Ok(new_events)
     }
 
-    fn handle_pull_request(
+    /// Room left before reaching the capacity in force
+    fn remaining_capacity(&self, context: &ProcessContext) -> f64 {
+        let capacity = self.capacity_at(context.current_time());
+        if capacity < 0.0 {
+            f64::MAX
+        } else {
+            (capacity - self.state.resources).max(0.0)
+        }
+    }
+
+    /// Pushes what fits into a receiver that answered a `CapacityQuery`
+    fn handle_capacity_response(
         &mut self,
         event: &Event,
         context: &ProcessContext,
+        room: f64,
     ) -> Result<Vec<Event>, SimulationError> {
-        let flow_rate = context
+        let Some(conn) = context
             .outputs_for_port(Some("out"))
             .find(|conn| conn.target_id == event.source_id)
-            .map(|conn| match conn.flow_rate {
+        else {
+            return Ok(vec![]);
+        };
+
+        let flow_rate = conn.effective_flow_rate(context.current_time());
+        let push_amount = conn.whole_packets(
+            self.available_resources(context.current_time())
+                .min(flow_rate)
+                .min(room),
+        );
+        if push_amount <= 0.0 {
+            return Ok(vec![]);
+        }
+
+        let transfer = Event::new(
+            self.id(),
+            &conn.target_id,
+            context.current_time(),
+            EventPayload::Resource(push_amount),
+        )
+        .with_source_port("out")
+        .with_target_port(conn.target_port.clone().unwrap_or("in".to_string()));
+        self.state.pending_outgoing_resources += push_amount;
+
+        Ok(vec![transfer])
+    }
+
+    fn handle_pull_request(
+        &mut self,
+        event: &Event,
+        context: &ProcessContext,
+    ) -> Result<Vec<Event>, SimulationError> {
+        let connection = context
+            .outputs_for_port(Some("out"))
+            .find(|conn| conn.target_id == event.source_id);
+        let flow_rate = connection
+            .map(|conn| match conn.flow_rate_at(context.current_time()) {
                 Some(rate) => rate,
                 None => {
                     warn!(
@@ -166,8 +470,11 @@ impl Pool {
                 0.0
             });
 
-        let available_resources = self.available_resources();
-        let amount = available_resources.min(flow_rate);
+        let available_resources = self.available_resources(context.current_time());
+        let amount = available_resources
+            .min(flow_rate)
+            .min(event.pull_limit.unwrap_or(f64::MAX));
+        let amount = connection.map_or(amount, |conn| conn.whole_packets(amount));
 
         if amount > 0.0 {
             self.state.pending_outgoing_resources += amount;
@@ -179,7 +486,11 @@ impl Pool {
                 EventPayload::Resource(amount),
             )
             .with_source_port("out")
-            .with_target_port("in");
+            .with_target_port(
+                connection
+                    .and_then(|conn| conn.target_port.clone())
+                    .unwrap_or("in".to_string()),
+            );
             Ok(vec![event])
         } else {
             Ok(vec![])
@@ -191,11 +502,13 @@ impl Pool {
         event: &Event,
         context: &ProcessContext,
     ) -> Result<Vec<Event>, SimulationError> {
-        let amount = context
+        let connection = context
             .outputs_for_port(Some("out"))
-            .find(|conn| conn.target_id == event.source_id)
+            .find(|conn| conn.target_id == event.source_id);
+        let amount = connection
             .map(|conn| {
-                let required = conn.flow_rate.unwrap_or(0.0);
+                let required =
+                    conn.whole_packets(conn.flow_rate_at(context.current_time()).unwrap_or(0.0));
 
                 if self.state.resources < required {
                     warn!(
@@ -230,7 +543,11 @@ impl Pool {
                 EventPayload::Resource(amount),
             );
             event = event.with_source_port("out");
-            event = event.with_target_port("in");
+            event = event.with_target_port(
+                connection
+                    .and_then(|conn| conn.target_port.clone())
+                    .unwrap_or("in".to_string()),
+            );
             Ok(vec![event])
         } else {
             Ok(vec![])
@@ -245,15 +562,21 @@ impl Pool {
     ) -> Result<Vec<Event>, SimulationError> {
         assert!(amount >= 0.0);
 
+        let capacity = self.capacity_at(context.current_time());
         let future_resources = self.state.resources + amount;
-        let (accepted, rejected) = if self.capacity < 0.0 || future_resources <= self.capacity {
+        let (accepted, rejected) = if capacity < 0.0 || future_resources <= capacity {
             self.state.resources += amount;
             (amount, 0.0)
         } else {
             match self.overflow {
                 Overflow::Block => (0.0, amount),
                 Overflow::Drain => {
-                    let accepted = (self.capacity - self.state.resources).max(0.0);
+                    // A packet is either accepted whole or rejected whole
+                    let room = (capacity - self.state.resources).max(0.0);
+                    let accepted = context
+                        .inputs_for_port(event.target_port.as_deref())
+                        .find(|conn| conn.source_id == event.source_id)
+                        .map_or(room, |conn| conn.whole_packets(room));
                     self.state.resources += accepted;
                     let rejected = amount - accepted;
                     (accepted, rejected)
@@ -261,6 +584,7 @@ impl Pool {
             }
         };
 
+        self.record_arrival(context.current_time(), accepted);
         let mut new_events = Vec::new();
 
         if accepted > 0.0 {
@@ -290,6 +614,23 @@ impl Processor for Pool {
         &self.id
     }
 
+    fn priority(&self) -> i64 {
+        self.priority
+    }
+
+    fn priority_class(&self) -> i64 {
+        self.priority_class
+    }
+
+    fn describe_settings(&self) -> Option<String> {
+        let capacity = if self.capacity < 0.0 {
+            "unbounded".to_string()
+        } else {
+            format!("capacity {}", self.capacity)
+        };
+        Some(format!("{}, overflow {:?}", capacity, self.overflow))
+    }
+
     fn on_events(
         &mut self,
         events: &[Event],
@@ -303,8 +644,16 @@ impl Processor for Pool {
         event: &Event,
         context: &ProcessContext,
     ) -> Result<Vec<Event>, SimulationError> {
-        let new_events: Vec<Event> = match &event.payload {
-            EventPayload::SimulationStart | EventPayload::SimulationEnd => vec![],
+        let capacity = self.capacity_at(context.current_time());
+        self.drain_excess(capacity, context.current_time());
+        let resources_before = self.state.resources;
+
+        let mut new_events: Vec<Event> = match &event.payload {
+            EventPayload::SimulationStart => {
+                self.initial_state.get_or_insert_with(|| self.state.clone());
+                vec![]
+            }
+            EventPayload::SimulationEnd => vec![],
             EventPayload::Step => match self.trigger_mode {
                 TriggerMode::Passive => vec![],
                 TriggerMode::Interactive => unimplemented!(),
@@ -320,16 +669,37 @@ impl Processor for Pool {
             EventPayload::Trigger => self.handle_automatic_action(context)?,
             EventPayload::PullRequest => self.handle_pull_request(event, context)?,
             EventPayload::PullAllRequest => self.handle_pull_all_request(event, context)?,
-            EventPayload::Resource(amount) => self.handle_resource(event, context, *amount)?,
+            EventPayload::Resource(amount) if !self.reorder => {
+                self.handle_resource(event, context, *amount)?
+            }
+            EventPayload::Resource(_) => {
+                let mut new_events = Vec::new();
+                for ready in self.reorder_buffer.receive(event) {
+                    if let EventPayload::Resource(amount) = ready.payload {
+                        new_events.extend(self.handle_resource(&ready, context, amount)?);
+                    }
+                }
+                new_events
+            }
             EventPayload::ResourceAccepted(amount) => {
                 self.state.pending_outgoing_resources -= amount;
                 self.state.resources -= amount;
+                self.trim_cohorts();
                 vec![]
             }
             EventPayload::ResourceRejected(amount) => {
                 self.state.pending_outgoing_resources -= amount;
                 vec![]
             }
+            EventPayload::CapacityQuery => vec![Event::new(
+                self.id(),
+                &event.source_id,
+                context.current_time(),
+                EventPayload::CapacityResponse(self.remaining_capacity(context)),
+            )],
+            EventPayload::CapacityResponse(room) => {
+                self.handle_capacity_response(event, context, *room)?
+            }
             event_payload => {
                 warn!("Unhandled event payload: {:?}", event_payload);
                 vec![]
@@ -348,15 +718,18 @@ impl Processor for Pool {
             self.id,
             self.state.resources
         );
-        if self.capacity >= 0.0 {
+        if capacity >= 0.0 {
+            // A blocking pool may sit above a reduced capacity, but must not grow further
             assert!(
-                self.state.resources <= self.capacity + f64::EPSILON,
+                self.state.resources <= capacity.max(resources_before) + f64::EPSILON,
                 "Resource Overflow: resources = {}, capacity = {}",
                 self.state.resources,
-                self.capacity
+                capacity
             );
         }
 
+        new_events.extend(self.boundary_signals(capacity, context));
+
         Ok(new_events)
     }
 
@@ -368,11 +741,18 @@ impl Processor for Pool {
         &["in"]
     }
 
+    /// `empty` and `full` carry boundary signals rather than resources
     fn get_output_ports(&self) -> &[&'static str] {
-        &["out"]
+        &["out", "empty", "full"]
     }
 
     fn reset(&mut self) {
-        self.state = PoolState::default();
+        if let Some(initial_state) = &self.initial_state {
+            self.state = initial_state.clone();
+        }
+        self.reorder_buffer.clear();
+        self.cohorts.clear();
+        self.rng = None;
+        self.boundary = None;
     }
 }
@@ -0,0 +1,143 @@
+use derive_builder::Builder;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use super::process_events_with_priority;
+use crate::{
+    model::{
+        process_state::{ProbeState, ProcessState},
+        ProcessContext, Processor, SerializableProcess,
+    },
+    simulator::event::{Event, EventPayload},
+    utils::errors::SimulationError,
+};
+
+/// Records the level of the processes linked to its input: on every step it asks each of them
+/// for the resources they hold and appends `(time, level)` to its samples.
+///
+/// A probe only observes, so it is linked from its sources' `OBSERVE_PORT`, which they never
+/// push through. With several inputs, each answer is recorded as a sample of its own.
+#[derive(Builder, Debug, Clone, Default, Serialize, Deserialize, SerializableProcess)]
+#[serde(default, rename_all = "camelCase")]
+#[builder(default)]
+pub struct Probe {
+    #[builder(setter(into))]
+    id: String,
+    state: ProbeState,
+    /// State the first run started from, restored on reset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[builder(setter(skip))]
+    initial_state: Option<ProbeState>,
+    /// Order among processes acting at the same time, highest first
+    priority: i64,
+}
+
+impl Probe {
+    pub fn new(id: &str) -> Probe {
+        Probe::builder().id(id.to_string()).build().unwrap()
+    }
+
+    pub fn builder() -> ProbeBuilder {
+        ProbeBuilder::default()
+    }
+
+    fn handle_step(&self, context: &ProcessContext) -> Vec<Event> {
+        context
+            .inputs_for_port(Some("in"))
+            .map(|conn| {
+                Event::new(
+                    self.id(),
+                    &conn.source_id,
+                    context.current_time(),
+                    EventPayload::LevelQuery,
+                )
+            })
+            .collect()
+    }
+}
+
+impl Processor for Probe {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn priority(&self) -> i64 {
+        self.priority
+    }
+
+    fn on_events(
+        &mut self,
+        events: &[Event],
+        context: &ProcessContext,
+    ) -> Result<Vec<Event>, SimulationError> {
+        process_events_with_priority(events, context, |event, ctx| self.on_event(event, ctx))
+    }
+
+    fn on_event(
+        &mut self,
+        event: &Event,
+        context: &ProcessContext,
+    ) -> Result<Vec<Event>, SimulationError> {
+        let new_events: Vec<Event> = match &event.payload {
+            EventPayload::SimulationStart => {
+                self.initial_state.get_or_insert_with(|| self.state.clone());
+                vec![]
+            }
+            EventPayload::SimulationEnd => vec![],
+            EventPayload::Step => self.handle_step(context),
+            EventPayload::LevelResponse(level) => {
+                self.state.samples.push((context.current_time(), *level));
+                vec![]
+            }
+            EventPayload::Resource(amount) => vec![Event::new(
+                self.id(),
+                &event.source_id,
+                context.current_time(),
+                EventPayload::ResourceRejected(*amount),
+            )],
+            // A probe only observes, so it has no room
+            EventPayload::CapacityQuery => vec![Event::new(
+                self.id(),
+                &event.source_id,
+                context.current_time(),
+                EventPayload::CapacityResponse(0.0),
+            )],
+            event_payload => {
+                warn!("Unhandled event payload: {:?}", event_payload);
+                vec![]
+            }
+        };
+
+        Ok(new_events)
+    }
+
+    fn is_observer(&self) -> bool {
+        true
+    }
+
+    fn get_state(&self) -> ProcessState {
+        ProcessState::Probe(self.state.clone())
+    }
+
+    fn get_input_ports(&self) -> &[&'static str] {
+        &["in"]
+    }
+
+    fn get_output_ports(&self) -> &[&'static str] {
+        &[]
+    }
+
+    fn get_required_input_ports(&self) -> &[&'static str] {
+        &["in"]
+    }
+
+    fn get_required_output_ports(&self) -> &[&'static str] {
+        &[]
+    }
+
+    fn reset(&mut self) {
+        if let Some(initial_state) = &self.initial_state {
+            self.state = initial_state.clone();
+        }
+    }
+}
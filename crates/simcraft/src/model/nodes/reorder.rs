@@ -0,0 +1,70 @@
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{Deserialize, Serialize};
+
+use crate::simulator::event::Event;
+
+/// Holds resources that arrive ahead of earlier dispatches from the same sender, releasing
+/// them in the order they were sent.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ReorderBuffer {
+    senders: HashMap<String, SenderQueue>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct SenderQueue {
+    next: u64,
+    held: BTreeMap<u64, Event>,
+}
+
+impl ReorderBuffer {
+    /// Accepts an arrival and returns every event now ready for delivery, in dispatch order.
+    /// Events without a dispatch index pass straight through.
+    pub fn receive(&mut self, event: &Event) -> Vec<Event> {
+        let Some(index) = event.dispatch_index else {
+            return vec![event.clone()];
+        };
+        let floor = event.dispatch_floor.unwrap_or(0);
+
+        // A sender first heard from mid-run may have delivered earlier dispatches elsewhere
+        let queue = self
+            .senders
+            .entry(event.source_id.clone())
+            .or_insert_with(|| SenderQueue {
+                next: floor,
+                held: BTreeMap::new(),
+            });
+
+        let mut ready = Vec::new();
+        if index < queue.next {
+            // Already delivered under this index, so the sender has started numbering afresh,
+            // e.g. after a reset: what was held under the old numbering goes out now
+            ready.extend(std::mem::take(&mut queue.held).into_values());
+            queue.next = floor;
+        } else if floor > queue.next {
+            // Nothing below the floor is still on its way, e.g. after this receiver replaced
+            // another mid-run, so stop waiting for it
+            let waiting = queue.held.split_off(&floor);
+            ready.extend(std::mem::replace(&mut queue.held, waiting).into_values());
+            queue.next = floor;
+        }
+
+        queue.held.insert(index, event.clone());
+        while let Some(event) = queue.held.remove(&queue.next) {
+            ready.push(event);
+            queue.next += 1;
+        }
+        ready
+    }
+
+    /// Number of events waiting on an earlier dispatch
+    pub fn held(&self) -> usize {
+        self.senders.values().map(|queue| queue.held.len()).sum()
+    }
+
+    pub fn clear(&mut self) {
+        self.senders.clear();
+    }
+}
@@ -2,14 +2,14 @@ use derive_builder::Builder;
 use serde::{Deserialize, Serialize};
 use tracing::warn;
 
-use super::{process_events_with_priority, Action, TriggerMode};
+use super::{check_action, process_events_with_priority, Action, TriggerMode};
 use crate::{
     model::{
         process_state::{ProcessState, SourceState},
         ProcessContext, Processor, SerializableProcess,
     },
     simulator::event::{Event, EventPayload},
-    utils::errors::SimulationError,
+    utils::{errors::SimulationError, ports::deserialize_ports},
 };
 
 #[derive(Builder, Debug, Clone, Serialize, Deserialize, SerializableProcess)]
@@ -19,8 +19,22 @@ pub struct Source {
     #[builder(setter(into))]
     id: String,
     state: SourceState,
+    /// State the first run started from, restored on reset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[builder(setter(skip))]
+    initial_state: Option<SourceState>,
     trigger_mode: TriggerMode,
     action: Action,
+    /// Order among processes acting at the same time, highest first
+    priority: i64,
+    /// Output ports resources are pushed through, each routing to its own connections
+    #[serde(deserialize_with = "deserialize_ports")]
+    output_ports: Vec<&'static str>,
+    /// Emit this total once, on the first trigger, and nothing after, instead of pushing
+    /// flow rates every step. Split evenly across the outputs; pull requests go unanswered.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[builder(setter(strip_option))]
+    batch_amount: Option<f64>,
 }
 
 impl Default for Source {
@@ -28,13 +42,20 @@ impl Default for Source {
         Self {
             id: String::new(),
             state: SourceState::default(),
+            initial_state: None,
             trigger_mode: TriggerMode::Automatic,
             action: Action::PushAny,
+            priority: 0,
+            output_ports: vec!["out"],
+            batch_amount: None,
         }
     }
 }
 
 impl Source {
+    /// Actions a source can perform; any other is rejected when the source is added
+    const ACTIONS: [Action; 2] = [Action::PushAny, Action::PushAll];
+
     pub fn new(id: &str) -> Source {
         Source::builder().id(id.to_string()).build().unwrap()
     }
@@ -47,12 +68,22 @@ impl Source {
         &mut self,
         context: &ProcessContext,
     ) -> Result<Vec<Event>, SimulationError> {
+        if let Some(batch_amount) = self.batch_amount {
+            return Ok(self.handle_batch(batch_amount, context));
+        }
+
         let new_events = match self.action {
             Action::PushAny => self.handle_push_any(context)?,
-            Action::PushAll => unimplemented!(),
-            // TODO Handle invalid actions at compile time
-            Action::PullAny => unimplemented!(),
-            Action::PullAll => unimplemented!(),
+            Action::PushAll => self.handle_push_all(context)?,
+            // Rejected by `validate`, which a restored snapshot skips
+            Action::PullAny
+            | Action::PullAll
+            | Action::PushLottery
+            | Action::PushProportional
+            | Action::PushIfRoom => {
+                warn!("Source '{}' can't perform {:?}", self.id, self.action);
+                vec![]
+            }
         };
 
         Ok(new_events)
@@ -61,30 +92,102 @@ impl Source {
     fn handle_push_any(&mut self, context: &ProcessContext) -> Result<Vec<Event>, SimulationError> {
         let mut new_events = Vec::new();
 
-        let outputs = context.outputs_for_port(Some("out"));
-        for conn in outputs {
-            let amount = conn.flow_rate.unwrap_or(1.0);
-            new_events.push(
+        for port in &self.output_ports {
+            for conn in context.outputs_for_port(Some(port)) {
+                if !conn.condition.allows(f64::INFINITY) {
+                    continue;
+                }
+                let amount = conn.whole_packets(conn.effective_flow_rate(context.current_time()));
+                if amount <= 0.0 {
+                    continue;
+                }
+                new_events.push(
+                    Event::new(
+                        self.id().to_string(),
+                        conn.target_id.clone(),
+                        context.current_time(),
+                        EventPayload::Resource(amount),
+                    )
+                    .with_source_port(*port)
+                    .with_target_port(conn.target_port.clone().unwrap_or("in".to_string())),
+                );
+            }
+        }
+
+        Ok(new_events)
+    }
+
+    /// Releases the whole batch the first time round, split evenly across the open outputs
+    fn handle_batch(&mut self, batch_amount: f64, context: &ProcessContext) -> Vec<Event> {
+        if self.state.batch_released {
+            return vec![];
+        }
+        self.state.batch_released = true;
+
+        let outputs: Vec<_> = self
+            .output_ports
+            .iter()
+            .flat_map(|port| {
+                context
+                    .outputs_for_port(Some(port))
+                    .filter(|conn| conn.condition.allows(f64::INFINITY))
+                    .map(move |conn| (*port, conn))
+            })
+            .collect();
+        if outputs.is_empty() || batch_amount <= 0.0 {
+            return vec![];
+        }
+
+        let share = batch_amount / outputs.len() as f64;
+        outputs
+            .into_iter()
+            .map(|(port, conn)| {
                 Event::new(
                     self.id().to_string(),
                     conn.target_id.clone(),
                     context.current_time(),
-                    EventPayload::Resource(amount),
+                    EventPayload::Resource(share),
                 )
-                .with_source_port("out")
-                .with_target_port(conn.target_port.clone().unwrap_or("in".to_string())),
-            );
-        }
-
-        Ok(new_events)
+                .with_source_port(port)
+                .with_target_port(conn.target_port.clone().unwrap_or("in".to_string()))
+            })
+            .collect()
     }
 
+    /// Pushes every open output its full flow rate at once, or nothing when any such output's
+    /// rate comes to less than a whole packet. Production is only counted as each receiver
+    /// accepts, so a share that is rejected is simply never produced.
     pub fn handle_push_all(
         &mut self,
-        _context: &ProcessContext,
+        context: &ProcessContext,
     ) -> Result<Vec<Event>, SimulationError> {
-        // NOTE This is complex due to resource delivery accept/reject logic
-        unimplemented!()
+        let mut transfers = Vec::new();
+        for port in &self.output_ports {
+            for conn in context
+                .outputs_for_port(Some(port))
+                .filter(|conn| conn.condition.allows(f64::INFINITY))
+            {
+                let amount = conn.whole_packets(conn.effective_flow_rate(context.current_time()));
+                if amount <= 0.0 {
+                    return Ok(vec![]);
+                }
+                transfers.push((*port, conn, amount));
+            }
+        }
+
+        Ok(transfers
+            .into_iter()
+            .map(|(port, conn, amount)| {
+                Event::new(
+                    self.id().to_string(),
+                    conn.target_id.clone(),
+                    context.current_time(),
+                    EventPayload::Resource(amount),
+                )
+                .with_source_port(port)
+                .with_target_port(conn.target_port.clone().unwrap_or("in".to_string()))
+            })
+            .collect())
     }
 
     fn handle_pull_request(
@@ -92,11 +195,28 @@ impl Source {
         event: &Event,
         context: &ProcessContext,
     ) -> Result<Vec<Event>, SimulationError> {
-        let amount = context
-            .outputs_for_port(Some("out"))
-            .find(|conn| conn.target_id == event.source_id)
-            .and_then(|conn| conn.flow_rate)
-            .unwrap_or(1.0);
+        if self.batch_amount.is_some() {
+            return Ok(vec![]);
+        }
+
+        let (port, amount) = self
+            .output_ports
+            .iter()
+            .find_map(|port| {
+                context
+                    .outputs_for_port(Some(port))
+                    .find(|conn| conn.target_id == event.source_id)
+                    .map(|conn| {
+                        let rate = conn
+                            .effective_flow_rate(context.current_time())
+                            .min(event.pull_limit.unwrap_or(f64::MAX));
+                        (*port, conn.whole_packets(rate))
+                    })
+            })
+            .unwrap_or((self.output_ports.first().copied().unwrap_or("out"), 1.0));
+        if amount <= 0.0 {
+            return Ok(vec![]);
+        }
 
         Ok(vec![Event::new(
             self.id().to_string(),
@@ -104,7 +224,7 @@ impl Source {
             context.current_time(),
             EventPayload::Resource(amount),
         )
-        .with_source_port("out")
+        .with_source_port(port)
         .with_target_port(
             event.source_port.clone().unwrap_or("in".to_string()),
         )])
@@ -116,6 +236,18 @@ impl Processor for Source {
         &self.id
     }
 
+    fn priority(&self) -> i64 {
+        self.priority
+    }
+
+    fn describe_settings(&self) -> Option<String> {
+        Some(format!("{:?}, {:?}", self.trigger_mode, self.action))
+    }
+
+    fn validate(&self) -> Result<(), SimulationError> {
+        check_action(self, self.action, &Self::ACTIONS)
+    }
+
     fn on_events(
         &mut self,
         events: &[Event],
@@ -130,7 +262,11 @@ impl Processor for Source {
         context: &ProcessContext,
     ) -> Result<Vec<Event>, SimulationError> {
         let new_events: Vec<Event> = match &event.payload {
-            EventPayload::SimulationStart | EventPayload::SimulationEnd => vec![],
+            EventPayload::SimulationStart => {
+                self.initial_state.get_or_insert_with(|| self.state.clone());
+                vec![]
+            }
+            EventPayload::SimulationEnd => vec![],
             EventPayload::Step => match self.trigger_mode {
                 TriggerMode::Passive => vec![],
                 TriggerMode::Interactive => unimplemented!(),
@@ -173,10 +309,16 @@ impl Processor for Source {
     }
 
     fn get_output_ports(&self) -> &[&'static str] {
-        &["out"]
+        &self.output_ports
+    }
+
+    fn get_required_output_ports(&self) -> &[&'static str] {
+        &self.output_ports
     }
 
     fn reset(&mut self) {
-        self.state = SourceState::default();
+        if let Some(initial_state) = &self.initial_state {
+            self.state = initial_state.clone();
+        }
     }
 }
@@ -0,0 +1,393 @@
+use derive_builder::Builder;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use super::{process_events_with_priority, TriggerMode};
+use crate::{
+    model::{
+        connection::Connection,
+        process_state::{ProcessState, SplitterState},
+        ProcessContext, Processor, SerializableProcess,
+    },
+    simulator::event::{Event, EventPayload},
+    utils::{errors::SimulationError, rng::Rng},
+};
+
+/// How a `Splitter` picks the output for each unit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum SplitStrategy {
+    /// Outputs take turns, in the order their connections were added
+    #[default]
+    RoundRobin,
+    /// Outputs take turns in proportion to their flow rates, spread as evenly as possible
+    Weighted,
+    /// Each unit goes to an output drawn uniformly at random
+    Random,
+}
+
+/// Sends what arrives on its input to its outputs one unit at a time, choosing the output for
+/// each unit by its `SplitStrategy`. A final fraction of a unit travels as a unit of its own.
+///
+/// The output that took the last unit is kept in the state, so rotation carries on across
+/// arrivals. Resources an output rejects stay in the splitter until the next arrival, or the
+/// next step when it is automatic.
+#[derive(Builder, Debug, Clone, Serialize, Deserialize, SerializableProcess)]
+#[serde(default, rename_all = "camelCase")]
+#[builder(default)]
+pub struct Splitter {
+    #[builder(setter(into))]
+    id: String,
+    state: SplitterState,
+    /// State the first run started from, restored on reset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[builder(setter(skip))]
+    initial_state: Option<SplitterState>,
+    trigger_mode: TriggerMode,
+    strategy: SplitStrategy,
+    /// Order among processes acting at the same time, highest first
+    priority: i64,
+    /// Fixed seed for random splits, in place of the one derived from the simulation seed
+    #[builder(setter(strip_option))]
+    seed: Option<u64>,
+    /// Random stream for `SplitStrategy::Random`, kept so a saved run draws on where it left off
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[builder(setter(skip))]
+    rng: Option<Rng>,
+}
+
+impl Default for Splitter {
+    fn default() -> Self {
+        Self {
+            id: String::new(),
+            state: SplitterState::default(),
+            initial_state: None,
+            trigger_mode: TriggerMode::Passive,
+            strategy: SplitStrategy::RoundRobin,
+            priority: 0,
+            seed: None,
+            rng: None,
+        }
+    }
+}
+
+impl Splitter {
+    pub fn new(id: &str) -> Splitter {
+        Splitter::builder().id(id.to_string()).build().unwrap()
+    }
+
+    pub fn builder() -> SplitterBuilder {
+        SplitterBuilder::default()
+    }
+
+    /// Index of the output the next unit goes to, out of outputs with the given weights
+    fn next_output(&mut self, weights: &[f64], context: &ProcessContext) -> usize {
+        let count = weights.len();
+        let round_robin = |last: Option<usize>| last.map_or(0, |index| (index + 1) % count);
+        let total: f64 = weights.iter().map(|weight| weight.max(0.0)).sum();
+
+        let index = match self.strategy {
+            SplitStrategy::RoundRobin => round_robin(self.state.last_output),
+            SplitStrategy::Weighted if total <= 0.0 => round_robin(self.state.last_output),
+            // Smooth weighted round robin: every output earns its weight in credit, the richest
+            // takes the unit and pays back the total
+            SplitStrategy::Weighted => {
+                self.state.credits.resize(count, 0.0);
+                for (credit, weight) in self.state.credits.iter_mut().zip(weights) {
+                    *credit += weight.max(0.0);
+                }
+                let mut index = 0;
+                for (candidate, credit) in self.state.credits.iter().enumerate() {
+                    if *credit > self.state.credits[index] {
+                        index = candidate;
+                    }
+                }
+                self.state.credits[index] -= total;
+                index
+            }
+            SplitStrategy::Random => {
+                let seed = self.seed.unwrap_or(context.seed());
+                let rng = self.rng.get_or_insert_with(|| Rng::new(seed));
+                rng.choose_weighted(&vec![1.0; count]).unwrap_or(0)
+            }
+        };
+
+        self.state.last_output = Some(index);
+        index
+    }
+
+    /// How many of `units` whole units each output takes, as if they were handed out one at
+    /// a time by `next_output`
+    fn split_units(&mut self, units: f64, weights: &[f64], context: &ProcessContext) -> Vec<f64> {
+        let count = weights.len();
+        let mut shares = vec![0.0; count];
+        if units <= 0.0 {
+            return shares;
+        }
+        let total: f64 = weights.iter().map(|weight| weight.max(0.0)).sum();
+
+        match self.strategy {
+            SplitStrategy::Weighted if total > 0.0 => {
+                // Each output's fair count is its credit plus its weight for every unit, in
+                // units of the total weight. Round those down, then settle the few units the
+                // rounding leaves over by who is owed the most, as the unit-by-unit rule would
+                self.state.credits.resize(count, 0.0);
+                let owed: Vec<f64> = self
+                    .state
+                    .credits
+                    .iter()
+                    .zip(weights)
+                    .map(|(credit, weight)| (credit + units * weight.max(0.0)) / total)
+                    .collect();
+                for (share, owed) in shares.iter_mut().zip(&owed) {
+                    *share = owed.floor().max(0.0);
+                }
+                let surplus = |shares: &[f64], index: usize| owed[index] - shares[index];
+                // Rounding is off by fewer units than there are outputs
+                let gap = (units - shares.iter().sum::<f64>())
+                    .round()
+                    .clamp(-(count as f64), count as f64) as i64;
+                for _ in gap..0 {
+                    let index = (0..count)
+                        .filter(|index| shares[*index] > 0.0)
+                        .max_by(|a, b| surplus(&shares, *b).total_cmp(&surplus(&shares, *a)))
+                        .unwrap_or(0);
+                    shares[index] -= 1.0;
+                }
+                let mut last = None;
+                for _ in 0..gap {
+                    let index = (0..count)
+                        .min_by(|a, b| surplus(&shares, *b).total_cmp(&surplus(&shares, *a)))
+                        .unwrap_or(0);
+                    shares[index] += 1.0;
+                    last = Some(index);
+                }
+
+                for ((credit, weight), share) in
+                    self.state.credits.iter_mut().zip(weights).zip(&shares)
+                {
+                    *credit += units * weight.max(0.0) - share * total;
+                }
+                self.state.last_output =
+                    last.or_else(|| (0..count).max_by(|a, b| shares[*a].total_cmp(&shares[*b])));
+            }
+            // Outputs take turns from the one after the last, so each gets the same number of
+            // rounds and the first few get one unit more
+            SplitStrategy::RoundRobin | SplitStrategy::Weighted => {
+                let start = self
+                    .state
+                    .last_output
+                    .map_or(0, |index| (index + 1) % count);
+                let extra = (units % count as f64) as usize;
+                let rounds = (units - extra as f64) / count as f64;
+                for offset in 0..count {
+                    shares[(start + offset) % count] =
+                        rounds + if offset < extra { 1.0 } else { 0.0 };
+                }
+                self.state.last_output = Some((start + (extra + count - 1) % count) % count);
+            }
+            // Outputs are equally likely, so each takes its draw out of the units no earlier
+            // output took
+            SplitStrategy::Random => {
+                let seed = self.seed.unwrap_or(context.seed());
+                let rng = self.rng.get_or_insert_with(|| Rng::new(seed));
+                let mut remaining = units;
+                for (index, share) in shares.iter_mut().enumerate() {
+                    *share = if index + 1 == count {
+                        remaining
+                    } else {
+                        rng.binomial(remaining, 1.0 / (count - index) as f64)
+                    };
+                    remaining -= *share;
+                    if *share > 0.0 {
+                        self.state.last_output = Some(index);
+                    }
+                }
+            }
+        }
+
+        shares
+    }
+
+    /// Hands everything the splitter holds to its outputs, a unit at a time
+    fn distribute(&mut self, context: &ProcessContext) -> Vec<Event> {
+        let mut outputs: Vec<&Connection> = context.outputs_for_port(Some("out")).collect();
+        if outputs.is_empty() {
+            return vec![];
+        }
+        outputs.sort_by_key(|conn| conn.sequence_number);
+        let weights: Vec<f64> = outputs
+            .iter()
+            .map(|conn| conn.effective_flow_rate(context.current_time()))
+            .collect();
+
+        // Whole units are counted out per output in one go, so a large arrival costs no more
+        // than a small one; a remaining fraction then travels as a unit of its own
+        let available = self.state.available_resources();
+        let units = available.floor();
+        let fraction = available - units;
+        let mut shares = self.split_units(units, &weights, context);
+        if fraction > 1e-9 {
+            shares[self.next_output(&weights, context)] += fraction;
+        }
+
+        let mut new_events = Vec::new();
+        for (conn, share) in outputs.into_iter().zip(shares) {
+            if share <= 0.0 {
+                continue;
+            }
+            self.state.pending_outgoing_resources += share;
+            new_events.push(
+                Event::new(
+                    self.id(),
+                    &conn.target_id,
+                    context.current_time(),
+                    EventPayload::Resource(share),
+                )
+                .with_source_port("out")
+                .with_target_port(conn.target_port.clone().unwrap_or("in".to_string())),
+            );
+        }
+
+        new_events
+    }
+
+    fn handle_resource(
+        &mut self,
+        event: &Event,
+        context: &ProcessContext,
+        amount: f64,
+    ) -> Result<Vec<Event>, SimulationError> {
+        assert!(amount >= 0.0);
+
+        if context.outputs_for_port(Some("out")).next().is_none() {
+            warn!("No output connection - rejecting resources");
+            return Ok(vec![Event::new(
+                self.id(),
+                &event.source_id,
+                context.current_time(),
+                EventPayload::ResourceRejected(amount),
+            )]);
+        }
+
+        self.state.resources_received += amount;
+        let mut new_events = vec![Event::new(
+            self.id(),
+            &event.source_id,
+            context.current_time(),
+            EventPayload::ResourceAccepted(amount),
+        )];
+        new_events.extend(self.distribute(context));
+
+        Ok(new_events)
+    }
+}
+
+impl Processor for Splitter {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn priority(&self) -> i64 {
+        self.priority
+    }
+
+    fn describe_settings(&self) -> Option<String> {
+        Some(format!("{:?}", self.strategy))
+    }
+
+    fn on_events(
+        &mut self,
+        events: &[Event],
+        context: &ProcessContext,
+    ) -> Result<Vec<Event>, SimulationError> {
+        process_events_with_priority(events, context, |event, ctx| self.on_event(event, ctx))
+    }
+
+    fn on_event(
+        &mut self,
+        event: &Event,
+        context: &ProcessContext,
+    ) -> Result<Vec<Event>, SimulationError> {
+        let new_events: Vec<Event> = match &event.payload {
+            EventPayload::SimulationStart => {
+                self.initial_state.get_or_insert_with(|| self.state.clone());
+                vec![]
+            }
+            EventPayload::SimulationEnd => vec![],
+            EventPayload::Step => match self.trigger_mode {
+                TriggerMode::Passive => vec![],
+                TriggerMode::Interactive => unimplemented!(),
+                TriggerMode::Automatic => self.distribute(context),
+                TriggerMode::Enabling => {
+                    if context.current_step() == 1 {
+                        self.distribute(context)
+                    } else {
+                        vec![]
+                    }
+                }
+            },
+            EventPayload::Trigger => self.distribute(context),
+            EventPayload::Resource(amount) => self.handle_resource(event, context, *amount)?,
+            EventPayload::ResourceAccepted(amount) => {
+                self.state.pending_outgoing_resources -= amount;
+                self.state.resources_released += amount;
+                vec![]
+            }
+            EventPayload::ResourceRejected(amount) => {
+                self.state.pending_outgoing_resources -= amount;
+                vec![]
+            }
+            // Anything is accepted while there is an output to pass it on to
+            EventPayload::CapacityQuery => vec![Event::new(
+                self.id(),
+                &event.source_id,
+                context.current_time(),
+                EventPayload::CapacityResponse(
+                    if context.outputs_for_port(Some("out")).next().is_some() {
+                        f64::MAX
+                    } else {
+                        0.0
+                    },
+                ),
+            )],
+            event_payload => {
+                warn!("Unhandled event payload: {:?}", event_payload);
+                vec![]
+            }
+        };
+
+        assert!(self.state.resources_received >= 0.0);
+        assert!(self.state.resources_released >= 0.0);
+        assert!(self.state.pending_outgoing_resources >= -1e-9);
+        assert!(self.state.current_resources() >= -1e-9);
+
+        Ok(new_events)
+    }
+
+    fn get_state(&self) -> ProcessState {
+        ProcessState::Splitter(self.state.clone())
+    }
+
+    fn get_input_ports(&self) -> &[&'static str] {
+        &["in"]
+    }
+
+    fn get_output_ports(&self) -> &[&'static str] {
+        &["out"]
+    }
+
+    fn get_required_input_ports(&self) -> &[&'static str] {
+        &["in"]
+    }
+
+    fn get_required_output_ports(&self) -> &[&'static str] {
+        &["out"]
+    }
+
+    fn reset(&mut self) {
+        if let Some(initial_state) = &self.initial_state {
+            self.state = initial_state.clone();
+        }
+        self.rng = None;
+    }
+}
@@ -11,6 +11,11 @@ use crate::{
     utils::SimulationError,
 };
 
+/// Broadcasts a `Step` every `dt`, which is what drives Automatic nodes.
+///
+/// A Stepper is optional. Without one the model is purely event-driven: the clock jumps from one
+/// scheduled event to the next, and Automatic nodes only act on events they receive, such as a
+/// `Trigger` or a pull request, never on their own.
 #[derive(Builder, Debug, Clone, Serialize, Deserialize, SerializableProcess)]
 #[serde(default, rename_all = "camelCase")]
 #[builder(default)]
@@ -40,6 +45,10 @@ impl Stepper {
         StepperBuilder::default()
     }
 
+    pub fn dt(&self) -> f64 {
+        self.dt
+    }
+
     pub fn set_dt(&mut self, dt: f64) -> Result<(), SimulationError> {
         // TODO Perform this validation in builder?
         if dt <= 0.0 {
@@ -17,6 +17,25 @@ impl Process {
     pub fn new(inner: Box<dyn Processor + Send>) -> Self {
         Self { inner }
     }
+
+    /// The wrapped node as a `T`, if that is its concrete type. A `Process` wrapping another
+    /// `Process` is looked through.
+    pub fn downcast_ref<T: Processor + 'static>(&self) -> Option<&T> {
+        let inner = (*self.inner).as_any();
+        match inner.downcast_ref::<Process>() {
+            Some(process) => process.downcast_ref::<T>(),
+            None => inner.downcast_ref::<T>(),
+        }
+    }
+
+    pub fn downcast_mut<T: Processor + 'static>(&mut self) -> Option<&mut T> {
+        let inner = (*self.inner).as_any_mut();
+        if inner.is::<Process>() {
+            inner.downcast_mut::<Process>()?.downcast_mut::<T>()
+        } else {
+            inner.downcast_mut::<T>()
+        }
+    }
 }
 
 impl PartialEq for Process {
@@ -52,7 +71,16 @@ impl<'de> Deserialize<'de> for Process {
     }
 }
 
-impl SerializableProcess for Process {}
+// Forward to the wrapped process so a `Process` nested in another serializes as its node type
+impl SerializableProcess for Process {
+    fn get_type(&self) -> &'static str {
+        self.inner.get_type()
+    }
+
+    fn serialize(&self) -> serde_yaml::Value {
+        self.inner.serialize()
+    }
+}
 
 impl Processor for Process {
     fn id(&self) -> &str {
@@ -60,6 +88,26 @@ impl Processor for Process {
         self.inner.id()
     }
 
+    fn priority(&self) -> i64 {
+        self.inner.priority()
+    }
+
+    fn priority_class(&self) -> i64 {
+        self.inner.priority_class()
+    }
+
+    fn describe_settings(&self) -> Option<String> {
+        self.inner.describe_settings()
+    }
+
+    fn validate(&self) -> Result<(), SimulationError> {
+        self.inner.validate()
+    }
+
+    fn is_observer(&self) -> bool {
+        self.inner.is_observer()
+    }
+
     #[instrument(skip_all, fields(payload = ?event.payload, source = event.source_id, target = self.id(), time = event.time, sequence_number = event.sequence_number))]
     fn on_event(
         &mut self,
@@ -104,6 +152,14 @@ impl Processor for Process {
         self.inner.get_output_ports()
     }
 
+    fn get_required_input_ports(&self) -> &[&'static str] {
+        self.inner.get_required_input_ports()
+    }
+
+    fn get_required_output_ports(&self) -> &[&'static str] {
+        self.inner.get_required_output_ports()
+    }
+
     fn reset(&mut self) {
         self.inner.reset()
     }
@@ -1,4 +1,7 @@
+use std::collections::HashMap;
+
 use crate::model::connection::Connection;
+use crate::simulator::{Event, EventPayload};
 
 #[derive(Clone, Debug)]
 pub struct ProcessContext<'a> {
@@ -6,6 +9,10 @@ pub struct ProcessContext<'a> {
     pub(crate) current_time: f64,
     pub(crate) inputs: Vec<&'a Connection>,
     pub(crate) outputs: Vec<&'a Connection>,
+    pub(crate) seed: u64,
+    pub(crate) dt: f64,
+    pub(crate) priorities: Option<&'a HashMap<String, i64>>,
+    pub(crate) priority_classes: Option<&'a HashMap<String, i64>>,
 }
 
 impl<'a> Default for ProcessContext<'a> {
@@ -15,6 +22,10 @@ impl<'a> Default for ProcessContext<'a> {
             current_time: 0.0,
             inputs: vec![],
             outputs: vec![],
+            seed: 0,
+            dt: 1.0,
+            priorities: None,
+            priority_classes: None,
         }
     }
 }
@@ -31,9 +42,33 @@ impl<'a> ProcessContext<'a> {
             current_time,
             inputs,
             outputs,
+            seed: 0,
+            dt: 1.0,
+            priorities: None,
+            priority_classes: None,
         }
     }
 
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn with_dt(mut self, dt: f64) -> Self {
+        self.dt = dt;
+        self
+    }
+
+    pub fn with_priorities(mut self, priorities: &'a HashMap<String, i64>) -> Self {
+        self.priorities = Some(priorities);
+        self
+    }
+
+    pub fn with_priority_classes(mut self, priority_classes: &'a HashMap<String, i64>) -> Self {
+        self.priority_classes = Some(priority_classes);
+        self
+    }
+
     pub fn current_step(&self) -> u64 {
         self.current_step
     }
@@ -42,6 +77,41 @@ impl<'a> ProcessContext<'a> {
         self.current_time
     }
 
+    /// Seed derived for this process from the simulation seed
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn dt(&self) -> f64 {
+        self.dt
+    }
+
+    /// Priority of another process, 0 when it has none
+    pub fn priority_of(&self, process_id: &str) -> i64 {
+        self.priorities
+            .and_then(|priorities| priorities.get(process_id))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Priority class of another process's pull requests, 0 when it has none
+    pub fn priority_class_of(&self, process_id: &str) -> i64 {
+        self.priority_classes
+            .and_then(|classes| classes.get(process_id))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Event that revisits `process_id` with `EventPayload::RequestStep` one `dt` from now
+    pub fn request_step(&self, process_id: &str) -> Event {
+        Event::new(
+            process_id,
+            process_id,
+            self.current_time + self.dt,
+            EventPayload::RequestStep,
+        )
+    }
+
     pub fn inputs_for_port(&self, port: Option<&str>) -> impl Iterator<Item = &Connection> {
         let port_str = port.map(String::from);
         self.inputs
@@ -1,6 +1,7 @@
 use super::Processor;
 use serde::de;
 use serde::Deserializer;
+use serde_json::{json, Map, Value};
 use std::collections::HashMap;
 use std::sync::Mutex;
 
@@ -10,6 +11,22 @@ pub type ProcessConstructor = fn(serde_yaml::Value) -> Option<Box<dyn Processor>
 lazy_static! {
     static ref CONSTRUCTORS: Mutex<HashMap<&'static str, ProcessConstructor>> = {
         let mut m = HashMap::new();
+        m.insert(
+            "Composite",
+            super::nodes::Composite::from_value as ProcessConstructor,
+        );
+        m.insert(
+            "CompositeInput",
+            super::nodes::composite::CompositeInput::from_value as ProcessConstructor,
+        );
+        m.insert(
+            "CompositeOutput",
+            super::nodes::composite::CompositeOutput::from_value as ProcessConstructor,
+        );
+        m.insert(
+            "Converter",
+            super::nodes::Converter::from_value as ProcessConstructor,
+        );
         m.insert(
             "Delay",
             super::nodes::Delay::from_value as ProcessConstructor,
@@ -18,11 +35,24 @@ lazy_static! {
             "Drain",
             super::nodes::Drain::from_value as ProcessConstructor,
         );
+        m.insert("Gate", super::nodes::Gate::from_value as ProcessConstructor);
+        m.insert(
+            "Merger",
+            super::nodes::Merger::from_value as ProcessConstructor,
+        );
         m.insert("Pool", super::nodes::Pool::from_value as ProcessConstructor);
+        m.insert(
+            "Probe",
+            super::nodes::Probe::from_value as ProcessConstructor,
+        );
         m.insert(
             "Source",
             super::nodes::Source::from_value as ProcessConstructor,
         );
+        m.insert(
+            "Splitter",
+            super::nodes::Splitter::from_value as ProcessConstructor,
+        );
         m.insert(
             "Stepper",
             super::nodes::Stepper::from_value as ProcessConstructor,
@@ -33,8 +63,7 @@ lazy_static! {
         CONSTRUCTORS
             .lock()
             .unwrap()
-            .iter()
-            .map(|(k, _)| k)
+            .keys()
             .copied()
             .collect::<Vec<_>>()
     };
@@ -61,3 +90,66 @@ pub fn create_process<'de, D: Deserializer<'de>>(
         None => Err(de::Error::unknown_variant(process_type, &VARIANTS)),
     }
 }
+
+/// Describes the fields a registered process type accepts, with their JSON types and
+/// defaults, derived from the serialized form of a default instance.
+pub fn type_schema(process_type: &str) -> Option<Value> {
+    let constructor = *CONSTRUCTORS.lock().unwrap().get(process_type)?;
+    let process = constructor(serde_yaml::Value::Mapping(Default::default()))?;
+    let Ok(Value::Object(fields)) = serde_json::to_value(process.serialize()) else {
+        return None;
+    };
+
+    let properties: Map<String, Value> = fields
+        .into_iter()
+        .map(|(name, default)| {
+            let field_type = match &default {
+                Value::Null => "null",
+                Value::Bool(_) => "boolean",
+                Value::Number(n) if n.is_f64() => "number",
+                Value::Number(_) => "integer",
+                Value::String(_) => "string",
+                Value::Array(_) => "array",
+                Value::Object(_) => "object",
+            };
+            (name, json!({ "type": field_type, "default": default }))
+        })
+        .collect();
+
+    Some(json!({
+        "title": process_type,
+        "type": "object",
+        "properties": properties,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pool_type_schema() {
+        let schema = type_schema("Pool").unwrap();
+        assert_eq!(schema["title"], "Pool");
+
+        let properties = &schema["properties"];
+        assert_eq!(
+            properties["capacity"],
+            json!({ "type": "number", "default": -1.0 })
+        );
+        assert_eq!(
+            properties["overflow"],
+            json!({ "type": "string", "default": "Block" })
+        );
+        assert_eq!(
+            properties["action"],
+            json!({ "type": "string", "default": "PullAny" })
+        );
+        assert_eq!(
+            properties["triggerMode"],
+            json!({ "type": "string", "default": "Passive" })
+        );
+
+        assert!(type_schema("Unknown").is_none());
+    }
+}
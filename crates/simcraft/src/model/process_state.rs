@@ -1,3 +1,5 @@
+use std::collections::BTreeMap;
+
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
@@ -21,11 +23,20 @@ impl PoolState {
 #[derive(Default, Debug, Clone, Serialize, Deserialize)]
 pub struct SourceState {
     pub resources_produced: f64,
+    /// Whether a batch source has already let out its batch
+    #[serde(default)]
+    pub batch_released: bool,
 }
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct DrainState {
     pub resources_consumed: f64,
+    /// Input the next round-robin pull starts from, as an offset into the drain's inputs
+    #[serde(default)]
+    pub rotation: usize,
+    /// Consumed since the last step, counted against a rate limit
+    #[serde(default)]
+    pub consumed_this_step: f64,
 }
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
@@ -33,6 +44,13 @@ pub struct DelayState {
     pub resources_received: f64,
     pub resources_released: f64,
     pub pending_outgoing_resources: f64,
+    /// Resources already in flight before the run, as `(amount, release time)` pairs,
+    /// scheduled for release when the simulation starts
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub backlog: Vec<(f64, f64)>,
+    /// Transfers sent on but not yet acknowledged downstream, as `(amount, release time)` pairs
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub in_flight: Vec<(f64, f64)>,
 }
 
 impl DelayState {
@@ -43,6 +61,28 @@ impl DelayState {
     pub fn available_resources(&self) -> f64 {
         (self.current_resources() - self.pending_outgoing_resources).max(0.0)
     }
+
+    /// Settles an acknowledged `amount` against the in-flight transfers due by `time`,
+    /// earliest first. Transfers carry no identity of their own, so a partial acceptance
+    /// and its rejection each settle part of the same entry.
+    pub(crate) fn settle_in_flight(&mut self, mut amount: f64, time: f64) {
+        let mut index = 0;
+        while amount > 0.0 && index < self.in_flight.len() {
+            let (held, release_time) = &mut self.in_flight[index];
+            if *release_time > time {
+                index += 1;
+                continue;
+            }
+            let settled = held.min(amount);
+            *held -= settled;
+            amount -= settled;
+            if *held <= 1e-9 {
+                self.in_flight.remove(index);
+            } else {
+                index += 1;
+            }
+        }
+    }
 }
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
@@ -57,6 +97,102 @@ impl QueueState {
     }
 }
 
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CompositeState {
+    pub resources_received: f64,
+    pub resources_released: f64,
+    pub outgoing_resources: f64,
+    pub pending_outgoing_resources: f64,
+}
+
+impl CompositeState {
+    /// Resources inside the composite, whether in its inner model or awaiting release
+    pub fn current_resources(&self) -> f64 {
+        self.resources_received - self.resources_released
+    }
+
+    pub fn available_resources(&self) -> f64 {
+        (self.outgoing_resources - self.pending_outgoing_resources).max(0.0)
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GateState {
+    pub resources_received: f64,
+    pub resources_released: f64,
+    pub pending_outgoing_resources: f64,
+}
+
+impl GateState {
+    /// Resources the gate holds, which is only what its outputs have turned away
+    pub fn current_resources(&self) -> f64 {
+        self.resources_received - self.resources_released
+    }
+
+    pub fn available_resources(&self) -> f64 {
+        (self.current_resources() - self.pending_outgoing_resources).max(0.0)
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SplitterState {
+    pub resources_received: f64,
+    pub resources_released: f64,
+    pub pending_outgoing_resources: f64,
+    /// Index of the output that took the last unit, in connection order
+    pub last_output: Option<usize>,
+    /// Running credit per output under `SplitStrategy::Weighted`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub credits: Vec<f64>,
+}
+
+impl SplitterState {
+    /// Resources the splitter holds, which is only what its outputs have turned away
+    pub fn current_resources(&self) -> f64 {
+        self.resources_received - self.resources_released
+    }
+
+    pub fn available_resources(&self) -> f64 {
+        (self.current_resources() - self.pending_outgoing_resources).max(0.0)
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MergerState {
+    /// Arrivals held per source until every input can cover a merge
+    pub buffered: BTreeMap<String, f64>,
+    pub resources_consumed: f64,
+    pub resources_produced: f64,
+    pub merges: u64,
+}
+
+impl MergerState {
+    pub fn current_resources(&self) -> f64 {
+        self.buffered.values().sum()
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ConverterState {
+    /// Inputs held per port until they complete a batch
+    pub buffered: BTreeMap<String, f64>,
+    pub resources_consumed: f64,
+    pub resources_produced: f64,
+    pub batches: u64,
+}
+
+impl ConverterState {
+    pub fn current_resources(&self) -> f64 {
+        self.buffered.values().sum()
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProbeState {
+    /// The observed level as `(time, value)`, one sample per step
+    pub samples: Vec<(f64, f64)>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ProcessState {
     Source(SourceState),
@@ -65,5 +201,11 @@ pub enum ProcessState {
     Delay(DelayState),
     Queue(QueueState),
     Stepper(StepperState),
+    Composite(CompositeState),
+    Converter(ConverterState),
+    Gate(GateState),
+    Splitter(SplitterState),
+    Merger(MergerState),
+    Probe(ProbeState),
     Custom(Value),
 }
@@ -1,3 +1,4 @@
+use std::any::Any;
 use std::fmt::Debug;
 
 use super::{ProcessContext, ProcessState};
@@ -5,6 +6,11 @@ use crate::{simulator::Event, utils::SimulationError};
 
 pub trait ProcessClone: Send + Debug {
     fn clone_box(&self) -> Box<dyn Processor + Send>;
+
+    /// The concrete node behind a `dyn Processor`, for downcasting
+    fn as_any(&self) -> &dyn Any;
+
+    fn as_any_mut(&mut self) -> &mut dyn Any;
 }
 
 impl<T> ProcessClone for T
@@ -14,6 +20,14 @@ where
     fn clone_box(&self) -> Box<dyn Processor + Send> {
         Box::new(self.clone())
     }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
 }
 
 impl Clone for Box<dyn Processor + Send> {
@@ -34,6 +48,36 @@ pub trait SerializableProcess {
 
 pub trait Processor: ProcessClone + SerializableProcess {
     fn id(&self) -> &str;
+
+    /// Processes reacting at the same time act in descending priority,
+    /// ahead of the default ordering by connection sequence.
+    fn priority(&self) -> i64 {
+        0
+    }
+
+    /// Class of demand this process's pull requests belong to. A scarce supplier serves
+    /// higher classes in full before lower ones, whatever their priority.
+    fn priority_class(&self) -> i64 {
+        0
+    }
+
+    /// Key settings shown alongside the process in messages, e.g. "capacity 10, overflow Drain"
+    fn describe_settings(&self) -> Option<String> {
+        None
+    }
+
+    /// Checks settings the process can't act on, such as an action its node type doesn't
+    /// perform. Called when the process is added to or replaced in a simulation.
+    fn validate(&self) -> Result<(), SimulationError> {
+        Ok(())
+    }
+
+    /// Whether the process only watches the processes linked to it, through their
+    /// `OBSERVE_PORT`, rather than taking part in their flows
+    fn is_observer(&self) -> bool {
+        false
+    }
+
     fn on_event(
         &mut self,
         event: &Event,
@@ -58,5 +102,16 @@ pub trait Processor: ProcessClone + SerializableProcess {
     fn get_state(&self) -> ProcessState;
     fn get_input_ports(&self) -> &[&'static str];
     fn get_output_ports(&self) -> &[&'static str];
+
+    /// Input ports that must have at least one connection for the process to do anything useful.
+    fn get_required_input_ports(&self) -> &[&'static str] {
+        &[]
+    }
+
+    /// Output ports that must have at least one connection for the process to do anything useful.
+    fn get_required_output_ports(&self) -> &[&'static str] {
+        &[]
+    }
+
     fn reset(&mut self);
 }
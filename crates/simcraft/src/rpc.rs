@@ -0,0 +1,280 @@
+//! A language-agnostic command interface to the engine.
+//!
+//! Hosts that cannot call Rust directly send `RpcCommand`s as JSON and get `RpcResponse`s
+//! back, through `handle_command`. Commands address simulations held by a
+//! `SimulationManager` by the id returned when they were created.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::api;
+use crate::model::{Connection, Process};
+use crate::simulator::{Event, RunConfiguration, Simulation, SimulationState};
+
+/// The simulations an embedding host is running, keyed by id
+#[derive(Debug, Default)]
+pub struct SimulationManager {
+    simulations: HashMap<String, Simulation>,
+    /// Number of simulations created so far, used to give each a fresh id
+    created: u64,
+}
+
+impl SimulationManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Takes over `simulation`, returning the id it is now known by
+    pub fn insert(&mut self, simulation: Simulation) -> String {
+        self.created += 1;
+        let id = format!("simulation-{}", self.created);
+        self.simulations.insert(id.clone(), simulation);
+        id
+    }
+
+    pub fn get(&self, simulation_id: &str) -> Result<&Simulation, String> {
+        self.simulations
+            .get(simulation_id)
+            .ok_or_else(|| format!("Simulation '{}' not found", simulation_id))
+    }
+
+    pub fn get_mut(&mut self, simulation_id: &str) -> Result<&mut Simulation, String> {
+        self.simulations
+            .get_mut(simulation_id)
+            .ok_or_else(|| format!("Simulation '{}' not found", simulation_id))
+    }
+
+    pub fn remove(&mut self, simulation_id: &str) -> Result<Simulation, String> {
+        self.simulations
+            .remove(simulation_id)
+            .ok_or_else(|| format!("Simulation '{}' not found", simulation_id))
+    }
+
+    pub fn ids(&self) -> Vec<String> {
+        let mut ids: Vec<String> = self.simulations.keys().cloned().collect();
+        ids.sort();
+        ids
+    }
+
+    pub fn len(&self) -> usize {
+        self.simulations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.simulations.is_empty()
+    }
+}
+
+/// A request to the engine, tagged by `command` in JSON, e.g.
+/// `{"command": "stepN", "simulationId": "simulation-1", "n": 10}`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(
+    tag = "command",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum RpcCommand {
+    Create {
+        processes: Vec<Process>,
+        connections: Vec<Connection>,
+        #[serde(default)]
+        config: Option<RunConfiguration>,
+    },
+    /// Builds a simulation from a JSON model, which may leave out connection ids and flow rates
+    Load {
+        model: String,
+    },
+    Destroy {
+        simulation_id: String,
+    },
+    List,
+    Step {
+        simulation_id: String,
+    },
+    StepN {
+        simulation_id: String,
+        n: usize,
+    },
+    StepUntil {
+        simulation_id: String,
+        until: f64,
+    },
+    Reset {
+        simulation_id: String,
+    },
+    GetState {
+        simulation_id: String,
+    },
+    GetProcesses {
+        simulation_id: String,
+    },
+    AddProcess {
+        simulation_id: String,
+        process: Process,
+    },
+    UpdateProcess {
+        simulation_id: String,
+        process_id: String,
+        process: Process,
+    },
+    RemoveProcess {
+        simulation_id: String,
+        process_id: String,
+    },
+    /// Every connection, in the order they were added
+    GetConnections {
+        simulation_id: String,
+    },
+    AddConnection {
+        simulation_id: String,
+        connection: Connection,
+    },
+    UpdateConnection {
+        simulation_id: String,
+        connection_id: String,
+        connection: Connection,
+    },
+    RemoveConnection {
+        simulation_id: String,
+        connection_id: String,
+    },
+    SetConnectionFlowRate {
+        simulation_id: String,
+        connection_id: String,
+        flow_rate: Option<f64>,
+    },
+    GetCurrentStep {
+        simulation_id: String,
+    },
+    GetCurrentTime {
+        simulation_id: String,
+    },
+}
+
+/// The engine's answer to an `RpcCommand`, tagged by `type` with any payload under `result`
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", content = "result", rename_all = "camelCase")]
+pub enum RpcResponse {
+    /// The command succeeded and has nothing to return
+    Ok,
+    Created(String),
+    Ids(Vec<String>),
+    Events(Vec<Event>),
+    State(SimulationState),
+    Processes(Vec<Process>),
+    Connections(Vec<Connection>),
+    Step(u64),
+    Time(f64),
+    Error(String),
+}
+
+/// Carries out `cmd` against the simulations in `state`
+pub fn handle_command(state: &mut SimulationManager, cmd: RpcCommand) -> RpcResponse {
+    dispatch(state, cmd).unwrap_or_else(RpcResponse::Error)
+}
+
+fn dispatch(state: &mut SimulationManager, cmd: RpcCommand) -> Result<RpcResponse, String> {
+    let response = match cmd {
+        RpcCommand::Create {
+            processes,
+            connections,
+            config,
+        } => {
+            let simulation = api::create(processes, connections, config.unwrap_or_default())?;
+            RpcResponse::Created(state.insert(simulation))
+        }
+        RpcCommand::Load { model } => RpcResponse::Created(state.insert(api::load(&model)?)),
+        RpcCommand::Destroy { simulation_id } => {
+            state.remove(&simulation_id)?;
+            RpcResponse::Ok
+        }
+        RpcCommand::List => RpcResponse::Ids(state.ids()),
+        RpcCommand::Step { simulation_id } => {
+            RpcResponse::Events(api::step(state.get_mut(&simulation_id)?)?)
+        }
+        RpcCommand::StepN { simulation_id, n } => {
+            RpcResponse::Events(api::step_n(state.get_mut(&simulation_id)?, n)?)
+        }
+        RpcCommand::StepUntil {
+            simulation_id,
+            until,
+        } => RpcResponse::Events(api::step_until(state.get_mut(&simulation_id)?, until)?),
+        RpcCommand::Reset { simulation_id } => {
+            api::reset(state.get_mut(&simulation_id)?)?;
+            RpcResponse::Ok
+        }
+        RpcCommand::GetState { simulation_id } => {
+            RpcResponse::State(api::state(state.get(&simulation_id)?))
+        }
+        RpcCommand::GetProcesses { simulation_id } => {
+            RpcResponse::Processes(api::processes(state.get(&simulation_id)?))
+        }
+        RpcCommand::AddProcess {
+            simulation_id,
+            process,
+        } => {
+            api::add_process(state.get_mut(&simulation_id)?, process)?;
+            RpcResponse::Ok
+        }
+        RpcCommand::UpdateProcess {
+            simulation_id,
+            process_id,
+            process,
+        } => {
+            api::update_process(state.get_mut(&simulation_id)?, &process_id, process)?;
+            RpcResponse::Ok
+        }
+        RpcCommand::RemoveProcess {
+            simulation_id,
+            process_id,
+        } => {
+            api::remove_process(state.get_mut(&simulation_id)?, &process_id)?;
+            RpcResponse::Ok
+        }
+        RpcCommand::GetConnections { simulation_id } => {
+            RpcResponse::Connections(api::connections(state.get(&simulation_id)?))
+        }
+        RpcCommand::AddConnection {
+            simulation_id,
+            connection,
+        } => {
+            api::add_connection(state.get_mut(&simulation_id)?, connection)?;
+            RpcResponse::Ok
+        }
+        RpcCommand::UpdateConnection {
+            simulation_id,
+            connection_id,
+            connection,
+        } => {
+            api::update_connection(state.get_mut(&simulation_id)?, &connection_id, connection)?;
+            RpcResponse::Ok
+        }
+        RpcCommand::RemoveConnection {
+            simulation_id,
+            connection_id,
+        } => {
+            api::remove_connection(state.get_mut(&simulation_id)?, &connection_id)?;
+            RpcResponse::Ok
+        }
+        RpcCommand::SetConnectionFlowRate {
+            simulation_id,
+            connection_id,
+            flow_rate,
+        } => {
+            api::set_connection_flow_rate(
+                state.get_mut(&simulation_id)?,
+                &connection_id,
+                flow_rate,
+            )?;
+            RpcResponse::Ok
+        }
+        RpcCommand::GetCurrentStep { simulation_id } => {
+            RpcResponse::Step(state.get(&simulation_id)?.current_step())
+        }
+        RpcCommand::GetCurrentTime { simulation_id } => {
+            RpcResponse::Time(state.get(&simulation_id)?.current_time())
+        }
+    };
+
+    Ok(response)
+}
@@ -0,0 +1,80 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::model::connection::{Connection, DEFAULT_FLOW_RATE};
+use crate::model::Process;
+use crate::utils::SimulationError;
+
+/// Serialisable description of a model: its processes and the connections between them.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SimulationDefinition {
+    pub processes: Vec<Process>,
+    pub connections: Vec<Connection>,
+}
+
+impl SimulationDefinition {
+    pub fn from_yaml(s: &str) -> Result<Self, SimulationError> {
+        serde_yaml::from_str(s).map_err(|e| SimulationError::Other(e.to_string()))
+    }
+
+    pub fn from_json(s: &str) -> Result<Self, SimulationError> {
+        serde_json::from_str(s).map_err(|e| SimulationError::Other(e.to_string()))
+    }
+
+    /// Fills in what a hand-written or client-built model may leave out: connections without an
+    /// id are named `conn_<source>_<target>`, numbered when that is taken, and connections
+    /// without a flow rate get the default rate.
+    pub fn normalize(&mut self) {
+        let mut taken: Vec<String> = self
+            .connections
+            .iter()
+            .filter(|conn| !conn.id.is_empty())
+            .map(|conn| conn.id.clone())
+            .collect();
+
+        for conn in &mut self.connections {
+            if conn.id.is_empty() {
+                let base = format!("conn_{}_{}", conn.source_id, conn.target_id);
+                let mut id = base.clone();
+                let mut suffix = 1;
+                while taken.contains(&id) {
+                    suffix += 1;
+                    id = format!("{}_{}", base, suffix);
+                }
+                taken.push(id.clone());
+                conn.id = id;
+            }
+            conn.flow_rate.get_or_insert(DEFAULT_FLOW_RATE);
+        }
+    }
+}
+
+/// Replaces every `${name}` token in `template` with the matching parameter value.
+pub fn substitute_params(
+    template: &str,
+    params: &HashMap<String, f64>,
+) -> Result<String, SimulationError> {
+    let mut output = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find("${") {
+        output.push_str(&rest[..start]);
+
+        let after = &rest[start + 2..];
+        let end = after.find('}').ok_or_else(|| {
+            SimulationError::Other(format!("Unterminated parameter in '{}'", &rest[start..]))
+        })?;
+
+        let name = after[..end].trim();
+        let value = params
+            .get(name)
+            .ok_or_else(|| SimulationError::UnknownParameter(name.to_string()))?;
+        output.push_str(&value.to_string());
+
+        rest = &after[end + 1..];
+    }
+
+    output.push_str(rest);
+    Ok(output)
+}
@@ -0,0 +1,27 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use super::event::Event;
+use super::run_configuration::RunConfiguration;
+use super::simulation_context::ConnectionStats;
+use crate::model::{Connection, Process};
+
+/// A running simulation laid out flat, for saving to JSON or YAML and resuming with
+/// `Simulation::from_document`.
+///
+/// Processes are ordered by id and connections by creation order. Unlike a snapshot it leaves
+/// out recorded history, metadata and anything derived from it.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct SimulationDocument {
+    pub processes: Vec<Process>,
+    pub connections: Vec<Connection>,
+    pub config: RunConfiguration,
+    pub step: u64,
+    pub time: f64,
+    /// Events still to be processed, in processing order
+    pub events: Vec<Event>,
+    /// What each connection has carried so far, by connection id
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub connection_stats: HashMap<String, ConnectionStats>,
+}
@@ -14,6 +14,16 @@ pub enum EventPayload {
     Custom(String),
     PullRequest,
     PullAllRequest,
+    /// Self-addressed request to be revisited one `dt` later, independent of the global stepper
+    RequestStep,
+    /// Asks a receiver how much it could accept right now, ahead of a transfer
+    CapacityQuery,
+    /// Answer to a `CapacityQuery`: the room left at the receiver, `f64::MAX` when unbounded
+    CapacityResponse(f64),
+    /// Asks a process for the resources it holds, answered by the simulation on its behalf
+    LevelQuery,
+    /// Answer to a `LevelQuery`: what the process held once it had handled the rest of the batch
+    LevelResponse(f64),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -25,6 +35,18 @@ pub struct Event {
     pub time: f64,
     pub payload: EventPayload,
     pub sequence_number: u64,
+    /// Position of a transfer in its sender's dispatch order, for senders that may deliver
+    /// out of order
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dispatch_index: Option<u64>,
+    /// Lowest dispatch index from the same sender that may still arrive after this transfer.
+    /// Every earlier dispatch has already been delivered.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dispatch_floor: Option<u64>,
+    /// Most a pull request asks for, when the requester has less room than the connection's
+    /// flow rate
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pull_limit: Option<f64>,
 }
 
 impl Ord for Event {
@@ -67,6 +89,9 @@ impl Event {
             source_port: None,
             target_port: None,
             sequence_number: 0,
+            dispatch_index: None,
+            dispatch_floor: None,
+            pull_limit: None,
         }
     }
 
@@ -80,6 +105,21 @@ impl Event {
         self
     }
 
+    pub fn with_dispatch_index(mut self, dispatch_index: u64) -> Self {
+        self.dispatch_index = Some(dispatch_index);
+        self
+    }
+
+    pub fn with_dispatch_floor(mut self, dispatch_floor: u64) -> Self {
+        self.dispatch_floor = Some(dispatch_floor);
+        self
+    }
+
+    pub fn with_pull_limit(mut self, pull_limit: f64) -> Self {
+        self.pull_limit = Some(pull_limit);
+        self
+    }
+
     pub fn with_ports(
         mut self,
         source_port: impl Into<String>,
@@ -110,6 +150,9 @@ mod tests {
                 source_port: None,
                 target_port: None,
                 sequence_number: 1,
+                dispatch_index: None,
+                dispatch_floor: None,
+                pull_limit: None,
             },
             Event {
                 source_id: "test".into(),
@@ -119,6 +162,9 @@ mod tests {
                 source_port: None,
                 target_port: None,
                 sequence_number: 2,
+                dispatch_index: None,
+                dispatch_floor: None,
+                pull_limit: None,
             },
             Event {
                 source_id: "test".into(),
@@ -128,6 +174,9 @@ mod tests {
                 source_port: None,
                 target_port: None,
                 sequence_number: 1,
+                dispatch_index: None,
+                dispatch_floor: None,
+                pull_limit: None,
             },
         ]);
 
@@ -0,0 +1,48 @@
+use std::fmt;
+use std::sync::Arc;
+
+/// Structural change to a simulation's model.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LifecycleEvent {
+    ProcessAdded(String),
+    ProcessRemoved(String),
+    ConnectionAdded(String),
+    ConnectionRemoved(String),
+    Reset,
+}
+
+pub type LifecycleCallback = Arc<dyn Fn(&LifecycleEvent) + Send + Sync>;
+
+/// Callbacks notified of every `LifecycleEvent`, in registration order.
+#[derive(Clone, Default)]
+pub struct LifecycleHooks {
+    callbacks: Vec<LifecycleCallback>,
+}
+
+impl LifecycleHooks {
+    pub fn register(&mut self, callback: LifecycleCallback) {
+        self.callbacks.push(callback);
+    }
+
+    pub fn len(&self) -> usize {
+        self.callbacks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.callbacks.is_empty()
+    }
+
+    pub(crate) fn notify(&self, event: LifecycleEvent) {
+        for callback in &self.callbacks {
+            callback(&event);
+        }
+    }
+}
+
+impl fmt::Debug for LifecycleHooks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LifecycleHooks")
+            .field("callbacks", &self.callbacks.len())
+            .finish()
+    }
+}
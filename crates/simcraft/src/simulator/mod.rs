@@ -1,12 +1,30 @@
+pub mod definition;
+pub mod document;
 pub mod event;
+pub mod lifecycle;
+pub mod model_op;
+pub mod recorder;
+pub mod replay;
+pub mod run_configuration;
+pub mod scheduler;
 pub mod simulation;
 pub mod simulation_context;
 pub mod simulation_state;
 pub mod simulation_trait;
+pub mod watchers;
 
+pub use definition::SimulationDefinition;
+pub use document::SimulationDocument;
 pub use event::Event;
 pub use event::EventPayload;
+pub use lifecycle::LifecycleEvent;
+pub use model_op::ModelOp;
+pub use recorder::{LossReport, Recorder};
+pub use replay::ReplayMismatch;
+pub use run_configuration::RunConfiguration;
+pub use scheduler::{BinaryHeapScheduler, CalendarScheduler, Scheduler};
 pub use simulation::Simulation;
-pub use simulation_state::SimulationState;
+pub use simulation_context::{CascadePolicy, ConnectionStats, DuplexPolicy};
+pub use simulation_state::{SimulationState, StateDelta};
 pub use simulation_trait::Simulate;
 pub use simulation_trait::StatefulSimulation;
@@ -0,0 +1,16 @@
+use serde::{Deserialize, Serialize};
+
+use crate::model::{connection::Connection, Process};
+
+/// A single structural edit to a simulation's model, as applied in batches by
+/// `Simulation::apply`. Serializable, so an editing session can be logged and replayed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "camelCase")]
+pub enum ModelOp {
+    AddProcess { process: Process },
+    UpdateProcess { id: String, process: Process },
+    RemoveProcess { id: String },
+    AddConnection { connection: Connection },
+    UpdateConnection { id: String, connection: Connection },
+    RemoveConnection { id: String },
+}
@@ -0,0 +1,113 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use super::{Event, SimulationState};
+
+/// Buffers of processed events and state snapshots, populated only while recording is enabled.
+#[derive(Clone, Default, Debug, Serialize, Deserialize)]
+pub struct Recorder {
+    pub(crate) enabled: bool,
+    pub(crate) events: Vec<Event>,
+    pub(crate) states: Vec<SimulationState>,
+    #[serde(default)]
+    pub(crate) losses: LossReport,
+    /// Keep a state snapshot only every this many steps; 0 and 1 both keep every step
+    #[serde(default)]
+    pub(crate) sample_interval: usize,
+}
+
+/// Resources turned away while recording, keyed by the process that rejected them and by
+/// the connection they were offered along.
+#[derive(Clone, Default, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LossReport {
+    pub total: f64,
+    pub by_process: HashMap<String, f64>,
+    pub by_connection: HashMap<String, f64>,
+}
+
+impl Recorder {
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn events(&self) -> &[Event] {
+        &self.events
+    }
+
+    pub fn states(&self) -> &[SimulationState] {
+        &self.states
+    }
+
+    pub fn losses(&self) -> &LossReport {
+        &self.losses
+    }
+
+    pub fn sample_interval(&self) -> usize {
+        self.sample_interval.max(1)
+    }
+
+    /// Returns an enabled or disabled recorder with empty buffers.
+    pub(crate) fn empty_like(&self) -> Self {
+        Self {
+            enabled: self.enabled,
+            sample_interval: self.sample_interval,
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.events.clear();
+        self.states.clear();
+        self.losses = LossReport::default();
+    }
+
+    /// Releases memory held beyond what the buffers currently contain
+    pub(crate) fn shrink_to_fit(&mut self) {
+        self.events.shrink_to_fit();
+        self.states.shrink_to_fit();
+        self.losses.by_process.shrink_to_fit();
+        self.losses.by_connection.shrink_to_fit();
+    }
+
+    pub(crate) fn record_event(&mut self, event: &Event) {
+        if self.enabled {
+            self.events.push(event.clone());
+        }
+    }
+
+    pub(crate) fn record_loss(
+        &mut self,
+        process_id: &str,
+        connection_id: Option<&str>,
+        amount: f64,
+    ) {
+        if !self.enabled {
+            return;
+        }
+        self.losses.total += amount;
+        *self
+            .losses
+            .by_process
+            .entry(process_id.to_string())
+            .or_default() += amount;
+        if let Some(connection_id) = connection_id {
+            *self
+                .losses
+                .by_connection
+                .entry(connection_id.to_string())
+                .or_default() += amount;
+        }
+    }
+
+    /// Whether the state after `step` falls on the sampling interval and should be recorded
+    pub(crate) fn samples_step(&self, step: u64) -> bool {
+        self.enabled && step.is_multiple_of(self.sample_interval() as u64)
+    }
+
+    pub(crate) fn record_state(&mut self, state: SimulationState) {
+        if self.enabled {
+            self.states.push(state);
+        }
+    }
+}
@@ -0,0 +1,50 @@
+use thiserror::Error;
+
+use super::event::Event;
+use crate::utils::errors::SimulationError;
+
+/// Where a re-run first departs from a recorded event stream, as reported by
+/// `Simulation::verify_replay`
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum ReplayMismatch {
+    #[error("Event {index} differs: recorded {expected:?}, replayed {actual:?}")]
+    Diverged {
+        index: usize,
+        expected: Box<Event>,
+        actual: Box<Event>,
+    },
+    #[error("Replay ended after {replayed} of {recorded} recorded events")]
+    Truncated { recorded: usize, replayed: usize },
+    #[error("Replay failed: {0}")]
+    Failed(SimulationError),
+}
+
+/// Whether two events agree on time, endpoints and payload; sequence numbers and ports are
+/// bookkeeping and may differ between runs
+fn same_event(a: &Event, b: &Event) -> bool {
+    a.time == b.time
+        && a.source_id == b.source_id
+        && a.target_id == b.target_id
+        && a.payload == b.payload
+}
+
+/// First difference between a recorded stream and a replayed one. Replayed events beyond the
+/// end of the recording are not compared.
+pub(crate) fn compare(recorded: &[Event], replayed: &[Event]) -> Result<(), ReplayMismatch> {
+    for (index, expected) in recorded.iter().enumerate() {
+        let Some(actual) = replayed.get(index) else {
+            return Err(ReplayMismatch::Truncated {
+                recorded: recorded.len(),
+                replayed: replayed.len(),
+            });
+        };
+        if !same_event(expected, actual) {
+            return Err(ReplayMismatch::Diverged {
+                index,
+                expected: Box::new(expected.clone()),
+                actual: Box::new(actual.clone()),
+            });
+        }
+    }
+    Ok(())
+}
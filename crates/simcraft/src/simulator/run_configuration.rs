@@ -0,0 +1,46 @@
+use serde::{Deserialize, Serialize};
+
+use super::simulation_context::{CascadePolicy, DuplexPolicy};
+use crate::utils::SimulationError;
+
+/// Runtime settings kept apart from the model, so a run can be reproduced from a
+/// serialized model and its configuration.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct RunConfiguration {
+    /// Interval after which a process asking for `EventPayload::RequestStep` is revisited
+    pub dt: f64,
+    /// Seed from which every stochastic process derives its own random stream
+    pub seed: u64,
+    /// Fail on the first pre-run diagnostic instead of logging a warning
+    pub strict: bool,
+    pub duplex_policy: DuplexPolicy,
+    pub cascade_policy: CascadePolicy,
+    /// Clock reading the run starts from, so times can be absolute, e.g. relative to an epoch
+    pub start_time: f64,
+}
+
+impl Default for RunConfiguration {
+    fn default() -> Self {
+        Self {
+            dt: 1.0,
+            seed: 0,
+            strict: false,
+            duplex_policy: DuplexPolicy::default(),
+            cascade_policy: CascadePolicy::default(),
+            start_time: 0.0,
+        }
+    }
+}
+
+impl RunConfiguration {
+    pub fn validate(&self) -> Result<(), SimulationError> {
+        if !(self.dt > 0.0 && self.dt.is_finite()) {
+            return Err(SimulationError::InvalidDt(self.dt));
+        }
+        if !self.start_time.is_finite() {
+            return Err(SimulationError::InvalidStartTime(self.start_time));
+        }
+        Ok(())
+    }
+}
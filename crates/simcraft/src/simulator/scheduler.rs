@@ -0,0 +1,342 @@
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BinaryHeap, HashMap};
+use std::fmt::Debug;
+
+use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+use super::Event;
+use crate::utils::errors::SimulationError;
+
+pub trait SchedulerClone: Send + Debug {
+    fn clone_box(&self) -> Box<dyn Scheduler>;
+}
+
+impl<T> SchedulerClone for T
+where
+    T: 'static + Scheduler + Clone + Send + Debug,
+{
+    fn clone_box(&self) -> Box<dyn Scheduler> {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for Box<dyn Scheduler> {
+    fn clone(&self) -> Box<dyn Scheduler> {
+        self.clone_box()
+    }
+}
+
+/// Future event list used by a `Simulation`.
+///
+/// Implementations must pop events in `Event` order: earliest time first,
+/// ties broken by the lowest sequence number.
+pub trait Scheduler: SchedulerClone {
+    fn push(&mut self, event: Event);
+    fn pop(&mut self) -> Option<Event>;
+    fn peek(&self) -> Option<&Event>;
+    fn len(&self) -> usize;
+    fn clear(&mut self);
+
+    /// Removes every scheduled event, returned in pop order
+    fn drain(&mut self) -> Vec<Event> {
+        let mut events = Vec::with_capacity(self.len());
+        while let Some(event) = self.pop() {
+            events.push(event);
+        }
+        events
+    }
+
+    /// All scheduled events, in no particular order
+    fn events(&self) -> &[Event];
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Width of the time buckets for schedulers that group events into them, kept when a
+    /// simulation is saved so it restores into the same kind of scheduler
+    fn bucket_width(&self) -> Option<f64> {
+        None
+    }
+}
+
+impl Default for Box<dyn Scheduler> {
+    fn default() -> Self {
+        Box::new(BinaryHeapScheduler::default())
+    }
+}
+
+/// Pending events as they are saved: a plain list for the default scheduler, or with the
+/// bucket width of a calendar queue. Any other scheduler is restored as the default.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum SavedScheduler<E> {
+    BinaryHeap(Vec<E>),
+    Calendar {
+        #[serde(rename = "bucketWidth")]
+        bucket_width: f64,
+        events: Vec<E>,
+    },
+}
+
+impl Serialize for Box<dyn Scheduler> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut events: Vec<&Event> = self.events().iter().collect();
+        events.sort_by(|a, b| b.cmp(a));
+        match self.bucket_width() {
+            Some(bucket_width) => SavedScheduler::Calendar {
+                bucket_width,
+                events,
+            },
+            None => SavedScheduler::BinaryHeap(events),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Box<dyn Scheduler> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let (mut scheduler, events): (Box<dyn Scheduler>, _) =
+            match SavedScheduler::<Event>::deserialize(deserializer)? {
+                SavedScheduler::BinaryHeap(events) => {
+                    (Box::new(BinaryHeapScheduler::default()), events)
+                }
+                SavedScheduler::Calendar {
+                    bucket_width,
+                    events,
+                } => (
+                    Box::new(CalendarScheduler::new(bucket_width).map_err(D::Error::custom)?),
+                    events,
+                ),
+            };
+        for event in events {
+            scheduler.push(event);
+        }
+        Ok(scheduler)
+    }
+}
+
+/// Default scheduler backed by a binary min-heap
+#[derive(Clone, Debug, Default)]
+pub struct BinaryHeapScheduler {
+    heap: BinaryHeap<Event>,
+}
+
+impl Scheduler for BinaryHeapScheduler {
+    fn push(&mut self, event: Event) {
+        self.heap.push(event);
+    }
+
+    fn pop(&mut self) -> Option<Event> {
+        self.heap.pop()
+    }
+
+    fn peek(&self) -> Option<&Event> {
+        self.heap.peek()
+    }
+
+    fn len(&self) -> usize {
+        self.heap.len()
+    }
+
+    fn clear(&mut self) {
+        self.heap.clear();
+    }
+
+    fn events(&self) -> &[Event] {
+        self.heap.as_slice()
+    }
+}
+
+/// Calendar queue that groups events into fixed-width time buckets.
+///
+/// Only the earliest bucket is kept ordered, so models with many events
+/// spread over time avoid paying for one large heap. The events themselves
+/// sit together in one list, with each bucket ordering entries that point into it.
+#[derive(Clone, Debug)]
+pub struct CalendarScheduler {
+    bucket_width: f64,
+    events: Vec<Event>,
+    /// Entry id of each event, in the same order as `events`
+    ids: Vec<u64>,
+    /// Position in `events` of the event behind each entry id
+    positions: HashMap<u64, usize>,
+    buckets: BTreeMap<i64, BinaryHeap<Entry>>,
+    next_id: u64,
+}
+
+/// Points a bucket at one event, ordered the way the event itself is
+#[derive(Clone, Debug)]
+struct Entry {
+    time: f64,
+    sequence_number: u64,
+    id: u64,
+}
+
+impl Ord for Entry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Reversed for a min-heap, as `Event` is
+        other
+            .time
+            .total_cmp(&self.time)
+            .then(other.sequence_number.cmp(&self.sequence_number))
+    }
+}
+
+impl PartialOrd for Entry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Entry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Entry {}
+
+impl CalendarScheduler {
+    /// Fails unless `bucket_width` is positive and finite
+    pub fn new(bucket_width: f64) -> Result<Self, SimulationError> {
+        if !(bucket_width > 0.0 && bucket_width.is_finite()) {
+            return Err(SimulationError::InvalidBucketWidth(bucket_width));
+        }
+        Ok(Self {
+            bucket_width,
+            events: Vec::new(),
+            ids: Vec::new(),
+            positions: HashMap::new(),
+            buckets: BTreeMap::new(),
+            next_id: 0,
+        })
+    }
+
+    fn bucket(&self, time: f64) -> i64 {
+        (time / self.bucket_width).floor() as i64
+    }
+
+    /// Earliest entry, taken out of its bucket
+    fn pop_entry(&mut self) -> Option<Entry> {
+        let mut bucket = self.buckets.first_entry()?;
+        let entry = bucket.get_mut().pop();
+        if bucket.get().is_empty() {
+            bucket.remove();
+        }
+        entry
+    }
+}
+
+impl Default for CalendarScheduler {
+    fn default() -> Self {
+        Self::new(1.0).unwrap()
+    }
+}
+
+impl Scheduler for CalendarScheduler {
+    fn push(&mut self, event: Event) {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        let bucket = self.bucket(event.time);
+        self.buckets.entry(bucket).or_default().push(Entry {
+            time: event.time,
+            sequence_number: event.sequence_number,
+            id,
+        });
+        self.positions.insert(id, self.events.len());
+        self.ids.push(id);
+        self.events.push(event);
+    }
+
+    fn pop(&mut self) -> Option<Event> {
+        let entry = self.pop_entry()?;
+        let position = self.positions.remove(&entry.id)?;
+
+        // The last event moves into the gap, so its position changes
+        self.ids.swap_remove(position);
+        let event = self.events.swap_remove(position);
+        if let Some(moved) = self.ids.get(position) {
+            self.positions.insert(*moved, position);
+        }
+        Some(event)
+    }
+
+    fn peek(&self) -> Option<&Event> {
+        let (_, bucket) = self.buckets.first_key_value()?;
+        let entry = bucket.peek()?;
+        self.events.get(self.positions[&entry.id])
+    }
+
+    fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    fn clear(&mut self) {
+        self.events.clear();
+        self.ids.clear();
+        self.positions.clear();
+        self.buckets.clear();
+    }
+
+    fn events(&self) -> &[Event] {
+        &self.events
+    }
+
+    fn bucket_width(&self) -> Option<f64> {
+        Some(self.bucket_width)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulator::EventPayload;
+
+    fn event(time: f64, sequence_number: u64) -> Event {
+        Event {
+            source_id: "test".into(),
+            target_id: "test".into(),
+            time,
+            payload: EventPayload::Step,
+            source_port: None,
+            target_port: None,
+            sequence_number,
+            dispatch_index: None,
+            dispatch_floor: None,
+            pull_limit: None,
+        }
+    }
+
+    #[test]
+    fn test_scheduler_ordering_matches() {
+        let times = [3.5, 0.0, 1.0, 1.0, 0.25, 10.0, 2.75, 1.0, 0.0, 7.5];
+        let events: Vec<Event> = times
+            .iter()
+            .enumerate()
+            .map(|(sequence_number, time)| event(*time, sequence_number as u64))
+            .collect();
+
+        let mut heap = BinaryHeapScheduler::default();
+        let mut calendar = CalendarScheduler::new(0.5).unwrap();
+        for event in &events {
+            heap.push(event.clone());
+            calendar.push(event.clone());
+        }
+        assert_eq!(heap.len(), calendar.len());
+        assert_eq!(
+            heap.peek().map(|e| e.sequence_number),
+            calendar.peek().map(|e| e.sequence_number)
+        );
+
+        let order = |events: Vec<Event>| -> Vec<(f64, u64)> {
+            events.iter().map(|e| (e.time, e.sequence_number)).collect()
+        };
+        let heap_order = order(heap.drain());
+        assert_eq!(heap_order, order(calendar.drain()));
+        assert_eq!(heap_order[..3], [(0.0, 1), (0.0, 8), (0.25, 4)]);
+        assert!(calendar.is_empty());
+        assert!(calendar.pop().is_none());
+    }
+}
@@ -1,40 +1,281 @@
 use serde::Deserialize;
 use serde::Serialize;
-use std::collections::{BinaryHeap, HashMap};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::sync::Arc;
 use tracing::instrument;
-use tracing::{debug, error};
-
-use super::simulation_context::SimulationContext;
-use super::simulation_state::SimulationState;
+use tracing::{debug, error, warn, Level};
+
+use super::definition::{substitute_params, SimulationDefinition};
+use super::document::SimulationDocument;
+use super::lifecycle::{LifecycleEvent, LifecycleHooks};
+use super::model_op::ModelOp;
+use super::recorder::{LossReport, Recorder};
+use super::replay::{self, ReplayMismatch};
+use super::run_configuration::RunConfiguration;
+use super::scheduler::Scheduler;
+use super::simulation_context::{CascadePolicy, ConnectionStats, DuplexPolicy, SimulationContext};
+use super::simulation_state::{SimulationState, StateDelta};
 use super::simulation_trait::Simulate;
 use super::simulation_trait::StatefulSimulation;
+use super::watchers::ConnectionWatchers;
 use super::Event;
 use super::EventPayload;
+use crate::analysis::graph::ModelGraph;
+use crate::analysis::summary::{held_resources, summarize, total_resources_in_system, RunSummary};
 use crate::analysis::utils::visualise_resource_transfers;
-use crate::utils::logging::init_logging_once;
+use crate::utils::hash::StableHasher;
+use crate::utils::logging::{init_logging_once, LogCapture, LogRecord};
 use crate::{
     model::{
-        connection::Connection, process_state::ProcessState, process_trait::Processor, Process,
+        connection::{Connection, OBSERVE_PORT},
+        nodes::{Delay, FnProcess, Stepper},
+        process_state::ProcessState,
+        process_trait::{Processor, SerializableProcess},
+        Process, ProcessContext,
     },
     utils::SimulationError,
 };
 
+/// Largest change in any process field still treated as standing still by `run_until_steady`
+const STEADY_STATE_TOLERANCE: f64 = 1e-9;
+
 #[derive(Clone, Default, Debug, Serialize, Deserialize)]
 pub struct Simulation {
     processes: HashMap<String, Process>,
     context: SimulationContext,
-    event_queue: BinaryHeap<Event>,
+    event_queue: Box<dyn Scheduler>,
     event_sequence_number: u64,
     connection_sequence_number: u64,
+    #[serde(default)]
+    recorder: Recorder,
+    #[serde(default)]
+    started: bool,
+    /// Resources held in the model when it started, for conservation checks
+    #[serde(default)]
+    initial_resources: f64,
+    /// Resources each process held when the model started, for locating leaks
+    #[serde(default)]
+    initial_holdings: HashMap<String, f64>,
+    #[serde(default)]
+    metadata: Map<String, Value>,
+    #[serde(skip)]
+    diagnostics: Vec<SimulationError>,
+    #[serde(skip)]
+    lifecycle_hooks: LifecycleHooks,
+    #[serde(skip)]
+    connection_watchers: ConnectionWatchers,
+    #[serde(skip)]
+    log_capture: LogCapture,
+    /// Largest change in process state over each completed step
+    #[serde(default, deserialize_with = "deserialize_state_changes")]
+    state_changes: Vec<f64>,
+    #[serde(skip)]
+    previous_state: Option<SimulationState>,
+    /// Rejects structural changes while set, e.g. for a published scenario
+    #[serde(default)]
+    frozen: bool,
 }
 
 impl Simulation {
+    /// Builds a simulation whose runtime settings all come from `config`
+    pub fn with_config(
+        processes: Vec<Process>,
+        connections: Vec<Connection>,
+        config: RunConfiguration,
+    ) -> Result<Self, SimulationError> {
+        let mut simulation = Self::new(processes, connections)?;
+        simulation.set_config(config)?;
+        Ok(simulation)
+    }
+
+    pub fn config(&self) -> &RunConfiguration {
+        self.context.config()
+    }
+
+    pub fn set_config(&mut self, config: RunConfiguration) -> Result<(), SimulationError> {
+        config.validate()?;
+        self.context.set_config(config);
+        if !self.has_started() {
+            self.context.set_current_time(config.start_time);
+        }
+        Ok(())
+    }
+
+    pub fn start_time(&self) -> f64 {
+        self.config().start_time
+    }
+
+    /// Starts the clock at `t0` instead of zero, both now and after every reset. Only allowed
+    /// before the first step.
+    pub fn set_start_time(&mut self, t0: f64) -> Result<(), SimulationError> {
+        if self.has_started() {
+            return Err(SimulationError::Other(
+                "Start time can only be set before the simulation starts".to_string(),
+            ));
+        }
+        self.set_config(RunConfiguration {
+            start_time: t0,
+            ..*self.config()
+        })
+    }
+
+    /// Builds a simulation from a YAML document listing `processes` and `connections`
+    pub fn from_yaml(s: &str) -> Result<Self, SimulationError> {
+        let definition = SimulationDefinition::from_yaml(s)?;
+        Self::new(definition.processes, definition.connections)
+    }
+
+    /// Safe entry point for models from untrusted clients. Parses a JSON document listing
+    /// `processes` and `connections`, fills in missing connection ids and flow rates, and
+    /// checks the whole model, returning every problem found rather than just the first.
+    pub fn load_and_validate(json: &str) -> Result<Self, Vec<SimulationError>> {
+        let mut definition = SimulationDefinition::from_json(json).map_err(|e| vec![e])?;
+        definition.normalize();
+
+        let mut simulation = Self::new(vec![], vec![]).map_err(|e| vec![e])?;
+        let mut problems: Vec<SimulationError> = definition
+            .processes
+            .into_iter()
+            .filter_map(|process| simulation.add_process(process).err())
+            .collect();
+
+        let missing = simulation.missing_processes(&definition.connections);
+        if !missing.is_empty() {
+            problems.push(SimulationError::MissingProcesses(missing));
+        } else {
+            problems.extend(
+                simulation
+                    .validate_connections(&definition.connections)
+                    .into_iter()
+                    .map(|(_, error)| error),
+            );
+        }
+        if !problems.is_empty() {
+            return Err(problems);
+        }
+
+        simulation
+            .add_connections(definition.connections)
+            .map_err(|e| vec![e])?;
+        Ok(simulation)
+    }
+
+    /// Builds a simulation from a YAML template, first replacing each `${name}` token with
+    /// the value of the matching parameter.
+    ///
+    /// Referencing a parameter missing from `params` is an error.
+    pub fn from_yaml_with_params(
+        s: &str,
+        params: &HashMap<String, f64>,
+    ) -> Result<Self, SimulationError> {
+        Self::from_yaml(&substitute_params(s, params)?)
+    }
+
+    /// Serializes the whole simulation, including pending events and recorded history,
+    /// so it can later be resumed with `restore`
+    pub fn snapshot(&self) -> Result<Vec<u8>, SimulationError> {
+        serde_json::to_vec(self).map_err(|e| SimulationError::Other(e.to_string()))
+    }
+
+    pub fn restore(snapshot: &[u8]) -> Result<Self, SimulationError> {
+        serde_json::from_slice(snapshot).map_err(|e| SimulationError::Other(e.to_string()))
+    }
+
+    /// Lays the simulation out as processes, connections, clock and pending events, from which
+    /// `from_document` rebuilds one that steps on identically
+    pub fn to_document(&self) -> SimulationDocument {
+        let mut processes: Vec<Process> = self.processes.values().cloned().collect();
+        processes.sort_by(|a, b| a.id().cmp(b.id()));
+        SimulationDocument {
+            processes,
+            connections: self.connections(),
+            config: *self.config(),
+            step: self.context.current_step(),
+            time: self.context.current_time(),
+            events: self.scheduled_events().into_iter().cloned().collect(),
+            connection_stats: self.context.connection_stats.clone(),
+        }
+    }
+
+    pub fn from_document(document: SimulationDocument) -> Result<Self, SimulationError> {
+        let started = document.step > 0 || !document.events.is_empty();
+        let mut simulation =
+            Self::with_config(document.processes, document.connections, document.config)?;
+        simulation.context.set_current_step(document.step);
+        simulation.context.set_current_time(document.time);
+        simulation.context.connection_stats = document.connection_stats;
+        simulation.schedule_events(document.events)?;
+        if started {
+            simulation.started = true;
+            let state = simulation.get_simulation_state();
+            simulation.previous_state = Some(state);
+        }
+        Ok(simulation)
+    }
+
     pub fn get_context(&self) -> &SimulationContext {
         &self.context
     }
 
+    /// Scheduled events, in no particular order
     pub fn get_events(&self) -> &[Event] {
-        self.event_queue.as_slice()
+        self.event_queue.events()
+    }
+
+    /// Scheduled events in the order they will be processed, each carrying the sequence
+    /// number that breaks ties between events at the same time
+    pub fn scheduled_events(&self) -> Vec<&Event> {
+        let mut events: Vec<&Event> = self.event_queue.events().iter().collect();
+        events.sort_by(|a, b| b.cmp(a));
+        events
+    }
+
+    /// Next event and connection sequence numbers to be assigned, as `(event, connection)`
+    pub fn sequence_counters(&self) -> (u64, u64) {
+        (self.event_sequence_number, self.connection_sequence_number)
+    }
+
+    pub fn scheduler(&self) -> &dyn Scheduler {
+        self.event_queue.as_ref()
+    }
+
+    /// Replaces the event scheduler, moving any pending events into the new one
+    pub fn set_scheduler(&mut self, mut scheduler: Box<dyn Scheduler>) {
+        scheduler.clear();
+        for event in self.event_queue.drain() {
+            scheduler.push(event);
+        }
+        self.event_queue = scheduler;
+    }
+
+    /// Schedules an `EventPayload::Trigger` for `process_id` at `at_time`, firing the process
+    /// as if its trigger mode had come due, e.g. to kick a passive node once
+    pub fn schedule_trigger(
+        &mut self,
+        process_id: &str,
+        at_time: f64,
+    ) -> Result<(), SimulationError> {
+        self.get_process(process_id)?;
+        if at_time < self.current_time() {
+            return Err(SimulationError::Other(format!(
+                "Cannot trigger '{}' at {}, before the current time {}",
+                process_id,
+                at_time,
+                self.current_time()
+            )));
+        }
+        self.schedule_event(Event::new(
+            "simulation",
+            process_id,
+            at_time,
+            EventPayload::Trigger,
+        ))
+    }
+
+    /// Time of the earliest scheduled event, if any
+    pub fn next_event_time(&self) -> Option<f64> {
+        self.event_queue.peek().map(|event| event.time)
     }
 
     pub fn current_step(&self) -> u64 {
@@ -45,6 +286,95 @@ impl Simulation {
         self.context.current_time()
     }
 
+    pub fn seed(&self) -> u64 {
+        self.context.seed()
+    }
+
+    pub fn duplex_policy(&self) -> DuplexPolicy {
+        self.context.duplex_policy()
+    }
+
+    pub fn set_duplex_policy(&mut self, policy: DuplexPolicy) {
+        self.context.set_duplex_policy(policy);
+    }
+
+    pub fn cascade_policy(&self) -> CascadePolicy {
+        self.context.cascade_policy()
+    }
+
+    pub fn set_cascade_policy(&mut self, policy: CascadePolicy) {
+        self.context.set_cascade_policy(policy);
+    }
+
+    /// Sets the seed from which every stochastic process derives its own random stream
+    pub fn set_seed(&mut self, seed: u64) {
+        self.context.set_seed(seed);
+    }
+
+    /// Tick length of the model's Stepper, or `None` unless there is exactly one Stepper
+    pub fn stepper_dt(&self) -> Option<f64> {
+        let id = self.stepper_id().ok()?;
+        self.get_process_as::<Stepper>(&id).map(Stepper::dt)
+    }
+
+    /// Changes the tick length of the model's Stepper. A step already scheduled keeps its time,
+    /// the steps after it are `dt` apart.
+    pub fn set_stepper_dt(&mut self, dt: f64) -> Result<(), SimulationError> {
+        let id = self.stepper_id()?;
+        self.get_process_as_mut::<Stepper>(&id)
+            .ok_or(SimulationError::NoStepper)?
+            .set_dt(dt)
+    }
+
+    fn stepper_id(&self) -> Result<String, SimulationError> {
+        let mut ids: Vec<String> = self
+            .processes
+            .iter()
+            .filter(|(_, process)| process.downcast_ref::<Stepper>().is_some())
+            .map(|(id, _)| id.clone())
+            .collect();
+        ids.sort();
+        match ids.len() {
+            0 => Err(SimulationError::NoStepper),
+            1 => Ok(ids.remove(0)),
+            _ => Err(SimulationError::AmbiguousStepper(ids)),
+        }
+    }
+
+    /// Free-form labels (owner, scenario name, tags, ...) carried with the simulation
+    pub fn metadata(&self) -> &Map<String, Value> {
+        &self.metadata
+    }
+
+    pub fn get_metadata(&self, key: &str) -> Option<&Value> {
+        self.metadata.get(key)
+    }
+
+    pub fn set_metadata(&mut self, key: impl Into<String>, value: Value) -> Option<Value> {
+        self.metadata.insert(key.into(), value)
+    }
+
+    /// Makes adding, updating and removing processes or connections fail with
+    /// `SimulationError::Frozen`. Stepping and queries are unaffected.
+    pub fn freeze(&mut self) {
+        self.frozen = true;
+    }
+
+    pub fn unfreeze(&mut self) {
+        self.frozen = false;
+    }
+
+    pub fn is_frozen(&self) -> bool {
+        self.frozen
+    }
+
+    fn ensure_mutable(&self) -> Result<(), SimulationError> {
+        if self.frozen {
+            return Err(SimulationError::Frozen);
+        }
+        Ok(())
+    }
+
     pub fn processes(&self) -> &HashMap<String, Process> {
         &self.processes
     }
@@ -65,18 +395,38 @@ impl Simulation {
         &mut self,
         processor: P,
     ) -> Result<(), SimulationError> {
+        self.ensure_mutable()?;
         let process = Process::new(Box::new(processor));
         let id = process.id().to_string();
 
         if self.processes.contains_key(&id) {
             return Err(SimulationError::DuplicateProcess(id));
         }
+        process.validate()?;
 
-        self.processes.insert(id, process);
+        self.context
+            .process_priorities
+            .insert(id.clone(), process.priority());
+        self.context
+            .process_priority_classes
+            .insert(id.clone(), process.priority_class());
+        self.processes.insert(id.clone(), process);
+        self.lifecycle_hooks
+            .notify(LifecycleEvent::ProcessAdded(id));
         Ok(())
     }
 
+    /// Adds a process whose behaviour is `handler`, called with each batch of events addressed
+    /// to it. Meant for prototyping: see `FnProcess` for its limits.
+    pub fn add_fn_process<F>(&mut self, id: &str, handler: F) -> Result<(), SimulationError>
+    where
+        F: FnMut(&[Event], &ProcessContext) -> Result<Vec<Event>, SimulationError> + Send + 'static,
+    {
+        self.add_process(FnProcess::new(id, handler))
+    }
+
     pub fn add_processes(&mut self, processes: Vec<Process>) -> Result<(), SimulationError> {
+        self.ensure_mutable()?;
         for process in processes {
             self.add_process(process)?;
         }
@@ -84,14 +434,62 @@ impl Simulation {
     }
 
     pub fn update_process(&mut self, id: &str, process: Process) -> Result<(), SimulationError> {
+        self.ensure_mutable()?;
+        process.validate()?;
+        self.context
+            .process_priorities
+            .insert(id.to_string(), process.priority());
+        self.context
+            .process_priority_classes
+            .insert(id.to_string(), process.priority_class());
         self.processes.insert(id.to_string(), process);
         Ok(())
     }
 
     pub fn remove_process(&mut self, id: &str) -> Result<Process, SimulationError> {
-        self.processes
+        self.ensure_mutable()?;
+        self.context.process_priorities.remove(id);
+        self.context.process_priority_classes.remove(id);
+        let process = self
+            .processes
             .remove(id)
-            .ok_or_else(|| SimulationError::ProcessNotFound(id.to_string()))
+            .ok_or_else(|| SimulationError::ProcessNotFound(id.to_string()))?;
+        self.lifecycle_hooks
+            .notify(LifecycleEvent::ProcessRemoved(id.to_string()));
+        Ok(process)
+    }
+
+    /// Registers a callback notified whenever processes or connections are added or removed,
+    /// and when the simulation is reset
+    pub fn on_lifecycle<F>(&mut self, callback: F)
+    where
+        F: Fn(&LifecycleEvent) + Send + Sync + 'static,
+    {
+        self.lifecycle_hooks.register(Arc::new(callback));
+    }
+
+    /// Calls `callback` with every resource transfer along the connection `id`, and with the
+    /// acknowledgement or rejection that answers it, as each is processed
+    pub fn watch_connection(
+        &mut self,
+        id: &str,
+        callback: Box<dyn FnMut(&Event) + Send>,
+    ) -> Result<(), SimulationError> {
+        self.get_connection(id)?;
+        self.connection_watchers.register(id, callback);
+        Ok(())
+    }
+
+    /// Resets a single process, leaving the clock and every other process untouched.
+    ///
+    /// Events already scheduled for the process stay queued: dropping a resource transfer here
+    /// would leave its sender waiting on an acknowledgement that never arrives.
+    pub fn reset_process(&mut self, id: &str) -> Result<(), SimulationError> {
+        self.processes
+            .get_mut(id)
+            .ok_or_else(|| SimulationError::ProcessNotFound(id.to_string()))?
+            .reset();
+        Ok(())
     }
 
     pub fn get_process(&self, id: &str) -> Result<&Process, SimulationError> {
@@ -100,6 +498,44 @@ impl Simulation {
             .ok_or_else(|| SimulationError::ProcessNotFound(id.to_string()))
     }
 
+    /// The process with `id` as its concrete node type, or `None` if there is no such process
+    /// or it is of another type
+    pub fn get_process_as<T: Processor + 'static>(&self, id: &str) -> Option<&T> {
+        self.processes.get(id)?.downcast_ref::<T>()
+    }
+
+    pub fn get_process_as_mut<T: Processor + 'static>(&mut self, id: &str) -> Option<&mut T> {
+        self.processes.get_mut(id)?.downcast_mut::<T>()
+    }
+
+    /// What the delay `id` holds in flight, as `(amount, release time)` pairs in release order.
+    /// Empty when `id` is not a delay.
+    pub fn delay_contents(&self, id: &str) -> Vec<(f64, f64)> {
+        self.get_process_as::<Delay>(id)
+            .map(Delay::contents)
+            .unwrap_or_default()
+    }
+
+    /// Human-readable identification of a process, e.g. "Pool 'roundabout' (capacity 10,
+    /// overflow Drain)"
+    pub fn describe_process(&self, id: &str) -> Option<String> {
+        let process = self.processes.get(id)?;
+        let description = format!("{} '{}'", process.get_type(), id);
+        Some(match process.describe_settings() {
+            Some(settings) => format!("{} ({})", description, settings),
+            None => description,
+        })
+    }
+
+    /// Error message extended with a description of the process it concerns, when that
+    /// process is part of the model
+    pub fn describe_error(&self, error: &SimulationError) -> String {
+        match error.process_id().and_then(|id| self.describe_process(id)) {
+            Some(description) => format!("{} [{}]", error, description),
+            None => error.to_string(),
+        }
+    }
+
     fn validate_connection(&self, connection: &Connection) -> Result<(), SimulationError> {
         // Validate source process and port
         let source_process = self
@@ -107,10 +543,22 @@ impl Simulation {
             .get(&connection.source_id)
             .ok_or_else(|| SimulationError::ProcessNotFound(connection.source_id.clone()))?;
 
+        // Every process can be observed, whatever ports it has for its flows
+        let observing = connection.source_port.as_deref() == Some(OBSERVE_PORT);
+
+        if !observing && source_process.get_output_ports().is_empty() {
+            return Err(SimulationError::NoPorts {
+                process: connection.source_id.clone(),
+                process_type: source_process.get_type().to_string(),
+                port_type: "output".to_string(),
+            });
+        }
+
         if let Some(port) = &connection.source_port {
-            if !source_process.get_output_ports().contains(&port.as_str()) {
+            if !observing && !source_process.get_output_ports().contains(&port.as_str()) {
                 return Err(SimulationError::InvalidPort {
                     process: connection.source_id.clone(),
+                    process_type: source_process.get_type().to_string(),
                     port: port.clone(),
                     port_type: "output".to_string(),
                 });
@@ -123,19 +571,76 @@ impl Simulation {
             .get(&connection.target_id)
             .ok_or_else(|| SimulationError::ProcessNotFound(connection.target_id.clone()))?;
 
+        if target_process.get_input_ports().is_empty() {
+            return Err(SimulationError::NoPorts {
+                process: connection.target_id.clone(),
+                process_type: target_process.get_type().to_string(),
+                port_type: "input".to_string(),
+            });
+        }
+
         if let Some(port) = &connection.target_port {
             if !target_process.get_input_ports().contains(&port.as_str()) {
                 return Err(SimulationError::InvalidPort {
                     process: connection.target_id.clone(),
+                    process_type: target_process.get_type().to_string(),
                     port: port.clone(),
                     port_type: "input".to_string(),
                 });
             }
         }
 
+        // Observers are linked through the observe port, and nothing else is
+        if target_process.is_observer() != observing {
+            return Err(if observing {
+                SimulationError::InvalidPort {
+                    process: connection.source_id.clone(),
+                    process_type: source_process.get_type().to_string(),
+                    port: OBSERVE_PORT.to_string(),
+                    port_type: "output".to_string(),
+                }
+            } else {
+                SimulationError::ObserverLink {
+                    process: connection.target_id.clone(),
+                    process_type: target_process.get_type().to_string(),
+                }
+            });
+        }
+
         Ok(())
     }
 
+    /// Ids referenced by `connections` with no matching process, in order of first mention
+    fn missing_processes(&self, connections: &[Connection]) -> Vec<String> {
+        let mut missing: Vec<String> = Vec::new();
+        for id in connections
+            .iter()
+            .flat_map(|conn| [&conn.source_id, &conn.target_id])
+        {
+            if !self.processes.contains_key(id) && !missing.contains(id) {
+                missing.push(id.clone());
+            }
+        }
+        missing
+    }
+
+    /// Validates a batch of connections without adding them, reporting every invalid
+    /// connection alongside its index in `connections`.
+    pub fn validate_connections(
+        &self,
+        connections: &[Connection],
+    ) -> Vec<(usize, SimulationError)> {
+        connections
+            .iter()
+            .enumerate()
+            .filter_map(|(index, connection)| {
+                self.validate_connection(connection)
+                    .err()
+                    .map(|error| (index, error))
+            })
+            .collect()
+    }
+
     fn add_connection_to_io_maps(&mut self, connection: Connection) -> Result<(), SimulationError> {
         // Add connection to input map
         self.context
@@ -158,7 +663,15 @@ impl Simulation {
         Ok(())
     }
 
+    /// Adds a connection behind every existing one in distribution order. Re-adding an existing
+    /// id replaces that connection in place, keeping its position; connections without an id
+    /// are always added.
     pub fn add_connection(&mut self, mut connection: Connection) -> Result<(), SimulationError> {
+        self.ensure_mutable()?;
+        if !connection.id.is_empty() && self.get_connection(&connection.id).is_ok() {
+            let id = connection.id.clone();
+            return self.update_connection(&id, connection);
+        }
         self.validate_connection(&connection)?;
 
         // Set sequence number for connection ordering
@@ -166,12 +679,16 @@ impl Simulation {
         self.connection_sequence_number += 1;
 
         // Add connection to input and output maps
+        let id = connection.id.clone();
         self.add_connection_to_io_maps(connection)?;
+        self.lifecycle_hooks
+            .notify(LifecycleEvent::ConnectionAdded(id));
 
         Ok(())
     }
 
     pub fn add_connections(&mut self, connections: Vec<Connection>) -> Result<(), SimulationError> {
+        self.ensure_mutable()?;
         for connection in connections {
             self.add_connection(connection)?;
         }
@@ -183,6 +700,7 @@ impl Simulation {
         connection_id: &str,
         mut connection: Connection,
     ) -> Result<(), SimulationError> {
+        self.ensure_mutable()?;
         self.validate_connection(&connection)?;
 
         // Set sequence number to the same as the existing connection
@@ -192,13 +710,106 @@ impl Simulation {
         // Remove old connection from input and output maps
         self.remove_connection(connection_id)?;
 
-        // Add new connection to input and output maps
+        // Add new connection to input and output maps, announcing it as a replacement
+        let id = connection.id.clone();
         self.add_connection_to_io_maps(connection)?;
+        self.lifecycle_hooks
+            .notify(LifecycleEvent::ConnectionAdded(id));
+
+        Ok(())
+    }
 
+    /// Changes one field of a connection in place, in both io-maps, leaving its endpoints,
+    /// position and everything else as they are
+    fn patch_connection(
+        &mut self,
+        connection_id: &str,
+        patch: impl Fn(&mut Connection),
+    ) -> Result<(), SimulationError> {
+        self.ensure_mutable()?;
+        self.get_connection(connection_id)?;
+
+        for map in [&mut self.context.input_map, &mut self.context.output_map] {
+            map.values_mut()
+                .flat_map(|ports| ports.values_mut())
+                .flatten()
+                .filter(|conn| conn.id == connection_id)
+                .for_each(&patch);
+        }
         Ok(())
     }
 
+    /// Sets a connection's flow rate, `None` falling back to the default rate. Takes effect
+    /// from the next transfer.
+    pub fn set_connection_flow_rate(
+        &mut self,
+        connection_id: &str,
+        flow_rate: Option<f64>,
+    ) -> Result<(), SimulationError> {
+        self.patch_connection(connection_id, |conn| conn.flow_rate = flow_rate)
+    }
+
+    pub fn set_connection_multiplier(
+        &mut self,
+        connection_id: &str,
+        multiplier: Option<f64>,
+    ) -> Result<(), SimulationError> {
+        self.patch_connection(connection_id, |conn| conn.multiplier = multiplier)
+    }
+
+    pub fn set_connection_packet_size(
+        &mut self,
+        connection_id: &str,
+        packet_size: Option<f64>,
+    ) -> Result<(), SimulationError> {
+        self.patch_connection(connection_id, |conn| conn.packet_size = packet_size)
+    }
+
+    pub fn set_connection_schedule(
+        &mut self,
+        connection_id: &str,
+        schedule: Vec<(f64, f64)>,
+    ) -> Result<(), SimulationError> {
+        let mut schedule = schedule;
+        schedule.sort_by(|a, b| a.0.total_cmp(&b.0));
+        self.patch_connection(connection_id, |conn| conn.schedule = schedule.clone())
+    }
+
+    /// Renumbers connections from zero in their current order, as if freshly added
+    fn renumber_connections(&mut self) {
+        // Every connection is stored in the output map of its source process
+        let mut sequence_numbers: Vec<u64> = self
+            .context
+            .output_map
+            .values()
+            .flat_map(|ports| ports.values())
+            .flatten()
+            .map(|conn| conn.sequence_number)
+            .collect();
+        sequence_numbers.sort_unstable();
+        sequence_numbers.dedup();
+        let renumbered: HashMap<u64, u64> = sequence_numbers
+            .into_iter()
+            .enumerate()
+            .map(|(index, sequence_number)| (sequence_number, index as u64))
+            .collect();
+
+        for map in [&mut self.context.input_map, &mut self.context.output_map] {
+            for connection in map
+                .values_mut()
+                .flat_map(|ports| ports.values_mut())
+                .flatten()
+            {
+                if let Some(&sequence_number) = renumbered.get(&connection.sequence_number) {
+                    connection.sequence_number = sequence_number;
+                }
+            }
+        }
+        self.connection_sequence_number = renumbered.len() as u64;
+    }
+
     pub fn remove_connection(&mut self, connection_id: &str) -> Result<(), SimulationError> {
+        self.ensure_mutable()?;
         let key = connection_id.to_string();
 
         fn remove_from_map(
@@ -221,6 +832,8 @@ impl Simulation {
         let found_in_output = remove_from_map(&mut self.context.output_map, &key);
 
         if found_in_input || found_in_output {
+            self.lifecycle_hooks
+                .notify(LifecycleEvent::ConnectionRemoved(key));
             Ok(())
         } else {
             Err(SimulationError::ConnectionNotFound(
@@ -229,33 +842,564 @@ impl Simulation {
         }
     }
 
+    /// Applies `ops` in order as one edit: if any op fails, none of them take effect and the
+    /// error is returned. The batch is first tried on a copy of the simulation, so lifecycle
+    /// hooks only hear about changes that are kept.
+    pub fn apply(&mut self, ops: Vec<ModelOp>) -> Result<(), SimulationError> {
+        self.ensure_mutable()?;
+
+        let mut staged = self.clone_without_history();
+        for op in &ops {
+            staged.apply_op(op.clone())?;
+        }
+
+        // The same ops from the same starting point, so these can't fail
+        for op in ops {
+            self.apply_op(op)?;
+        }
+        Ok(())
+    }
+
+    fn apply_op(&mut self, op: ModelOp) -> Result<(), SimulationError> {
+        match op {
+            ModelOp::AddProcess { process } => self.add_process(process),
+            ModelOp::UpdateProcess { id, process } => self.update_process(&id, process),
+            ModelOp::RemoveProcess { id } => self.remove_process(&id).map(|_| ()),
+            ModelOp::AddConnection { connection } => self.add_connection(connection),
+            ModelOp::UpdateConnection { id, connection } => self.update_connection(&id, connection),
+            ModelOp::RemoveConnection { id } => self.remove_connection(&id),
+        }
+    }
+
+    /// Process/connection graph of the model as it stands, for analysis
+    pub fn graph(&self) -> ModelGraph {
+        ModelGraph::from_simulation(self)
+    }
+
+    /// Every connection in the model, in the order they were added
+    pub fn connections(&self) -> Vec<Connection> {
+        let mut connections: Vec<Connection> = self
+            .context
+            .output_map
+            .values()
+            .flat_map(|ports| ports.values())
+            .flatten()
+            .cloned()
+            .collect();
+        connections.sort_by_key(|conn| conn.sequence_number);
+        connections
+    }
+
     pub fn get_connection(&self, connection_id: &str) -> Result<&Connection, SimulationError> {
-        // Check input and output maps for connection
-        if let Some(connections) = self.context.input_map.get(connection_id) {
-            if let Some(connection) = connections
-                .values()
-                .flatten()
-                .find(|con| con.id == connection_id)
+        // Every connection is stored in the output map of its source process
+        self.context
+            .output_map
+            .values()
+            .flat_map(|ports| ports.values())
+            .flatten()
+            .find(|con| con.id == connection_id)
+            .ok_or_else(|| SimulationError::ConnectionNotFound(connection_id.to_string()))
+    }
+
+    /// Rate nodes currently apply to a connection, after the default, schedule and multiplier
+    pub fn effective_flow_rate(&self, connection_id: &str) -> Result<f64, SimulationError> {
+        Ok(self
+            .get_connection(connection_id)?
+            .effective_flow_rate(self.context.current_time()))
+    }
+
+    /// Events each process has handled and emitted since the last reset, as
+    /// `(received, emitted)`. Broadcasts such as `Step` count once per process.
+    pub fn process_event_counts(&self) -> HashMap<String, (u64, u64)> {
+        self.context.event_counts.clone()
+    }
+
+    pub fn connection_stats(&self, connection_id: &str) -> ConnectionStats {
+        self.context.connection_stats(connection_id)
+    }
+
+    /// Starts recording processed events and per-step state snapshots, beginning with the current state
+    pub fn enable_recording(&mut self) {
+        if !self.recorder.enabled {
+            self.recorder.enabled = true;
+            self.recorder.record_state(self.get_simulation_state());
+        }
+    }
+
+    /// Records a state snapshot only every `n` steps instead of after each one, to keep long
+    /// runs cheap. The state at the moment recording starts, or after a reset, is always kept.
+    pub fn sample_every(&mut self, n: usize) {
+        self.recorder.sample_interval = n.max(1);
+    }
+
+    pub fn disable_recording(&mut self) {
+        self.recorder.enabled = false;
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recorder.is_enabled()
+    }
+
+    /// Keeps engine log messages at `min_level` and above emitted while stepping, for `logs()`
+    pub fn enable_log_capture(&mut self, min_level: Level) {
+        self.log_capture.enable(min_level);
+    }
+
+    pub fn disable_log_capture(&mut self) {
+        self.log_capture.disable();
+    }
+
+    /// Log messages captured so far, oldest first
+    pub fn logs(&self) -> Vec<LogRecord> {
+        self.log_capture.records()
+    }
+
+    pub fn clear_logs(&mut self) {
+        self.log_capture.clear();
+    }
+
+    pub fn recorder(&self) -> &Recorder {
+        &self.recorder
+    }
+
+    pub fn recorded_events(&self) -> &[Event] {
+        self.recorder.events()
+    }
+
+    pub fn recorded_states(&self) -> &[SimulationState] {
+        self.recorder.states()
+    }
+
+    /// Resources rejected since recording was enabled, per process and per connection
+    pub fn loss_report(&self) -> &LossReport {
+        self.recorder.losses()
+    }
+
+    /// Returns recorded events with `t0 <= time < t1`, in processing order.
+    ///
+    /// The window is half-open so consecutive windows never report an event twice.
+    /// An empty or inverted window returns no events.
+    pub fn events_between(&self, t0: f64, t1: f64) -> Vec<&Event> {
+        self.recorder
+            .events()
+            .iter()
+            .filter(|event| event.time >= t0 && event.time < t1)
+            .collect()
+    }
+
+    /// Drops recorded events, state snapshots and losses. Recording stays enabled or disabled
+    /// as it was.
+    pub fn clear_recording(&mut self) {
+        self.recorder.clear();
+    }
+
+    /// Clears recording and hands the memory behind it and other run history back to the
+    /// allocator. The live model, its pending events and per-connection statistics are kept.
+    pub fn compact(&mut self) {
+        self.clear_recording();
+        self.recorder.shrink_to_fit();
+        self.state_changes.shrink_to_fit();
+        self.diagnostics.shrink_to_fit();
+    }
+
+    /// Clones the live simulation without copying any recorded events or state history, or the
+    /// lifecycle hooks and connection watchers registered on it.
+    ///
+    /// Useful when forking a base model many times, e.g. for parameter sweeps.
+    pub fn clone_without_history(&self) -> Simulation {
+        Simulation {
+            processes: self.processes.clone(),
+            context: self.context.clone(),
+            event_queue: self.event_queue.clone(),
+            event_sequence_number: self.event_sequence_number,
+            connection_sequence_number: self.connection_sequence_number,
+            recorder: self.recorder.empty_like(),
+            started: self.started,
+            initial_resources: self.initial_resources,
+            initial_holdings: self.initial_holdings.clone(),
+            metadata: self.metadata.clone(),
+            diagnostics: vec![],
+            lifecycle_hooks: LifecycleHooks::default(),
+            connection_watchers: ConnectionWatchers::default(),
+            log_capture: self.log_capture.empty_like(),
+            state_changes: vec![],
+            previous_state: self.previous_state.clone(),
+            frozen: self.frozen,
+        }
+    }
+
+    /// In strict mode, model diagnostics that would otherwise be warnings fail the first step
+    pub fn set_strict(&mut self, strict: bool) {
+        self.context.config.strict = strict;
+    }
+
+    pub fn is_strict(&self) -> bool {
+        self.context.config().strict
+    }
+
+    /// Warnings collected by the diagnostics run at the start of the simulation
+    pub fn diagnostics(&self) -> &[SimulationError] {
+        &self.diagnostics
+    }
+
+    /// Reports every required port that has no connection, e.g. a Source without outputs.
+    pub fn check_unconnected_ports(&self) -> Vec<SimulationError> {
+        let mut process_ids: Vec<&String> = self.processes.keys().collect();
+        process_ids.sort();
+
+        let mut problems = Vec::new();
+        for id in process_ids {
+            let process = &self.processes[id];
+
+            for port in process.get_required_input_ports() {
+                if self
+                    .context
+                    .process_inputs_for_port(id, Some(port))
+                    .is_empty()
+                {
+                    problems.push(SimulationError::UnconnectedPort {
+                        process: id.clone(),
+                        port: port.to_string(),
+                        port_type: "input".to_string(),
+                    });
+                }
+            }
+
+            for port in process.get_required_output_ports() {
+                if self
+                    .context
+                    .process_outputs_for_port(id, Some(port))
+                    .is_empty()
+                {
+                    problems.push(SimulationError::UnconnectedPort {
+                        process: id.clone(),
+                        port: port.to_string(),
+                        port_type: "output".to_string(),
+                    });
+                }
+            }
+        }
+
+        problems
+    }
+
+    /// Runs model diagnostics before the simulation starts, failing in strict mode
+    fn run_diagnostics(&mut self) -> Result<(), SimulationError> {
+        let mut problems = self.check_unconnected_ports();
+
+        if self.is_strict() && !problems.is_empty() {
+            return Err(problems.remove(0));
+        }
+
+        for problem in &problems {
+            warn!("{}", problem);
+        }
+        self.diagnostics = problems;
+
+        Ok(())
+    }
+
+    /// Jumps the clock straight to the next scheduled event's time and processes every event at
+    /// that time, including any same-time events they cause.
+    ///
+    /// This is the pure discrete-event way of driving a model: there are no fixed-dt ticks,
+    /// so the clock moves from one scheduled event to the next however far apart they are.
+    /// Returns no events if nothing is scheduled.
+    pub fn advance_to_next_event(&mut self) -> Result<Vec<Event>, SimulationError> {
+        if self.event_queue.is_empty() && self.has_started() {
+            return Ok(vec![]);
+        }
+        self.step()
+    }
+
+    /// Steps the simulation `n` times like `step_n`, but drops each step's events as it goes
+    /// and returns only how many were processed, so long runs use constant memory
+    pub fn step_n_discard(&mut self, n: usize) -> Result<usize, SimulationError> {
+        let mut processed = 0;
+        for _ in 0..n {
+            processed += self.step()?.len();
+        }
+        Ok(processed)
+    }
+
+    /// Releases everything held in delays and queues at the current time, whatever delay it
+    /// had left, and processes the resulting transfers so balances settle before the run is
+    /// wound up. Returns the events processed.
+    pub fn flush(&mut self) -> Result<Vec<Event>, SimulationError> {
+        let _capture = self.log_capture.activate();
+        self.start()?;
+        let now = self.context.current_time();
+
+        let mut delay_ids: Vec<String> = self
+            .processes
+            .iter()
+            .filter(|(_, process)| process.downcast_ref::<Delay>().is_some())
+            .map(|(id, _)| id.clone())
+            .collect();
+        delay_ids.sort();
+
+        let mut new_events = Vec::new();
+        for id in &delay_ids {
+            let context = self.context.context_for_process(id);
+            if let Some(delay) = self
+                .processes
+                .get_mut(id)
+                .and_then(|process| process.downcast_mut::<Delay>())
             {
-                return Ok(connection);
+                new_events.extend(delay.flush(&context));
             }
         }
 
-        if let Some(connections) = self.context.output_map.get(connection_id) {
-            if let Some(connection) = connections
-                .values()
-                .flatten()
-                .find(|con| con.id == connection_id)
+        // Transfers already scheduled by a delay arrive now instead
+        for mut event in self.event_queue.drain() {
+            if event.time > now
+                && matches!(event.payload, EventPayload::Resource(_))
+                && delay_ids.contains(&event.source_id)
             {
-                return Ok(connection);
+                event.time = now;
             }
+            self.event_queue.push(event);
         }
+        self.schedule_events(new_events)?;
 
-        Err(SimulationError::ConnectionNotFound(
-            connection_id.to_string(),
+        self.process_events_at(now)
+    }
+
+    /// Re-runs the model from its starting state on a copy and checks the events it processes
+    /// match `recorded` one for one, by time, source, target and payload.
+    ///
+    /// `recorded` should cover the run from its start, as recorded with recording enabled
+    /// before the first step. Hooks and watchers are not called during the re-run.
+    pub fn verify_replay(&self, recorded: &[Event]) -> Result<(), ReplayMismatch> {
+        let mut replay = self.clone_without_history();
+        replay.reset().map_err(ReplayMismatch::Failed)?;
+        replay.enable_recording();
+
+        while replay.recorded_events().len() < recorded.len() && !replay.is_completed() {
+            match replay.step() {
+                Ok(_) | Err(SimulationError::NoEvents) => {}
+                Err(err) => return Err(ReplayMismatch::Failed(err)),
+            }
+        }
+
+        replay::compare(recorded, replay.recorded_events())
+    }
+
+    /// Steps the simulation `steps` times and returns a stable hash of every state along the way.
+    ///
+    /// Handy for snapshot tests: identical models produce identical hashes.
+    pub fn run_hash(&mut self, steps: usize) -> Result<u64, SimulationError> {
+        let mut hash = StableHasher::default();
+        hash.write_u64(self.get_simulation_state().stable_hash());
+
+        for _ in 0..steps {
+            self.step()?;
+            hash.write_u64(self.get_simulation_state().stable_hash());
+        }
+
+        Ok(hash.finish())
+    }
+
+    /// Steps the simulation `steps` times, collecting the events delivered to each process.
+    ///
+    /// Events are keyed by their target, so what a process emitted appears under its receiver.
+    /// Broadcasts such as `Step` are left out. Unlike recording, nothing is kept afterwards.
+    pub fn run_collecting_by_process(
+        &mut self,
+        steps: usize,
+    ) -> Result<HashMap<String, Vec<Event>>, SimulationError> {
+        let mut events_by_process: HashMap<String, Vec<Event>> = HashMap::new();
+
+        for _ in 0..steps {
+            for event in self.step()? {
+                if self.processes.contains_key(&event.target_id) {
+                    events_by_process
+                        .entry(event.target_id.clone())
+                        .or_default()
+                        .push(event);
+                }
+            }
+        }
+
+        Ok(events_by_process)
+    }
+
+    /// Number of consecutive steps, counting back from the latest, over which no process field
+    /// changed by more than `tolerance`
+    pub fn steps_since_state_change(&self, tolerance: f64) -> usize {
+        self.state_changes
+            .iter()
+            .rev()
+            .take_while(|change| **change <= tolerance)
+            .count()
+    }
+
+    /// Steps until the state has held still for `stable_steps` consecutive steps, the event
+    /// queue runs dry, or `max_steps` have been taken. Returns whether steady state was reached.
+    pub fn run_until_steady(
+        &mut self,
+        max_steps: usize,
+        stable_steps: usize,
+    ) -> Result<bool, SimulationError> {
+        for _ in 0..max_steps {
+            if self.steps_since_state_change(STEADY_STATE_TOLERANCE) >= stable_steps {
+                return Ok(true);
+            }
+            self.start()?;
+            if self.event_queue.is_empty() {
+                break;
+            }
+            self.step()?;
+        }
+        Ok(self.steps_since_state_change(STEADY_STATE_TOLERANCE) >= stable_steps)
+    }
+
+    /// Takes one step and returns only the processes whose state it changed
+    pub fn step_delta(&mut self) -> Result<StateDelta, SimulationError> {
+        let before = self.get_simulation_state();
+        self.step()?;
+        Ok(self.get_simulation_state().delta_since(&before))
+    }
+
+    /// Resources a process held when the model started, or what it holds now before then
+    pub fn initial_holding(&self, process_id: &str) -> f64 {
+        if self.has_started() {
+            self.initial_holdings
+                .get(process_id)
+                .copied()
+                .unwrap_or(0.0)
+        } else {
+            self.get_process_state(process_id)
+                .map_or(0.0, |state| held_resources(&state))
+        }
+    }
+
+    /// Balance sheet of resources produced, consumed and still held at the current time
+    pub fn summary(&self) -> RunSummary {
+        let state = self.get_simulation_state();
+        let initial = if self.has_started() {
+            self.initial_resources
+        } else {
+            total_resources_in_system(&state)
+        };
+        summarize(&state, initial)
+    }
+
+    /// Applies the duplex policy and connection capacities before an event is delivered,
+    /// returning what is left to deliver.
+    ///
+    /// A resource transfer the policy forbids is bounced straight back to its sender as a
+    /// rejection, so the sender unwinds it exactly as if the target had refused it. A transfer
+    /// that would take its connection past `total_capacity` is cut down to the room left and
+    /// the excess bounced back the same way.
+    fn admit_event(&mut self, event: Event) -> Result<Option<Event>, SimulationError> {
+        let EventPayload::Resource(amount) = event.payload else {
+            return Ok(Some(event));
+        };
+
+        if !self.context.claim_transfer(&event) {
+            debug!("Duplex policy rejecting duplicate transfer: {:?}", event);
+            self.bounce_transfer(&event, amount)?;
+            return Ok(None);
+        }
+
+        let admitted = self.context.claim_capacity(&event);
+        if admitted >= amount {
+            return Ok(Some(event));
+        }
+        debug!(
+            "Connection capacity truncating transfer to {}: {:?}",
+            admitted, event
+        );
+        self.bounce_transfer(&event, amount - admitted)?;
+        if admitted <= 0.0 {
+            return Ok(None);
+        }
+        Ok(Some(Event {
+            payload: EventPayload::Resource(admitted),
+            ..event
+        }))
+    }
+
+    /// Rejects `amount` of a resource event on its target's behalf
+    fn bounce_transfer(&mut self, event: &Event, amount: f64) -> Result<(), SimulationError> {
+        self.schedule_event(Event::new(
+            &event.target_id,
+            &event.source_id,
+            event.time,
+            EventPayload::ResourceRejected(amount),
         ))
     }
 
+    /// Whether `SimulationStart` has been broadcast since construction or the last reset
+    pub fn has_started(&self) -> bool {
+        self.started || self.context.current_step() > 0
+    }
+
+    /// Whether the run has started and has no events left, so `SimulationEnd` has been
+    /// broadcast. Scheduling a new event resumes it.
+    pub fn is_completed(&self) -> bool {
+        self.has_started() && self.event_queue.is_empty()
+    }
+
+    /// Runs diagnostics and broadcasts `SimulationStart`, once, before the first event
+    fn start(&mut self) -> Result<(), SimulationError> {
+        if self.has_started() {
+            return Ok(());
+        }
+        self.run_diagnostics()?;
+        let state = self.get_simulation_state();
+        self.initial_resources = total_resources_in_system(&state);
+        self.initial_holdings = state
+            .process_states
+            .iter()
+            .map(|(id, process_state)| (id.clone(), held_resources(process_state)))
+            .collect();
+
+        let start_event = Event::new(
+            "simulation",
+            "broadcast",
+            self.context.current_time(),
+            EventPayload::SimulationStart,
+        );
+
+        let new_events = self.process_broadcast_event(&start_event)?;
+        self.schedule_events(new_events)?;
+        self.started = true;
+        self.previous_state = Some(self.get_simulation_state());
+
+        Ok(())
+    }
+
+    /// Updates bookkeeping derived from an event once it has been processed
+    fn record_processed_event(&mut self, event: &Event) {
+        self.context.record_transfer(event);
+        self.recorder.record_event(event);
+        if !self.connection_watchers.is_empty() {
+            if let Some(conn) = self.context.connection_for_transfer(event) {
+                self.connection_watchers.notify(&conn.id, event);
+            }
+        }
+
+        if !self.recorder.is_enabled() {
+            return;
+        }
+        if let EventPayload::ResourceRejected(amount) = event.payload {
+            // Rejections travel from the rejecting receiver back along the connection
+            let connection_id = self
+                .context
+                .find_connection(
+                    &event.target_id,
+                    event.target_port.as_deref(),
+                    &event.source_id,
+                    event.source_port.as_deref(),
+                )
+                .map(|conn| conn.id.clone());
+            self.recorder
+                .record_loss(&event.source_id, connection_id.as_deref(), amount);
+        }
+    }
+
     /// Collects all events that occur at the same time as the given event
     fn collect_simultaneous_events(&mut self, first_event: Event) -> Vec<Event> {
         let mut events = vec![first_event];
@@ -289,7 +1433,13 @@ impl Simulation {
     /// Process a batch of events at the same time and return any new events generated
     fn process_event_batch(&mut self, events: Vec<Event>) -> Result<Vec<Event>, SimulationError> {
         let mut processed_events = Vec::new();
-        let grouped_events = self.group_events_by_target(events);
+        let mut admitted_events = Vec::with_capacity(events.len());
+        for event in events {
+            if let Some(event) = self.admit_event(event)? {
+                admitted_events.push(event);
+            }
+        }
+        let grouped_events = self.group_events_by_target(admitted_events);
 
         for (target_id, target_events) in grouped_events {
             let events = if target_id == "broadcast" {
@@ -307,10 +1457,42 @@ impl Simulation {
                     .ok_or_else(|| SimulationError::ProcessNotFound(target_id.clone()))?;
 
                 let context = self.context.context_for_process(&target_id);
-                target_process.on_events(&target_events, &context)?
+                let events = if target_events.iter().any(is_level_query) {
+                    let node_events: Vec<Event> = target_events
+                        .iter()
+                        .filter(|event| !is_level_query(event))
+                        .cloned()
+                        .collect();
+                    let mut events = if node_events.is_empty() {
+                        vec![]
+                    } else {
+                        target_process.on_events(&node_events, &context)?
+                    };
+                    // Level queries are answered from the state the rest of the batch left behind
+                    let level = held_resources(&target_process.get_state());
+                    events.extend(target_events.iter().filter(|e| is_level_query(e)).map(
+                        |query| {
+                            Event::new(
+                                &target_id,
+                                &query.source_id,
+                                query.time,
+                                EventPayload::LevelResponse(level),
+                            )
+                        },
+                    ));
+                    events
+                } else {
+                    target_process.on_events(&target_events, &context)?
+                };
+                self.context
+                    .count_events(&target_id, target_events.len(), events.len());
+                events
             };
 
             self.schedule_events(events)?;
+            for event in &target_events {
+                self.record_processed_event(event);
+            }
             processed_events.extend(target_events);
         }
 
@@ -332,6 +1514,7 @@ impl Simulation {
                 if !valid_ports.contains(&port.as_str()) {
                     return Err(SimulationError::InvalidPort {
                         process: event.target_id.clone(),
+                        process_type: process.get_type().to_string(),
                         port: port.clone(),
                         port_type: "input".to_string(),
                     });
@@ -349,6 +1532,7 @@ impl Simulation {
                 if !valid_ports.contains(&port.as_str()) {
                     return Err(SimulationError::InvalidPort {
                         process: event.source_id.clone(),
+                        process_type: process.get_type().to_string(),
                         port: port.clone(),
                         port_type: "output".to_string(),
                     });
@@ -369,32 +1553,45 @@ impl Simulate for Simulation {
         let mut simulation = Self {
             processes: HashMap::new(),
             context: SimulationContext::default(),
-            event_queue: BinaryHeap::new(),
+            event_queue: Box::default(),
             event_sequence_number: 0,
             connection_sequence_number: 0,
+            recorder: Recorder::default(),
+            started: false,
+            initial_resources: 0.0,
+            initial_holdings: HashMap::new(),
+            metadata: Map::new(),
+            diagnostics: vec![],
+            lifecycle_hooks: LifecycleHooks::default(),
+            connection_watchers: ConnectionWatchers::default(),
+            log_capture: LogCapture::default(),
+            state_changes: vec![],
+            previous_state: None,
+            frozen: false,
         };
 
         simulation.add_processes(processes)?;
+        let missing = simulation.missing_processes(&connections);
+        if !missing.is_empty() {
+            return Err(SimulationError::MissingProcesses(missing));
+        }
         simulation.add_connections(connections)?;
 
         Ok(simulation)
     }
 
     fn next(&mut self) -> Result<Vec<Event>, SimulationError> {
+        let _capture = self.log_capture.activate();
         let mut processed_events = Vec::new();
-
-        // Pre-simulation: capture initial state and broadcast SimulationStart
-        if self.context.current_step() == 0 {
-            let start_event = Event::new(
-                "simulation",
-                "broadcast",
-                self.context.current_time(),
-                EventPayload::SimulationStart,
-            );
-
-            let new_events = self.process_broadcast_event(&start_event)?;
-            self.schedule_events(new_events)?;
+        if self.processes.is_empty() {
+            return Err(SimulationError::EmptySimulation);
         }
+        if self.is_completed() {
+            return Ok(processed_events);
+        }
+
+        // Pre-simulation: broadcast SimulationStart
+        self.start()?;
 
         // Process next event if available
         if let Some(next_event) = self.event_queue.pop() {
@@ -404,14 +1601,17 @@ impl Simulate for Simulation {
             }
             self.context.set_current_time(next_event.time);
 
-            let new_events = if next_event.target_id == "broadcast" {
-                self.process_broadcast_event(&next_event)?
-            } else {
-                self.process_event(&next_event)?
-            };
+            if let Some(next_event) = self.admit_event(next_event)? {
+                let new_events = if next_event.target_id == "broadcast" {
+                    self.process_broadcast_event(&next_event)?
+                } else {
+                    self.process_event(&next_event)?
+                };
 
-            self.schedule_events(new_events)?;
-            processed_events.push(next_event);
+                self.schedule_events(new_events)?;
+                self.record_processed_event(&next_event);
+                processed_events.push(next_event);
+            }
         }
 
         // If queue is empty, broadcast SimulationEnd
@@ -431,20 +1631,18 @@ impl Simulate for Simulation {
 
     #[instrument(skip_all, fields(step = %self.current_step(), time = %self.current_time()))]
     fn step(&mut self) -> Result<Vec<Event>, SimulationError> {
+        let _capture = self.log_capture.activate();
         let mut processed_events = Vec::new();
+        if self.processes.is_empty() {
+            return Err(SimulationError::EmptySimulation);
+        }
+        // A finished run stays finished, without announcing its end again
+        if self.is_completed() {
+            return Ok(processed_events);
+        }
 
         // Pre-simulation: broadcast SimulationStart
-        if self.context.current_step() == 0 {
-            let start_event = Event::new(
-                "simulation",
-                "broadcast",
-                self.context.current_time(),
-                EventPayload::SimulationStart,
-            );
-
-            let new_events = self.process_broadcast_event(&start_event)?;
-            self.schedule_events(new_events)?;
-        }
+        self.start()?;
 
         // If no events in queue, send SimulationEnd and return
         if self.event_queue.is_empty() {
@@ -458,17 +1656,23 @@ impl Simulate for Simulation {
             return Ok(processed_events);
         }
 
-        // Get next event and update time
+        // Get next event and update time. Under single-hop cascading every batch is a step.
+        let single_hop = self.cascade_policy() == CascadePolicy::SingleHop;
         let next_event = self.event_queue.pop().unwrap();
         if (next_event.time - self.context.current_time()).abs() > f64::EPSILON {
             self.context.increment_current_step();
             self.context.set_current_time(next_event.time);
+        } else if single_hop {
+            self.context.increment_current_step();
         }
 
         // Process all events at the current timestep
         let mut events_to_process = self.collect_simultaneous_events(next_event);
         while !events_to_process.is_empty() {
             processed_events.extend(self.process_event_batch(events_to_process)?);
+            if single_hop {
+                break;
+            }
 
             // Check for new events at the current time
             if let Some(event) = self.event_queue.peek() {
@@ -490,6 +1694,17 @@ impl Simulate for Simulation {
         );
         debug!("\n{}", visualise_resource_transfers(&processed_events));
 
+        let state = self.get_simulation_state();
+        let change = self
+            .previous_state
+            .as_ref()
+            .map_or(f64::INFINITY, |previous| state.max_change_since(previous));
+        self.state_changes.push(change);
+        if self.recorder.samples_step(self.current_step()) {
+            self.recorder.record_state(state.clone());
+        }
+        self.previous_state = Some(state);
+
         // If queue is now empty after processing, send SimulationEnd
         if self.event_queue.is_empty() {
             let end_event = Event::new(
@@ -509,6 +1724,11 @@ impl Simulate for Simulation {
 
         // TODO Enable user-defined tolerance and use instead of f64::EPSILON
         while self.context.current_time() < until + f64::EPSILON {
+            // Without a Stepper nothing keeps the queue topped up, so it may run dry first
+            self.start()?;
+            if self.event_queue.is_empty() {
+                break;
+            }
             match self.step() {
                 Ok(events) => processed_events.extend(events),
                 Err(e) => {
@@ -568,18 +1788,33 @@ impl Simulate for Simulation {
             .ok_or_else(|| SimulationError::ProcessNotFound(event.target_id.clone()))?;
 
         let context = self.context.context_for_process(target_process.id());
-        let new_events = target_process.on_events(&vec![event.clone()], &context)?;
+        let new_events = target_process.on_events(std::slice::from_ref(event), &context)?;
+        self.context
+            .count_events(&event.target_id, 1, new_events.len());
 
         Ok(new_events)
     }
 
     fn process_broadcast_event(&mut self, event: &Event) -> Result<Vec<Event>, SimulationError> {
         let mut new_events = Vec::new();
-        let event_slice = &[event.clone()];
+        let event_slice = std::slice::from_ref(event);
+
+        // Higher priority processes react first, ties going by id so that the order, and with
+        // it which Enabling node claims a scarce resource, is the same on every run
+        let mut processes: Vec<(&String, &mut Process)> = self.processes.iter_mut().collect();
+        processes.sort_by(|(a_id, a), (b_id, b)| {
+            b.priority().cmp(&a.priority()).then_with(|| a_id.cmp(b_id))
+        });
 
-        for (id, process) in self.processes.iter_mut() {
+        let mut counts = Vec::with_capacity(processes.len());
+        for (id, process) in processes {
             let context = self.context.context_for_process(id);
-            new_events.extend(process.on_events(event_slice, &context)?);
+            let events = process.on_events(event_slice, &context)?;
+            counts.push((id.clone(), events.len()));
+            new_events.extend(events);
+        }
+        for (id, emitted) in counts {
+            self.context.count_events(&id, 1, emitted);
         }
 
         Ok(new_events)
@@ -596,6 +1831,9 @@ impl Simulate for Simulation {
 
             let event = self.event_queue.pop().unwrap();
             debug!("Processing event at time {}: {:?}", time, event);
+            let Some(event) = self.admit_event(event)? else {
+                continue;
+            };
 
             let new_events = if event.target_id == "broadcast" {
                 self.process_broadcast_event(&event)?
@@ -604,6 +1842,7 @@ impl Simulate for Simulation {
             };
 
             self.schedule_events(new_events)?;
+            self.record_processed_event(&event);
             processed_events.push(event);
         }
 
@@ -640,11 +1879,38 @@ impl StatefulSimulation for Simulation {
 
         self.context.reset();
         self.event_queue.clear();
+        self.started = false;
+        self.event_sequence_number = 0;
+        self.renumber_connections();
+        self.state_changes.clear();
+        self.previous_state = None;
+
+        self.recorder.clear();
+        if self.recorder.is_enabled() {
+            self.recorder.record_state(self.get_simulation_state());
+        }
 
+        self.lifecycle_hooks.notify(LifecycleEvent::Reset);
         Ok(())
     }
 }
 
+fn is_level_query(event: &Event) -> bool {
+    event.payload == EventPayload::LevelQuery
+}
+
+/// Reads back the `null` that JSON writes for a step whose processes changed shape, which was
+/// recorded as an unbounded change
+fn deserialize_state_changes<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<f64>, D::Error> {
+    let changes = Vec::<Option<f64>>::deserialize(deserializer)?;
+    Ok(changes
+        .into_iter()
+        .map(|change| change.unwrap_or(f64::INFINITY))
+        .collect())
+}
+
 pub struct EventIterator<'a> {
     sim: &'a mut Simulation,
 }
@@ -715,7 +1981,7 @@ mod simulation_unit_tests {
         );
 
         simulation.add_connection(connection).unwrap();
-        simulation.remove_connection(&connection_id)?;
+        simulation.remove_connection(connection_id)?;
 
         Ok(())
     }
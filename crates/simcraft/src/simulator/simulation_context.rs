@@ -1,43 +1,129 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use crate::model::{connection::Connection, ProcessContext};
+use crate::utils::rng::derive_seed;
+
+use super::run_configuration::RunConfiguration;
+use super::{Event, EventPayload};
 
 type ProcessId = String;
 type PortId = String;
+type ConnectionId = String;
+
+/// Running totals of the resources that have moved along a single connection.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ConnectionStats {
+    pub transferred: f64,
+    pub rejected: f64,
+    pub transfers: u64,
+}
+
+/// How to treat a connection whose endpoints both move resources across it in the same step,
+/// e.g. an automatic push source feeding an automatic pull pool.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DuplexPolicy {
+    /// Deliver both the push and the pull, so the connection may carry twice its flow rate
+    #[default]
+    Allow,
+    /// Deliver only the first transfer on a connection each step and reject the rest
+    Dedup,
+}
+
+/// How far a single step follows chains of events scheduled for the time being processed
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CascadePolicy {
+    /// Keep processing until no events remain at the current time
+    #[default]
+    SameTimeExhaustive,
+    /// Process one batch of events per step, leaving whatever it schedules for the same time
+    /// to the following steps
+    SingleHop,
+}
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct SimulationContext {
-    // pub(crate) rng: Rng,
+    #[serde(flatten)]
+    pub(crate) config: RunConfiguration,
     pub(crate) current_step: u64,
     pub(crate) current_time: f64,
-    pub(crate) dt: f64,
     pub(crate) input_map: HashMap<ProcessId, HashMap<Option<PortId>, Vec<Connection>>>,
     pub(crate) output_map: HashMap<ProcessId, HashMap<Option<PortId>, Vec<Connection>>>,
+    #[serde(default)]
+    pub(crate) connection_stats: HashMap<ConnectionId, ConnectionStats>,
+    #[serde(default)]
+    pub(crate) process_priorities: HashMap<ProcessId, i64>,
+    #[serde(default)]
+    pub(crate) process_priority_classes: HashMap<ProcessId, i64>,
+    /// Events each process has handled and emitted, as `(received, emitted)`
+    #[serde(default)]
+    pub(crate) event_counts: HashMap<ProcessId, (u64, u64)>,
+    /// Resources delivered along capacity-limited connections and not yet acknowledged
+    #[serde(default)]
+    pub(crate) unacknowledged: HashMap<ConnectionId, f64>,
+    #[serde(skip)]
+    step_transfers: (u64, HashSet<ConnectionId>),
 }
 
 impl Default for SimulationContext {
     fn default() -> Self {
         Self {
+            config: RunConfiguration::default(),
             current_step: 0,
             current_time: 0.0,
-            dt: 1.0,
             input_map: HashMap::new(),
             output_map: HashMap::new(),
+            connection_stats: HashMap::new(),
+            process_priorities: HashMap::new(),
+            process_priority_classes: HashMap::new(),
+            event_counts: HashMap::new(),
+            unacknowledged: HashMap::new(),
+            step_transfers: (0, HashSet::new()),
         }
     }
 }
 
 impl SimulationContext {
-    // TODO Implement global rng
-    // pub fn rng(&self) -> Rng {
-    //     self.rng.clone()
-    // }
+    pub fn config(&self) -> &RunConfiguration {
+        &self.config
+    }
+
+    pub fn set_config(&mut self, config: RunConfiguration) {
+        self.config = config;
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.config.seed
+    }
+
+    pub fn set_seed(&mut self, seed: u64) {
+        self.config.seed = seed;
+    }
 
     pub fn reset(&mut self) {
         self.current_step = 0;
-        self.current_time = 0.0;
+        self.current_time = self.config.start_time;
+        self.connection_stats.clear();
+        self.event_counts.clear();
+        self.unacknowledged.clear();
+        self.step_transfers = (0, HashSet::new());
+    }
+
+    pub fn duplex_policy(&self) -> DuplexPolicy {
+        self.config.duplex_policy
+    }
+
+    pub fn set_duplex_policy(&mut self, policy: DuplexPolicy) {
+        self.config.duplex_policy = policy;
+    }
+
+    pub fn cascade_policy(&self) -> CascadePolicy {
+        self.config.cascade_policy
+    }
+
+    pub fn set_cascade_policy(&mut self, policy: CascadePolicy) {
+        self.config.cascade_policy = policy;
     }
 
     pub fn current_step(&self) -> u64 {
@@ -69,6 +155,10 @@ impl SimulationContext {
             self.process_inputs(process_id),
             self.process_outputs(process_id),
         )
+        .with_seed(derive_seed(self.config.seed, process_id))
+        .with_dt(self.config.dt)
+        .with_priorities(&self.process_priorities)
+        .with_priority_classes(&self.process_priority_classes)
     }
 
     /// Returns all input connections for the given process.
@@ -107,3 +197,155 @@ impl SimulationContext {
             .unwrap_or(&[])
     }
 }
+
+impl SimulationContext {
+    /// Finds the connection carrying resources from `source_id` to `target_id`.
+    ///
+    /// When several connections link the same pair of processes, the ports are used
+    /// to disambiguate, falling back to the first connection created.
+    pub fn find_connection(
+        &self,
+        source_id: &str,
+        source_port: Option<&str>,
+        target_id: &str,
+        target_port: Option<&str>,
+    ) -> Option<&Connection> {
+        let mut candidates: Vec<&Connection> = self
+            .process_outputs(source_id)
+            .into_iter()
+            .filter(|conn| conn.target_id == target_id)
+            .collect();
+        candidates.sort_by_key(|conn| conn.sequence_number);
+
+        let port_matches = |conn: &&&Connection| {
+            source_port.is_none_or(|port| port.is_empty() || conn.source_port() == Some(port))
+                && target_port
+                    .is_none_or(|port| port.is_empty() || conn.target_port() == Some(port))
+        };
+
+        candidates
+            .iter()
+            .find(port_matches)
+            .or_else(|| candidates.first())
+            .copied()
+    }
+
+    /// Returns the accumulated statistics for a connection.
+    pub fn connection_stats(&self, connection_id: &str) -> ConnectionStats {
+        self.connection_stats
+            .get(connection_id)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn count_events(&mut self, process_id: &str, received: usize, emitted: usize) {
+        let counts = self.event_counts.entry(process_id.to_string()).or_default();
+        counts.0 += received as u64;
+        counts.1 += emitted as u64;
+    }
+
+    /// The connection a resource transfer or its acknowledgement travels along, if any.
+    pub fn connection_for_transfer(&self, event: &Event) -> Option<&Connection> {
+        match event.payload {
+            EventPayload::Resource(_) => self.find_connection(
+                &event.source_id,
+                event.source_port.as_deref(),
+                &event.target_id,
+                event.target_port.as_deref(),
+            ),
+            // Acknowledgements travel from receiver back to sender, against the connection
+            EventPayload::ResourceAccepted(_) | EventPayload::ResourceRejected(_) => self
+                .find_connection(
+                    &event.target_id,
+                    event.target_port.as_deref(),
+                    &event.source_id,
+                    event.source_port.as_deref(),
+                ),
+            _ => None,
+        }
+    }
+
+    /// Attributes a processed resource acknowledgement to the connection it travelled along.
+    pub(crate) fn record_transfer(&mut self, event: &Event) {
+        let (amount, accepted) = match event.payload {
+            EventPayload::ResourceAccepted(amount) => (amount, true),
+            EventPayload::ResourceRejected(amount) => (amount, false),
+            _ => return,
+        };
+
+        let Some(connection_id) = self
+            .connection_for_transfer(event)
+            .map(|conn| conn.id.clone())
+        else {
+            return;
+        };
+
+        if let Some(unacknowledged) = self.unacknowledged.get_mut(&connection_id) {
+            *unacknowledged = (*unacknowledged - amount).max(0.0);
+        }
+        let stats = self.connection_stats.entry(connection_id).or_default();
+        if accepted {
+            stats.transferred += amount;
+            stats.transfers += 1;
+        } else {
+            stats.rejected += amount;
+        }
+    }
+
+    /// Claims the connection a resource event travels along for the current step.
+    ///
+    /// Returns `false` when the duplex policy forbids delivering it.
+    pub(crate) fn claim_transfer(&mut self, event: &Event) -> bool {
+        if self.config.duplex_policy == DuplexPolicy::Allow
+            || !matches!(event.payload, EventPayload::Resource(_))
+        {
+            return true;
+        }
+
+        let Some(connection_id) = self
+            .find_connection(
+                &event.source_id,
+                event.source_port.as_deref(),
+                &event.target_id,
+                event.target_port.as_deref(),
+            )
+            .map(|conn| conn.id.clone())
+        else {
+            return true;
+        };
+
+        if self.step_transfers.0 != self.current_step {
+            self.step_transfers = (self.current_step, HashSet::new());
+        }
+        self.step_transfers.1.insert(connection_id)
+    }
+
+    /// Claims room on a connection with a `total_capacity` for a resource event, returning
+    /// how much of it may be delivered.
+    ///
+    /// Room is what the capacity leaves after everything already accepted or still awaiting
+    /// acknowledgement. The whole amount is counted as unacknowledged, since the part beyond
+    /// the room comes back as a rejection.
+    pub(crate) fn claim_capacity(&mut self, event: &Event) -> f64 {
+        let EventPayload::Resource(amount) = event.payload else {
+            return 0.0;
+        };
+        let Some((connection_id, capacity)) = self
+            .find_connection(
+                &event.source_id,
+                event.source_port.as_deref(),
+                &event.target_id,
+                event.target_port.as_deref(),
+            )
+            .and_then(|conn| Some((conn.id.clone(), conn.total_capacity?)))
+        else {
+            return amount;
+        };
+
+        let transferred = self.connection_stats(&connection_id).transferred;
+        let unacknowledged = self.unacknowledged.entry(connection_id).or_default();
+        let room = (capacity - transferred - *unacknowledged).max(0.0);
+        *unacknowledged += amount;
+        amount.min(room)
+    }
+}
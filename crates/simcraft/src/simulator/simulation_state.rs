@@ -2,7 +2,9 @@ use serde::Deserialize;
 use serde::Serialize;
 use std::collections::HashMap;
 
+use crate::analysis::timeseries::scalar_fields;
 use crate::model::ProcessState;
+use crate::utils::hash::StableHasher;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SimulationState {
@@ -10,3 +12,121 @@ pub struct SimulationState {
     pub time: f64,
     pub process_states: HashMap<String, ProcessState>,
 }
+
+impl SimulationState {
+    /// Returns a hash of the step, time and every numeric process field that is stable
+    /// across runs, platforms and `HashMap` iteration order.
+    pub fn stable_hash(&self) -> u64 {
+        let mut hash = StableHasher::default();
+        hash.write_u64(self.step);
+        hash.write_f64(self.time);
+
+        let mut process_ids: Vec<&String> = self.process_states.keys().collect();
+        process_ids.sort();
+
+        for id in process_ids {
+            hash.write_str(id);
+
+            let mut fields = scalar_fields(&self.process_states[id]);
+            fields.sort_by(|a, b| a.0.cmp(&b.0));
+            for (field, value) in fields {
+                hash.write_str(&field);
+                hash.write_f64(value);
+            }
+        }
+
+        hash.finish()
+    }
+
+    /// Whether `other` is at the same step, and its time and every numeric process field are
+    /// within `epsilon` of this state's. States holding different processes are never equal.
+    pub fn approx_eq(&self, other: &SimulationState, epsilon: f64) -> bool {
+        self.step == other.step
+            && (self.time - other.time).abs() <= epsilon
+            && self.process_states.len() == other.process_states.len()
+            && self
+                .process_states
+                .iter()
+                .all(|(id, state)| process_change(state, other.process_states.get(id)) <= epsilon)
+    }
+
+    /// Largest change in any numeric process field since `previous`, or infinity when a
+    /// process or field appeared or disappeared. Steppers are skipped: their counters advance
+    /// every step regardless of the model.
+    pub fn max_change_since(&self, previous: &SimulationState) -> f64 {
+        if self.process_states.len() != previous.process_states.len() {
+            return f64::INFINITY;
+        }
+
+        self.process_states
+            .iter()
+            .filter(|(_, state)| !matches!(state, ProcessState::Stepper(_)))
+            .map(|(id, state)| process_change(state, previous.process_states.get(id)))
+            .fold(0.0, f64::max)
+    }
+
+    /// Processes whose state differs from `previous`, plus those no longer present.
+    /// Steppers are left out, as in `max_change_since`.
+    pub fn delta_since(&self, previous: &SimulationState) -> StateDelta {
+        let changed = self
+            .process_states
+            .iter()
+            .filter(|(_, state)| !matches!(state, ProcessState::Stepper(_)))
+            .filter(|(id, state)| process_change(state, previous.process_states.get(*id)) > 0.0)
+            .map(|(id, state)| (id.clone(), state.clone()))
+            .collect();
+
+        let mut removed: Vec<String> = previous
+            .process_states
+            .keys()
+            .filter(|id| !self.process_states.contains_key(*id))
+            .cloned()
+            .collect();
+        removed.sort();
+
+        StateDelta {
+            step: self.step,
+            time: self.time,
+            changed,
+            removed,
+        }
+    }
+}
+
+/// The part of a `SimulationState` that changed over a step, for clients that cannot afford
+/// the full state every time
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StateDelta {
+    pub step: u64,
+    pub time: f64,
+    pub changed: HashMap<String, ProcessState>,
+    pub removed: Vec<String>,
+}
+
+impl StateDelta {
+    pub fn is_empty(&self) -> bool {
+        self.changed.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// Largest change in any numeric field of a process, infinite when it is new or its fields differ
+fn process_change(state: &ProcessState, previous: Option<&ProcessState>) -> f64 {
+    let Some(previous) = previous else {
+        return f64::INFINITY;
+    };
+
+    let previous_fields: HashMap<String, f64> = scalar_fields(previous).into_iter().collect();
+    let fields = scalar_fields(state);
+    if fields.len() != previous_fields.len() {
+        return f64::INFINITY;
+    }
+
+    let mut max_change: f64 = 0.0;
+    for (field, value) in fields {
+        let Some(previous_value) = previous_fields.get(&field) else {
+            return f64::INFINITY;
+        };
+        max_change = max_change.max((value - previous_value).abs());
+    }
+    max_change
+}
@@ -0,0 +1,52 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use super::Event;
+
+pub type ConnectionWatcher = Box<dyn FnMut(&Event) + Send>;
+
+/// Callbacks invoked for the resource traffic along individual connections, keyed by
+/// connection id.
+///
+/// Watchers are tied to one running simulation: clones start without any.
+#[derive(Default)]
+pub struct ConnectionWatchers {
+    watchers: HashMap<String, Vec<ConnectionWatcher>>,
+}
+
+impl ConnectionWatchers {
+    pub fn register(&mut self, connection_id: &str, watcher: ConnectionWatcher) {
+        self.watchers
+            .entry(connection_id.to_string())
+            .or_default()
+            .push(watcher);
+    }
+
+    pub fn is_watched(&self, connection_id: &str) -> bool {
+        self.watchers.contains_key(connection_id)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.watchers.is_empty()
+    }
+
+    pub(crate) fn notify(&mut self, connection_id: &str, event: &Event) {
+        for watcher in self.watchers.get_mut(connection_id).into_iter().flatten() {
+            watcher(event);
+        }
+    }
+}
+
+impl Clone for ConnectionWatchers {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
+
+impl fmt::Debug for ConnectionWatchers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConnectionWatchers")
+            .field("connections", &self.watchers.len())
+            .finish()
+    }
+}
@@ -1,24 +1,83 @@
 use serde::Serialize;
 use thiserror::Error;
 
-#[derive(Error, Debug, Serialize)]
+#[derive(Error, Debug, Clone, PartialEq, Serialize)]
 pub enum SimulationError {
     #[error("Duplicate process ID: {0}")]
     DuplicateProcess(String),
-    #[error("Invalid {port_type} port '{port}' for process '{process}'")]
+    #[error("Invalid {port_type} port '{port}' for {process_type} process '{process}'")]
     InvalidPort {
         process: String,
+        process_type: String,
         port: String,
         port_type: String,
     },
     #[error("Invalid dt value: {0}")]
     InvalidDt(f64),
+    #[error("Invalid start time: {0}")]
+    InvalidStartTime(f64),
+    #[error("Invalid calendar bucket width: {0}")]
+    InvalidBucketWidth(f64),
     #[error("No events remaining in queue")]
     NoEvents,
+    #[error("Simulation has no processes to step")]
+    EmptySimulation,
     #[error("{0}")]
     Other(String),
     #[error("Process with ID '{0}' not found")]
     ProcessNotFound(String),
     #[error("Connection with ID '{0}' not found")]
     ConnectionNotFound(String),
+    #[error("Connections reference undefined processes: {}", .0.join(", "))]
+    MissingProcesses(Vec<String>),
+    #[error("Required {port_type} port '{port}' of process '{process}' has no connections")]
+    UnconnectedPort {
+        process: String,
+        port: String,
+        port_type: String,
+    },
+    #[error("{process_type} process '{process}' has no {port_type} ports to connect")]
+    NoPorts {
+        process: String,
+        process_type: String,
+        port_type: String,
+    },
+    #[error("No value given for parameter '{0}'")]
+    UnknownParameter(String),
+    #[error("Simulation is frozen; unfreeze it before changing processes or connections")]
+    Frozen,
+    #[error("Simulation has no Stepper process")]
+    NoStepper,
+    #[error("Simulation has several Stepper processes: {}", .0.join(", "))]
+    AmbiguousStepper(Vec<String>),
+    #[error("{process_type} process '{process}' can't perform action {action}")]
+    UnsupportedAction {
+        process: String,
+        process_type: String,
+        action: String,
+    },
+    #[error(
+        "{process_type} process '{process}' only observes; connect it from the 'observe' port"
+    )]
+    ObserverLink {
+        process: String,
+        process_type: String,
+    },
+}
+
+impl SimulationError {
+    /// Id of the process the error is about, if any
+    pub fn process_id(&self) -> Option<&str> {
+        match self {
+            SimulationError::DuplicateProcess(id) | SimulationError::ProcessNotFound(id) => {
+                Some(id)
+            }
+            SimulationError::InvalidPort { process, .. }
+            | SimulationError::UnconnectedPort { process, .. }
+            | SimulationError::NoPorts { process, .. }
+            | SimulationError::UnsupportedAction { process, .. }
+            | SimulationError::ObserverLink { process, .. } => Some(process),
+            _ => None,
+        }
+    }
 }
@@ -0,0 +1,69 @@
+/// Resolution at which floats are quantized before hashing, to absorb representation noise
+const HASH_QUANTUM: f64 = 1e-9;
+
+/// FNV-1a hasher, used instead of `DefaultHasher` whose output may change between Rust releases
+pub struct StableHasher(u64);
+
+impl Default for StableHasher {
+    fn default() -> Self {
+        Self(0xCBF2_9CE4_8422_2325)
+    }
+}
+
+impl StableHasher {
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = (self.0 ^ *byte as u64).wrapping_mul(0x0000_0100_0000_01B3);
+        }
+    }
+
+    pub fn write_u64(&mut self, value: u64) {
+        self.write_bytes(&value.to_le_bytes());
+    }
+
+    pub fn write_f64(&mut self, value: f64) {
+        // Hash the bits rather than casting to an integer, which saturates for large values.
+        // Zero and NaN each have more than one representation, so they are written as one
+        let quantized = (value / HASH_QUANTUM).round();
+        let quantized = if quantized.is_nan() {
+            f64::NAN
+        } else if quantized == 0.0 {
+            0.0
+        } else {
+            quantized
+        };
+        self.write_u64(quantized.to_bits());
+    }
+
+    pub fn write_str(&mut self, value: &str) {
+        self.write_bytes(value.as_bytes());
+        // Separator so that adjacent strings can't collide by shifting characters
+        self.write_bytes(&[0xFF]);
+    }
+
+    pub fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hash_f64(value: f64) -> u64 {
+        let mut hash = StableHasher::default();
+        hash.write_f64(value);
+        hash.finish()
+    }
+
+    #[test]
+    fn test_large_and_special_floats() {
+        // Casting the quantized value to an integer saturated everything above ~9.2e9
+        assert_ne!(hash_f64(1e10), hash_f64(2e10));
+        assert_ne!(hash_f64(1e290), hash_f64(2e290));
+
+        assert_eq!(hash_f64(-0.0), hash_f64(0.0));
+        assert_eq!(hash_f64(-1e-12), hash_f64(0.0));
+        assert_eq!(hash_f64(f64::NAN), hash_f64(-f64::NAN));
+    }
+}
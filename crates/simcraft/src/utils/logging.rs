@@ -1,5 +1,12 @@
-use std::sync::Once;
-use tracing_subscriber::{fmt, EnvFilter};
+use std::sync::{Arc, Mutex, Once};
+
+use serde::Serialize;
+use tracing::dispatcher::DefaultGuard;
+use tracing::field::{Field, Visit};
+use tracing::{Dispatch, Level, Subscriber};
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+use tracing_subscriber::{fmt, EnvFilter, Registry};
 
 static INIT_LOGGING_ONCE: Once = Once::new();
 
@@ -14,3 +21,123 @@ pub fn init_logging_once() {
         }
     });
 }
+
+/// An engine log message captured for an embedder, e.g. to show in a UI log panel
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LogRecord {
+    /// Severity, e.g. "WARN"
+    pub level: String,
+    /// Module the message came from
+    pub target: String,
+    pub message: String,
+}
+
+/// Collects the log messages a simulation emits while it steps, at or above a minimum level.
+///
+/// Capturing installs a tracing dispatcher for the duration of each step, on the stepping
+/// thread only, so simulations running side by side keep separate logs. Messages still reach
+/// the usual formatted output as well. Clones start with a copy of the records collected so far.
+#[derive(Default)]
+pub struct LogCapture {
+    records: Arc<Mutex<Vec<LogRecord>>>,
+    dispatch: Option<(Level, Dispatch)>,
+}
+
+impl LogCapture {
+    /// Starts capturing messages at `min_level` and above, keeping any already captured
+    pub fn enable(&mut self, min_level: Level) {
+        let layer = CaptureLayer {
+            records: self.records.clone(),
+        };
+        let subscriber = Registry::default()
+            .with(fmt::layer().with_filter(EnvFilter::from_default_env()))
+            .with(layer.with_filter(LevelFilter::from_level(min_level)));
+        self.dispatch = Some((min_level, Dispatch::new(subscriber)));
+    }
+
+    pub fn disable(&mut self) {
+        self.dispatch = None;
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.dispatch.is_some()
+    }
+
+    pub fn records(&self) -> Vec<LogRecord> {
+        self.records.lock().unwrap().clone()
+    }
+
+    pub fn clear(&self) {
+        self.records.lock().unwrap().clear();
+    }
+
+    /// Routes log messages into the capture until the returned guard is dropped
+    pub(crate) fn activate(&self) -> Option<DefaultGuard> {
+        self.dispatch
+            .as_ref()
+            .map(|(_, dispatch)| tracing::dispatcher::set_default(dispatch))
+    }
+
+    /// Returns a capture at the same level with no records
+    pub(crate) fn empty_like(&self) -> Self {
+        let mut capture = Self::default();
+        if let Some((level, _)) = &self.dispatch {
+            capture.enable(*level);
+        }
+        capture
+    }
+}
+
+impl Clone for LogCapture {
+    fn clone(&self) -> Self {
+        let capture = self.empty_like();
+        *capture.records.lock().unwrap() = self.records();
+        capture
+    }
+}
+
+impl std::fmt::Debug for LogCapture {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LogCapture")
+            .field("records", &self.records.lock().unwrap().len())
+            .field("level", &self.dispatch.as_ref().map(|(level, _)| level))
+            .finish()
+    }
+}
+
+struct CaptureLayer {
+    records: Arc<Mutex<Vec<LogRecord>>>,
+}
+
+impl<S: Subscriber> Layer<S> for CaptureLayer {
+    fn on_event(&self, event: &tracing::Event<'_>, _ctx: Context<'_, S>) {
+        let mut visitor = MessageVisitor::default();
+        event.record(&mut visitor);
+        let metadata = event.metadata();
+        self.records.lock().unwrap().push(LogRecord {
+            level: metadata.level().to_string(),
+            target: metadata.target().to_string(),
+            message: visitor.message,
+        });
+    }
+}
+
+/// Reads an event's formatted message, ignoring its other fields
+#[derive(Default)]
+struct MessageVisitor {
+    message: String,
+}
+
+impl Visit for MessageVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            self.message = format!("{:?}", value);
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message = value.to_string();
+        }
+    }
+}
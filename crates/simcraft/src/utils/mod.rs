@@ -1,4 +1,10 @@
 pub mod errors;
+pub mod hash;
 pub mod logging;
+pub mod ports;
+pub mod rng;
+pub mod sum;
 
 pub use errors::SimulationError;
+pub use rng::Rng;
+pub use sum::KahanSum;
@@ -0,0 +1,31 @@
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+use lazy_static::lazy_static;
+use serde::{Deserialize, Deserializer};
+
+lazy_static! {
+    static ref PORT_NAMES: Mutex<HashSet<&'static str>> = Mutex::new(HashSet::new());
+}
+
+/// Returns a `'static` copy of a port name, allocating each distinct name only once.
+///
+/// Processes report their ports as `&'static str`, so names chosen at runtime
+/// (e.g. read from a model file) are interned here.
+pub fn intern_port(name: &str) -> &'static str {
+    let mut names = PORT_NAMES.lock().unwrap();
+    if let Some(interned) = names.get(name) {
+        return interned;
+    }
+    let interned: &'static str = Box::leak(name.to_string().into_boxed_str());
+    names.insert(interned);
+    interned
+}
+
+/// Deserializes a list of port names into interned `'static` names
+pub fn deserialize_ports<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<&'static str>, D::Error> {
+    let names = Vec::<String>::deserialize(deserializer)?;
+    Ok(names.iter().map(|name| intern_port(name)).collect())
+}
@@ -0,0 +1,87 @@
+use serde::{Deserialize, Serialize};
+
+/// Small deterministic pseudo-random number generator (SplitMix64).
+///
+/// Produces identical sequences on every platform, including WASM, for a given seed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a uniformly distributed value in `[0, 1)`.
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Picks an index with probability proportional to its weight.
+    ///
+    /// Returns `None` if there are no positive weights.
+    pub fn choose_weighted(&mut self, weights: &[f64]) -> Option<usize> {
+        let total: f64 = weights.iter().filter(|w| **w > 0.0).sum();
+        if total <= 0.0 {
+            return None;
+        }
+
+        let mut target = self.next_f64() * total;
+        for (index, weight) in weights.iter().enumerate() {
+            if *weight <= 0.0 {
+                continue;
+            }
+            if target < *weight {
+                return Some(index);
+            }
+            target -= weight;
+        }
+
+        weights.iter().rposition(|w| *w > 0.0)
+    }
+
+    /// Number of successes in `trials` whole trials that each succeed with `probability`.
+    ///
+    /// Small counts are drawn trial by trial; larger ones from the normal approximation, so
+    /// the cost doesn't grow with the count.
+    pub fn binomial(&mut self, trials: f64, probability: f64) -> f64 {
+        if trials <= 0.0 || probability <= 0.0 {
+            return 0.0;
+        }
+        if probability >= 1.0 {
+            return trials;
+        }
+        if trials <= 64.0 {
+            return (0..trials as usize)
+                .filter(|_| self.next_f64() < probability)
+                .count() as f64;
+        }
+
+        // Box-Muller, with the first draw kept off zero for the logarithm
+        let radius = (-2.0 * (1.0 - self.next_f64()).ln()).sqrt();
+        let normal = radius * (std::f64::consts::TAU * self.next_f64()).cos();
+        let mean = trials * probability;
+        let spread = (mean * (1.0 - probability)).sqrt();
+        (mean + spread * normal).round().clamp(0.0, trials)
+    }
+}
+
+/// Derives a stable per-process seed from the simulation seed and a process id.
+pub fn derive_seed(seed: u64, process_id: &str) -> u64 {
+    // FNV-1a keeps the hash stable across Rust versions, unlike `DefaultHasher`
+    let hash = process_id
+        .bytes()
+        .fold(0xCBF2_9CE4_8422_2325u64, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01B3)
+        });
+    Rng::new(seed ^ hash).next_u64()
+}
@@ -0,0 +1,58 @@
+/// Compensated (Kahan-Babuska) summation, which keeps the rounding error of a long
+/// sum of floats bounded independently of the number of terms.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct KahanSum {
+    sum: f64,
+    compensation: f64,
+}
+
+impl KahanSum {
+    pub fn add(&mut self, value: f64) {
+        let total = self.sum + value;
+        // Recover the low-order bits lost by whichever operand was smaller
+        if self.sum.abs() >= value.abs() {
+            self.compensation += (self.sum - total) + value;
+        } else {
+            self.compensation += (value - total) + self.sum;
+        }
+        self.sum = total;
+    }
+
+    pub fn value(&self) -> f64 {
+        self.sum + self.compensation
+    }
+}
+
+impl Extend<f64> for KahanSum {
+    fn extend<I: IntoIterator<Item = f64>>(&mut self, values: I) {
+        for value in values {
+            self.add(value);
+        }
+    }
+}
+
+impl FromIterator<f64> for KahanSum {
+    fn from_iter<I: IntoIterator<Item = f64>>(values: I) -> Self {
+        let mut sum = KahanSum::default();
+        sum.extend(values);
+        sum
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compensated_sum_of_small_fractions() {
+        let terms = std::iter::repeat_n(0.1, 1_000_000);
+
+        let naive: f64 = terms.clone().sum();
+        let compensated = terms.collect::<KahanSum>().value();
+
+        // The stored 0.1 is off by ~5.6e-18, so the exact total is within 1e-11 of 1e5
+        let exact = 100_000.0;
+        assert!((compensated - exact).abs() < 1e-9);
+        assert!((naive - exact).abs() > 1e-7);
+    }
+}
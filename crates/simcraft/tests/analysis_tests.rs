@@ -0,0 +1,388 @@
+mod common;
+
+#[cfg(test)]
+mod analysis_tests {
+    use std::collections::HashMap;
+
+    use simcraft::analysis::{animation, conservation, flow_matrix, maxflow, timeseries, Metrics};
+    use simcraft::model::nodes::{Delay, DelayAction, Drain, Pool, Source};
+    use simcraft::model::process_state::PoolState;
+    use simcraft::model::{ProcessContext, ProcessState, Processor};
+    use simcraft::prelude::*;
+    use simcraft::simulator::{Event, EventPayload, SimulationState};
+
+    use crate::common::{create_stepped_simulation, setup};
+
+    #[test]
+    fn test_timeseries_collect() -> Result<(), SimulationError> {
+        setup();
+
+        let source = Process::new(Box::new(Source::new("source1")));
+        let pool = Process::new(Box::new(Pool::new("pool1")));
+        let connection = Connection::new(
+            "conn1".to_string(),
+            "source1".to_string(),
+            Some("out".to_string()),
+            "pool1".to_string(),
+            Some("in".to_string()),
+            Some(1.5),
+        );
+
+        let mut sim = create_stepped_simulation(vec![source, pool], vec![connection])?;
+
+        let mut states = vec![sim.get_simulation_state()];
+        for _ in 0..4 {
+            sim.step()?;
+            states.push(sim.get_simulation_state());
+        }
+
+        let series = timeseries::collect(&states);
+        let expected: Vec<(f64, f64)> = states
+            .iter()
+            .map(|state| match &state.process_states["pool1"] {
+                ProcessState::Pool(pool) => (state.time, pool.resources),
+                _ => unreachable!(),
+            })
+            .collect();
+
+        assert_eq!(series["pool1.resources"], expected);
+        assert_eq!(series["pool1.resources"].last(), Some(&(4.0, 6.0)));
+        assert_eq!(series["source1.resources_produced"].len(), states.len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_step_flow_matrix() -> Result<(), SimulationError> {
+        setup();
+
+        let source1 = Process::new(Box::new(Source::new("source1")));
+        let source2 = Process::new(Box::new(Source::new("source2")));
+        let pool = Process::new(Box::new(Pool::new("pool1")));
+        let connections = vec![
+            Connection::new(
+                "conn1".to_string(),
+                "source1".to_string(),
+                Some("out".to_string()),
+                "pool1".to_string(),
+                Some("in".to_string()),
+                Some(1.0),
+            ),
+            Connection::new(
+                "conn2".to_string(),
+                "source2".to_string(),
+                Some("out".to_string()),
+                "pool1".to_string(),
+                Some("in".to_string()),
+                Some(2.0),
+            ),
+        ];
+        let mut sim = create_stepped_simulation(vec![source1, source2, pool], connections)?;
+
+        let matrix = flow_matrix::step_flow_matrix(&sim.step()?);
+        assert_eq!(matrix.len(), 2);
+        assert_eq!(matrix[&("source1".to_string(), "pool1".to_string())], 1.0);
+        assert_eq!(matrix[&("source2".to_string(), "pool1".to_string())], 2.0);
+
+        // Accumulated over a run the matrix gives the flow network
+        let mut network: HashMap<(String, String), f64> = HashMap::new();
+        for _ in 0..4 {
+            for (pair, amount) in flow_matrix::step_flow_matrix(&sim.step()?) {
+                *network.entry(pair).or_default() += amount;
+            }
+        }
+        assert_eq!(network[&("source1".to_string(), "pool1".to_string())], 4.0);
+        assert_eq!(network[&("source2".to_string(), "pool1".to_string())], 8.0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_max_flow_diamond() -> Result<(), SimulationError> {
+        setup();
+
+        // source -> {a, b} -> drain, with a cross link a -> b
+        let processes = vec![
+            Process::new(Box::new(Source::new("source"))),
+            Process::new(Box::new(Pool::new("a"))),
+            Process::new(Box::new(Pool::new("b"))),
+            Process::new(Box::new(Drain::new("drain"))),
+        ];
+        let edges = [
+            ("source", "a", 3.0),
+            ("source", "b", 2.0),
+            ("a", "b", 1.0),
+            ("a", "drain", 1.5),
+            ("b", "drain", 4.0),
+        ];
+        let connections = edges
+            .iter()
+            .map(|(from, to, rate)| {
+                Connection::new(
+                    format!("{}_{}", from, to),
+                    from.to_string(),
+                    Some("out".to_string()),
+                    to.to_string(),
+                    Some("in".to_string()),
+                    Some(*rate),
+                )
+            })
+            .collect();
+        let sim = create_stepped_simulation(processes, connections)?;
+
+        // The cheapest cut separates {source, a} from {b, drain}: 2 + 1 + 1.5
+        assert_eq!(maxflow::max_flow(&sim, "source", "drain"), 4.5);
+        assert_eq!(maxflow::max_flow(&sim, "drain", "source"), 0.0);
+        assert_eq!(maxflow::max_flow(&sim, "source", "missing"), 0.0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_animation_frames() -> Result<(), SimulationError> {
+        setup();
+
+        let source = Process::new(Box::new(Source::new("source1")));
+        let pool = Process::new(Box::new(Pool::new("pool1")));
+        let connection = Connection::new(
+            "conn1".to_string(),
+            "source1".to_string(),
+            Some("out".to_string()),
+            "pool1".to_string(),
+            Some("in".to_string()),
+            Some(2.0),
+        );
+        let mut sim = create_stepped_simulation(vec![source, pool], vec![connection])?;
+
+        let steps = 4;
+        let mut states = Vec::new();
+        let mut matrices = Vec::new();
+        for _ in 0..steps {
+            matrices.push(flow_matrix::step_flow_matrix(&sim.step()?));
+            states.push(sim.get_simulation_state());
+        }
+
+        let frames = animation::frames(&states, &matrices);
+        assert_eq!(frames.len(), steps);
+        for (index, frame) in frames.iter().enumerate() {
+            assert_eq!(frame.step, index as u64 + 1);
+            assert_eq!(
+                frame.flows,
+                vec![animation::Flow {
+                    source: "source1".to_string(),
+                    target: "pool1".to_string(),
+                    amount: 2.0,
+                }]
+            );
+            let ProcessState::Pool(pool) = &frame.process_states["pool1"] else {
+                unreachable!()
+            };
+            assert_eq!(pool.resources, 2.0 * (index + 1) as f64);
+        }
+
+        let json = serde_json::to_value(&frames[0]).unwrap();
+        assert_eq!(json["flows"][0]["amount"], 2.0);
+        assert!(json["processStates"]["pool1"].is_object());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_locate_leak() -> Result<(), SimulationError> {
+        setup();
+
+        /// Accepts everything it is sent but never counts it in its state
+        #[derive(Clone, Debug)]
+        struct Hoarder {
+            id: String,
+        }
+
+        impl SerializableProcess for Hoarder {}
+
+        impl Processor for Hoarder {
+            fn id(&self) -> &str {
+                &self.id
+            }
+
+            fn on_event(
+                &mut self,
+                event: &Event,
+                context: &ProcessContext,
+            ) -> Result<Vec<Event>, SimulationError> {
+                match event.payload {
+                    EventPayload::Resource(amount) => Ok(vec![Event::new(
+                        &self.id,
+                        &event.source_id,
+                        context.current_time(),
+                        EventPayload::ResourceAccepted(amount),
+                    )]),
+                    _ => Ok(vec![]),
+                }
+            }
+
+            fn get_state(&self) -> ProcessState {
+                ProcessState::Custom(serde_json::json!({}))
+            }
+
+            fn get_input_ports(&self) -> &[&'static str] {
+                &["in"]
+            }
+
+            fn get_output_ports(&self) -> &[&'static str] {
+                &[]
+            }
+
+            fn reset(&mut self) {}
+        }
+
+        let connection = |id: &str, source: &str, target: &str, flow_rate: f64| {
+            Connection::new(
+                id.to_string(),
+                source.to_string(),
+                Some("out".to_string()),
+                target.to_string(),
+                Some("in".to_string()),
+                Some(flow_rate),
+            )
+        };
+        let mut sim = create_stepped_simulation(
+            vec![
+                Process::new(Box::new(Source::new("source"))),
+                Process::new(Box::new(Pool::new("pool"))),
+                Process::new(Box::new(Hoarder {
+                    id: "hoarder".to_string(),
+                })),
+            ],
+            vec![
+                connection("to_pool", "source", "pool", 1.0),
+                connection("to_hoarder", "source", "hoarder", 2.0),
+            ],
+        )?;
+
+        sim.step_n(3)?;
+        assert!(!sim.summary().conserved);
+
+        let report = conservation::locate_leak(&sim).expect("Expected a leak");
+        assert_eq!(report.leaked, 6.0);
+        assert_eq!(
+            report.processes,
+            vec![conservation::ProcessLeak {
+                process: "hoarder".to_string(),
+                leaked: 6.0,
+                inflow: 6.0,
+                outflow: 0.0,
+                held_before: 0.0,
+                held_after: 0.0,
+            }]
+        );
+
+        // Without the hoarder every process balances
+        sim.remove_connection("to_hoarder")?;
+        sim.remove_process("hoarder")?;
+        sim.reset()?;
+        sim.step_n(3)?;
+        assert_eq!(conservation::locate_leak(&sim), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_model_graph_city_traffic() -> Result<(), SimulationError> {
+        setup();
+
+        // The city traffic model: cars_in -> traffic_light -> one_lane_bridge -> roundabout -> exit
+        let processes = vec![
+            Process::new(Box::new(Source::new("cars_in"))),
+            Process::new(Box::new(
+                Delay::builder()
+                    .id("traffic_light")
+                    .action(DelayAction::Delay)
+                    .build()
+                    .unwrap(),
+            )),
+            Process::new(Box::new(
+                Delay::builder()
+                    .id("one_lane_bridge")
+                    .action(DelayAction::Queue)
+                    .build()
+                    .unwrap(),
+            )),
+            Process::new(Box::new(
+                Pool::builder()
+                    .id("roundabout")
+                    .capacity(10.0)
+                    .build()
+                    .unwrap(),
+            )),
+            Process::new(Box::new(Drain::new("exit"))),
+        ];
+        let edges = [
+            ("cars_in", "traffic_light", 5.0),
+            ("traffic_light", "one_lane_bridge", 2.0),
+            ("one_lane_bridge", "roundabout", 2.0),
+            ("roundabout", "exit", 1.0),
+        ];
+        let connections = edges
+            .iter()
+            .map(|(from, to, rate)| {
+                Connection::new(
+                    format!("{}_{}", from, to),
+                    from.to_string(),
+                    Some("out".to_string()),
+                    to.to_string(),
+                    Some("in".to_string()),
+                    Some(*rate),
+                )
+            })
+            .collect();
+        let sim = create_stepped_simulation(processes, connections)?;
+
+        let graph = sim.graph();
+        assert_eq!(graph.len(), 6);
+        assert_eq!(graph.edges().len(), 4);
+        for (from, to, rate) in edges {
+            assert_eq!(graph.successors(from), vec![to]);
+            assert_eq!(graph.predecessors(to), vec![from]);
+            let edge = graph
+                .outgoing(graph.index_of(from).unwrap())
+                .next()
+                .unwrap();
+            assert_eq!(edge.capacity, rate);
+        }
+        assert!(graph.predecessors("cars_in").is_empty());
+        assert!(graph.successors("exit").is_empty());
+        // The stepper drives the model without being wired into it
+        assert!(graph.successors("stepper").is_empty());
+        assert!(graph.predecessors("stepper").is_empty());
+
+        let node_type = |id: &str| graph.nodes()[graph.index_of(id).unwrap()].node_type;
+        assert_eq!(node_type("cars_in"), "Source");
+        assert_eq!(node_type("traffic_light"), "Delay");
+        assert_eq!(node_type("roundabout"), "Pool");
+        assert_eq!(node_type("exit"), "Drain");
+        assert_eq!(node_type("stepper"), "Stepper");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_time_weighted_occupancy() {
+        let states: Vec<SimulationState> = (0..20)
+            .map(|step| SimulationState {
+                step,
+                time: step as f64,
+                process_states: HashMap::from([(
+                    "pool1".to_string(),
+                    ProcessState::Pool(PoolState {
+                        resources: if step < 10 { 0.0 } else { 10.0 },
+                        pending_outgoing_resources: 0.0,
+                    }),
+                )]),
+            })
+            .collect();
+
+        let metrics = Metrics::new(&states);
+        assert!((metrics.time_weighted_occupancy("pool1") - 5.0).abs() < 1e-9);
+        assert_eq!(metrics.time_weighted_occupancy("missing"), 0.0);
+    }
+}
@@ -0,0 +1,205 @@
+mod common;
+
+use crate::common::{create_stepped_simulation, setup};
+use simcraft::{
+    api,
+    model::{
+        nodes::{Pool, Source},
+        Connection, Process, ProcessState, Processor,
+    },
+    simulator::Simulation,
+    utils::SimulationError,
+};
+
+fn source_to_pool() -> Simulation {
+    create_stepped_simulation(
+        vec![
+            Process::new(Box::new(Source::new("source"))),
+            Process::new(Box::new(Pool::new("pool"))),
+        ],
+        vec![Connection::builder()
+            .id("source_to_pool")
+            .source("source.out")
+            .target("pool.in")
+            .flow_rate(1.0)
+            .build()
+            .unwrap()],
+    )
+    .unwrap()
+}
+
+fn pool_resources(simulation: &Simulation) -> f64 {
+    match api::process_state(simulation, "pool").unwrap() {
+        ProcessState::Pool(pool) => pool.resources,
+        _ => unreachable!(),
+    }
+}
+
+#[test]
+fn test_api_stepping_and_reset() {
+    setup();
+    let mut simulation = source_to_pool();
+
+    assert!(!api::step(&mut simulation).unwrap().is_empty());
+    assert!(!api::step_n(&mut simulation, 2).unwrap().is_empty());
+    assert_eq!(pool_resources(&simulation), 3.0);
+
+    api::step_until(&mut simulation, 5.0).unwrap();
+    let state = api::state(&simulation);
+    assert_eq!(state.step, 5);
+    assert_eq!(pool_resources(&simulation), 5.0);
+
+    api::reset(&mut simulation).unwrap();
+    assert_eq!(api::state(&simulation).step, 0);
+    assert_eq!(pool_resources(&simulation), 0.0);
+
+    let error = api::process_state(&simulation, "missing").unwrap_err();
+    assert_eq!(error.action, "get process state");
+    assert!(error
+        .to_string()
+        .starts_with("Failed to get process state: "));
+}
+
+#[test]
+fn test_api_editing_processes_and_connections() {
+    setup();
+    let mut simulation = source_to_pool();
+
+    let ids: Vec<String> = api::processes(&simulation)
+        .iter()
+        .map(|p| p.id().to_string())
+        .collect();
+    assert_eq!(ids, vec!["pool", "source", "stepper"]);
+
+    api::add_process(
+        &mut simulation,
+        Process::new(Box::new(Pool::new("overflow"))),
+    )
+    .unwrap();
+    let error = api::add_process(
+        &mut simulation,
+        Process::new(Box::new(Pool::new("overflow"))),
+    )
+    .unwrap_err();
+    assert_eq!(error.action, "add process");
+
+    api::update_process(
+        &mut simulation,
+        "overflow",
+        Process::new(Box::new(Pool::builder().id("overflow").build().unwrap())),
+    )
+    .unwrap();
+
+    let connection = Connection::builder()
+        .id("source_to_overflow")
+        .source("source.out")
+        .target("overflow.in")
+        .flow_rate(2.0)
+        .build()
+        .unwrap();
+    api::add_connection(&mut simulation, connection.clone()).unwrap();
+    let ids: Vec<String> = api::connections(&simulation)
+        .iter()
+        .map(|c| c.id.clone())
+        .collect();
+    assert_eq!(ids, vec!["source_to_pool", "source_to_overflow"]);
+
+    api::update_connection(&mut simulation, "source_to_overflow", connection).unwrap();
+    api::set_connection_flow_rate(&mut simulation, "source_to_pool", Some(3.0)).unwrap();
+    api::step(&mut simulation).unwrap();
+    assert_eq!(pool_resources(&simulation), 3.0);
+
+    let error = api::set_connection_flow_rate(&mut simulation, "missing", None).unwrap_err();
+    assert_eq!(error.action, "set connection flow rate");
+
+    api::remove_connection(&mut simulation, "source_to_overflow").unwrap();
+    api::remove_process(&mut simulation, "overflow").unwrap();
+    assert_eq!(api::processes(&simulation).len(), 3);
+    assert_eq!(api::connections(&simulation).len(), 1);
+
+    let error = api::remove_process(&mut simulation, "overflow").unwrap_err();
+    assert_eq!(error.action, "remove process");
+    let error = api::remove_connection(&mut simulation, "source_to_overflow").unwrap_err();
+    assert_eq!(error.action, "remove connection");
+}
+
+#[test]
+fn test_api_log_capture_and_export() {
+    setup();
+    let mut simulation = source_to_pool();
+
+    api::enable_log_capture(&mut simulation, "DEBUG").unwrap();
+    let error = api::enable_log_capture(&mut simulation, "LOUD").unwrap_err();
+    assert_eq!(error.action, "capture logs");
+    assert!(matches!(*error.error, SimulationError::Other(_)));
+
+    api::step_n(&mut simulation, 2).unwrap();
+    let blob = api::export(&simulation).unwrap();
+    let mut restored = api::import(&blob).unwrap();
+    assert_eq!(api::state(&restored).step, 2);
+    api::step(&mut restored).unwrap();
+    assert_eq!(pool_resources(&restored), 3.0);
+
+    let error = api::import(b"not a snapshot").unwrap_err();
+    assert_eq!(error.action, "import simulation");
+}
+
+#[test]
+fn test_api_create_and_load() {
+    setup();
+
+    let simulation = api::create(
+        vec![
+            Process::new(Box::new(Source::new("source"))),
+            Process::new(Box::new(Pool::new("pool"))),
+        ],
+        vec![],
+        Default::default(),
+    )
+    .unwrap();
+    assert_eq!(api::processes(&simulation).len(), 2);
+
+    let error = api::create(
+        vec![
+            Process::new(Box::new(Pool::new("pool"))),
+            Process::new(Box::new(Pool::new("pool"))),
+        ],
+        vec![],
+        Default::default(),
+    )
+    .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Failed to create simulation: Duplicate process ID: pool"
+    );
+
+    let model = r#"{
+        "processes": [
+            { "type": "Source", "id": "source" },
+            { "type": "Pool", "id": "pool" }
+        ],
+        "connections": [
+            { "sourceID": "source", "sourcePort": "out", "targetID": "pool", "targetPort": "in" }
+        ]
+    }"#;
+    let simulation = api::load(model).unwrap();
+    assert_eq!(api::connections(&simulation).len(), 1);
+
+    // Every problem is listed, and the first is kept as the error
+    let broken = r#"{
+        "processes": [
+            { "type": "Pool", "id": "pool" },
+            { "type": "Pool", "id": "pool" }
+        ],
+        "connections": [
+            { "sourceID": "pool", "sourcePort": "out", "targetID": "missing", "targetPort": "in" }
+        ]
+    }"#;
+    let error = api::load(broken).unwrap_err();
+    assert_eq!(error.action, "load simulation");
+    assert_eq!(
+        *error.error,
+        SimulationError::DuplicateProcess("pool".to_string())
+    );
+    assert!(error.description.contains("; "));
+}
@@ -5,7 +5,7 @@ use simcraft::{
     model::{
         nodes::{Drain, Pool},
         process_state::PoolState,
-        Connection, ProcessState,
+        Connection, Process, ProcessState,
     },
     prelude::StatefulSimulation,
     simulator::Simulate,
@@ -91,3 +91,136 @@ fn test_connection_order_priority() {
         assert_eq!(drain_state.resources_consumed, 0.0);
     }
 }
+
+#[test]
+fn test_connection_order_stable_across_reset() {
+    setup();
+
+    let pool = || {
+        Pool::builder()
+            .id("pool")
+            .state(PoolState {
+                resources: 2.0,
+                ..Default::default()
+            })
+            .build()
+            .unwrap()
+    };
+    let connections = || {
+        (1..=3).map(|index| {
+            Connection::new(
+                format!("conn{}", index),
+                "pool".to_string(),
+                Some("out".to_string()),
+                format!("drain{}", index),
+                Some("in".to_string()),
+                Some(1.0),
+            )
+        })
+    };
+    let consumed = |sim: &simcraft::simulator::Simulation| -> Vec<f64> {
+        (1..=3)
+            .map(
+                |index| match sim.get_process_state(&format!("drain{}", index)) {
+                    Ok(ProcessState::Drain(state)) => state.resources_consumed,
+                    _ => panic!("Expected drain state"),
+                },
+            )
+            .collect()
+    };
+
+    let mut sim = create_stepped_simulation(vec![], vec![]).unwrap();
+    sim.add_process(pool()).unwrap();
+    for index in 1..=3 {
+        sim.add_process(Drain::new(&format!("drain{}", index)))
+            .unwrap();
+    }
+    for connection in connections() {
+        sim.add_connection(connection).unwrap();
+    }
+    let fresh_counters = sim.sequence_counters();
+
+    sim.step().unwrap();
+    assert_eq!(consumed(&sim), vec![1.0, 1.0, 0.0]);
+
+    // Rebuild after a reset: restock the pool and add the same connections again
+    sim.reset().unwrap();
+    sim.update_process("pool", Process::new(Box::new(pool())))
+        .unwrap();
+    for connection in connections() {
+        sim.add_connection(connection).unwrap();
+    }
+    assert_eq!(sim.connections().len(), 3);
+    assert_eq!(sim.sequence_counters(), fresh_counters);
+
+    sim.step().unwrap();
+    assert_eq!(consumed(&sim), vec![1.0, 1.0, 0.0]);
+}
+
+#[test]
+fn test_connections_listed_in_insertion_order() {
+    setup();
+
+    let mut sim = create_stepped_simulation(vec![], vec![]).unwrap();
+    for id in ["zeta", "alpha", "mid"] {
+        sim.add_process(Pool::new(id)).unwrap();
+    }
+
+    // Ids and endpoints deliberately out of alphabetical order
+    let links = [
+        ("c_mid_alpha", "mid", "alpha"),
+        ("a_zeta_mid", "zeta", "mid"),
+        ("b_alpha_zeta", "alpha", "zeta"),
+        ("d_zeta_alpha", "zeta", "alpha"),
+    ];
+    for (id, source, target) in links {
+        sim.add_connection(Connection::new(
+            id.to_string(),
+            source.to_string(),
+            Some("out".to_string()),
+            target.to_string(),
+            Some("in".to_string()),
+            Some(1.0),
+        ))
+        .unwrap();
+    }
+
+    let ids: Vec<String> = sim.connections().into_iter().map(|conn| conn.id).collect();
+    assert_eq!(
+        ids,
+        vec!["c_mid_alpha", "a_zeta_mid", "b_alpha_zeta", "d_zeta_alpha"]
+    );
+}
+
+#[test]
+fn test_connections_without_ids_are_all_listed() {
+    setup();
+
+    let mut sim = create_stepped_simulation(vec![], vec![]).unwrap();
+    sim.add_process(Pool::new("pool")).unwrap();
+    sim.add_process(Drain::new("drain1")).unwrap();
+    sim.add_process(Drain::new("drain2")).unwrap();
+    for drain in ["drain1", "drain2"] {
+        sim.add_connection(Connection::new(
+            String::new(),
+            "pool".to_string(),
+            Some("out".to_string()),
+            drain.to_string(),
+            Some("in".to_string()),
+            Some(1.0),
+        ))
+        .unwrap();
+    }
+    assert_eq!(sim.connections().len(), 2);
+
+    sim.step().unwrap();
+    sim.reset().unwrap();
+
+    let targets: Vec<String> = sim
+        .to_document()
+        .connections
+        .into_iter()
+        .map(|conn| conn.target_id)
+        .collect();
+    assert_eq!(targets, vec!["drain1", "drain2"]);
+}
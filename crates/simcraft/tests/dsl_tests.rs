@@ -7,7 +7,7 @@ mod dsl_tests {
     use simcraft::model::nodes::{Action, TriggerMode};
     use simcraft::model::process_state::ProcessState;
     use simcraft::prelude::*;
-    use simcraft::simulator::SimulationState;
+    use simcraft::simulator::{EventPayload, SimulationState};
 
     use crate::common::setup;
 
@@ -165,6 +165,69 @@ mod dsl_tests {
         Ok(())
     }
 
+    #[test]
+    fn test_run_simulation_until() -> Result<(), SimulationError> {
+        setup();
+
+        let (_, states) = run_simulation! {
+            until: 3.0,
+            processes {
+                source "source1" {}
+                pool "pool1" {}
+            }
+            connections {
+                "source1.out" -> "pool1.in" {
+                    id: "conn1",
+                    flow_rate: 1.0
+                }
+            }
+        }?;
+
+        // The initial state plus one per step, stopping once time reaches 3.0
+        let times: Vec<f64> = states.iter().map(|state| state.time).collect();
+        assert_eq!(times, vec![0.0, 1.0, 2.0, 3.0]);
+        if let ProcessState::Pool(state) = &states.last().unwrap().process_states["pool1"] {
+            assert_eq!(state.resources, 3.0);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dsl_stepper_dt() -> Result<(), SimulationError> {
+        setup();
+
+        let mut sim = simulation! {
+            processes {
+                stepper "stepper" {
+                    dt: 0.5
+                }
+                source "source1" {}
+                pool "pool1" {}
+            }
+            connections {
+                "source1.out" -> "pool1.in" {
+                    id: "conn1",
+                    flow_rate: 1.0
+                }
+            }
+        }?;
+
+        // The declared stepper replaces the default one
+        assert_eq!(sim.process_count(), 3);
+
+        let events = sim.step_until(2.5)?;
+        let mut step_times: Vec<f64> = events
+            .iter()
+            .filter(|event| event.source_id == "stepper" && event.payload == EventPayload::Step)
+            .map(|event| event.time)
+            .collect();
+        step_times.dedup();
+        assert_eq!(step_times, vec![0.5, 1.0, 1.5, 2.0, 2.5]);
+
+        Ok(())
+    }
+
     #[test]
     fn test_source_pool_loop() -> Result<(), SimulationError> {
         setup();
@@ -548,4 +611,56 @@ mod dsl_tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_dsl_source_output_ports() -> Result<(), SimulationError> {
+        setup();
+
+        // One supplier feeding two product lines through separate ports
+        let mut sim = simulation! {
+            processes {
+                source "supplier" {
+                    output_ports: vec!["a", "b"],
+                }
+                pool "line_a" {}
+                pool "line_b" {}
+            }
+            connections {
+                "supplier.a" -> "line_a.in" {
+                    id: "conn_a",
+                    flow_rate: 1.0
+                }
+                "supplier.b" -> "line_b.in" {
+                    id: "conn_b",
+                    flow_rate: 3.0
+                }
+            }
+        }?;
+        sim.step_n(4)?;
+        assert!(sim.diagnostics().is_empty());
+
+        let state = sim.get_simulation_state();
+        let resources = |id: &str| match &state.process_states[id] {
+            ProcessState::Pool(pool) => pool.resources,
+            _ => unreachable!(),
+        };
+        assert_eq!(resources("line_a"), 4.0);
+        assert_eq!(resources("line_b"), 12.0);
+
+        // The default "out" port is no longer offered
+        let connection = Connection::new(
+            "conn_out".to_string(),
+            "supplier".to_string(),
+            Some("out".to_string()),
+            "line_a".to_string(),
+            Some("in".to_string()),
+            Some(1.0),
+        );
+        assert!(matches!(
+            sim.add_connection(connection),
+            Err(SimulationError::InvalidPort { .. })
+        ));
+
+        Ok(())
+    }
 }
@@ -0,0 +1,91 @@
+mod common;
+
+#[cfg(test)]
+mod rpc_tests {
+    use serde_json::json;
+    use simcraft::model::nodes::{Pool, Source};
+    use simcraft::model::{Connection, Process, ProcessState};
+    use simcraft::rpc::{handle_command, RpcCommand, RpcResponse, SimulationManager};
+
+    use crate::common::{create_stepper, setup};
+
+    /// Sends a command given as JSON, the way a non-Rust host would
+    fn send(manager: &mut SimulationManager, command: serde_json::Value) -> RpcResponse {
+        let command: RpcCommand = serde_json::from_value(command).unwrap();
+        handle_command(manager, command)
+    }
+
+    #[test]
+    fn test_rpc_lifecycle() {
+        setup();
+
+        let mut manager = SimulationManager::new();
+        let create = RpcCommand::Create {
+            processes: vec![
+                create_stepper(),
+                Process::new(Box::new(Source::new("source"))),
+                Process::new(Box::new(Pool::new("pool"))),
+            ],
+            connections: vec![Connection::builder()
+                .id("source_to_pool")
+                .source("source.out")
+                .target("pool.in")
+                .flow_rate(2.0)
+                .build()
+                .unwrap()],
+            config: None,
+        };
+        // Commands survive the round trip through JSON
+        let create = serde_json::from_value(serde_json::to_value(&create).unwrap()).unwrap();
+        let RpcResponse::Created(id) = handle_command(&mut manager, create) else {
+            panic!("simulation was not created");
+        };
+        assert_eq!(manager.ids(), vec![id.clone()]);
+
+        let response = send(
+            &mut manager,
+            json!({"command": "stepN", "simulationId": id, "n": 3}),
+        );
+        assert!(matches!(response, RpcResponse::Events(events) if !events.is_empty()));
+
+        let response = send(
+            &mut manager,
+            json!({"command": "getCurrentStep", "simulationId": id}),
+        );
+        assert!(matches!(response, RpcResponse::Step(3)));
+
+        let RpcResponse::State(state) = send(
+            &mut manager,
+            json!({"command": "getState", "simulationId": id}),
+        ) else {
+            panic!("no state returned");
+        };
+        match &state.process_states["pool"] {
+            ProcessState::Pool(pool) => assert_eq!(pool.resources, 6.0),
+            _ => unreachable!(),
+        }
+
+        let response = send(
+            &mut manager,
+            json!({"command": "removeProcess", "simulationId": id, "processId": "missing"}),
+        );
+        let RpcResponse::Error(message) = response else {
+            panic!("removing an unknown process succeeded");
+        };
+        assert!(message.starts_with("Failed to remove process"));
+
+        let response = send(
+            &mut manager,
+            json!({"command": "destroy", "simulationId": id}),
+        );
+        assert!(matches!(response, RpcResponse::Ok));
+        assert!(manager.is_empty());
+
+        // Responses are tagged by type for the host
+        let response = send(&mut manager, json!({"command": "step", "simulationId": id}));
+        assert_eq!(
+            serde_json::to_value(&response).unwrap(),
+            json!({"type": "error", "result": format!("Simulation '{}' not found", id)})
+        );
+    }
+}
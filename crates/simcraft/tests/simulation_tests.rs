@@ -3,20 +3,38 @@ mod common;
 #[cfg(test)]
 mod simulation_tests {
     use log::info;
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
 
     use simcraft::model::nodes::Action;
+    use simcraft::model::nodes::Composite;
+    use simcraft::model::nodes::Converter;
     use simcraft::model::nodes::Delay;
     use simcraft::model::nodes::DelayAction;
     use simcraft::model::nodes::Drain;
+    use simcraft::model::nodes::Gate;
+    use simcraft::model::nodes::Merger;
     use simcraft::model::nodes::Overflow;
     use simcraft::model::nodes::Pool;
+    use simcraft::model::nodes::Probe;
+    use simcraft::model::nodes::PullPolicy;
+    use simcraft::model::nodes::Recipe;
     use simcraft::model::nodes::Source;
     use simcraft::model::nodes::Stepper;
     use simcraft::model::nodes::TriggerMode;
+    use simcraft::model::nodes::{SplitStrategy, Splitter};
     use simcraft::model::process_state::PoolState;
+    use simcraft::model::FlowCondition;
+    use simcraft::model::ProcessContext;
     use simcraft::model::ProcessState;
     use simcraft::model::Processor;
+    use simcraft::simulator::simulation_context::SimulationContext;
     use simcraft::simulator::simulation_trait::StatefulSimulation;
+    use simcraft::simulator::{
+        CalendarScheduler, CascadePolicy, DuplexPolicy, Event, EventPayload, LifecycleEvent,
+        ModelOp, ReplayMismatch, RunConfiguration, SimulationDocument,
+    };
+    use simcraft::utils::KahanSum;
 
     use crate::common::{create_stepped_simulation, setup};
     use simcraft::prelude::*;
@@ -922,7 +940,7 @@ mod simulation_tests {
         // Track initial resources in the system
         let initial_resources = {
             let mut total = 0.0;
-            for (_, process) in simulation.processes() {
+            for process in simulation.processes().values() {
                 if let ProcessState::Pool(state) = process.get_state() {
                     total += state.resources;
                 }
@@ -932,33 +950,43 @@ mod simulation_tests {
 
         // Helper function to calculate total resources in the system
         let get_system_resources = |sim: &Simulation| -> f64 {
-            let mut total = 0.0;
-            let mut resources_produced = 0.0;
-            let mut resources_consumed = 0.0;
+            let mut total = KahanSum::default();
+            let mut resources_produced = KahanSum::default();
+            let mut resources_consumed = KahanSum::default();
 
-            for (_id, process) in sim.processes() {
+            for process in sim.processes().values() {
                 match process.get_state() {
                     ProcessState::Source(state) => {
-                        resources_produced += state.resources_produced;
+                        resources_produced.add(state.resources_produced);
                     }
                     ProcessState::Pool(state) => {
-                        total += state.resources;
+                        total.add(state.resources);
                     }
                     ProcessState::Drain(state) => {
-                        resources_consumed += state.resources_consumed;
+                        resources_consumed.add(state.resources_consumed);
                     }
                     ProcessState::Delay(state) => {
-                        total += state.resources_received - state.resources_released;
+                        total.add(state.resources_received - state.resources_released);
                     }
                     _ => {}
                 }
             }
+            let (total, resources_produced, resources_consumed) = (
+                total.value(),
+                resources_produced.value(),
+                resources_consumed.value(),
+            );
 
             // Verify that initial resources + resources produced - consumed equals resources in system
             assert!((initial_resources + resources_produced - resources_consumed - total).abs() < f64::EPSILON,
                 "Resource inconsistency detected! Initial: {}, Produced: {}, Consumed: {}, In System: {}",
                 initial_resources, resources_produced, resources_consumed, total);
 
+            // The run summary reaches the same balance, starting stock included
+            let summary = sim.summary();
+            assert_eq!(summary.initial, initial_resources);
+            assert!(summary.conserved, "Unbalanced summary: {:?}", summary);
+
             total
         };
 
@@ -979,4 +1007,5295 @@ mod simulation_tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_connection_stats() -> Result<(), SimulationError> {
+        setup();
+
+        let source = Process::new(Box::new(Source::new("source1")));
+        let pool = Process::new(Box::new(Pool::new("pool1")));
+
+        let connection = Connection::new(
+            "conn1".to_string(),
+            "source1".to_string(),
+            Some("out".to_string()),
+            "pool1".to_string(),
+            Some("in".to_string()),
+            Some(2.0),
+        );
+
+        let mut sim = create_stepped_simulation(vec![source, pool], vec![connection])?;
+        sim.step_n(5)?;
+
+        let stats = sim.connection_stats("conn1");
+        assert_eq!(stats.transferred, 2.0 * 5.0);
+        assert_eq!(stats.rejected, 0.0);
+        assert_eq!(stats.transfers, 5);
+
+        sim.reset()?;
+        assert_eq!(sim.connection_stats("conn1").transfers, 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_clone_without_history() -> Result<(), SimulationError> {
+        setup();
+
+        let build = || {
+            let source = Process::new(Box::new(Source::new("source1")));
+            let pool = Process::new(Box::new(Pool::new("pool1")));
+            let connection = Connection::new(
+                "conn1".to_string(),
+                "source1".to_string(),
+                Some("out".to_string()),
+                "pool1".to_string(),
+                Some("in".to_string()),
+                Some(1.0),
+            );
+            create_stepped_simulation(vec![source, pool], vec![connection])
+        };
+
+        let mut parent = build()?;
+        parent.enable_recording();
+        parent.step_n(3)?;
+        assert!(!parent.recorded_events().is_empty());
+
+        let mut fork = parent.clone_without_history();
+        assert!(fork.recorded_events().is_empty());
+        assert!(fork.recorded_states().is_empty());
+        assert_eq!(fork.current_step(), parent.current_step());
+
+        let mut fresh = build()?;
+        fresh.step_n(3)?;
+
+        for _ in 0..3 {
+            fork.step()?;
+            fresh.step()?;
+            let fork_state = fork.get_simulation_state();
+            let fresh_state = fresh.get_simulation_state();
+            assert_eq!(fork_state.time, fresh_state.time);
+            if let (ProcessState::Pool(a), ProcessState::Pool(b)) = (
+                &fork_state.process_states["pool1"],
+                &fresh_state.process_states["pool1"],
+            ) {
+                assert_eq!(a.resources, b.resources);
+            }
+        }
+
+        // Parent recording is unaffected by the fork
+        assert_eq!(parent.recorded_states().len(), 4);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_clone_without_history_leaves_hooks_behind() -> Result<(), SimulationError> {
+        setup();
+
+        let mut parent = create_stepped_simulation(vec![], vec![])?;
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let log = Arc::clone(&seen);
+        parent.on_lifecycle(move |event| log.lock().unwrap().push(event.clone()));
+
+        // Edits to a fork aren't reported to whoever is watching the parent
+        let mut fork = parent.clone_without_history();
+        fork.add_process(Pool::new("pool"))?;
+        fork.reset()?;
+        assert!(seen.lock().unwrap().is_empty());
+
+        parent.add_process(Pool::new("pool"))?;
+        assert_eq!(seen.lock().unwrap().len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_pool_push_lottery_distribution() -> Result<(), SimulationError> {
+        setup();
+
+        let run = |seed: u64| -> Result<(f64, f64), SimulationError> {
+            let loot = Pool::builder()
+                .id("loot")
+                .trigger_mode(TriggerMode::Automatic)
+                .action(Action::PushLottery)
+                .state(PoolState {
+                    resources: 4000.0,
+                    pending_outgoing_resources: 0.0,
+                })
+                .build()
+                .unwrap();
+
+            let mut sim = create_stepped_simulation(
+                vec![
+                    Process::new(Box::new(loot)),
+                    Process::new(Box::new(Pool::new("common"))),
+                    Process::new(Box::new(Pool::new("rare"))),
+                ],
+                vec![
+                    Connection::new(
+                        "to_common".to_string(),
+                        "loot".to_string(),
+                        Some("out".to_string()),
+                        "common".to_string(),
+                        Some("in".to_string()),
+                        Some(3.0),
+                    ),
+                    Connection::new(
+                        "to_rare".to_string(),
+                        "loot".to_string(),
+                        Some("out".to_string()),
+                        "rare".to_string(),
+                        Some("in".to_string()),
+                        Some(1.0),
+                    ),
+                ],
+            )?;
+            sim.set_seed(seed);
+            sim.step_n(4000)?;
+
+            let resources = |id: &str| match sim.get_process_state(id) {
+                Ok(ProcessState::Pool(state)) => state.resources,
+                _ => panic!("Expected pool state for {}", id),
+            };
+            assert_eq!(resources("loot"), 0.0, "One unit is drawn per step");
+
+            Ok((resources("common"), resources("rare")))
+        };
+
+        let (common, rare) = run(42)?;
+        assert_eq!(common + rare, 4000.0);
+        assert!(
+            (common / 4000.0 - 0.75).abs() < 0.03,
+            "Expected ~75% common draws, got {}",
+            common / 4000.0
+        );
+
+        // Same seed reproduces the same draws
+        assert_eq!(run(42)?, (common, rare));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_events_between() -> Result<(), SimulationError> {
+        setup();
+
+        let source = Process::new(Box::new(Source::new("source1")));
+        let pool = Process::new(Box::new(Pool::new("pool1")));
+        let connection = Connection::new(
+            "conn1".to_string(),
+            "source1".to_string(),
+            Some("out".to_string()),
+            "pool1".to_string(),
+            Some("in".to_string()),
+            Some(1.0),
+        );
+
+        let mut sim = create_stepped_simulation(vec![source, pool], vec![connection])?;
+        sim.enable_recording();
+        sim.step_n(6)?;
+
+        let window = sim.events_between(2.0, 4.0);
+        assert!(!window.is_empty());
+        assert!(window
+            .iter()
+            .all(|event| event.time >= 2.0 && event.time < 4.0));
+
+        let expected = sim
+            .recorded_events()
+            .iter()
+            .filter(|event| event.time == 2.0 || event.time == 3.0)
+            .count();
+        assert_eq!(window.len(), expected);
+
+        // Adjacent windows partition the log without overlap
+        let total = sim.events_between(0.0, 2.0).len()
+            + sim.events_between(2.0, 4.0).len()
+            + sim.events_between(4.0, 7.0).len();
+        assert_eq!(total, sim.recorded_events().len());
+
+        assert!(sim.events_between(3.0, 3.0).is_empty());
+        assert!(sim.events_between(4.0, 2.0).is_empty());
+        assert!(sim.events_between(100.0, 200.0).is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unconnected_required_port() -> Result<(), SimulationError> {
+        setup();
+
+        let source = Process::new(Box::new(Source::new("lonely_source")));
+        let mut sim = create_stepped_simulation(vec![source], vec![])?;
+
+        let problems = sim.check_unconnected_ports();
+        assert_eq!(problems.len(), 1);
+        assert!(matches!(
+            &problems[0],
+            SimulationError::UnconnectedPort { process, port, port_type }
+                if process == "lonely_source" && port == "out" && port_type == "output"
+        ));
+
+        // Without strict mode the problem is only reported as a warning
+        sim.step()?;
+        assert_eq!(sim.diagnostics().len(), 1);
+
+        sim.reset()?;
+        sim.set_strict(true);
+        let result = sim.step();
+        assert!(matches!(
+            result,
+            Err(SimulationError::UnconnectedPort { .. })
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_stable_run_hash() -> Result<(), SimulationError> {
+        setup();
+
+        let build = |flow_rate: f64| {
+            let source = Process::new(Box::new(Source::new("source1")));
+            let pool = Process::new(Box::new(Pool::new("pool1")));
+            let connection = Connection::new(
+                "conn1".to_string(),
+                "source1".to_string(),
+                Some("out".to_string()),
+                "pool1".to_string(),
+                Some("in".to_string()),
+                Some(flow_rate),
+            );
+            create_stepped_simulation(vec![source, pool], vec![connection])
+        };
+
+        let first = build(1.0)?.run_hash(10)?;
+        let second = build(1.0)?.run_hash(10)?;
+        assert_eq!(first, second);
+
+        let changed = build(2.0)?.run_hash(10)?;
+        assert_ne!(first, changed);
+
+        // State hashes ignore sub-quantum float noise
+        let state = build(1.0)?.get_simulation_state();
+        let mut noisy = state.clone();
+        noisy.time += 1e-13;
+        assert_eq!(state.stable_hash(), noisy.stable_hash());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_composite_subgraph() -> Result<(), SimulationError> {
+        setup();
+
+        // Subgraph: delay (2s) -> buffer pool, exposed as a single node
+        let delay = Delay::builder()
+            .id("delay")
+            .action(DelayAction::Delay)
+            .build()
+            .unwrap();
+        let buffer = Pool::builder()
+            .id("buffer")
+            .trigger_mode(TriggerMode::Automatic)
+            .action(Action::PushAny)
+            .build()
+            .unwrap();
+        let inner = Simulation::new(
+            vec![
+                Process::new(Box::new(delay)),
+                Process::new(Box::new(buffer)),
+            ],
+            vec![Connection::new(
+                "delay_to_buffer".to_string(),
+                "delay".to_string(),
+                Some("out".to_string()),
+                "buffer".to_string(),
+                Some("in".to_string()),
+                Some(2.0),
+            )],
+        )?;
+        let composite = Composite::new("module", inner, ("delay", "in"), ("buffer", "out"))?;
+
+        let mut sim = create_stepped_simulation(
+            vec![
+                Process::new(Box::new(Source::new("source"))),
+                Process::new(Box::new(composite)),
+                Process::new(Box::new(Pool::new("sink"))),
+            ],
+            vec![
+                Connection::new(
+                    "source_to_module".to_string(),
+                    "source".to_string(),
+                    Some("out".to_string()),
+                    "module".to_string(),
+                    Some("in".to_string()),
+                    Some(1.0),
+                ),
+                Connection::new(
+                    "module_to_sink".to_string(),
+                    "module".to_string(),
+                    Some("out".to_string()),
+                    "sink".to_string(),
+                    Some("in".to_string()),
+                    Some(1.0),
+                ),
+            ],
+        )?;
+
+        let sink_resources = |sim: &Simulation| match sim.get_process_state("sink") {
+            Ok(ProcessState::Pool(state)) => state.resources,
+            _ => panic!("Expected sink pool state"),
+        };
+
+        // Nothing can leave the module before the inner delay has elapsed
+        sim.step_n(2)?;
+        assert_eq!(sink_resources(&sim), 0.0);
+
+        sim.step_n(8)?;
+        assert!(sink_resources(&sim) >= 7.0);
+
+        // Resources are conserved across the boundary
+        let produced = match sim.get_process_state("source")? {
+            ProcessState::Source(state) => state.resources_produced,
+            _ => panic!("Expected source state"),
+        };
+        let in_module = match sim.get_process_state("module")? {
+            ProcessState::Composite(state) => state.current_resources(),
+            _ => panic!("Expected composite state"),
+        };
+        assert_eq!(produced, 10.0);
+        assert_eq!(in_module + sink_resources(&sim), produced);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_advance_to_next_event() -> Result<(), SimulationError> {
+        setup();
+
+        let source = Source::builder()
+            .id("source")
+            .trigger_mode(TriggerMode::Passive)
+            .build()
+            .unwrap();
+
+        // No stepper: the model is driven purely by scheduled events
+        let mut sim = Simulation::new(
+            vec![
+                Process::new(Box::new(source)),
+                Process::new(Box::new(Pool::new("pool"))),
+            ],
+            vec![Connection::new(
+                "conn".to_string(),
+                "source".to_string(),
+                Some("out".to_string()),
+                "pool".to_string(),
+                Some("in".to_string()),
+                Some(1.0),
+            )],
+        )?;
+
+        for time in [0.0, 100.0] {
+            sim.schedule_event(Event::new(
+                "simulation",
+                "source",
+                time,
+                EventPayload::Trigger,
+            ))?;
+        }
+
+        sim.advance_to_next_event()?;
+        assert_eq!(sim.current_time(), 0.0);
+
+        let events = sim.advance_to_next_event()?;
+        assert_eq!(sim.current_time(), 100.0);
+        assert!(events.iter().all(|event| event.time == 100.0));
+        if let ProcessState::Pool(state) = sim.get_process_state("pool")? {
+            assert_eq!(state.resources, 2.0);
+        }
+
+        assert!(sim.advance_to_next_event()?.is_empty());
+        assert_eq!(sim.current_time(), 100.0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_scheduler_implementations_agree() -> Result<(), SimulationError> {
+        setup();
+
+        let build = || {
+            let source = Process::new(Box::new(Source::new("source1")));
+            let delay = Delay::builder()
+                .id("delay1")
+                .action(DelayAction::Delay)
+                .release_amount(1.0)
+                .build()
+                .unwrap();
+            let pool = Process::new(Box::new(Pool::new("pool1")));
+            let connections = vec![
+                Connection::new(
+                    "conn1".to_string(),
+                    "source1".to_string(),
+                    Some("out".to_string()),
+                    "delay1".to_string(),
+                    Some("in".to_string()),
+                    Some(2.0),
+                ),
+                Connection::new(
+                    "conn2".to_string(),
+                    "delay1".to_string(),
+                    Some("out".to_string()),
+                    "pool1".to_string(),
+                    Some("in".to_string()),
+                    Some(3.0),
+                ),
+            ];
+            create_stepped_simulation(
+                vec![source, Process::new(Box::new(delay)), pool],
+                connections,
+            )
+        };
+
+        let expected = build()?.run_hash(20)?;
+
+        let mut sim = build()?;
+        sim.set_scheduler(Box::new(CalendarScheduler::new(0.5)?));
+        assert_eq!(sim.run_hash(20)?, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_calendar_scheduler_survives_snapshots() -> Result<(), SimulationError> {
+        setup();
+
+        for width in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            assert_eq!(
+                CalendarScheduler::new(width).err().map(|e| e.to_string()),
+                Some(format!("Invalid calendar bucket width: {}", width))
+            );
+        }
+
+        let mut sim = create_stepped_simulation(
+            vec![
+                Process::new(Box::new(Source::new("source"))),
+                Process::new(Box::new(Pool::new("pool"))),
+            ],
+            vec![Connection::builder()
+                .id("source_to_pool")
+                .source("source.out")
+                .target("pool.in")
+                .build()
+                .unwrap()],
+        )?;
+        sim.set_scheduler(Box::new(CalendarScheduler::new(0.5)?));
+        sim.step_n(3)?;
+
+        let mut restored = Simulation::restore(&sim.snapshot()?)?;
+        assert_eq!(restored.scheduler().bucket_width(), Some(0.5));
+        assert_eq!(restored.get_events().len(), sim.get_events().len());
+
+        sim.step_n(3)?;
+        restored.step_n(3)?;
+        assert_eq!(
+            restored.get_simulation_state().stable_hash(),
+            sim.get_simulation_state().stable_hash()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_run_summary() -> Result<(), SimulationError> {
+        setup();
+
+        let source = Process::new(Box::new(Source::new("source1")));
+        let pool = Process::new(Box::new(Pool::new("pool1")));
+        let drain = Drain::builder()
+            .id("drain1")
+            .trigger_mode(TriggerMode::Automatic)
+            .action(Action::PullAny)
+            .build()
+            .unwrap();
+        let connections = vec![
+            Connection::new(
+                "conn1".to_string(),
+                "source1".to_string(),
+                Some("out".to_string()),
+                "pool1".to_string(),
+                Some("in".to_string()),
+                Some(3.0),
+            ),
+            Connection::new(
+                "conn2".to_string(),
+                "pool1".to_string(),
+                Some("out".to_string()),
+                "drain1".to_string(),
+                Some("in".to_string()),
+                Some(1.0),
+            ),
+        ];
+        let mut sim = create_stepped_simulation(
+            vec![source, pool, Process::new(Box::new(drain))],
+            connections,
+        )?;
+        sim.step_n(5)?;
+
+        let state = sim.get_simulation_state();
+        let mut produced = 0.0;
+        let mut consumed = 0.0;
+        let mut in_system = 0.0;
+        if let ProcessState::Source(s) = &state.process_states["source1"] {
+            produced = s.resources_produced;
+        }
+        if let ProcessState::Drain(d) = &state.process_states["drain1"] {
+            consumed = d.resources_consumed;
+        }
+        if let ProcessState::Pool(p) = &state.process_states["pool1"] {
+            in_system = p.resources;
+        }
+
+        let summary = sim.summary();
+        assert_eq!(summary.step, 5);
+        assert_eq!(summary.produced, produced);
+        assert_eq!(summary.consumed, consumed);
+        assert_eq!(summary.in_system, in_system);
+        assert!(summary.produced > 0.0);
+        assert!(summary.consumed > 0.0);
+        assert_eq!(summary.produced, summary.consumed + summary.in_system);
+        assert!(summary.conserved);
+        assert_eq!(summary.imbalance(), 0.0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_duplex_policy() -> Result<(), SimulationError> {
+        setup();
+
+        // Automatic push source feeding a delay that an automatic pull pool drains
+        let build = |policy: DuplexPolicy| {
+            let source = Source::builder()
+                .id("source")
+                .trigger_mode(TriggerMode::Automatic)
+                .action(Action::PushAny)
+                .build()
+                .unwrap();
+            let delay = Delay::builder()
+                .id("delay")
+                .action(DelayAction::Delay)
+                .build()
+                .unwrap();
+            let pool = Pool::builder()
+                .id("pool")
+                .trigger_mode(TriggerMode::Automatic)
+                .action(Action::PullAny)
+                .build()
+                .unwrap();
+            let connections = vec![
+                Connection::new(
+                    "conn1".to_string(),
+                    "source".to_string(),
+                    Some("out".to_string()),
+                    "delay".to_string(),
+                    Some("in".to_string()),
+                    Some(5.0),
+                ),
+                Connection::new(
+                    "conn2".to_string(),
+                    "delay".to_string(),
+                    Some("out".to_string()),
+                    "pool".to_string(),
+                    Some("in".to_string()),
+                    Some(2.0),
+                ),
+            ];
+            let mut sim = create_stepped_simulation(
+                vec![
+                    Process::new(Box::new(source)),
+                    Process::new(Box::new(delay)),
+                    Process::new(Box::new(pool)),
+                ],
+                connections,
+            )?;
+            sim.set_duplex_policy(policy);
+            Ok::<_, SimulationError>(sim)
+        };
+
+        // Allow: both the push and the pull cross the connection every step
+        let mut sim = build(DuplexPolicy::Allow)?;
+        assert_eq!(sim.duplex_policy(), DuplexPolicy::Allow);
+        for step in 1..=10 {
+            sim.step()?;
+            let stats = sim.connection_stats("conn1");
+            assert_eq!(stats.transfers, 2 * step);
+            assert_eq!(stats.rejected, 0.0);
+        }
+        assert_eq!(sim.connection_stats("conn1").transferred, 100.0);
+
+        // Dedup: only one transfer per step, the duplicate is rejected back to the source
+        let mut sim = build(DuplexPolicy::Dedup)?;
+        for step in 1..=10 {
+            sim.step()?;
+            let stats = sim.connection_stats("conn1");
+            assert_eq!(stats.transfers, step);
+            assert_eq!(stats.rejected, 5.0 * step as f64);
+        }
+        assert_eq!(sim.connection_stats("conn1").transferred, 50.0);
+        if let ProcessState::Source(s) = &sim.get_simulation_state().process_states["source"] {
+            assert_eq!(s.resources_produced, 50.0);
+        }
+        assert!(sim.summary().conserved);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_reset_process() -> Result<(), SimulationError> {
+        setup();
+
+        let source = Process::new(Box::new(Source::new("source1")));
+        let pool1 = Process::new(Box::new(Pool::new("pool1")));
+        let pool2 = Process::new(Box::new(Pool::new("pool2")));
+        let connections = vec![
+            Connection::new(
+                "conn1".to_string(),
+                "source1".to_string(),
+                Some("out".to_string()),
+                "pool1".to_string(),
+                Some("in".to_string()),
+                Some(1.0),
+            ),
+            Connection::new(
+                "conn2".to_string(),
+                "source1".to_string(),
+                Some("out".to_string()),
+                "pool2".to_string(),
+                Some("in".to_string()),
+                Some(2.0),
+            ),
+        ];
+        let mut sim = create_stepped_simulation(vec![source, pool1, pool2], connections)?;
+        sim.step_n(3)?;
+
+        let time = sim.current_time();
+        sim.reset_process("pool1")?;
+        assert_eq!(sim.current_time(), time);
+
+        let state = sim.get_simulation_state();
+        if let ProcessState::Pool(p) = &state.process_states["pool1"] {
+            assert_eq!(p.resources, 0.0);
+        }
+        if let ProcessState::Pool(p) = &state.process_states["pool2"] {
+            assert_eq!(p.resources, 6.0);
+        }
+        if let ProcessState::Source(s) = &state.process_states["source1"] {
+            assert_eq!(s.resources_produced, 9.0);
+        }
+
+        // Neighbours carry on and the reset pool fills again from zero
+        sim.step()?;
+        let state = sim.get_simulation_state();
+        if let ProcessState::Pool(p) = &state.process_states["pool1"] {
+            assert_eq!(p.resources, 1.0);
+        }
+        if let ProcessState::Pool(p) = &state.process_states["pool2"] {
+            assert_eq!(p.resources, 8.0);
+        }
+
+        assert!(matches!(
+            sim.reset_process("missing"),
+            Err(SimulationError::ProcessNotFound(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate_connections_reports_all() -> Result<(), SimulationError> {
+        setup();
+
+        let source = Process::new(Box::new(Source::new("source1")));
+        let pool = Process::new(Box::new(Pool::new("pool1")));
+        let sim = create_stepped_simulation(vec![source, pool], vec![])?;
+
+        let connections = vec![
+            Connection::new(
+                "conn1".to_string(),
+                "source1".to_string(),
+                Some("out".to_string()),
+                "pool1".to_string(),
+                Some("in".to_string()),
+                Some(1.0),
+            ),
+            Connection::new(
+                "conn2".to_string(),
+                "source1".to_string(),
+                Some("bad".to_string()),
+                "pool1".to_string(),
+                Some("in".to_string()),
+                Some(1.0),
+            ),
+            Connection::new(
+                "conn3".to_string(),
+                "source1".to_string(),
+                Some("out".to_string()),
+                "missing".to_string(),
+                Some("in".to_string()),
+                Some(1.0),
+            ),
+        ];
+
+        let errors = sim.validate_connections(&connections);
+        assert_eq!(
+            errors,
+            vec![
+                (
+                    1,
+                    SimulationError::InvalidPort {
+                        process: "source1".to_string(),
+                        process_type: "Source".to_string(),
+                        port: "bad".to_string(),
+                        port_type: "output".to_string(),
+                    }
+                ),
+                (2, SimulationError::ProcessNotFound("missing".to_string())),
+            ]
+        );
+
+        // Validation alone never adds connections
+        assert!(sim
+            .get_context()
+            .process_outputs_for_port("source1", Some("out"))
+            .is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_yaml_with_params() -> Result<(), SimulationError> {
+        setup();
+
+        let template = r#"
+processes:
+  - type: Source
+    id: source1
+  - type: Pool
+    id: pool1
+  - type: Pool
+    id: pool2
+connections:
+  - id: conn1
+    sourceID: source1
+    sourcePort: out
+    targetID: pool1
+    targetPort: in
+    flowRate: ${rate}
+  - id: conn2
+    sourceID: source1
+    sourcePort: out
+    targetID: pool2
+    targetPort: in
+    flowRate: ${ slow_rate }
+"#;
+
+        let params = HashMap::from([("rate".to_string(), 2.5), ("slow_rate".to_string(), 0.5)]);
+        let sim = Simulation::from_yaml_with_params(template, &params)?;
+
+        let flow_rates: HashMap<String, Option<f64>> = sim
+            .get_context()
+            .process_outputs_for_port("source1", Some("out"))
+            .iter()
+            .map(|conn| (conn.id.clone(), conn.flow_rate))
+            .collect();
+        assert_eq!(flow_rates["conn1"], Some(2.5));
+        assert_eq!(flow_rates["conn2"], Some(0.5));
+
+        let missing = HashMap::from([("rate".to_string(), 2.5)]);
+        assert!(matches!(
+            Simulation::from_yaml_with_params(template, &missing),
+            Err(SimulationError::UnknownParameter(name)) if name == "slow_rate"
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_effective_flow_rate() -> Result<(), SimulationError> {
+        setup();
+
+        let source = Process::new(Box::new(Source::new("source1")));
+        let pool = Process::new(Box::new(Pool::new("pool1")));
+        let scheduled = Connection::new(
+            "conn1".to_string(),
+            "source1".to_string(),
+            Some("out".to_string()),
+            "pool1".to_string(),
+            Some("in".to_string()),
+            Some(1.0),
+        )
+        .with_schedule(vec![(5.0, 4.0), (2.0, 2.0)])
+        .with_multiplier(0.5);
+        let unset = Connection::new(
+            "conn2".to_string(),
+            "source1".to_string(),
+            Some("out".to_string()),
+            "pool1".to_string(),
+            Some("in".to_string()),
+            None,
+        );
+        let mut sim = create_stepped_simulation(vec![source, pool], vec![scheduled, unset])?;
+
+        // Base rate until the first scheduled change, always halved by the multiplier
+        assert_eq!(sim.effective_flow_rate("conn1")?, 0.5);
+        assert_eq!(sim.effective_flow_rate("conn2")?, 1.0);
+
+        let mut rates = vec![];
+        for _ in 0..6 {
+            sim.step()?;
+            rates.push((sim.current_time(), sim.effective_flow_rate("conn1")?));
+        }
+        assert_eq!(
+            rates,
+            vec![
+                (1.0, 0.5),
+                (2.0, 1.0),
+                (3.0, 1.0),
+                (4.0, 1.0),
+                (5.0, 2.0),
+                (6.0, 2.0)
+            ]
+        );
+
+        // The pool receives exactly what the effective rates promised
+        if let ProcessState::Pool(p) = &sim.get_simulation_state().process_states["pool1"] {
+            assert_eq!(p.resources, 0.5 + 1.0 * 3.0 + 2.0 * 2.0 + 6.0);
+        }
+
+        assert!(matches!(
+            sim.effective_flow_rate("missing"),
+            Err(SimulationError::ConnectionNotFound(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_request_step_retriggers_process() -> Result<(), SimulationError> {
+        setup();
+
+        /// Pulls one input per visit and keeps revisiting itself until it can assemble an output
+        #[derive(Clone, Debug)]
+        struct Assembler {
+            id: String,
+            collected: f64,
+            assembled: f64,
+            attempts: Vec<f64>,
+        }
+
+        impl SerializableProcess for Assembler {}
+
+        impl Processor for Assembler {
+            fn id(&self) -> &str {
+                &self.id
+            }
+
+            fn on_event(
+                &mut self,
+                event: &Event,
+                context: &ProcessContext,
+            ) -> Result<Vec<Event>, SimulationError> {
+                match event.payload {
+                    EventPayload::SimulationStart | EventPayload::RequestStep => {
+                        if self.collected >= 3.0 {
+                            self.collected -= 3.0;
+                            self.assembled += 1.0;
+                            return Ok(vec![]);
+                        }
+
+                        self.attempts.push(context.current_time());
+                        Ok(context
+                            .inputs_for_port(Some("in"))
+                            .map(|conn| {
+                                Event::new(
+                                    &self.id,
+                                    &conn.source_id,
+                                    context.current_time(),
+                                    EventPayload::PullRequest,
+                                )
+                            })
+                            .chain([context.request_step(&self.id)])
+                            .collect())
+                    }
+                    EventPayload::Resource(amount) => {
+                        self.collected += amount;
+                        Ok(vec![Event::new(
+                            &self.id,
+                            &event.source_id,
+                            context.current_time(),
+                            EventPayload::ResourceAccepted(amount),
+                        )])
+                    }
+                    _ => Ok(vec![]),
+                }
+            }
+
+            fn get_state(&self) -> ProcessState {
+                ProcessState::Custom(serde_json::json!({
+                    "collected": self.collected,
+                    "assembled": self.assembled,
+                    "attempts": self.attempts,
+                }))
+            }
+
+            fn get_input_ports(&self) -> &[&'static str] {
+                &["in"]
+            }
+
+            fn get_output_ports(&self) -> &[&'static str] {
+                &[]
+            }
+
+            fn reset(&mut self) {}
+        }
+
+        // No stepper: the assembler is only revisited because it asks to be
+        let mut sim = Simulation::new(
+            vec![
+                Process::new(Box::new(Source::new("source1"))),
+                Process::new(Box::new(Assembler {
+                    id: "assembler".to_string(),
+                    collected: 0.0,
+                    assembled: 0.0,
+                    attempts: vec![],
+                })),
+            ],
+            vec![Connection::new(
+                "conn1".to_string(),
+                "source1".to_string(),
+                Some("out".to_string()),
+                "assembler".to_string(),
+                Some("in".to_string()),
+                Some(1.0),
+            )],
+        )?;
+
+        for _ in 0..10 {
+            sim.advance_to_next_event()?;
+        }
+
+        let ProcessState::Custom(state) = sim.get_process_state("assembler").unwrap() else {
+            panic!("expected custom assembler state");
+        };
+        assert_eq!(state["attempts"], serde_json::json!([0.0, 1.0, 2.0]));
+        assert_eq!(state["assembled"], 1.0);
+        assert_eq!(state["collected"], 0.0);
+
+        // Once assembled it stops requesting steps and the queue drains
+        assert_eq!(sim.current_time(), 3.0);
+        assert!(sim.get_events().is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_with_config() -> Result<(), SimulationError> {
+        setup();
+
+        let config: RunConfiguration = serde_json::from_str(
+            r#"{ "dt": 0.5, "seed": 42, "strict": true, "duplexPolicy": "Dedup" }"#,
+        )
+        .unwrap();
+
+        let sim = Simulation::with_config(
+            vec![
+                Process::new(Box::new(Source::new("source1"))),
+                Process::new(Box::new(Pool::new("pool1"))),
+            ],
+            vec![Connection::new(
+                "conn1".to_string(),
+                "source1".to_string(),
+                Some("out".to_string()),
+                "pool1".to_string(),
+                Some("in".to_string()),
+                Some(1.0),
+            )],
+            config,
+        )?;
+
+        assert_eq!(sim.config(), &config);
+        assert_eq!(sim.config().dt, 0.5);
+        assert_eq!(sim.seed(), 42);
+        assert!(sim.is_strict());
+        assert_eq!(sim.duplex_policy(), DuplexPolicy::Dedup);
+
+        // The configuration survives a serialization round trip of the simulation context
+        let restored: SimulationContext =
+            serde_json::from_str(&serde_json::to_string(sim.get_context()).unwrap()).unwrap();
+        assert_eq!(restored.config(), &config);
+
+        // Omitted settings keep their defaults, and invalid ones are refused
+        let partial: RunConfiguration = serde_json::from_str(r#"{ "seed": 7 }"#).unwrap();
+        assert_eq!(
+            partial,
+            RunConfiguration {
+                seed: 7,
+                ..Default::default()
+            }
+        );
+        let invalid = RunConfiguration {
+            dt: 0.0,
+            ..Default::default()
+        };
+        assert!(matches!(
+            Simulation::with_config(vec![], vec![], invalid),
+            Err(SimulationError::InvalidDt(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_simulation_metadata() -> Result<(), SimulationError> {
+        setup();
+
+        let mut sim = Simulation::new(vec![], vec![])?;
+        assert!(sim.metadata().is_empty());
+
+        sim.set_metadata("owner", serde_json::json!("alice"));
+        sim.set_metadata("scenario", serde_json::json!("baseline"));
+        let previous = sim.set_metadata("tags", serde_json::json!(["draft"]));
+        assert_eq!(previous, None);
+        let previous = sim.set_metadata("tags", serde_json::json!(["draft", "sweep"]));
+        assert_eq!(previous, Some(serde_json::json!(["draft"])));
+
+        let restored: Simulation =
+            serde_json::from_str(&serde_json::to_string(&sim).unwrap()).unwrap();
+        assert_eq!(restored.metadata(), sim.metadata());
+        assert_eq!(
+            restored.get_metadata("owner"),
+            Some(&serde_json::json!("alice"))
+        );
+        assert_eq!(
+            restored.get_metadata("tags"),
+            Some(&serde_json::json!(["draft", "sweep"]))
+        );
+        assert_eq!(restored.get_metadata("missing"), None);
+
+        // Metadata describes the run, not its state, so a reset keeps it
+        sim.reset()?;
+        assert_eq!(sim.metadata().len(), 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_delay_queue_release_partial() -> Result<(), SimulationError> {
+        setup();
+
+        // A single batch of 2.5 queued behind a delay that releases 1.0 at a time
+        let run = |release_partial: bool| {
+            let source = Source::builder()
+                .id("source")
+                .trigger_mode(TriggerMode::Enabling)
+                .build()
+                .unwrap();
+            let delay = Delay::builder()
+                .id("queue")
+                .action(DelayAction::Queue)
+                .release_amount(1.0)
+                .release_partial(release_partial)
+                .build()
+                .unwrap();
+            let pool = Process::new(Box::new(Pool::new("pool")));
+            let connections = vec![
+                Connection::new(
+                    "conn1".to_string(),
+                    "source".to_string(),
+                    Some("out".to_string()),
+                    "queue".to_string(),
+                    Some("in".to_string()),
+                    Some(2.5),
+                ),
+                Connection::new(
+                    "conn2".to_string(),
+                    "queue".to_string(),
+                    Some("out".to_string()),
+                    "pool".to_string(),
+                    Some("in".to_string()),
+                    Some(1.0),
+                ),
+            ];
+            let mut sim = create_stepped_simulation(
+                vec![
+                    Process::new(Box::new(source)),
+                    Process::new(Box::new(delay)),
+                    pool,
+                ],
+                connections,
+            )?;
+            sim.step_n(10)?;
+
+            let state = sim.get_simulation_state();
+            let ProcessState::Pool(pool) = &state.process_states["pool"] else {
+                unreachable!()
+            };
+            let ProcessState::Delay(queue) = &state.process_states["queue"] else {
+                unreachable!()
+            };
+            Ok::<_, SimulationError>((pool.resources, queue.current_resources()))
+        };
+
+        // By default the trailing 0.5 never makes up a full release and stays queued
+        assert_eq!(run(false)?, (2.0, 0.5));
+
+        // With release_partial it follows the full releases out
+        assert_eq!(run(true)?, (2.5, 0.0));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_pool_capacity_schedule() -> Result<(), SimulationError> {
+        setup();
+
+        // A full pool of 10 whose capacity shrinks to 5 at t=3, fed one unit per step
+        let run = |overflow: Overflow| {
+            let source = Source::builder().id("source").build().unwrap();
+            let pool = Pool::builder()
+                .id("pool")
+                .overflow(overflow)
+                .capacity(10.0)
+                .capacity_schedule(vec![(3.0, 5.0)])
+                .state(PoolState {
+                    resources: 10.0,
+                    pending_outgoing_resources: 0.0,
+                })
+                .build()
+                .unwrap();
+            let connections = vec![Connection::new(
+                "conn1".to_string(),
+                "source".to_string(),
+                Some("out".to_string()),
+                "pool".to_string(),
+                Some("in".to_string()),
+                Some(1.0),
+            )];
+            let mut sim = create_stepped_simulation(
+                vec![Process::new(Box::new(source)), Process::new(Box::new(pool))],
+                connections,
+            )?;
+
+            let mut resources = Vec::new();
+            for _ in 0..5 {
+                sim.step()?;
+                let ProcessState::Pool(pool) = &sim.get_simulation_state().process_states["pool"]
+                else {
+                    unreachable!()
+                };
+                resources.push(pool.resources);
+            }
+            Ok::<_, SimulationError>((resources, sim.connection_stats("conn1").rejected))
+        };
+
+        // Drain: the excess 5 units are discarded once the lower capacity applies
+        let (resources, rejected) = run(Overflow::Drain)?;
+        assert_eq!(resources, vec![10.0, 10.0, 5.0, 5.0, 5.0]);
+        assert_eq!(rejected, 5.0);
+
+        // Block: the excess is kept, and every further unit is rejected
+        let (resources, rejected) = run(Overflow::Block)?;
+        assert_eq!(resources, vec![10.0; 5]);
+        assert_eq!(rejected, 5.0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sequence_counters() -> Result<(), SimulationError> {
+        setup();
+
+        let mut sim = create_stepped_simulation(
+            vec![
+                Process::new(Box::new(Source::new("source"))),
+                Process::new(Box::new(Pool::new("pool"))),
+            ],
+            vec![Connection::new(
+                "conn".to_string(),
+                "source".to_string(),
+                Some("out".to_string()),
+                "pool".to_string(),
+                Some("in".to_string()),
+                Some(1.0),
+            )],
+        )?;
+        let (events_before, connections) = sim.sequence_counters();
+        assert_eq!(connections, 1);
+
+        // Events at the same time come out in the order they were scheduled
+        let n = 5;
+        for _ in 0..n {
+            sim.schedule_event(Event::new(
+                "simulation",
+                "source",
+                50.0,
+                EventPayload::Trigger,
+            ))?;
+        }
+        assert_eq!(sim.sequence_counters(), (events_before + n, connections));
+
+        let sequence_numbers: Vec<u64> = sim
+            .scheduled_events()
+            .iter()
+            .filter(|event| event.time == 50.0)
+            .map(|event| event.sequence_number)
+            .collect();
+        assert_eq!(
+            sequence_numbers,
+            (events_before..events_before + n).collect::<Vec<_>>()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_connection_packet_size() -> Result<(), SimulationError> {
+        setup();
+
+        // A trickle of one unit per step into a depot that ships packets of 5
+        let depot = Pool::builder()
+            .id("depot")
+            .trigger_mode(TriggerMode::Automatic)
+            .action(Action::PushAny)
+            .build()
+            .unwrap();
+        let connections = vec![
+            Connection::new(
+                "trickle".to_string(),
+                "source".to_string(),
+                Some("out".to_string()),
+                "depot".to_string(),
+                Some("in".to_string()),
+                Some(1.0),
+            ),
+            Connection::new(
+                "truck".to_string(),
+                "depot".to_string(),
+                Some("out".to_string()),
+                "store".to_string(),
+                Some("in".to_string()),
+                Some(100.0),
+            )
+            .with_packet_size(5.0),
+        ];
+        let mut sim = create_stepped_simulation(
+            vec![
+                Process::new(Box::new(Source::new("source"))),
+                Process::new(Box::new(depot)),
+                Process::new(Box::new(Pool::new("store"))),
+            ],
+            connections,
+        )?;
+
+        let mut store = Vec::new();
+        for _ in 0..12 {
+            sim.step()?;
+            let ProcessState::Pool(pool) = &sim.get_simulation_state().process_states["store"]
+            else {
+                unreachable!()
+            };
+            store.push(pool.resources);
+        }
+
+        // The depot pushes the stock it held before the step, so a packet leaves every fifth step
+        assert_eq!(
+            store,
+            vec![0.0, 0.0, 0.0, 0.0, 0.0, 5.0, 5.0, 5.0, 5.0, 5.0, 10.0, 10.0]
+        );
+        let stats = sim.connection_stats("truck");
+        assert_eq!((stats.transfers, stats.transferred), (2, 10.0));
+        assert!(sim.summary().conserved);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_priority() -> Result<(), SimulationError> {
+        setup();
+
+        // Two drains pulling a single unit from a pool; "low" is connected first
+        let run = |low_priority: i64, high_priority: i64| {
+            let pool = Pool::builder()
+                .id("pool")
+                .state(PoolState {
+                    resources: 1.0,
+                    pending_outgoing_resources: 0.0,
+                })
+                .build()
+                .unwrap();
+            let low = Drain::builder()
+                .id("low")
+                .priority(low_priority)
+                .build()
+                .unwrap();
+            let high = Drain::builder()
+                .id("high")
+                .priority(high_priority)
+                .build()
+                .unwrap();
+            let connections = ["low", "high"]
+                .iter()
+                .map(|drain| {
+                    Connection::new(
+                        format!("to_{}", drain),
+                        "pool".to_string(),
+                        Some("out".to_string()),
+                        drain.to_string(),
+                        Some("in".to_string()),
+                        Some(1.0),
+                    )
+                })
+                .collect();
+            let mut sim = create_stepped_simulation(
+                vec![
+                    Process::new(Box::new(pool)),
+                    Process::new(Box::new(low)),
+                    Process::new(Box::new(high)),
+                ],
+                connections,
+            )?;
+            sim.step()?;
+
+            let state = sim.get_simulation_state();
+            let consumed = |id: &str| match &state.process_states[id] {
+                ProcessState::Drain(drain) => drain.resources_consumed,
+                _ => unreachable!(),
+            };
+            Ok::<_, SimulationError>((consumed("low"), consumed("high")))
+        };
+
+        // Equal priorities fall back to connection order
+        assert_eq!(run(0, 0)?, (1.0, 0.0));
+
+        // The higher-priority drain wins the scarce unit
+        assert_eq!(run(0, 5)?, (0.0, 1.0));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_snapshot_restore() -> Result<(), SimulationError> {
+        setup();
+
+        let delay = Delay::builder().id("delay").build().unwrap();
+        let connections = vec![
+            Connection::new(
+                "conn1".to_string(),
+                "source".to_string(),
+                Some("out".to_string()),
+                "delay".to_string(),
+                Some("in".to_string()),
+                Some(1.0),
+            ),
+            Connection::new(
+                "conn2".to_string(),
+                "delay".to_string(),
+                Some("out".to_string()),
+                "pool".to_string(),
+                Some("in".to_string()),
+                Some(3.0),
+            ),
+        ];
+        let mut sim = create_stepped_simulation(
+            vec![
+                Process::new(Box::new(Source::new("source"))),
+                Process::new(Box::new(delay)),
+                Process::new(Box::new(Pool::new("pool"))),
+            ],
+            connections,
+        )?;
+        sim.step_n(4)?;
+
+        // Resources still in flight through the delay are carried over
+        let mut restored = Simulation::restore(&sim.snapshot()?)?;
+        assert_eq!(
+            restored.get_simulation_state().stable_hash(),
+            sim.get_simulation_state().stable_hash()
+        );
+        assert_eq!(restored.get_events().len(), sim.get_events().len());
+        assert_eq!(restored.run_hash(10)?, sim.run_hash(10)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_loss_report() -> Result<(), SimulationError> {
+        setup();
+
+        // Two sources feed a blocking pool that fills up after three units
+        let pool = Pool::builder()
+            .id("pool")
+            .overflow(Overflow::Block)
+            .capacity(3.0)
+            .build()
+            .unwrap();
+        let connections = ["source1", "source2"]
+            .iter()
+            .map(|source| {
+                Connection::new(
+                    format!("{}_pool", source),
+                    source.to_string(),
+                    Some("out".to_string()),
+                    "pool".to_string(),
+                    Some("in".to_string()),
+                    Some(1.0),
+                )
+            })
+            .collect();
+        let mut sim = create_stepped_simulation(
+            vec![
+                Process::new(Box::new(Source::new("source1"))),
+                Process::new(Box::new(Source::new("source2"))),
+                Process::new(Box::new(pool)),
+            ],
+            connections,
+        )?;
+
+        // Nothing is tallied until recording starts
+        sim.step()?;
+        assert_eq!(sim.loss_report().total, 0.0);
+
+        sim.enable_recording();
+        sim.step_n(4)?;
+
+        // Step 2 fills the pool to 3 with one unit turned away, then both are turned away
+        let report = sim.loss_report();
+        assert_eq!(report.total, 7.0);
+        assert_eq!(report.by_process["pool"], 7.0);
+        assert_eq!(report.by_connection.values().sum::<f64>(), report.total);
+        let rejected: f64 = ["source1_pool", "source2_pool"]
+            .iter()
+            .map(|id| sim.connection_stats(id).rejected)
+            .sum();
+        assert_eq!(rejected, report.total);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_delay_pending_backlog() -> Result<(), SimulationError> {
+        setup();
+
+        // A delay warm-started with 4 units due at t=2 and nothing upstream
+        let delay = Delay::builder()
+            .id("delay")
+            .pending(vec![(4.0, 2.0)])
+            .build()
+            .unwrap();
+        let ProcessState::Delay(state) = delay.get_state() else {
+            unreachable!()
+        };
+        let serialized = serde_json::to_value(&state).unwrap();
+        assert_eq!(serialized["backlog"], serde_json::json!([[4.0, 2.0]]));
+        assert_eq!(state.current_resources(), 4.0);
+
+        let connections = vec![
+            Connection::new(
+                "conn1".to_string(),
+                "source".to_string(),
+                Some("out".to_string()),
+                "delay".to_string(),
+                Some("in".to_string()),
+                Some(1.0),
+            ),
+            Connection::new(
+                "conn2".to_string(),
+                "delay".to_string(),
+                Some("out".to_string()),
+                "pool".to_string(),
+                Some("in".to_string()),
+                Some(1.0),
+            ),
+        ];
+        let source = Source::builder()
+            .id("source")
+            .trigger_mode(TriggerMode::Passive)
+            .build()
+            .unwrap();
+        let mut sim = create_stepped_simulation(
+            vec![
+                Process::new(Box::new(source)),
+                Process::new(Box::new(delay)),
+                Process::new(Box::new(Pool::new("pool"))),
+            ],
+            connections,
+        )?;
+
+        let mut arrivals = Vec::new();
+        for _ in 0..3 {
+            sim.step()?;
+            let ProcessState::Pool(pool) = sim.get_process_state("pool")? else {
+                unreachable!()
+            };
+            arrivals.push((sim.current_time(), pool.resources));
+        }
+        assert_eq!(arrivals, vec![(1.0, 0.0), (2.0, 4.0), (3.0, 4.0)]);
+        assert!(sim.summary().conserved);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_lifecycle_callbacks() -> Result<(), SimulationError> {
+        setup();
+
+        let mut sim = create_stepped_simulation(vec![], vec![])?;
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let log = Arc::clone(&seen);
+        sim.on_lifecycle(move |event| log.lock().unwrap().push(event.clone()));
+
+        sim.add_process(Source::new("source"))?;
+        sim.add_process(Pool::new("pool"))?;
+        sim.add_connection(Connection::new(
+            "conn".to_string(),
+            "source".to_string(),
+            Some("out".to_string()),
+            "pool".to_string(),
+            Some("in".to_string()),
+            Some(1.0),
+        ))?;
+        sim.remove_connection("conn")?;
+        sim.remove_process("pool")?;
+        sim.reset()?;
+
+        // Failed mutations change nothing and notify nobody
+        assert!(sim.remove_process("missing").is_err());
+
+        assert_eq!(
+            *seen.lock().unwrap(),
+            vec![
+                LifecycleEvent::ProcessAdded("source".to_string()),
+                LifecycleEvent::ProcessAdded("pool".to_string()),
+                LifecycleEvent::ConnectionAdded("conn".to_string()),
+                LifecycleEvent::ConnectionRemoved("conn".to_string()),
+                LifecycleEvent::ProcessRemoved("pool".to_string()),
+                LifecycleEvent::Reset,
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_run_collecting_by_process() -> Result<(), SimulationError> {
+        setup();
+
+        let mut sim = create_stepped_simulation(
+            vec![
+                Process::new(Box::new(Source::new("source"))),
+                Process::new(Box::new(Pool::new("pool"))),
+            ],
+            vec![Connection::new(
+                "conn".to_string(),
+                "source".to_string(),
+                Some("out".to_string()),
+                "pool".to_string(),
+                Some("in".to_string()),
+                Some(1.0),
+            )],
+        )?;
+        let events = sim.run_collecting_by_process(5)?;
+        assert!(!sim.is_recording());
+
+        // The source only hears back acknowledgements of what it pushed
+        assert_eq!(events["source"].len(), 5);
+        assert!(events["source"]
+            .iter()
+            .all(|event| matches!(event.payload, EventPayload::ResourceAccepted(_))));
+        assert!(events["pool"]
+            .iter()
+            .any(|event| matches!(event.payload, EventPayload::Resource(_))));
+        assert!(!events.contains_key("broadcast"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_trigger_passive_drain() -> Result<(), SimulationError> {
+        setup();
+
+        let pool = Pool::builder()
+            .id("pool")
+            .state(PoolState {
+                resources: 3.0,
+                pending_outgoing_resources: 0.0,
+            })
+            .build()
+            .unwrap();
+        let drain = Drain::builder()
+            .id("drain")
+            .trigger_mode(TriggerMode::Passive)
+            .build()
+            .unwrap();
+        let mut sim = Simulation::new(
+            vec![Process::new(Box::new(pool)), Process::new(Box::new(drain))],
+            vec![Connection::new(
+                "conn".to_string(),
+                "pool".to_string(),
+                Some("out".to_string()),
+                "drain".to_string(),
+                Some("in".to_string()),
+                Some(2.0),
+            )],
+        )?;
+
+        sim.schedule_event(Event::new(
+            "simulation",
+            "drain",
+            0.0,
+            EventPayload::Trigger,
+        ))?;
+        while !sim.advance_to_next_event()?.is_empty() {}
+
+        let ProcessState::Drain(drain) = sim.get_process_state("drain")? else {
+            unreachable!()
+        };
+        assert_eq!(drain.resources_consumed, 2.0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_trigger_queue_delay() -> Result<(), SimulationError> {
+        setup();
+
+        let source = Source::builder()
+            .id("source")
+            .trigger_mode(TriggerMode::Passive)
+            .build()
+            .unwrap();
+        let queue = Delay::builder()
+            .id("queue")
+            .action(DelayAction::Queue)
+            .build()
+            .unwrap();
+        let connections = vec![
+            Connection::new(
+                "conn1".to_string(),
+                "source".to_string(),
+                Some("out".to_string()),
+                "queue".to_string(),
+                Some("in".to_string()),
+                Some(1.0),
+            ),
+            Connection::new(
+                "conn2".to_string(),
+                "queue".to_string(),
+                Some("out".to_string()),
+                "pool".to_string(),
+                Some("in".to_string()),
+                Some(1.0),
+            ),
+        ];
+        let mut sim = Simulation::new(
+            vec![
+                Process::new(Box::new(source)),
+                Process::new(Box::new(queue)),
+                Process::new(Box::new(Pool::new("pool"))),
+            ],
+            connections,
+        )?;
+        let pool_resources = |sim: &Simulation| match sim.get_process_state("pool") {
+            Ok(ProcessState::Pool(pool)) => pool.resources,
+            _ => unreachable!(),
+        };
+
+        // Without a stepper, the queue only releases when triggered after its delay
+        sim.schedule_event(Event::new(
+            "simulation",
+            "source",
+            0.0,
+            EventPayload::Trigger,
+        ))?;
+        while !sim.advance_to_next_event()?.is_empty() {}
+        assert_eq!(pool_resources(&sim), 0.0);
+
+        sim.schedule_event(Event::new(
+            "simulation",
+            "queue",
+            1.0,
+            EventPayload::Trigger,
+        ))?;
+        while !sim.advance_to_next_event()?.is_empty() {}
+        assert_eq!(pool_resources(&sim), 1.0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_reorder_buffer_restores_dispatch_order() -> Result<(), SimulationError> {
+        setup();
+
+        // Amounts 1..=10 leave the source one step apart, each distinct so order is visible
+        let schedule: Vec<(f64, f64)> = (1..=10)
+            .map(|k| (k as f64 - 0.5, k as f64))
+            .chain([(10.5, 0.0)])
+            .collect();
+
+        let run = |reorder: bool| -> Result<(Vec<f64>, f64), SimulationError> {
+            let connections = vec![
+                Connection::new(
+                    "conn1".to_string(),
+                    "source".to_string(),
+                    Some("out".to_string()),
+                    "delay".to_string(),
+                    Some("in".to_string()),
+                    Some(0.0),
+                )
+                .with_schedule(schedule.clone()),
+                Connection::new(
+                    "conn2".to_string(),
+                    "delay".to_string(),
+                    Some("out".to_string()),
+                    "pool".to_string(),
+                    Some("in".to_string()),
+                    Some(3.0),
+                ),
+            ];
+            let delay = Delay::builder().id("delay").jitter(2.5).build().unwrap();
+            let pool = Pool::builder().id("pool").reorder(reorder).build().unwrap();
+            let mut sim = create_stepped_simulation(
+                vec![
+                    Process::new(Box::new(Source::new("source"))),
+                    Process::new(Box::new(delay)),
+                    Process::new(Box::new(pool)),
+                ],
+                connections,
+            )?;
+            sim.enable_recording();
+            sim.step_n(60)?;
+
+            let delivered = sim
+                .recorded_events()
+                .iter()
+                .filter(|e| e.source_id == "pool" && e.target_id == "delay")
+                .filter_map(|e| match e.payload {
+                    EventPayload::ResourceAccepted(amount) => Some(amount),
+                    _ => None,
+                })
+                .collect();
+            let ProcessState::Pool(state) = sim.get_process_state("pool")? else {
+                unreachable!()
+            };
+            Ok((delivered, state.resources))
+        };
+
+        let (unordered, unordered_total) = run(false)?;
+        assert!(unordered.windows(2).any(|w| w[0] > w[1]));
+
+        let (ordered, ordered_total) = run(true)?;
+        assert_eq!(ordered, (1..=10).map(|k| k as f64).collect::<Vec<_>>());
+        assert_eq!(unordered_total, 55.0);
+        assert_eq!(ordered_total, unordered_total);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_reorder_buffer_follows_restarts_and_replacements() -> Result<(), SimulationError> {
+        setup();
+
+        let run = |reorder: bool| -> Result<f64, SimulationError> {
+            let pool = || Pool::builder().id("pool").reorder(reorder).build().unwrap();
+            let delay = Delay::builder().id("delay").jitter(2.5).build().unwrap();
+            let mut sim = create_stepped_simulation(
+                vec![
+                    Process::new(Box::new(Source::new("source"))),
+                    Process::new(Box::new(delay)),
+                    Process::new(Box::new(pool())),
+                ],
+                vec![
+                    Connection::new(
+                        "source_to_delay".to_string(),
+                        "source".to_string(),
+                        Some("out".to_string()),
+                        "delay".to_string(),
+                        Some("in".to_string()),
+                        Some(0.0),
+                    )
+                    .with_schedule(vec![
+                        (0.5, 1.0),
+                        (5.5, 0.0),
+                        (15.5, 1.0),
+                        (25.5, 0.0),
+                    ]),
+                    Connection::new(
+                        "delay_to_pool".to_string(),
+                        "delay".to_string(),
+                        Some("out".to_string()),
+                        "pool".to_string(),
+                        Some("in".to_string()),
+                        Some(3.0),
+                    ),
+                ],
+            )?;
+            let step_until = |sim: &mut Simulation, time: f64| -> Result<(), SimulationError> {
+                while sim.current_time() < time {
+                    sim.step()?;
+                }
+                Ok(())
+            };
+
+            // Between bursts, with nothing in flight, the delay numbers its dispatches from
+            // zero again, which the pool must follow
+            step_until(&mut sim, 12.0)?;
+            sim.reset_process("delay")?;
+
+            // A fresh pool joins a stream that is already well past its first dispatch
+            step_until(&mut sim, 20.0)?;
+            sim.update_process("pool", Process::new(Box::new(pool())))?;
+            step_until(&mut sim, 40.0)?;
+
+            match sim.get_process_state("pool")? {
+                ProcessState::Pool(state) => Ok(state.resources),
+                _ => unreachable!(),
+            }
+        };
+
+        // Reordering may hold arrivals back for a while, but never strands them
+        let unordered = run(false)?;
+        assert!(unordered > 0.0);
+        assert_eq!(run(true)?, unordered);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_run_until_steady() -> Result<(), SimulationError> {
+        setup();
+
+        // Two pools passing 2 units back and forth settle once the second has stock to return
+        let pool = |id: &str, resources: f64| {
+            Pool::builder()
+                .id(id)
+                .trigger_mode(TriggerMode::Automatic)
+                .action(Action::PushAny)
+                .state(PoolState {
+                    resources,
+                    pending_outgoing_resources: 0.0,
+                })
+                .build()
+                .unwrap()
+        };
+        let connections = vec![
+            Connection::new(
+                "there".to_string(),
+                "left".to_string(),
+                Some("out".to_string()),
+                "right".to_string(),
+                Some("in".to_string()),
+                Some(2.0),
+            ),
+            Connection::new(
+                "back".to_string(),
+                "right".to_string(),
+                Some("out".to_string()),
+                "left".to_string(),
+                Some("in".to_string()),
+                Some(2.0),
+            ),
+        ];
+        let mut sim = create_stepped_simulation(
+            vec![
+                Process::new(Box::new(pool("left", 10.0))),
+                Process::new(Box::new(pool("right", 0.0))),
+            ],
+            connections,
+        )?;
+
+        sim.step()?;
+        assert_eq!(sim.steps_since_state_change(0.0), 0);
+
+        assert!(sim.run_until_steady(100, 5)?);
+        assert_eq!(sim.steps_since_state_change(0.0), 5);
+        assert!(sim.current_step() < 10);
+
+        let ProcessState::Pool(left) = sim.get_process_state("left")? else {
+            unreachable!()
+        };
+        assert_eq!(left.resources, 8.0);
+        assert!(sim.summary().conserved);
+
+        // Resetting forgets the history
+        sim.reset()?;
+        assert_eq!(sim.steps_since_state_change(0.0), 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_state_change_history_survives_snapshots() -> Result<(), SimulationError> {
+        setup();
+
+        let mut sim = create_stepped_simulation(
+            vec![
+                Process::new(Box::new(Source::new("source"))),
+                Process::new(Box::new(Pool::new("pool"))),
+            ],
+            vec![Connection::new(
+                "source_to_pool".to_string(),
+                "source".to_string(),
+                Some("out".to_string()),
+                "pool".to_string(),
+                Some("in".to_string()),
+                Some(1.0),
+            )],
+        )?;
+        sim.step_n(3)?;
+
+        // The first step after a restore has nothing to compare against and counts as unbounded
+        let mut restored = Simulation::restore(&sim.snapshot()?)?;
+        restored.step()?;
+        let restored_again = Simulation::restore(&restored.snapshot()?)?;
+        assert_eq!(restored_again.steps_since_state_change(f64::MAX), 0);
+        assert_eq!(restored_again.steps_since_state_change(f64::INFINITY), 4);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_errors_name_process_type() -> Result<(), SimulationError> {
+        setup();
+
+        let roundabout = Pool::builder()
+            .id("roundabout")
+            .capacity(10.0)
+            .overflow(Overflow::Drain)
+            .build()
+            .unwrap();
+        let mut sim = create_stepped_simulation(
+            vec![
+                Process::new(Box::new(Source::new("source"))),
+                Process::new(Box::new(roundabout)),
+            ],
+            vec![],
+        )?;
+
+        assert_eq!(
+            sim.describe_process("roundabout").as_deref(),
+            Some("Pool 'roundabout' (capacity 10, overflow Drain)")
+        );
+        assert_eq!(sim.describe_process("missing"), None);
+
+        let error = sim
+            .add_connection(Connection::new(
+                "conn1".to_string(),
+                "source".to_string(),
+                Some("out".to_string()),
+                "roundabout".to_string(),
+                Some("sideways".to_string()),
+                Some(1.0),
+            ))
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Invalid input port 'sideways' for Pool process 'roundabout'"
+        );
+        assert_eq!(
+            sim.describe_error(&error),
+            "Invalid input port 'sideways' for Pool process 'roundabout' \
+             [Pool 'roundabout' (capacity 10, overflow Drain)]"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_pool_push_proportional() -> Result<(), SimulationError> {
+        setup();
+
+        let run = |action: Action| -> Result<Vec<f64>, SimulationError> {
+            let hub = Pool::builder()
+                .id("hub")
+                .trigger_mode(TriggerMode::Automatic)
+                .action(action)
+                .state(PoolState {
+                    resources: 3.0,
+                    pending_outgoing_resources: 0.0,
+                })
+                .build()
+                .unwrap();
+            let targets = ["a", "b", "c"];
+
+            let mut processes = vec![Process::new(Box::new(hub))];
+            let mut connections = vec![];
+            for target in targets {
+                processes.push(Process::new(Box::new(Pool::new(target))));
+                connections.push(Connection::new(
+                    format!("to_{}", target),
+                    "hub".to_string(),
+                    Some("out".to_string()),
+                    target.to_string(),
+                    Some("in".to_string()),
+                    Some(2.0),
+                ));
+            }
+            let mut sim = create_stepped_simulation(processes, connections)?;
+            sim.step()?;
+
+            targets
+                .iter()
+                .map(|id| match sim.get_process_state(id)? {
+                    ProcessState::Pool(state) => Ok(state.resources),
+                    _ => unreachable!(),
+                })
+                .collect()
+        };
+
+        assert_eq!(run(Action::PushAny)?, vec![2.0, 1.0, 0.0]);
+
+        let shares = run(Action::PushProportional)?;
+        for share in &shares {
+            assert!(
+                (share - 1.0).abs() < 1e-9,
+                "Expected ~1.0, got {:?}",
+                shares
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_step_delta() -> Result<(), SimulationError> {
+        setup();
+
+        // Only "shrinking" changes, when its capacity drops at t=2 and the excess drains away
+        let shrinking = Pool::builder()
+            .id("shrinking")
+            .overflow(Overflow::Drain)
+            .capacity(10.0)
+            .capacity_schedule(vec![(2.0, 4.0)])
+            .state(PoolState {
+                resources: 10.0,
+                pending_outgoing_resources: 0.0,
+            })
+            .build()
+            .unwrap();
+        let steady = Pool::builder()
+            .id("steady")
+            .state(PoolState {
+                resources: 5.0,
+                pending_outgoing_resources: 0.0,
+            })
+            .build()
+            .unwrap();
+        let mut sim = create_stepped_simulation(
+            vec![
+                Process::new(Box::new(shrinking)),
+                Process::new(Box::new(steady)),
+            ],
+            vec![],
+        )?;
+
+        let delta = sim.step_delta()?;
+        assert_eq!(delta.step, 1);
+        assert!(delta.is_empty());
+
+        let delta = sim.step_delta()?;
+        assert_eq!(delta.time, 2.0);
+        assert_eq!(delta.changed.len(), 1);
+        let Some(ProcessState::Pool(state)) = delta.changed.get("shrinking") else {
+            panic!("Expected the shrinking pool in {:?}", delta);
+        };
+        assert_eq!(state.resources, 4.0);
+        assert!(delta.removed.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_reject_connections_without_ports() -> Result<(), SimulationError> {
+        setup();
+
+        let mut sim = create_stepped_simulation(
+            vec![
+                Process::new(Box::new(Source::new("source"))),
+                Process::new(Box::new(Pool::new("pool"))),
+                Process::new(Box::new(Drain::new("drain"))),
+            ],
+            vec![],
+        )?;
+
+        // Ports left unspecified still cannot leave a drain
+        let from_drain = sim.add_connection(Connection::new(
+            "from_drain".to_string(),
+            "drain".to_string(),
+            None,
+            "pool".to_string(),
+            None,
+            Some(1.0),
+        ));
+        assert_eq!(
+            from_drain,
+            Err(SimulationError::NoPorts {
+                process: "drain".to_string(),
+                process_type: "Drain".to_string(),
+                port_type: "output".to_string(),
+            })
+        );
+        assert_eq!(
+            from_drain.unwrap_err().to_string(),
+            "Drain process 'drain' has no output ports to connect"
+        );
+
+        let into_source = sim.add_connection(Connection::new(
+            "into_source".to_string(),
+            "pool".to_string(),
+            Some("out".to_string()),
+            "source".to_string(),
+            None,
+            Some(1.0),
+        ));
+        assert_eq!(
+            into_source,
+            Err(SimulationError::NoPorts {
+                process: "source".to_string(),
+                process_type: "Source".to_string(),
+                port_type: "input".to_string(),
+            })
+        );
+
+        assert!(sim.connections().is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_pinned_node_seed() -> Result<(), SimulationError> {
+        setup();
+
+        let run = |global_seed: u64, pinned: Option<u64>| -> Result<f64, SimulationError> {
+            let mut loot = Pool::builder();
+            loot.id("loot")
+                .trigger_mode(TriggerMode::Automatic)
+                .action(Action::PushLottery)
+                .state(PoolState {
+                    resources: 200.0,
+                    pending_outgoing_resources: 0.0,
+                });
+            if let Some(seed) = pinned {
+                loot.seed(seed);
+            }
+
+            let mut sim = create_stepped_simulation(
+                vec![
+                    Process::new(Box::new(loot.build().unwrap())),
+                    Process::new(Box::new(Pool::new("common"))),
+                    Process::new(Box::new(Pool::new("rare"))),
+                ],
+                vec![
+                    Connection::new(
+                        "to_common".to_string(),
+                        "loot".to_string(),
+                        Some("out".to_string()),
+                        "common".to_string(),
+                        Some("in".to_string()),
+                        Some(1.0),
+                    ),
+                    Connection::new(
+                        "to_rare".to_string(),
+                        "loot".to_string(),
+                        Some("out".to_string()),
+                        "rare".to_string(),
+                        Some("in".to_string()),
+                        Some(1.0),
+                    ),
+                ],
+            )?;
+            sim.set_seed(global_seed);
+            sim.step_n(200)?;
+
+            match sim.get_process_state("rare")? {
+                ProcessState::Pool(state) => Ok(state.resources),
+                _ => unreachable!(),
+            }
+        };
+
+        // The global seed drives the draws unless the node pins its own
+        assert_ne!(run(1, None)?, run(2, None)?);
+        let pinned = run(1, Some(7))?;
+        assert_eq!(run(2, Some(7))?, pinned);
+        assert_eq!(run(3, Some(7))?, pinned);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_pool_push_if_room() -> Result<(), SimulationError> {
+        setup();
+
+        let pool = |id: &str, trigger_mode: TriggerMode, capacity: f64, resources: f64| {
+            Pool::builder()
+                .id(id)
+                .trigger_mode(trigger_mode)
+                .action(Action::PushIfRoom)
+                .capacity(capacity)
+                .state(PoolState {
+                    resources,
+                    pending_outgoing_resources: 0.0,
+                })
+                .build()
+                .unwrap()
+        };
+        let connection = |id: &str, target: &str| {
+            Connection::new(
+                id.to_string(),
+                "hub".to_string(),
+                Some("out".to_string()),
+                target.to_string(),
+                Some("in".to_string()),
+                Some(3.0),
+            )
+        };
+        let mut sim = create_stepped_simulation(
+            vec![
+                Process::new(Box::new(pool("hub", TriggerMode::Automatic, -1.0, 10.0))),
+                Process::new(Box::new(pool("full", TriggerMode::Passive, 5.0, 5.0))),
+                Process::new(Box::new(pool("roomy", TriggerMode::Passive, 5.0, 3.0))),
+            ],
+            vec![
+                connection("to_full", "full"),
+                connection("to_roomy", "roomy"),
+            ],
+        )?;
+        sim.enable_recording();
+        sim.step_n(3)?;
+
+        let involving = |id: &str| -> Vec<EventPayload> {
+            sim.recorded_events()
+                .iter()
+                .filter(|e| e.source_id == id || e.target_id == id)
+                .filter(|e| e.source_id != "stepper" && e.target_id != "stepper")
+                .map(|e| e.payload.clone())
+                .collect()
+        };
+
+        // The full pool is only ever asked, never sent anything to refuse
+        let full = involving("full");
+        assert!(!full.is_empty());
+        assert!(full.iter().all(|payload| matches!(
+            payload,
+            EventPayload::CapacityQuery | EventPayload::CapacityResponse(_)
+        )));
+        assert!(full.contains(&EventPayload::CapacityResponse(0.0)));
+
+        // The roomy pool receives exactly the 2 units it had room for
+        let roomy = involving("roomy");
+        assert!(roomy.contains(&EventPayload::Resource(2.0)));
+        assert!(!roomy
+            .iter()
+            .any(|payload| matches!(payload, EventPayload::ResourceRejected(_))));
+
+        let resources = |id: &str| match sim.get_process_state(id) {
+            Ok(ProcessState::Pool(state)) => state.resources,
+            _ => unreachable!(),
+        };
+        assert_eq!(resources("hub"), 8.0);
+        assert_eq!(resources("full"), 5.0);
+        assert_eq!(resources("roomy"), 5.0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_pool_push_if_room_through_gate() -> Result<(), SimulationError> {
+        setup();
+
+        let hub = Pool::builder()
+            .id("hub")
+            .trigger_mode(TriggerMode::Automatic)
+            .action(Action::PushIfRoom)
+            .state(PoolState {
+                resources: 10.0,
+                pending_outgoing_resources: 0.0,
+            })
+            .build()
+            .unwrap();
+        let connection = |source: &str, target: &str| {
+            Connection::new(
+                format!("{}_to_{}", source, target),
+                source.to_string(),
+                Some("out".to_string()),
+                target.to_string(),
+                Some("in".to_string()),
+                Some(3.0),
+            )
+        };
+        let mut sim = create_stepped_simulation(
+            vec![
+                Process::new(Box::new(hub)),
+                Process::new(Box::new(Gate::new("gate"))),
+                Process::new(Box::new(Pool::new("after"))),
+            ],
+            vec![connection("hub", "gate"), connection("gate", "after")],
+        )?;
+        sim.step_n(3)?;
+
+        // The gate answers the hub's capacity queries, so resources flow through it
+        let resources = |id: &str| match sim.get_process_state(id) {
+            Ok(ProcessState::Pool(state)) => state.resources,
+            _ => unreachable!(),
+        };
+        assert_eq!(resources("hub"), 1.0);
+        assert_eq!(resources("after"), 9.0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_pool_min_dwell() -> Result<(), SimulationError> {
+        setup();
+
+        // 5 units arrive at t=1 only; the curing pool passes on whatever is ready
+        let curing = Pool::builder()
+            .id("curing")
+            .trigger_mode(TriggerMode::Automatic)
+            .action(Action::PushAny)
+            .min_dwell(2.0)
+            .build()
+            .unwrap();
+        let connections = vec![
+            Connection::new(
+                "conn1".to_string(),
+                "source".to_string(),
+                Some("out".to_string()),
+                "curing".to_string(),
+                Some("in".to_string()),
+                Some(5.0),
+            )
+            .with_schedule(vec![(1.5, 0.0)]),
+            Connection::new(
+                "conn2".to_string(),
+                "curing".to_string(),
+                Some("out".to_string()),
+                "cured".to_string(),
+                Some("in".to_string()),
+                Some(10.0),
+            ),
+        ];
+        let mut sim = create_stepped_simulation(
+            vec![
+                Process::new(Box::new(Source::new("source"))),
+                Process::new(Box::new(curing)),
+                Process::new(Box::new(Pool::new("cured"))),
+            ],
+            connections,
+        )?;
+
+        let mut cured = Vec::new();
+        for _ in 0..4 {
+            sim.step()?;
+            match sim.get_process_state("cured")? {
+                ProcessState::Pool(state) => cured.push((sim.current_time(), state.resources)),
+                _ => unreachable!(),
+            }
+        }
+        assert_eq!(cured, vec![(1.0, 0.0), (2.0, 0.0), (3.0, 5.0), (4.0, 5.0)]);
+        assert!(sim.summary().conserved);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_cascade_policy_single_hop() -> Result<(), SimulationError> {
+        setup();
+
+        // Positions after each step: (time, held by the pool, consumed by the drain)
+        let run = |policy: CascadePolicy| -> Result<Vec<(f64, f64, f64)>, SimulationError> {
+            let pool = Pool::builder()
+                .id("pool")
+                .trigger_mode(TriggerMode::Automatic)
+                .action(Action::PushAny)
+                .build()
+                .unwrap();
+            let connections = vec![
+                Connection::new(
+                    "conn1".to_string(),
+                    "source".to_string(),
+                    Some("out".to_string()),
+                    "pool".to_string(),
+                    Some("in".to_string()),
+                    Some(1.0),
+                )
+                .with_schedule(vec![(1.5, 0.0)]),
+                Connection::new(
+                    "conn2".to_string(),
+                    "pool".to_string(),
+                    Some("out".to_string()),
+                    "drain".to_string(),
+                    Some("in".to_string()),
+                    Some(1.0),
+                ),
+            ];
+            let mut sim = create_stepped_simulation(
+                vec![
+                    Process::new(Box::new(Source::new("source"))),
+                    Process::new(Box::new(pool)),
+                    Process::new(Box::new(
+                        Drain::builder()
+                            .id("drain")
+                            .trigger_mode(TriggerMode::Passive)
+                            .build()
+                            .unwrap(),
+                    )),
+                ],
+                connections,
+            )?;
+            sim.set_cascade_policy(policy);
+
+            let mut positions = Vec::new();
+            for _ in 0..6 {
+                sim.step()?;
+                let state = sim.get_simulation_state();
+                let (ProcessState::Pool(pool), ProcessState::Drain(drain)) = (
+                    &state.process_states["pool"],
+                    &state.process_states["drain"],
+                ) else {
+                    unreachable!()
+                };
+                positions.push((sim.current_time(), pool.resources, drain.resources_consumed));
+            }
+            Ok(positions)
+        };
+
+        // A whole push, delivery and acknowledgement fits in one step by default
+        assert_eq!(
+            run(CascadePolicy::SameTimeExhaustive)?,
+            vec![
+                (1.0, 1.0, 0.0),
+                (2.0, 0.0, 1.0),
+                (3.0, 0.0, 1.0),
+                (4.0, 0.0, 1.0),
+                (5.0, 0.0, 1.0),
+                (6.0, 0.0, 1.0),
+            ]
+        );
+
+        // One hop at a time: sent, received, acknowledged, then on to the drain at t=2
+        assert_eq!(
+            run(CascadePolicy::SingleHop)?,
+            vec![
+                (1.0, 0.0, 0.0),
+                (1.0, 1.0, 0.0),
+                (1.0, 1.0, 0.0),
+                (2.0, 1.0, 0.0),
+                (2.0, 1.0, 1.0),
+                (2.0, 0.0, 1.0),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_connection_builder() -> Result<(), SimulationError> {
+        setup();
+
+        let connection = Connection::builder()
+            .id("a_to_b")
+            .source("a.out")
+            .target("b")
+            .flow_rate(2.0)
+            .schedule(vec![(5.0, 3.0), (1.0, 4.0)])
+            .build()
+            .unwrap();
+
+        assert_eq!(connection.id, "a_to_b");
+        assert_eq!(connection.source_id(), "a");
+        assert_eq!(connection.source_port(), Some("out"));
+        assert_eq!(connection.target_id(), "b");
+        assert_eq!(connection.target_port(), None);
+        assert_eq!(connection.flow_rate, Some(2.0));
+        assert_eq!(connection.schedule, vec![(1.0, 4.0), (5.0, 3.0)]);
+        assert_eq!(connection.multiplier, None);
+
+        let mut sim = create_stepped_simulation(
+            vec![
+                Process::new(Box::new(Source::new("a"))),
+                Process::new(Box::new(Pool::new("b"))),
+            ],
+            vec![Connection::builder()
+                .id("a_to_b")
+                .source("a.out")
+                .target("b.in")
+                .flow_rate(2.0)
+                .build()
+                .unwrap()],
+        )?;
+        sim.step_n(2)?;
+        match sim.get_process("b").unwrap().get_state() {
+            ProcessState::Pool(state) => assert_eq!(state.resources, 4.0),
+            _ => panic!("Expected pool state"),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_frozen_simulation() -> Result<(), SimulationError> {
+        setup();
+
+        let mut sim = create_stepped_simulation(
+            vec![
+                Process::new(Box::new(Source::new("source"))),
+                Process::new(Box::new(Pool::new("pool"))),
+            ],
+            vec![Connection::builder()
+                .id("source_to_pool")
+                .source("source.out")
+                .target("pool.in")
+                .build()
+                .unwrap()],
+        )?;
+
+        sim.freeze();
+        assert!(sim.is_frozen());
+
+        assert_eq!(
+            sim.add_process(Drain::new("drain")),
+            Err(SimulationError::Frozen)
+        );
+        assert_eq!(
+            sim.update_process("pool", Process::new(Box::new(Pool::new("pool")))),
+            Err(SimulationError::Frozen)
+        );
+        assert!(matches!(
+            sim.remove_process("pool"),
+            Err(SimulationError::Frozen)
+        ));
+        assert_eq!(
+            sim.remove_connection("source_to_pool"),
+            Err(SimulationError::Frozen)
+        );
+        assert_eq!(
+            sim.add_connection(
+                Connection::builder()
+                    .source("pool")
+                    .target("source")
+                    .build()
+                    .unwrap()
+            ),
+            Err(SimulationError::Frozen)
+        );
+        assert_eq!(sim.process_count(), 3);
+        assert_eq!(sim.connections().len(), 1);
+
+        // Running the frozen model is still allowed
+        sim.step_n(2)?;
+        match sim.get_process("pool")?.get_state() {
+            ProcessState::Pool(state) => assert_eq!(state.resources, 2.0),
+            _ => panic!("Expected pool state"),
+        }
+
+        sim.unfreeze();
+        assert!(!sim.is_frozen());
+        sim.add_process(Drain::new("drain"))?;
+        sim.remove_connection("source_to_pool")?;
+        assert_eq!(sim.process_count(), 4);
+        assert!(sim.connections().is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_enabling_nodes_fire_in_id_order() -> Result<(), SimulationError> {
+        setup();
+
+        /// Has room for a single arrival and takes whichever reaches it first
+        #[derive(Clone, Debug, Default)]
+        struct Turnstile {
+            admitted: Option<String>,
+        }
+
+        impl SerializableProcess for Turnstile {}
+
+        impl Processor for Turnstile {
+            fn id(&self) -> &str {
+                "turnstile"
+            }
+
+            fn on_event(
+                &mut self,
+                event: &Event,
+                context: &ProcessContext,
+            ) -> Result<Vec<Event>, SimulationError> {
+                let EventPayload::Resource(amount) = event.payload else {
+                    return Ok(vec![]);
+                };
+                let payload = if self.admitted.is_none() {
+                    self.admitted = Some(event.source_id.clone());
+                    EventPayload::ResourceAccepted(amount)
+                } else {
+                    EventPayload::ResourceRejected(amount)
+                };
+                Ok(vec![Event::new(
+                    self.id(),
+                    &event.source_id,
+                    context.current_time(),
+                    payload,
+                )])
+            }
+
+            fn get_state(&self) -> ProcessState {
+                ProcessState::Custom(serde_json::json!({ "admitted": self.admitted }))
+            }
+
+            fn get_input_ports(&self) -> &[&'static str] {
+                &["in"]
+            }
+
+            fn get_output_ports(&self) -> &[&'static str] {
+                &[]
+            }
+
+            fn reset(&mut self) {
+                self.admitted = None;
+            }
+        }
+
+        // Equal-priority Enabling sources race for the turnstile on the first step
+        let run = || {
+            let source = |id: &str| {
+                Process::new(Box::new(
+                    Source::builder()
+                        .id(id)
+                        .trigger_mode(TriggerMode::Enabling)
+                        .build()
+                        .unwrap(),
+                ))
+            };
+            let connection = |id: &str, source: &str| {
+                Connection::builder()
+                    .id(id)
+                    .source(source)
+                    .target("turnstile.in")
+                    .build()
+                    .unwrap()
+            };
+            let mut sim = create_stepped_simulation(
+                vec![
+                    source("c"),
+                    source("a"),
+                    source("b"),
+                    Process::new(Box::new(Turnstile::default())),
+                ],
+                vec![
+                    connection("c_to_turnstile", "c.out"),
+                    connection("b_to_turnstile", "b.out"),
+                    connection("a_to_turnstile", "a.out"),
+                ],
+            )?;
+            sim.step_n(3)?;
+
+            match sim.get_process("turnstile")?.get_state() {
+                ProcessState::Custom(state) => Ok(state["admitted"].clone()),
+                _ => unreachable!(),
+            }
+        };
+
+        for _ in 0..20 {
+            assert_eq!(run()?, serde_json::json!("a"));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_stepper_dt() -> Result<(), SimulationError> {
+        setup();
+
+        let mut sim = create_stepped_simulation(
+            vec![
+                Process::new(Box::new(Source::new("source"))),
+                Process::new(Box::new(Pool::new("pool"))),
+            ],
+            vec![Connection::builder()
+                .id("source_to_pool")
+                .source("source.out")
+                .target("pool.in")
+                .build()
+                .unwrap()],
+        )?;
+        assert_eq!(sim.stepper_dt(), Some(1.0));
+
+        sim.step_n(3)?;
+        assert_eq!(sim.current_time(), 3.0);
+
+        sim.set_stepper_dt(0.5)?;
+        assert_eq!(sim.stepper_dt(), Some(0.5));
+        assert_eq!(
+            sim.set_stepper_dt(0.0),
+            Err(SimulationError::InvalidDt(0.0))
+        );
+
+        // The step scheduled before the change still lands a whole tick later
+        sim.step()?;
+        assert_eq!(sim.current_time(), 4.0);
+        sim.step()?;
+        assert_eq!(sim.current_time(), 4.5);
+        sim.step()?;
+        assert_eq!(sim.current_time(), 5.0);
+
+        // Without exactly one Stepper there is no dt to report or change
+        let mut unstepped =
+            Simulation::new(vec![Process::new(Box::new(Pool::new("pool")))], vec![])?;
+        assert_eq!(unstepped.stepper_dt(), None);
+        assert_eq!(
+            unstepped.set_stepper_dt(2.0),
+            Err(SimulationError::NoStepper)
+        );
+
+        sim.add_process(Stepper::builder().id("stepper_2").build().unwrap())?;
+        assert_eq!(sim.stepper_dt(), None);
+        assert_eq!(
+            sim.set_stepper_dt(2.0),
+            Err(SimulationError::AmbiguousStepper(vec![
+                "stepper".to_string(),
+                "stepper_2".to_string()
+            ]))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_process_as() -> Result<(), SimulationError> {
+        setup();
+
+        let pool = Pool::builder().id("pool").capacity(5.0).build().unwrap();
+        let mut sim = create_stepped_simulation(
+            vec![
+                Process::new(Box::new(pool)),
+                Process::new(Box::new(Pool::new("unbounded"))),
+            ],
+            vec![],
+        )?;
+
+        let pool = sim.get_process_as::<Pool>("pool").expect("Expected a pool");
+        assert_eq!(pool.id(), "pool");
+        assert_eq!(pool.capacity(), Some(5.0));
+        assert_eq!(
+            sim.get_process_as::<Pool>("unbounded").unwrap().capacity(),
+            None
+        );
+
+        // Wrong type or unknown id
+        assert!(sim.get_process_as::<Source>("pool").is_none());
+        assert!(sim.get_process_as::<Pool>("missing").is_none());
+
+        let stepper = sim
+            .get_process_as_mut::<Stepper>("stepper")
+            .expect("Expected a stepper");
+        stepper.set_dt(2.0)?;
+        assert_eq!(sim.stepper_dt(), Some(2.0));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_drain_round_robin() -> Result<(), SimulationError> {
+        setup();
+
+        // Three scarce pools holding a single resource each feed one drain
+        let pool = |id: &str| {
+            Process::new(Box::new(
+                Pool::builder()
+                    .id(id)
+                    .state(PoolState {
+                        resources: 1.0,
+                        pending_outgoing_resources: 0.0,
+                    })
+                    .build()
+                    .unwrap(),
+            ))
+        };
+        let connection = |source: &str| {
+            Connection::builder()
+                .id(format!("{}_to_drain", source))
+                .source(&format!("{}.out", source))
+                .target("drain.in")
+                .flow_rate(0.5)
+                .build()
+                .unwrap()
+        };
+        let drain = Drain::builder()
+            .id("drain")
+            .round_robin(true)
+            .build()
+            .unwrap();
+        let mut sim = create_stepped_simulation(
+            vec![
+                pool("a"),
+                pool("b"),
+                pool("c"),
+                Process::new(Box::new(drain)),
+            ],
+            vec![connection("a"), connection("b"), connection("c")],
+        )?;
+
+        // Inputs the drain asks in dispatch order on each step
+        let mut pull_orders = Vec::new();
+        for _ in 0..4 {
+            let mut pulls: Vec<Event> = sim
+                .step()?
+                .into_iter()
+                .filter(|event| event.payload == EventPayload::PullRequest)
+                .collect();
+            pulls.sort_by_key(|event| event.sequence_number);
+            pull_orders.push(
+                pulls
+                    .into_iter()
+                    .map(|event| event.target_id)
+                    .collect::<Vec<_>>(),
+            );
+        }
+
+        assert_eq!(
+            pull_orders,
+            vec![
+                vec!["a", "b", "c"],
+                vec!["b", "c", "a"],
+                vec!["c", "a", "b"],
+                vec!["a", "b", "c"],
+            ]
+        );
+        match sim.get_process("drain")?.get_state() {
+            ProcessState::Drain(state) => {
+                assert_eq!(state.resources_consumed, 3.0);
+                assert_eq!(state.rotation, 1);
+            }
+            _ => panic!("Expected drain state"),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_pool_full_signal_opens_gate() -> Result<(), SimulationError> {
+        setup();
+
+        /// Holds back its input until signalled, then pulls through every step
+        #[derive(Clone, Debug, Default)]
+        struct OverflowGate {
+            open: bool,
+            signals: u32,
+            released: f64,
+        }
+
+        impl SerializableProcess for OverflowGate {}
+
+        impl Processor for OverflowGate {
+            fn id(&self) -> &str {
+                "gate"
+            }
+
+            fn on_event(
+                &mut self,
+                event: &Event,
+                context: &ProcessContext,
+            ) -> Result<Vec<Event>, SimulationError> {
+                match &event.payload {
+                    EventPayload::Custom(signal) if signal == "full" => {
+                        self.open = true;
+                        self.signals += 1;
+                        Ok(vec![])
+                    }
+                    EventPayload::Step if self.open => Ok(context
+                        .inputs_for_port(Some("in"))
+                        .map(|conn| {
+                            Event::new(
+                                self.id(),
+                                &conn.source_id,
+                                context.current_time(),
+                                EventPayload::PullRequest,
+                            )
+                        })
+                        .collect()),
+                    EventPayload::Resource(amount) => {
+                        self.released += amount;
+                        Ok(vec![Event::new(
+                            self.id(),
+                            &event.source_id,
+                            context.current_time(),
+                            EventPayload::ResourceAccepted(*amount),
+                        )])
+                    }
+                    _ => Ok(vec![]),
+                }
+            }
+
+            fn get_state(&self) -> ProcessState {
+                ProcessState::Custom(serde_json::json!({
+                    "open": self.open,
+                    "signals": self.signals,
+                    "released": self.released,
+                }))
+            }
+
+            fn get_input_ports(&self) -> &[&'static str] {
+                &["signal", "in"]
+            }
+
+            fn get_output_ports(&self) -> &[&'static str] {
+                &[]
+            }
+
+            fn reset(&mut self) {
+                *self = OverflowGate::default();
+            }
+        }
+
+        let pool = Pool::builder().id("pool").capacity(3.0).build().unwrap();
+        let mut sim = create_stepped_simulation(
+            vec![
+                Process::new(Box::new(Source::new("source"))),
+                Process::new(Box::new(pool)),
+                Process::new(Box::new(OverflowGate::default())),
+            ],
+            vec![
+                Connection::builder()
+                    .id("source_to_pool")
+                    .source("source.out")
+                    .target("pool.in")
+                    .build()
+                    .unwrap(),
+                Connection::builder()
+                    .id("pool_full")
+                    .source("pool.full")
+                    .target("gate.signal")
+                    .build()
+                    .unwrap(),
+                Connection::builder()
+                    .id("pool_to_gate")
+                    .source("pool.out")
+                    .target("gate.in")
+                    .flow_rate(2.0)
+                    .build()
+                    .unwrap(),
+            ],
+        )?;
+        let gate = |sim: &Simulation| match sim.get_process("gate").unwrap().get_state() {
+            ProcessState::Custom(state) => state,
+            _ => unreachable!(),
+        };
+
+        sim.step_n(2)?;
+        assert_eq!(gate(&sim)["open"], false);
+
+        // Reaching capacity on the third step signals the gate once
+        sim.step()?;
+        assert_eq!(gate(&sim)["open"], true);
+        assert_eq!(gate(&sim)["signals"], 1);
+
+        // The open gate drains the pool faster than the source refills it, so no further signal
+        sim.step_n(4)?;
+        assert_eq!(gate(&sim)["signals"], 1);
+        assert_eq!(gate(&sim)["released"], 5.0);
+        match sim.get_process("pool")?.get_state() {
+            ProcessState::Pool(state) => assert_eq!(state.resources, 1.0),
+            _ => panic!("Expected pool state"),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_report_all_missing_processes() -> Result<(), SimulationError> {
+        setup();
+
+        let result = Simulation::new(
+            vec![
+                Process::new(Box::new(Source::new("source"))),
+                Process::new(Box::new(Pool::new("pool"))),
+            ],
+            vec![
+                Connection::builder()
+                    .id("source_to_pol")
+                    .source("source.out")
+                    .target("pol.in")
+                    .build()
+                    .unwrap(),
+                Connection::builder()
+                    .id("pool_to_drain")
+                    .source("pool.out")
+                    .target("drain.in")
+                    .build()
+                    .unwrap(),
+                Connection::builder()
+                    .id("source_to_drain")
+                    .source("source.out")
+                    .target("drain.in")
+                    .build()
+                    .unwrap(),
+            ],
+        );
+
+        let Err(error) = result else {
+            panic!("Expected missing processes to be rejected");
+        };
+        assert_eq!(
+            error,
+            SimulationError::MissingProcesses(vec!["pol".to_string(), "drain".to_string()])
+        );
+        assert_eq!(
+            error.to_string(),
+            "Connections reference undefined processes: pol, drain"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_event_driven_without_stepper() -> Result<(), SimulationError> {
+        setup();
+
+        // No Stepper: the source only acts when triggered
+        let mut sim = Simulation::new(
+            vec![
+                Process::new(Box::new(Source::new("source"))),
+                Process::new(Box::new(Pool::new("pool"))),
+            ],
+            vec![Connection::builder()
+                .id("source_to_pool")
+                .source("source.out")
+                .target("pool.in")
+                .flow_rate(3.0)
+                .build()
+                .unwrap()],
+        )?;
+        for time in [2.0, 7.5] {
+            sim.schedule_event(Event::new(
+                "simulation",
+                "source",
+                time,
+                EventPayload::Trigger,
+            ))?;
+        }
+
+        sim.step()?;
+        assert_eq!(sim.current_time(), 2.0);
+
+        // Runs out of events well before the horizon instead of waiting on ticks
+        sim.step_until(100.0)?;
+        assert_eq!(sim.current_time(), 7.5);
+        match sim.get_process("pool")?.get_state() {
+            ProcessState::Pool(state) => assert_eq!(state.resources, 6.0),
+            _ => panic!("Expected pool state"),
+        }
+
+        assert!(sim.step()?.is_empty());
+        assert_eq!(sim.current_time(), 7.5);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_event_counts() -> Result<(), SimulationError> {
+        setup();
+
+        let mut sim = create_stepped_simulation(
+            vec![
+                Process::new(Box::new(Source::new("source"))),
+                Process::new(Box::new(Pool::new("pool"))),
+            ],
+            vec![Connection::builder()
+                .id("source_to_pool")
+                .source("source.out")
+                .target("pool.in")
+                .build()
+                .unwrap()],
+        )?;
+        sim.step_n(3)?;
+
+        // Each receives the start broadcast and three steps; the source also gets three
+        // acknowledgements, the pool three transfers
+        let counts = sim.process_event_counts();
+        assert_eq!(counts["source"], (7, 3));
+        assert_eq!(counts["pool"], (7, 3));
+        assert_eq!(counts["stepper"], (4, 4));
+
+        sim.reset()?;
+        assert!(sim.process_event_counts().is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_start_time_offset() -> Result<(), SimulationError> {
+        setup();
+
+        let mut sim = create_stepped_simulation(
+            vec![
+                Process::new(Box::new(Source::new("source"))),
+                Process::new(Box::new(Pool::new("pool"))),
+            ],
+            vec![Connection::builder()
+                .id("source_to_pool")
+                .source("source.out")
+                .target("pool.in")
+                .build()
+                .unwrap()],
+        )?;
+        sim.set_start_time(100.0)?;
+        assert_eq!(sim.current_time(), 100.0);
+
+        sim.step()?;
+        assert_eq!(sim.current_time(), 101.0);
+        sim.step()?;
+        assert_eq!(sim.current_time(), 102.0);
+        assert!(sim.set_start_time(0.0).is_err());
+
+        // A reset goes back to the configured start, not zero
+        sim.reset()?;
+        assert_eq!(sim.current_time(), 100.0);
+        sim.step()?;
+        assert_eq!(sim.current_time(), 101.0);
+        match sim.get_process("pool")?.get_state() {
+            ProcessState::Pool(state) => assert_eq!(state.resources, 1.0),
+            _ => panic!("Expected pool state"),
+        }
+
+        sim.reset()?;
+        assert_eq!(
+            sim.set_start_time(f64::NAN).map_err(|e| e.to_string()),
+            Err("Invalid start time: NaN".to_string())
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_and_validate() -> Result<(), SimulationError> {
+        setup();
+
+        let model = r#"{
+            "processes": [
+                { "type": "Stepper", "id": "stepper" },
+                { "type": "Source", "id": "source" },
+                { "type": "Pool", "id": "pool" },
+                { "type": "Drain", "id": "drain", "triggerMode": "Passive" }
+            ],
+            "connections": [
+                { "sourceID": "source", "sourcePort": "out", "targetID": "pool", "targetPort": "in" },
+                { "sourceID": "source", "sourcePort": "out", "targetID": "pool", "targetPort": "in", "flowRate": 2.0 },
+                { "id": "drain_link", "sourceID": "pool", "sourcePort": "out", "targetID": "drain", "targetPort": "in" }
+            ]
+        }"#;
+
+        let mut sim = Simulation::load_and_validate(model).expect("Expected a valid model");
+        let mut connections: Vec<(String, Option<f64>)> = sim
+            .connections()
+            .into_iter()
+            .map(|conn| (conn.id.clone(), conn.flow_rate))
+            .collect();
+        connections.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            connections,
+            vec![
+                ("conn_source_pool".to_string(), Some(1.0)),
+                ("conn_source_pool_2".to_string(), Some(2.0)),
+                ("drain_link".to_string(), Some(1.0)),
+            ]
+        );
+
+        sim.step_n(2)?;
+        match sim.get_process("pool")?.get_state() {
+            ProcessState::Pool(state) => assert_eq!(state.resources, 6.0),
+            _ => panic!("Expected pool state"),
+        }
+
+        // Every problem is reported, not only the first
+        let broken = r#"{
+            "processes": [
+                { "type": "Source", "id": "source" },
+                { "type": "Pool", "id": "pool" },
+                { "type": "Pool", "id": "pool" }
+            ],
+            "connections": [
+                { "sourceID": "source", "sourcePort": "side", "targetID": "pool", "targetPort": "in" },
+                { "sourceID": "pool", "sourcePort": "out", "targetID": "pool", "targetPort": "top" }
+            ]
+        }"#;
+        let Err(problems) = Simulation::load_and_validate(broken) else {
+            panic!("Expected the broken model to be rejected");
+        };
+        assert_eq!(problems.len(), 3);
+        assert_eq!(
+            problems[0],
+            SimulationError::DuplicateProcess("pool".to_string())
+        );
+        assert!(matches!(
+            &problems[1],
+            SimulationError::InvalidPort { port, .. } if port == "side"
+        ));
+        assert!(matches!(
+            &problems[2],
+            SimulationError::InvalidPort { port, .. } if port == "top"
+        ));
+
+        assert!(Simulation::load_and_validate("{ not json").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unsupported_actions_rejected() -> Result<(), SimulationError> {
+        setup();
+
+        let lottery_source = Source::builder()
+            .id("source")
+            .action(Action::PushLottery)
+            .build()
+            .unwrap();
+        let mut sim = create_stepped_simulation(vec![], vec![])?;
+        let error = sim.add_process(lottery_source).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Source process 'source' can't perform action PushLottery"
+        );
+        assert_eq!(error.process_id(), Some("source"));
+
+        let pushing_drain = Drain::builder()
+            .id("drain")
+            .action(Action::PushAny)
+            .build()
+            .unwrap();
+        assert!(matches!(
+            sim.add_process(pushing_drain),
+            Err(SimulationError::UnsupportedAction { ref action, .. }) if action == "PushAny"
+        ));
+
+        // Replacing a process is checked the same way
+        sim.add_process(Source::new("source"))?;
+        let lottery_source = Source::builder()
+            .id("source")
+            .action(Action::PushLottery)
+            .build()
+            .unwrap();
+        assert!(sim
+            .update_process("source", Process::new(Box::new(lottery_source)))
+            .is_err());
+
+        // Untrusted models are turned away instead of failing on the first step
+        let model = r#"{
+            "processes": [
+                { "type": "Stepper", "id": "stepper" },
+                { "type": "Source", "id": "source", "action": "PushLottery" }
+            ],
+            "connections": []
+        }"#;
+        let Err(problems) = Simulation::load_and_validate(model) else {
+            panic!("Expected the lottery source to be rejected");
+        };
+        assert!(matches!(
+            &problems[..],
+            [SimulationError::UnsupportedAction { process, .. }] if process == "source"
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_watch_connection() -> Result<(), SimulationError> {
+        setup();
+
+        let connection = |id: &str, target: &str| {
+            Connection::builder()
+                .id(id)
+                .source("source.out")
+                .target(target)
+                .build()
+                .unwrap()
+        };
+        let mut sim = create_stepped_simulation(
+            vec![
+                Process::new(Box::new(Source::new("source"))),
+                Process::new(Box::new(Pool::new("watched"))),
+                Process::new(Box::new(Pool::new("ignored"))),
+            ],
+            vec![
+                connection("to_watched", "watched.in"),
+                connection("to_ignored", "ignored.in"),
+            ],
+        )?;
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let log = seen.clone();
+        sim.watch_connection(
+            "to_watched",
+            Box::new(move |event: &Event| {
+                log.lock().unwrap().push((
+                    event.source_id.clone(),
+                    event.target_id.clone(),
+                    event.payload.clone(),
+                ))
+            }),
+        )?;
+        assert!(sim
+            .watch_connection("missing", Box::new(|_: &Event| {}))
+            .is_err());
+
+        sim.step_n(2)?;
+
+        // Each transfer along the watched link and its acknowledgement, nothing else
+        let transfer = (
+            "source".to_string(),
+            "watched".to_string(),
+            EventPayload::Resource(1.0),
+        );
+        let accepted = (
+            "watched".to_string(),
+            "source".to_string(),
+            EventPayload::ResourceAccepted(1.0),
+        );
+        assert_eq!(
+            *seen.lock().unwrap(),
+            vec![transfer.clone(), accepted.clone(), transfer, accepted]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_step_n_discard() -> Result<(), SimulationError> {
+        setup();
+
+        let build = || {
+            create_stepped_simulation(
+                vec![
+                    Process::new(Box::new(Source::new("source"))),
+                    Process::new(Box::new(Pool::new("pool"))),
+                    Process::new(Box::new(Drain::new("drain"))),
+                ],
+                vec![
+                    Connection::builder()
+                        .id("source_to_pool")
+                        .source("source.out")
+                        .target("pool.in")
+                        .flow_rate(2.0)
+                        .build()
+                        .unwrap(),
+                    Connection::builder()
+                        .id("pool_to_drain")
+                        .source("pool.out")
+                        .target("drain.in")
+                        .build()
+                        .unwrap(),
+                ],
+            )
+        };
+
+        let mut kept = build()?;
+        let mut discarded = build()?;
+        let events = kept.step_n(50)?;
+        let count = discarded.step_n_discard(50)?;
+
+        assert_eq!(count, events.len());
+        assert_eq!(discarded.current_step(), kept.current_step());
+        assert_eq!(discarded.current_time(), kept.current_time());
+        assert_eq!(
+            discarded.get_simulation_state().stable_hash(),
+            kept.get_simulation_state().stable_hash()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_fn_process_doubles_resources() -> Result<(), SimulationError> {
+        setup();
+
+        let mut sim = create_stepped_simulation(
+            vec![
+                Process::new(Box::new(Source::new("source"))),
+                Process::new(Box::new(Pool::new("pool"))),
+            ],
+            vec![],
+        )?;
+
+        // Accepts whatever arrives and passes twice as much on
+        sim.add_fn_process("doubler", |events, ctx| {
+            let mut emitted = Vec::new();
+            for event in events {
+                let EventPayload::Resource(amount) = event.payload else {
+                    continue;
+                };
+                emitted.push(Event::new(
+                    "doubler",
+                    &event.source_id,
+                    ctx.current_time(),
+                    EventPayload::ResourceAccepted(amount),
+                ));
+                for conn in ctx.outputs_for_port(Some("out")) {
+                    emitted.push(Event::new(
+                        "doubler",
+                        &conn.target_id,
+                        ctx.current_time(),
+                        EventPayload::Resource(amount * 2.0),
+                    ));
+                }
+            }
+            Ok(emitted)
+        })?;
+        sim.add_connections(vec![
+            Connection::builder()
+                .id("source_to_doubler")
+                .source("source.out")
+                .target("doubler.in")
+                .build()
+                .unwrap(),
+            Connection::builder()
+                .id("doubler_to_pool")
+                .source("doubler.out")
+                .target("pool.in")
+                .build()
+                .unwrap(),
+        ])?;
+
+        sim.step_n(3)?;
+        match sim.get_process("pool")?.get_state() {
+            ProcessState::Pool(state) => assert_eq!(state.resources, 6.0),
+            _ => panic!("Expected pool state"),
+        }
+        assert_eq!(sim.get_process("doubler")?.get_type(), "FnProcess");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_compact_recording() -> Result<(), SimulationError> {
+        setup();
+
+        let mut sim = create_stepped_simulation(
+            vec![
+                Process::new(Box::new(Source::new("source"))),
+                Process::new(Box::new(Pool::new("pool"))),
+            ],
+            vec![Connection::builder()
+                .id("source_to_pool")
+                .source("source.out")
+                .target("pool.in")
+                .build()
+                .unwrap()],
+        )?;
+        sim.enable_recording();
+        sim.step_n(20)?;
+
+        // Read what is needed from the recording first
+        let transfers = sim
+            .recorded_events()
+            .iter()
+            .filter(|event| matches!(event.payload, EventPayload::Resource(_)))
+            .count();
+        assert_eq!(transfers, 20);
+        assert_eq!(sim.recorded_states().len(), 21);
+        let live_state = sim.get_simulation_state();
+
+        sim.compact();
+        assert!(sim.recorded_events().is_empty());
+        assert!(sim.recorded_states().is_empty());
+        assert!(sim.is_recording());
+
+        // The live model carries on from where it was
+        assert_eq!(
+            sim.get_simulation_state().stable_hash(),
+            live_state.stable_hash()
+        );
+        assert_eq!(sim.connection_stats("source_to_pool").transferred, 20.0);
+        sim.step()?;
+        match sim.get_process("pool")?.get_state() {
+            ProcessState::Pool(state) => assert_eq!(state.resources, 21.0),
+            _ => panic!("Expected pool state"),
+        }
+        assert_eq!(sim.recorded_states().len(), 1);
+
+        sim.clear_recording();
+        assert!(sim.recorded_events().is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_step_empty_simulation() -> Result<(), SimulationError> {
+        setup();
+
+        let mut sim = Simulation::new(vec![], vec![])?;
+        assert_eq!(sim.step(), Err(SimulationError::EmptySimulation));
+        assert_eq!(sim.next(), Err(SimulationError::EmptySimulation));
+        assert!(!sim.has_started());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_step_completed_simulation() -> Result<(), SimulationError> {
+        setup();
+
+        // Without a Stepper the run completes once the one scheduled trigger is handled
+        let mut sim = Simulation::new(
+            vec![
+                Process::new(Box::new(Source::new("source"))),
+                Process::new(Box::new(Pool::new("pool"))),
+            ],
+            vec![Connection::builder()
+                .id("source_to_pool")
+                .source("source.out")
+                .target("pool.in")
+                .build()
+                .unwrap()],
+        )?;
+        sim.schedule_event(Event::new(
+            "simulation",
+            "source",
+            1.0,
+            EventPayload::Trigger,
+        ))?;
+        assert!(!sim.is_completed());
+
+        sim.step()?;
+        assert!(sim.is_completed());
+        let counts = sim.process_event_counts();
+
+        // Further steps do nothing, not even repeat the end broadcast
+        assert_eq!(sim.step()?, vec![]);
+        assert_eq!(sim.next()?, vec![]);
+        assert!(sim.is_completed());
+        assert_eq!(sim.process_event_counts(), counts);
+        assert_eq!(sim.current_time(), 1.0);
+
+        // New work resumes the run
+        sim.schedule_event(Event::new(
+            "simulation",
+            "source",
+            3.0,
+            EventPayload::Trigger,
+        ))?;
+        assert!(!sim.is_completed());
+        sim.step()?;
+        assert_eq!(sim.current_time(), 3.0);
+        match sim.get_process("pool")?.get_state() {
+            ProcessState::Pool(state) => assert_eq!(state.resources, 2.0),
+            _ => panic!("Expected pool state"),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_priority_class_served_first() -> Result<(), SimulationError> {
+        setup();
+
+        // The pool can only satisfy one of the two drains per step
+        let pool = Pool::builder()
+            .id("pool")
+            .state(PoolState {
+                resources: 3.0,
+                pending_outgoing_resources: 0.0,
+            })
+            .build()
+            .unwrap();
+        // The low class drain is connected first and has the higher priority
+        let low = Drain::builder().id("low").priority(5).build().unwrap();
+        let high = Drain::builder()
+            .id("high")
+            .priority_class(1)
+            .build()
+            .unwrap();
+        let connection = |target: &str| {
+            Connection::builder()
+                .id(format!("pool_to_{}", target))
+                .source("pool.out")
+                .target(&format!("{}.in", target))
+                .flow_rate(3.0)
+                .build()
+                .unwrap()
+        };
+        let mut sim = create_stepped_simulation(
+            vec![
+                Process::new(Box::new(pool)),
+                Process::new(Box::new(low)),
+                Process::new(Box::new(high)),
+            ],
+            vec![connection("low"), connection("high")],
+        )?;
+        sim.step_n(2)?;
+
+        let consumed = |id: &str| match sim.get_process_as::<Drain>(id).unwrap().get_state() {
+            ProcessState::Drain(state) => state.resources_consumed,
+            _ => unreachable!(),
+        };
+        assert_eq!(consumed("high"), 3.0);
+        assert_eq!(consumed("low"), 0.0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_converter_recipe() -> Result<(), SimulationError> {
+        setup();
+
+        let pool = |id: &str, resources: f64| {
+            Process::new(Box::new(
+                Pool::builder()
+                    .id(id)
+                    .state(PoolState {
+                        resources,
+                        pending_outgoing_resources: 0.0,
+                    })
+                    .build()
+                    .unwrap(),
+            ))
+        };
+        // Two iron and one coal make one steel
+        let smelter = Converter::new(
+            "smelter",
+            Recipe::new(&[("iron", 2.0), ("coal", 1.0)], &[("steel", 1.0)]),
+        );
+        let connection = |source: &str, target: &str| {
+            Connection::builder()
+                .id(format!("{}_{}", source, target))
+                .source(source)
+                .target(target)
+                .build()
+                .unwrap()
+        };
+        let mut sim = create_stepped_simulation(
+            vec![
+                pool("iron", 5.0),
+                pool("coal", 2.0),
+                Process::new(Box::new(smelter)),
+                pool("steel", 0.0),
+            ],
+            vec![
+                connection("iron.out", "smelter.iron"),
+                connection("coal.out", "smelter.coal"),
+                connection("smelter.steel", "steel.in"),
+            ],
+        )?;
+
+        let held = |sim: &Simulation, id: &str| match sim.get_process(id).unwrap().get_state() {
+            ProcessState::Pool(state) => state.resources,
+            ProcessState::Converter(state) => state.current_resources(),
+            _ => unreachable!(),
+        };
+
+        // A partial batch is held until the recipe is complete
+        sim.step()?;
+        assert_eq!(held(&sim, "steel"), 0.0);
+        assert_eq!(held(&sim, "smelter"), 2.0);
+
+        sim.step_n(4)?;
+        let ProcessState::Converter(state) = sim.get_process("smelter").unwrap().get_state() else {
+            unreachable!()
+        };
+        assert_eq!(held(&sim, "steel"), 2.0);
+        assert_eq!(state.batches, 2);
+        assert_eq!(state.resources_consumed, 6.0);
+        assert_eq!(state.resources_produced, 2.0);
+        // Iron beyond the two batches the coal allows stays buffered
+        assert_eq!(state.buffered["iron"], 1.0);
+        assert_eq!(state.buffered["coal"], 0.0);
+        assert!(sim.summary().conserved);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_delay_contents() -> Result<(), SimulationError> {
+        setup();
+
+        // A source pushing one resource, then two per step from time 2, into a delay of three
+        let source = Source::new("source");
+        let delay = Delay::new("delay");
+        let pool = Pool::new("pool");
+        let mut sim = create_stepped_simulation(
+            vec![
+                Process::new(Box::new(source)),
+                Process::new(Box::new(delay)),
+                Process::new(Box::new(pool)),
+            ],
+            vec![
+                Connection::builder()
+                    .id("source_to_delay")
+                    .source("source.out")
+                    .target("delay.in")
+                    .flow_rate(1.0)
+                    .schedule(vec![(2.0, 2.0)])
+                    .build()
+                    .unwrap(),
+                Connection::builder()
+                    .id("delay_to_pool")
+                    .source("delay.out")
+                    .target("pool.in")
+                    .flow_rate(3.0)
+                    .build()
+                    .unwrap(),
+            ],
+        )?;
+
+        sim.step_n(3)?;
+        assert_eq!(
+            sim.delay_contents("delay"),
+            vec![(1.0, 4.0), (2.0, 5.0), (2.0, 6.0)]
+        );
+
+        // Released transfers leave the schedule once the pool accepts them
+        sim.step_n(2)?;
+        assert_eq!(
+            sim.delay_contents("delay"),
+            vec![(2.0, 6.0), (2.0, 7.0), (2.0, 8.0)]
+        );
+        assert!(sim.delay_contents("pool").is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_gate_splits_by_weight() -> Result<(), SimulationError> {
+        setup();
+
+        let source = Source::new("source");
+        let gate = Gate::new("gate");
+        let connection = |id: &str, source: &str, target: &str, flow_rate: f64| {
+            Connection::builder()
+                .id(id)
+                .source(source)
+                .target(target)
+                .flow_rate(flow_rate)
+                .build()
+                .unwrap()
+        };
+        let mut sim = create_stepped_simulation(
+            vec![
+                Process::new(Box::new(source)),
+                Process::new(Box::new(gate)),
+                Process::new(Box::new(Pool::new("a"))),
+                Process::new(Box::new(Pool::new("b"))),
+                Process::new(Box::new(Pool::new("c"))),
+            ],
+            vec![
+                connection("source_to_gate", "source.out", "gate.in", 8.0),
+                // Weights of 1, 2 and 1
+                connection("gate_to_a", "gate.out", "a.in", 1.0),
+                connection("gate_to_b", "gate.out", "b.in", 2.0),
+                connection("gate_to_c", "gate.out", "c.in", 1.0),
+            ],
+        )?;
+
+        let resources = |sim: &Simulation, id: &str| match sim.get_process(id).unwrap().get_state()
+        {
+            ProcessState::Pool(state) => state.resources,
+            _ => unreachable!(),
+        };
+
+        sim.step()?;
+        assert_eq!(resources(&sim, "a"), 2.0);
+        assert_eq!(resources(&sim, "b"), 4.0);
+        assert_eq!(resources(&sim, "c"), 2.0);
+
+        // Shares of an amount that doesn't divide evenly still add up to all of it
+        sim.update_connection(
+            "source_to_gate",
+            connection("source_to_gate", "source.out", "gate.in", 1.1),
+        )?;
+        sim.step()?;
+        let total = resources(&sim, "a") + resources(&sim, "b") + resources(&sim, "c");
+        assert!((total - 9.1).abs() < 1e-12);
+        let ProcessState::Gate(gate) = sim.get_process("gate")?.get_state() else {
+            unreachable!()
+        };
+        assert!(gate.current_resources().abs() < 1e-12);
+        assert!(sim.summary().conserved);
+
+        // The gate round-trips through serialization
+        let restored: Simulation =
+            serde_json::from_str(&serde_json::to_string(&sim).unwrap()).unwrap();
+        assert_eq!(restored.get_process("gate")?.get_type(), "Gate");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_apply_model_ops() -> Result<(), SimulationError> {
+        setup();
+
+        let mut sim = create_stepped_simulation(vec![], vec![])?;
+        let notified = Arc::new(Mutex::new(Vec::new()));
+        let log = notified.clone();
+        sim.on_lifecycle(move |event| log.lock().unwrap().push(event.clone()));
+
+        let ops = vec![
+            ModelOp::AddProcess {
+                process: Process::new(Box::new(Source::new("source"))),
+            },
+            ModelOp::AddProcess {
+                process: Process::new(Box::new(Pool::new("pool"))),
+            },
+            ModelOp::AddConnection {
+                connection: Connection::builder()
+                    .id("source_to_pool")
+                    .source("source.out")
+                    .target("pool.in")
+                    .build()
+                    .unwrap(),
+            },
+        ];
+        // Ops serialize, so a batch can be logged and replayed
+        let ops: Vec<ModelOp> =
+            serde_json::from_str(&serde_json::to_string(&ops).unwrap()).unwrap();
+        sim.apply(ops)?;
+        assert!(sim.get_process("source").is_ok());
+        assert!(sim.get_process("pool").is_ok());
+        assert_eq!(sim.connections().len(), 1);
+        assert_eq!(notified.lock().unwrap().len(), 3);
+
+        // The second op fails, so the drain added before it is rolled back too
+        let result = sim.apply(vec![
+            ModelOp::AddProcess {
+                process: Process::new(Box::new(Drain::new("drain"))),
+            },
+            ModelOp::AddConnection {
+                connection: Connection::builder()
+                    .id("pool_to_ghost")
+                    .source("pool.out")
+                    .target("ghost.in")
+                    .build()
+                    .unwrap(),
+            },
+        ]);
+        assert!(matches!(result, Err(SimulationError::ProcessNotFound(id)) if id == "ghost"));
+        assert!(sim.get_process("drain").is_err());
+        assert_eq!(sim.connections().len(), 1);
+        assert_eq!(notified.lock().unwrap().len(), 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sample_every() -> Result<(), SimulationError> {
+        setup();
+
+        let mut sim = create_stepped_simulation(
+            vec![
+                Process::new(Box::new(Source::new("source"))),
+                Process::new(Box::new(Pool::new("pool"))),
+            ],
+            vec![Connection::builder()
+                .id("source_to_pool")
+                .source("source.out")
+                .target("pool.in")
+                .build()
+                .unwrap()],
+        )?;
+        sim.sample_every(10);
+        sim.enable_recording();
+        sim.step_n(100)?;
+
+        // The initial state, then one every tenth step
+        let steps: Vec<u64> = sim
+            .recorded_states()
+            .iter()
+            .map(|state| state.step)
+            .collect();
+        assert_eq!(steps.len(), 11);
+        assert_eq!(steps, (0..=100).step_by(10).collect::<Vec<u64>>());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_schedule_trigger() -> Result<(), SimulationError> {
+        setup();
+
+        let source = Source::builder()
+            .id("source")
+            .trigger_mode(TriggerMode::Passive)
+            .build()
+            .unwrap();
+        let mut sim = create_stepped_simulation(
+            vec![
+                Process::new(Box::new(source)),
+                Process::new(Box::new(Pool::new("pool"))),
+            ],
+            vec![Connection::builder()
+                .id("source_to_pool")
+                .source("source.out")
+                .target("pool.in")
+                .build()
+                .unwrap()],
+        )?;
+        sim.schedule_trigger("source", 3.0)?;
+        assert!(matches!(
+            sim.schedule_trigger("missing", 3.0),
+            Err(SimulationError::ProcessNotFound(_))
+        ));
+
+        let pool = |sim: &Simulation| match sim.get_process("pool").unwrap().get_state() {
+            ProcessState::Pool(state) => state.resources,
+            _ => unreachable!(),
+        };
+        let mut produced_at = Vec::new();
+        for _ in 0..5 {
+            let events = sim.step()?;
+            if events
+                .iter()
+                .any(|event| event.source_id == "source" && event.target_id == "pool")
+            {
+                produced_at.push(sim.current_time());
+            }
+        }
+
+        assert_eq!(produced_at, vec![3.0]);
+        assert_eq!(pool(&sim), 1.0);
+        assert!(sim.schedule_trigger("source", 1.0).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_reset_restores_initial_pool_resources() -> Result<(), SimulationError> {
+        setup();
+
+        let pool = Pool::builder()
+            .id("pool")
+            .state(PoolState {
+                resources: 5.0,
+                pending_outgoing_resources: 0.0,
+            })
+            .build()
+            .unwrap();
+        let mut sim = create_stepped_simulation(
+            vec![
+                Process::new(Box::new(pool)),
+                Process::new(Box::new(Drain::new("drain"))),
+            ],
+            vec![Connection::builder()
+                .id("pool_to_drain")
+                .source("pool.out")
+                .target("drain.in")
+                .build()
+                .unwrap()],
+        )?;
+
+        let resources = |sim: &Simulation| match sim.get_process("pool").unwrap().get_state() {
+            ProcessState::Pool(state) => state.resources,
+            _ => unreachable!(),
+        };
+        sim.step_n(3)?;
+        assert_eq!(resources(&sim), 2.0);
+
+        sim.reset()?;
+        assert_eq!(resources(&sim), 5.0);
+
+        // The restored stock runs down the same way again
+        sim.step_n(3)?;
+        assert_eq!(resources(&sim), 2.0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_log_capture() -> Result<(), SimulationError> {
+        setup();
+
+        // A pull along a connection without a flow rate is warned about
+        let pool = Pool::builder()
+            .id("pool")
+            .state(PoolState {
+                resources: 5.0,
+                pending_outgoing_resources: 0.0,
+            })
+            .build()
+            .unwrap();
+        let mut sim = create_stepped_simulation(
+            vec![
+                Process::new(Box::new(pool)),
+                Process::new(Box::new(Drain::new("drain"))),
+            ],
+            vec![Connection::builder()
+                .id("pool_to_drain")
+                .source("pool.out")
+                .target("drain.in")
+                .build()
+                .unwrap()],
+        )?;
+        sim.step()?;
+        assert!(sim.logs().is_empty());
+
+        sim.enable_log_capture(tracing::Level::WARN);
+        sim.step()?;
+        let logs = sim.logs();
+        assert_eq!(logs.len(), 1);
+        assert_eq!(logs[0].level, "WARN");
+        assert_eq!(
+            logs[0].message,
+            "Pool 'pool' has no flow_rate set for connection to 'drain'. Defaulting to flow rate of 1.0."
+        );
+
+        sim.clear_logs();
+        sim.disable_log_capture();
+        sim.step()?;
+        assert!(sim.logs().is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_drain_pull_all() -> Result<(), SimulationError> {
+        setup();
+
+        let pool = |id: &str, resources: f64| {
+            Process::new(Box::new(
+                Pool::builder()
+                    .id(id)
+                    .state(PoolState {
+                        resources,
+                        pending_outgoing_resources: 0.0,
+                    })
+                    .build()
+                    .unwrap(),
+            ))
+        };
+        let drain = Drain::builder()
+            .id("drain")
+            .action(Action::PullAll)
+            .build()
+            .unwrap();
+        let connection = |source: &str| {
+            Connection::builder()
+                .id(format!("{}_to_drain", source))
+                .source(&format!("{}.out", source))
+                .target("drain.in")
+                .flow_rate(2.0)
+                .build()
+                .unwrap()
+        };
+        let mut sim = create_stepped_simulation(
+            vec![
+                pool("a", 10.0),
+                pool("b", 3.0),
+                Process::new(Box::new(drain)),
+            ],
+            vec![connection("a"), connection("b")],
+        )?;
+
+        let resources = |sim: &Simulation, id: &str| match sim.get_process(id).unwrap().get_state()
+        {
+            ProcessState::Pool(state) => state.resources,
+            ProcessState::Drain(state) => state.resources_consumed,
+            _ => unreachable!(),
+        };
+
+        // Each upstream pool is asked for its full amount
+        let events = sim.step()?;
+        let mut requested: Vec<&str> = events
+            .iter()
+            .filter(|event| event.payload == EventPayload::PullAllRequest)
+            .map(|event| event.target_id.as_str())
+            .collect();
+        requested.sort();
+        assert_eq!(requested, vec!["a", "b"]);
+        assert_eq!(resources(&sim, "drain"), 4.0);
+
+        // Pool b can no longer cover its share, so nothing is taken from a either
+        sim.step()?;
+        assert_eq!(resources(&sim, "drain"), 4.0);
+        assert_eq!(resources(&sim, "a"), 8.0);
+        assert_eq!(resources(&sim, "b"), 1.0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_pull_any_within_capacity() -> Result<(), SimulationError> {
+        setup();
+
+        let supplier = |id: &str| {
+            Pool::builder()
+                .id(id)
+                .state(PoolState {
+                    resources: 2.0,
+                    pending_outgoing_resources: 0.0,
+                })
+                .build()
+                .unwrap()
+        };
+        let collector = Pool::builder()
+            .id("collector")
+            .trigger_mode(TriggerMode::Automatic)
+            .action(Action::PullAny)
+            .pull_policy(PullPolicy::WithinCapacity)
+            .capacity(3.0)
+            .build()
+            .unwrap();
+        let connection = |id: &str, source: &str| {
+            Connection::new(
+                id.to_string(),
+                source.to_string(),
+                Some("out".to_string()),
+                "collector".to_string(),
+                Some("in".to_string()),
+                Some(2.0),
+            )
+        };
+        let mut sim = create_stepped_simulation(
+            vec![
+                Process::new(Box::new(supplier("a"))),
+                Process::new(Box::new(supplier("b"))),
+                Process::new(Box::new(collector)),
+            ],
+            vec![
+                connection("a_to_collector", "a"),
+                connection("b_to_collector", "b"),
+            ],
+        )?;
+        sim.enable_recording();
+        sim.step()?;
+
+        let resources = |id: &str| match sim.get_process_state(id) {
+            Ok(ProcessState::Pool(state)) => state.resources,
+            _ => unreachable!(),
+        };
+        assert_eq!(resources("collector"), 3.0);
+        assert_eq!(resources("a") + resources("b"), 1.0);
+
+        // The second input is only asked for the room the first left, so nothing bounces
+        assert!(!sim
+            .recorded_events()
+            .iter()
+            .any(|event| matches!(event.payload, EventPayload::ResourceRejected(_))));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_pool_pull_all() -> Result<(), SimulationError> {
+        setup();
+
+        let supplier = Pool::builder()
+            .id("supplier")
+            .state(PoolState {
+                resources: 5.0,
+                pending_outgoing_resources: 0.0,
+            })
+            .build()
+            .unwrap();
+        let puller = Pool::builder()
+            .id("puller")
+            .trigger_mode(TriggerMode::Automatic)
+            .action(Action::PullAll)
+            .build()
+            .unwrap();
+        let mut sim = create_stepped_simulation(
+            vec![
+                Process::new(Box::new(supplier)),
+                Process::new(Box::new(puller)),
+            ],
+            vec![Connection::builder()
+                .id("supplier_to_puller")
+                .source("supplier.out")
+                .target("puller.in")
+                .flow_rate(2.0)
+                .build()
+                .unwrap()],
+        )?;
+
+        let resources = |sim: &Simulation, id: &str| match sim.get_process_state(id) {
+            Ok(ProcessState::Pool(state)) => state.resources,
+            _ => unreachable!(),
+        };
+
+        // The request goes upstream and the full flow rate comes back
+        let events = sim.step()?;
+        assert!(events
+            .iter()
+            .any(|event| event.payload == EventPayload::PullAllRequest
+                && event.source_id == "puller"
+                && event.target_id == "supplier"));
+        assert_eq!(resources(&sim, "puller"), 2.0);
+
+        sim.step()?;
+        assert_eq!(resources(&sim, "puller"), 4.0);
+        assert_eq!(resources(&sim, "supplier"), 1.0);
+
+        // One unit left is short of the flow rate, so none of it moves
+        sim.step()?;
+        assert_eq!(resources(&sim, "puller"), 4.0);
+        assert_eq!(resources(&sim, "supplier"), 1.0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_replay() -> Result<(), SimulationError> {
+        setup();
+
+        let model = || {
+            create_stepped_simulation(
+                vec![
+                    Process::new(Box::new(Source::new("source"))),
+                    Process::new(Box::new(Pool::new("pool"))),
+                ],
+                vec![Connection::builder()
+                    .id("source_to_pool")
+                    .source("source.out")
+                    .target("pool.in")
+                    .flow_rate(2.0)
+                    .build()
+                    .unwrap()],
+            )
+        };
+
+        let mut sim = model()?;
+        sim.enable_recording();
+        sim.step_n(5)?;
+        let recorded = sim.recorded_events().to_vec();
+        assert!(!recorded.is_empty());
+
+        // A fresh run of the same model reproduces the stream, whatever state sim is in now
+        assert_eq!(sim.verify_replay(&recorded), Ok(()));
+        assert_eq!(model()?.verify_replay(&recorded), Ok(()));
+
+        // A different flow rate shows up as the first transfer that differs
+        let mut changed = model()?;
+        changed.update_connection(
+            "source_to_pool",
+            Connection::builder()
+                .id("source_to_pool")
+                .source("source.out")
+                .target("pool.in")
+                .flow_rate(3.0)
+                .build()
+                .unwrap(),
+        )?;
+        match changed.verify_replay(&recorded) {
+            Err(ReplayMismatch::Diverged {
+                expected, actual, ..
+            }) => {
+                assert_eq!(expected.payload, EventPayload::Resource(2.0));
+                assert_eq!(actual.payload, EventPayload::Resource(3.0));
+            }
+            other => panic!("expected a divergence, got {:?}", other),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_connection_flow_rate() -> Result<(), SimulationError> {
+        setup();
+
+        let mut sim = create_stepped_simulation(
+            vec![
+                Process::new(Box::new(Source::new("source"))),
+                Process::new(Box::new(Pool::new("pool"))),
+            ],
+            vec![Connection::builder()
+                .id("source_to_pool")
+                .source("source.out")
+                .target("pool.in")
+                .flow_rate(1.0)
+                .build()
+                .unwrap()],
+        )?;
+        let resources = |sim: &Simulation| match sim.get_process_state("pool") {
+            Ok(ProcessState::Pool(state)) => state.resources,
+            _ => unreachable!(),
+        };
+
+        sim.step_n(2)?;
+        assert_eq!(resources(&sim), 2.0);
+
+        sim.set_connection_flow_rate("source_to_pool", Some(3.0))?;
+        let connection = sim.get_connection("source_to_pool")?;
+        assert_eq!(connection.flow_rate, Some(3.0));
+        assert_eq!(connection.target_port.as_deref(), Some("in"));
+
+        sim.step()?;
+        assert_eq!(resources(&sim), 5.0);
+
+        // Clearing the rate falls back to the default of 1.0
+        sim.set_connection_flow_rate("source_to_pool", None)?;
+        sim.step()?;
+        assert_eq!(resources(&sim), 6.0);
+
+        assert_eq!(
+            sim.set_connection_flow_rate("missing", Some(1.0)),
+            Err(SimulationError::ConnectionNotFound("missing".to_string()))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_flush_releases_delayed_resources() -> Result<(), SimulationError> {
+        setup();
+
+        let mut sim = create_stepped_simulation(
+            vec![
+                Process::new(Box::new(Source::new("source"))),
+                Process::new(Box::new(Delay::new("delay"))),
+                Process::new(Box::new(Pool::new("pool"))),
+            ],
+            vec![
+                Connection::builder()
+                    .id("source_to_delay")
+                    .source("source.out")
+                    .target("delay.in")
+                    .flow_rate(1.0)
+                    .build()
+                    .unwrap(),
+                // A delay's flow rate is how long resources take to pass through
+                Connection::builder()
+                    .id("delay_to_pool")
+                    .source("delay.out")
+                    .target("pool.in")
+                    .flow_rate(5.0)
+                    .build()
+                    .unwrap(),
+            ],
+        )?;
+        let pool_resources = |sim: &Simulation| match sim.get_process_state("pool") {
+            Ok(ProcessState::Pool(state)) => state.resources,
+            _ => unreachable!(),
+        };
+
+        sim.step_n(3)?;
+        assert_eq!(pool_resources(&sim), 0.0);
+        assert_eq!(sim.delay_contents("delay").len(), 3);
+
+        // Everything under way arrives at once, without time moving on
+        sim.flush()?;
+        assert_eq!(sim.current_time(), 3.0);
+        assert_eq!(pool_resources(&sim), 3.0);
+        assert!(sim.delay_contents("delay").is_empty());
+        match sim.get_process_state("delay")? {
+            ProcessState::Delay(state) => {
+                assert_eq!(state.resources_released, 3.0);
+                assert_eq!(state.current_resources(), 0.0);
+            }
+            _ => unreachable!(),
+        }
+
+        // Nothing arrives a second time when the original release times come round, while
+        // what enters from now on still takes the full delay
+        sim.step_until(8.0)?;
+        assert_eq!(pool_resources(&sim), 3.0);
+        sim.step()?;
+        assert_eq!(pool_resources(&sim), 4.0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_source_push_all() -> Result<(), SimulationError> {
+        setup();
+
+        let source = Source::builder()
+            .id("source")
+            .action(Action::PushAll)
+            .build()
+            .unwrap();
+        let full = Pool::builder()
+            .id("full")
+            .capacity(2.0)
+            .state(PoolState {
+                resources: 2.0,
+                pending_outgoing_resources: 0.0,
+            })
+            .build()
+            .unwrap();
+        let connection = |target: &str| {
+            Connection::builder()
+                .id(format!("source_to_{}", target))
+                .source("source.out")
+                .target(&format!("{}.in", target))
+                .flow_rate(2.0)
+                .build()
+                .unwrap()
+        };
+        let mut sim = create_stepped_simulation(
+            vec![
+                Process::new(Box::new(source)),
+                Process::new(Box::new(Pool::new("open"))),
+                Process::new(Box::new(full)),
+            ],
+            vec![connection("open"), connection("full")],
+        )?;
+        sim.enable_recording();
+        sim.step_n(2)?;
+
+        let state = |id: &str| sim.get_process_state(id).unwrap();
+        match (state("open"), state("full")) {
+            (ProcessState::Pool(open), ProcessState::Pool(full)) => {
+                assert_eq!(open.resources, 4.0);
+                assert_eq!(full.resources, 2.0);
+            }
+            _ => unreachable!(),
+        }
+
+        // Both outputs are offered their rate each step, but only accepted shares count
+        let offered = sim
+            .recorded_events()
+            .iter()
+            .filter(|event| event.source_id == "source")
+            .filter(|event| event.payload == EventPayload::Resource(2.0))
+            .count();
+        assert_eq!(offered, 4);
+        assert!(sim
+            .recorded_events()
+            .iter()
+            .any(|event| event.source_id == "full"
+                && event.payload == EventPayload::ResourceRejected(2.0)));
+        match state("source") {
+            ProcessState::Source(source) => assert_eq!(source.resources_produced, 4.0),
+            _ => unreachable!(),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_splitter_round_robin() -> Result<(), SimulationError> {
+        setup();
+
+        let splitter = Splitter::builder()
+            .id("splitter")
+            .strategy(SplitStrategy::RoundRobin)
+            .build()
+            .unwrap();
+        let connection = |source: &str, target: &str, flow_rate: f64| {
+            Connection::builder()
+                .id(format!("{}_to_{}", source, target))
+                .source(&format!("{}.out", source))
+                .target(&format!("{}.in", target))
+                .flow_rate(flow_rate)
+                .build()
+                .unwrap()
+        };
+        let mut sim = create_stepped_simulation(
+            vec![
+                Process::new(Box::new(Source::new("source"))),
+                Process::new(Box::new(splitter)),
+                Process::new(Box::new(Pool::new("a"))),
+                Process::new(Box::new(Pool::new("b"))),
+                Process::new(Box::new(Pool::new("c"))),
+            ],
+            vec![
+                connection("source", "splitter", 1.0),
+                connection("splitter", "a", 1.0),
+                connection("splitter", "b", 1.0),
+                connection("splitter", "c", 1.0),
+            ],
+        )?;
+        let resources = |sim: &Simulation| -> Vec<f64> {
+            ["a", "b", "c"]
+                .iter()
+                .map(|id| match sim.get_process_state(id) {
+                    Ok(ProcessState::Pool(state)) => state.resources,
+                    _ => unreachable!(),
+                })
+                .collect()
+        };
+
+        // One unit per step, handed to each pool in turn in connection order
+        sim.step()?;
+        assert_eq!(resources(&sim), vec![1.0, 0.0, 0.0]);
+        sim.step()?;
+        assert_eq!(resources(&sim), vec![1.0, 1.0, 0.0]);
+        sim.step_n(4)?;
+        assert_eq!(resources(&sim), vec![2.0, 2.0, 2.0]);
+
+        match sim.get_process_state("splitter")? {
+            ProcessState::Splitter(state) => {
+                assert_eq!(state.last_output, Some(2));
+                assert_eq!(state.resources_released, 6.0);
+                assert_eq!(state.current_resources(), 0.0);
+            }
+            _ => unreachable!(),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_splitter_splits_large_arrivals_at_once() -> Result<(), SimulationError> {
+        setup();
+
+        let run = |strategy: SplitStrategy,
+                   amount: f64,
+                   rates: &[f64]|
+         -> Result<Vec<f64>, SimulationError> {
+            let splitter = Splitter::builder()
+                .id("splitter")
+                .strategy(strategy)
+                .build()
+                .unwrap();
+            let connection = |source: &str, target: &str, flow_rate: f64| {
+                Connection::builder()
+                    .id(format!("{}_to_{}", source, target))
+                    .source(&format!("{}.out", source))
+                    .target(&format!("{}.in", target))
+                    .flow_rate(flow_rate)
+                    .build()
+                    .unwrap()
+            };
+            let targets: Vec<String> = (0..rates.len()).map(|i| format!("t{}", i)).collect();
+            let mut processes = vec![
+                Process::new(Box::new(Source::new("source"))),
+                Process::new(Box::new(splitter)),
+            ];
+            let mut connections = vec![connection("source", "splitter", amount)];
+            for (target, rate) in targets.iter().zip(rates) {
+                processes.push(Process::new(Box::new(Pool::new(target))));
+                connections.push(connection("splitter", target, *rate));
+            }
+            let mut sim = create_stepped_simulation(processes, connections)?;
+            sim.step()?;
+
+            Ok(targets
+                .iter()
+                .map(|id| match sim.get_process_state(id) {
+                    Ok(ProcessState::Pool(state)) => state.resources,
+                    _ => unreachable!(),
+                })
+                .collect())
+        };
+
+        // A billion and a half: whole units in turn, the half to the output after the last
+        assert_eq!(
+            run(SplitStrategy::RoundRobin, 1e9 + 0.5, &[1.0, 1.0, 1.0])?,
+            vec![333_333_334.0, 333_333_333.5, 333_333_333.0]
+        );
+
+        // Past the point where adding a single unit no longer changes an f64
+        let huge = 2f64.powi(53);
+        assert_eq!(
+            run(SplitStrategy::Weighted, huge, &[1.0, 3.0])?,
+            vec![huge / 4.0, huge / 4.0 * 3.0]
+        );
+
+        let shares = run(SplitStrategy::Random, huge, &[1.0, 1.0])?;
+        assert_eq!(shares.iter().sum::<f64>(), huge);
+        assert!(shares.iter().all(|share| (share / huge - 0.5).abs() < 1e-3));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_state_approx_eq() -> Result<(), SimulationError> {
+        setup();
+
+        let mut sim = create_stepped_simulation(
+            vec![
+                Process::new(Box::new(Source::new("source"))),
+                Process::new(Box::new(Pool::new("pool"))),
+            ],
+            vec![Connection::builder()
+                .id("source_to_pool")
+                .source("source.out")
+                .target("pool.in")
+                .flow_rate(0.1)
+                .build()
+                .unwrap()],
+        )?;
+        sim.step_n(3)?;
+        let state = sim.get_simulation_state();
+
+        let mut nudged = state.clone();
+        if let Some(ProcessState::Pool(pool)) = nudged.process_states.get_mut("pool") {
+            pool.resources += 1e-12;
+        }
+        assert!(state.approx_eq(&nudged, 1e-9));
+        assert!(!state.approx_eq(&nudged, 1e-15));
+
+        // A process missing on either side makes the states differ at any tolerance
+        let mut fewer = state.clone();
+        fewer.process_states.remove("pool");
+        assert!(!state.approx_eq(&fewer, f64::MAX));
+        assert!(!fewer.approx_eq(&state, f64::MAX));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_merger_waits_for_every_input() -> Result<(), SimulationError> {
+        setup();
+
+        // The slow source only sends when triggered
+        let slow = Source::builder()
+            .id("slow")
+            .trigger_mode(TriggerMode::Passive)
+            .build()
+            .unwrap();
+        let merger = Merger::builder()
+            .id("merger")
+            .trigger_mode(TriggerMode::Passive)
+            .build()
+            .unwrap();
+        let connection = |source: &str, target: &str| {
+            Connection::builder()
+                .id(format!("{}_to_{}", source, target))
+                .source(&format!("{}.out", source))
+                .target(&format!("{}.in", target))
+                .flow_rate(1.0)
+                .build()
+                .unwrap()
+        };
+        let mut sim = create_stepped_simulation(
+            vec![
+                Process::new(Box::new(Source::new("fast"))),
+                Process::new(Box::new(slow)),
+                Process::new(Box::new(merger)),
+                Process::new(Box::new(Pool::new("pool"))),
+            ],
+            vec![
+                connection("fast", "merger"),
+                connection("slow", "merger"),
+                connection("merger", "pool"),
+            ],
+        )?;
+        sim.schedule_trigger("slow", 3.0)?;
+        sim.schedule_trigger("slow", 5.0)?;
+
+        let pool = |sim: &Simulation| match sim.get_process_state("pool") {
+            Ok(ProcessState::Pool(state)) => state.resources,
+            _ => unreachable!(),
+        };
+        let merger = |sim: &Simulation| match sim.get_process_state("merger") {
+            Ok(ProcessState::Merger(state)) => state,
+            _ => unreachable!(),
+        };
+
+        // Blocked until the slow input arrives
+        sim.step_n(2)?;
+        assert_eq!(pool(&sim), 0.0);
+        assert_eq!(merger(&sim).buffered["fast"], 2.0);
+
+        // One unit from each makes one merged unit, the fast input's surplus carrying over
+        sim.step()?;
+        assert_eq!(pool(&sim), 1.0);
+        assert_eq!(merger(&sim).buffered["fast"], 2.0);
+        assert_eq!(merger(&sim).buffered["slow"], 0.0);
+
+        sim.step_n(2)?;
+        let state = merger(&sim);
+        assert_eq!(pool(&sim), 2.0);
+        assert_eq!(state.merges, 2);
+        assert_eq!(state.resources_consumed, 4.0);
+        assert_eq!(state.resources_produced, 2.0);
+        assert_eq!(state.buffered["fast"], 3.0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_batch_source() -> Result<(), SimulationError> {
+        setup();
+
+        let source = Source::builder()
+            .id("endowment")
+            .batch_amount(50.0)
+            .build()
+            .unwrap();
+        let mut sim = create_stepped_simulation(
+            vec![
+                Process::new(Box::new(source)),
+                Process::new(Box::new(Pool::new("pool"))),
+            ],
+            vec![Connection::builder()
+                .id("endowment_to_pool")
+                .source("endowment.out")
+                .target("pool.in")
+                .flow_rate(1.0)
+                .build()
+                .unwrap()],
+        )?;
+        let state = |sim: &Simulation| match (
+            sim.get_process_state("endowment"),
+            sim.get_process_state("pool"),
+        ) {
+            (Ok(ProcessState::Source(source)), Ok(ProcessState::Pool(pool))) => {
+                (source.resources_produced, pool.resources)
+            }
+            _ => unreachable!(),
+        };
+
+        // The whole batch lands on the first step, whatever the flow rate
+        sim.step()?;
+        assert_eq!(state(&sim), (50.0, 50.0));
+
+        sim.step_n(5)?;
+        assert_eq!(state(&sim), (50.0, 50.0));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_conditional_connection_stops_below_threshold() -> Result<(), SimulationError> {
+        setup();
+
+        let upstream = Pool::builder()
+            .id("upstream")
+            .trigger_mode(TriggerMode::Automatic)
+            .action(Action::PushAny)
+            .state(PoolState {
+                resources: 5.0,
+                pending_outgoing_resources: 0.0,
+            })
+            .build()
+            .unwrap();
+        let mut sim = create_stepped_simulation(
+            vec![
+                Process::new(Box::new(upstream)),
+                Process::new(Box::new(Pool::new("downstream"))),
+            ],
+            vec![Connection::builder()
+                .id("upstream_to_downstream")
+                .source("upstream.out")
+                .target("downstream.in")
+                .flow_rate(1.0)
+                .condition(FlowCondition::SourceAbove(2.0))
+                .build()
+                .unwrap()],
+        )?;
+        let resources = |sim: &Simulation, id: &str| match sim.get_process_state(id) {
+            Ok(ProcessState::Pool(state)) => state.resources,
+            _ => unreachable!(),
+        };
+
+        sim.step_n(2)?;
+        assert_eq!(resources(&sim, "downstream"), 2.0);
+
+        // Flow stops once the upstream pool is down to the threshold
+        sim.step_n(5)?;
+        assert_eq!(resources(&sim, "upstream"), 2.0);
+        assert_eq!(resources(&sim, "downstream"), 3.0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_source_skips_closed_connections() -> Result<(), SimulationError> {
+        setup();
+
+        let push_all = Source::builder()
+            .id("source")
+            .action(Action::PushAll)
+            .build()
+            .unwrap();
+        let batch = Source::builder()
+            .id("source")
+            .batch_amount(10.0)
+            .build()
+            .unwrap();
+
+        for source in [push_all, batch] {
+            // A source's supply is unlimited, so it is never below a threshold
+            let mut sim = create_stepped_simulation(
+                vec![
+                    Process::new(Box::new(source)),
+                    Process::new(Box::new(Pool::new("open"))),
+                    Process::new(Box::new(Pool::new("closed"))),
+                ],
+                vec![
+                    Connection::builder()
+                        .id("source_to_open")
+                        .source("source.out")
+                        .target("open.in")
+                        .flow_rate(10.0)
+                        .build()
+                        .unwrap(),
+                    Connection::builder()
+                        .id("source_to_closed")
+                        .source("source.out")
+                        .target("closed.in")
+                        .flow_rate(10.0)
+                        .condition(FlowCondition::SourceBelow(5.0))
+                        .build()
+                        .unwrap(),
+                ],
+            )?;
+            sim.step()?;
+
+            let resources = |id: &str| match sim.get_process_state(id) {
+                Ok(ProcessState::Pool(state)) => state.resources,
+                _ => unreachable!(),
+            };
+            assert_eq!(resources("open"), 10.0);
+            assert_eq!(resources("closed"), 0.0);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_connection_total_capacity() -> Result<(), SimulationError> {
+        setup();
+
+        let mut sim = create_stepped_simulation(
+            vec![
+                Process::new(Box::new(Source::new("source"))),
+                Process::new(Box::new(Pool::new("pool"))),
+            ],
+            vec![Connection::builder()
+                .id("source_to_pool")
+                .source("source.out")
+                .target("pool.in")
+                .flow_rate(3.0)
+                .total_capacity(10.0)
+                .build()
+                .unwrap()],
+        )?;
+        let pool_resources = |sim: &Simulation| match sim.get_process_state("pool") {
+            Ok(ProcessState::Pool(state)) => state.resources,
+            _ => unreachable!(),
+        };
+
+        sim.step_n(3)?;
+        assert_eq!(pool_resources(&sim), 9.0);
+
+        // The fourth transfer is cut down to the one unit of capacity left
+        sim.step()?;
+        assert_eq!(pool_resources(&sim), 10.0);
+        let stats = sim.connection_stats("source_to_pool");
+        assert_eq!(stats.transferred, 10.0);
+        assert_eq!(stats.rejected, 2.0);
+
+        sim.step_n(3)?;
+        assert_eq!(pool_resources(&sim), 10.0);
+        assert_eq!(sim.connection_stats("source_to_pool").rejected, 11.0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rate_limited_drain() -> Result<(), SimulationError> {
+        setup();
+
+        let drain = Drain::builder()
+            .id("drain")
+            .trigger_mode(TriggerMode::Passive)
+            .max_rate(3.0)
+            .build()
+            .unwrap();
+        let mut sim = create_stepped_simulation(
+            vec![
+                Process::new(Box::new(Source::new("source"))),
+                Process::new(Box::new(drain)),
+            ],
+            vec![Connection::builder()
+                .id("source_to_drain")
+                .source("source.out")
+                .target("drain.in")
+                .flow_rate(10.0)
+                .build()
+                .unwrap()],
+        )?;
+
+        for step in 1..=3 {
+            sim.step()?;
+            let stats = sim.connection_stats("source_to_drain");
+            assert_eq!(stats.transferred, 3.0 * step as f64);
+            assert_eq!(stats.rejected, 7.0 * step as f64);
+        }
+        match sim.get_process_state("drain")? {
+            ProcessState::Drain(state) => assert_eq!(state.resources_consumed, 9.0),
+            _ => unreachable!(),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_queue_release_cadence_with_interleaved_arrivals() -> Result<(), SimulationError> {
+        setup();
+
+        let source = Source::builder()
+            .id("source")
+            .trigger_mode(TriggerMode::Passive)
+            .build()
+            .unwrap();
+        let queue = Delay::builder()
+            .id("queue")
+            .action(DelayAction::Queue)
+            .release_amount(1.0)
+            .build()
+            .unwrap();
+        let mut sim = create_stepped_simulation(
+            vec![
+                Process::new(Box::new(source)),
+                Process::new(Box::new(queue)),
+                Process::new(Box::new(Pool::new("pool"))),
+            ],
+            vec![
+                Connection::builder()
+                    .id("source_to_queue")
+                    .source("source.out")
+                    .target("queue.in")
+                    .flow_rate(1.0)
+                    .build()
+                    .unwrap(),
+                Connection::builder()
+                    .id("queue_to_pool")
+                    .source("queue.out")
+                    .target("pool.in")
+                    .flow_rate(2.0)
+                    .build()
+                    .unwrap(),
+            ],
+        )?;
+
+        // One arrival, two steps, then two more arrivals while the first is still queued
+        sim.schedule_trigger("source", 1.0)?;
+        sim.schedule_trigger("source", 2.5)?;
+        sim.schedule_trigger("source", 2.5)?;
+
+        let mut release_times = Vec::new();
+        let mut released = 0.0;
+        for _ in 0..12 {
+            sim.step()?;
+            let ProcessState::Pool(pool) = sim.get_process_state("pool")? else {
+                unreachable!()
+            };
+            if pool.resources > released {
+                release_times.push(sim.current_time());
+                released = pool.resources;
+            }
+        }
+
+        // Each release waits out the full two-unit delay after the one before it
+        assert_eq!(release_times, vec![3.0, 5.0, 7.0]);
+        assert_eq!(released, 3.0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_document_round_trip_resumes_identically() -> Result<(), SimulationError> {
+        setup();
+
+        let delay = Delay::builder()
+            .id("delay")
+            .action(DelayAction::Delay)
+            .build()
+            .unwrap();
+        let mut sim = create_stepped_simulation(
+            vec![
+                Process::new(Box::new(Source::new("source"))),
+                Process::new(Box::new(delay)),
+                Process::new(Box::new(Pool::new("pool"))),
+            ],
+            vec![
+                Connection::builder()
+                    .id("source_to_delay")
+                    .source("source.out")
+                    .target("delay.in")
+                    .flow_rate(1.0)
+                    .build()
+                    .unwrap(),
+                Connection::builder()
+                    .id("delay_to_pool")
+                    .source("delay.out")
+                    .target("pool.in")
+                    .flow_rate(3.0)
+                    .build()
+                    .unwrap(),
+            ],
+        )?;
+        sim.step_n(4)?;
+
+        // Saved mid-run, with transfers still in flight through the delay
+        let document = sim.to_document();
+        assert_eq!(document.step, 4);
+        assert!(!document.events.is_empty());
+        let connection_ids: Vec<&str> = document
+            .connections
+            .iter()
+            .map(|conn| conn.id.as_str())
+            .collect();
+        assert_eq!(connection_ids, vec!["source_to_delay", "delay_to_pool"]);
+
+        let json = serde_json::to_string(&document).unwrap();
+        let mut from_json = Simulation::from_document(serde_json::from_str(&json).unwrap())?;
+        let yaml = serde_yaml::to_string(&document).unwrap();
+        let from_yaml: SimulationDocument = serde_yaml::from_str(&yaml).unwrap();
+        let mut from_yaml = Simulation::from_document(from_yaml)?;
+
+        for _ in 0..6 {
+            sim.step()?;
+            from_json.step()?;
+            from_yaml.step()?;
+            let expected = sim.get_simulation_state().stable_hash();
+            assert_eq!(from_json.get_simulation_state().stable_hash(), expected);
+            assert_eq!(from_yaml.get_simulation_state().stable_hash(), expected);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_probe_tracks_growing_pool() -> Result<(), SimulationError> {
+        setup();
+
+        let mut sim = create_stepped_simulation(
+            vec![
+                Process::new(Box::new(Source::new("source"))),
+                Process::new(Box::new(Pool::new("pool"))),
+                Process::new(Box::new(Probe::new("probe"))),
+            ],
+            vec![
+                Connection::builder()
+                    .id("source_to_pool")
+                    .source("source.out")
+                    .target("pool.in")
+                    .flow_rate(2.0)
+                    .build()
+                    .unwrap(),
+                Connection::builder()
+                    .id("pool_to_probe")
+                    .source("pool.observe")
+                    .target("probe.in")
+                    .build()
+                    .unwrap(),
+            ],
+        )?;
+
+        let samples = |sim: &Simulation| match sim.get_process_state("probe") {
+            Ok(ProcessState::Probe(state)) => state.samples,
+            _ => unreachable!(),
+        };
+
+        let mut expected = Vec::new();
+        for _ in 0..5 {
+            sim.step()?;
+            let level = match sim.get_process_state("pool")? {
+                ProcessState::Pool(pool) => pool.resources,
+                _ => unreachable!(),
+            };
+            expected.push((sim.current_time(), level));
+        }
+        assert_eq!(
+            expected,
+            vec![(1.0, 2.0), (2.0, 4.0), (3.0, 6.0), (4.0, 8.0), (5.0, 10.0)]
+        );
+        assert_eq!(samples(&sim), expected);
+
+        // Observing moves nothing
+        assert_eq!(simcraft::analysis::conservation::locate_leak(&sim), None);
+
+        sim.reset()?;
+        assert!(samples(&sim).is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_probe_takes_no_share_of_pushes() -> Result<(), SimulationError> {
+        setup();
+
+        for action in [Action::PushAny, Action::PushLottery] {
+            let pool = Pool::builder()
+                .id("pool")
+                .trigger_mode(TriggerMode::Automatic)
+                .action(action)
+                .state(PoolState {
+                    resources: 10.0,
+                    pending_outgoing_resources: 0.0,
+                })
+                .build()
+                .unwrap();
+            let mut sim = create_stepped_simulation(
+                vec![
+                    Process::new(Box::new(pool)),
+                    Process::new(Box::new(Pool::new("target"))),
+                    Process::new(Box::new(Probe::new("probe"))),
+                ],
+                vec![
+                    Connection::builder()
+                        .id("pool_to_target")
+                        .source("pool.out")
+                        .target("target.in")
+                        .flow_rate(1.0)
+                        .build()
+                        .unwrap(),
+                    Connection::builder()
+                        .id("pool_to_probe")
+                        .source("pool.observe")
+                        .target("probe.in")
+                        .build()
+                        .unwrap(),
+                ],
+            )?;
+            sim.enable_recording();
+            sim.step_n(5)?;
+
+            // Everything pushed reaches the target, and the probe turns nothing away
+            match sim.get_process_state("target")? {
+                ProcessState::Pool(state) => assert_eq!(state.resources, 5.0),
+                _ => unreachable!(),
+            }
+            assert_eq!(sim.loss_report().total, 0.0);
+            assert_eq!(sim.connection_stats("pool_to_probe").transfers, 0);
+            match sim.get_process_state("probe")? {
+                ProcessState::Probe(state) => assert_eq!(state.samples.len(), 5),
+                _ => unreachable!(),
+            }
+
+            // A probe can't be put on a flow
+            let flow = Connection::builder()
+                .id("flow_to_probe")
+                .source("target.out")
+                .target("probe.in")
+                .build()
+                .unwrap();
+            assert!(matches!(
+                sim.add_connection(flow),
+                Err(SimulationError::ObserverLink { .. })
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Saves `sim` both as a snapshot and as a document, then steps the original and both
+    /// copies `steps` times, checking after each step that all three agree
+    fn assert_resumes_identically(
+        mut sim: Simulation,
+        steps: usize,
+    ) -> Result<(), SimulationError> {
+        let mut restored = Simulation::restore(&sim.snapshot()?)?;
+        let mut from_document = Simulation::from_document(sim.to_document())?;
+        for _ in 0..steps {
+            sim.step()?;
+            restored.step()?;
+            from_document.step()?;
+            let expected = sim.get_simulation_state().stable_hash();
+            assert_eq!(restored.get_simulation_state().stable_hash(), expected);
+            assert_eq!(from_document.get_simulation_state().stable_hash(), expected);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_lottery_pool_resumes_its_random_stream() -> Result<(), SimulationError> {
+        setup();
+
+        let loot = Pool::builder()
+            .id("loot")
+            .trigger_mode(TriggerMode::Automatic)
+            .action(Action::PushLottery)
+            .state(PoolState {
+                resources: 26.0,
+                pending_outgoing_resources: 0.0,
+            })
+            .build()
+            .unwrap();
+        let connection = |target: &str| {
+            Connection::builder()
+                .id(format!("loot_to_{}", target))
+                .source("loot.out")
+                .target(&format!("{}.in", target))
+                .flow_rate(1.0)
+                .build()
+                .unwrap()
+        };
+        let mut sim = create_stepped_simulation(
+            vec![
+                Process::new(Box::new(loot)),
+                Process::new(Box::new(Pool::new("a"))),
+                Process::new(Box::new(Pool::new("b"))),
+            ],
+            vec![connection("a"), connection("b")],
+        )?;
+        sim.set_seed(42);
+        sim.step_n(6)?;
+
+        assert_resumes_identically(sim, 20)
+    }
+
+    #[test]
+    fn test_jittered_delay_resumes_its_random_stream() -> Result<(), SimulationError> {
+        setup();
+
+        let delay = Delay::builder().id("delay").jitter(2.5).build().unwrap();
+        let mut sim = create_stepped_simulation(
+            vec![
+                Process::new(Box::new(Source::new("source"))),
+                Process::new(Box::new(delay)),
+                Process::new(Box::new(Pool::new("pool"))),
+            ],
+            vec![
+                Connection::builder()
+                    .id("source_to_delay")
+                    .source("source.out")
+                    .target("delay.in")
+                    .flow_rate(1.0)
+                    .build()
+                    .unwrap(),
+                Connection::builder()
+                    .id("delay_to_pool")
+                    .source("delay.out")
+                    .target("pool.in")
+                    .flow_rate(3.0)
+                    .build()
+                    .unwrap(),
+            ],
+        )?;
+        sim.set_seed(7);
+        sim.step_n(5)?;
+
+        assert_resumes_identically(sim, 20)
+    }
+
+    #[test]
+    fn test_random_splitter_resumes_its_random_stream() -> Result<(), SimulationError> {
+        setup();
+
+        let splitter = Splitter::builder()
+            .id("splitter")
+            .strategy(SplitStrategy::Random)
+            .build()
+            .unwrap();
+        let connection = |source: &str, target: &str| {
+            Connection::builder()
+                .id(format!("{}_to_{}", source, target))
+                .source(&format!("{}.out", source))
+                .target(&format!("{}.in", target))
+                .flow_rate(1.0)
+                .build()
+                .unwrap()
+        };
+        let mut sim = create_stepped_simulation(
+            vec![
+                Process::new(Box::new(Source::new("source"))),
+                Process::new(Box::new(splitter)),
+                Process::new(Box::new(Pool::new("a"))),
+                Process::new(Box::new(Pool::new("b"))),
+            ],
+            vec![
+                connection("source", "splitter"),
+                connection("splitter", "a"),
+                connection("splitter", "b"),
+            ],
+        )?;
+        sim.set_seed(42);
+        sim.step_n(6)?;
+
+        assert_resumes_identically(sim, 20)
+    }
 }
@@ -1,291 +1,323 @@
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use serde_json::{Map, Value};
 use std::sync::{Arc, Mutex};
 use tauri::State;
-use uuid::Uuid;
 
 use simcraft::{
+    api,
     model::{connection::Connection, process::Process},
-    simulator::{Event, Simulate, Simulation, SimulationState, StatefulSimulation},
+    rpc::{handle_command, RpcCommand, RpcResponse, SimulationManager},
+    simulator::{Event, RunConfiguration, SimulationState},
+    utils::logging::LogRecord,
 };
 
-struct SimulationManager {
-    simulations: Mutex<HashMap<String, Simulation>>,
+type Manager = Arc<Mutex<SimulationManager>>;
+
+fn export(manager: &SimulationManager, simulation_id: &str) -> Result<Vec<u8>, String> {
+    Ok(api::export(manager.get(simulation_id)?)?)
 }
 
-impl Default for SimulationManager {
-    fn default() -> Self {
-        Self {
-            simulations: Mutex::new(HashMap::new()),
-        }
-    }
+fn import(manager: &mut SimulationManager, blob: &[u8]) -> Result<String, String> {
+    Ok(manager.insert(api::import(blob)?))
+}
+
+/// Runs a command through the engine's JSON command dispatcher, which covers everything the
+/// commands below do
+#[tauri::command]
+async fn rpc(manager: State<'_, Manager>, command: RpcCommand) -> Result<RpcResponse, String> {
+    Ok(handle_command(&mut manager.lock().unwrap(), command))
 }
 
 #[tauri::command]
 async fn create_simulation(
-    manager: State<'_, Arc<SimulationManager>>,
+    manager: State<'_, Manager>,
     processes: Vec<Process>,
     connections: Vec<Connection>,
+    config: Option<RunConfiguration>,
 ) -> Result<String, String> {
-    let simulation = Simulation::new(processes, connections)
-        .map_err(|e| format!("Failed to create simulation: {}", e))?;
+    let simulation = api::create(processes, connections, config.unwrap_or_default())?;
 
-    let id = Uuid::new_v4().to_string();
+    Ok(manager.lock().unwrap().insert(simulation))
+}
 
-    manager
-        .simulations
-        .lock()
-        .unwrap()
-        .insert(id.clone(), simulation);
+/// Creates a simulation from a JSON model sent by the client, which may leave out connection
+/// ids and flow rates. Every problem with the model is reported, not just the first.
+#[tauri::command]
+async fn load_simulation(manager: State<'_, Manager>, model: String) -> Result<String, String> {
+    let simulation = api::load(&model)?;
 
-    Ok(id)
+    Ok(manager.lock().unwrap().insert(simulation))
 }
 
 #[tauri::command]
 async fn simulation_step(
-    manager: State<'_, Arc<SimulationManager>>,
+    manager: State<'_, Manager>,
     simulation_id: String,
 ) -> Result<Vec<Event>, String> {
-    let mut simulations = manager.simulations.lock().unwrap();
+    let mut manager = manager.lock().unwrap();
+    let simulation = manager.get_mut(&simulation_id)?;
 
-    let simulation = simulations
-        .get_mut(&simulation_id)
-        .ok_or_else(|| "Simulation not found".to_string())?;
-
-    simulation
-        .step()
-        .map_err(|e| format!("Failed to step simulation: {}", e))
+    Ok(api::step(simulation)?)
 }
 
 #[tauri::command]
 async fn simulation_step_n(
-    manager: State<'_, Arc<SimulationManager>>,
+    manager: State<'_, Manager>,
     simulation_id: String,
     n: usize,
 ) -> Result<Vec<Event>, String> {
-    let mut simulations = manager.simulations.lock().unwrap();
-
-    let simulation = simulations
-        .get_mut(&simulation_id)
-        .ok_or_else(|| "Simulation not found".to_string())?;
+    let mut manager = manager.lock().unwrap();
+    let simulation = manager.get_mut(&simulation_id)?;
 
-    simulation
-        .step_n(n)
-        .map_err(|e| format!("Failed to step simulation {} times: {}", n, e))
+    Ok(api::step_n(simulation, n)?)
 }
 
 #[tauri::command]
 async fn destroy_simulation(
-    manager: State<'_, Arc<SimulationManager>>,
+    manager: State<'_, Manager>,
     simulation_id: String,
 ) -> Result<(), String> {
-    let mut simulations = manager.simulations.lock().unwrap();
-
-    simulations
-        .remove(&simulation_id)
-        .ok_or_else(|| "Simulation not found".to_string())?;
+    manager.lock().unwrap().remove(&simulation_id)?;
 
     Ok(())
 }
 
 #[tauri::command]
 async fn step_until(
-    manager: State<'_, Arc<SimulationManager>>,
+    manager: State<'_, Manager>,
     simulation_id: String,
     until: f64,
 ) -> Result<Vec<Event>, String> {
-    let mut simulations = manager.simulations.lock().unwrap();
-
-    let simulation = simulations
-        .get_mut(&simulation_id)
-        .ok_or_else(|| "Simulation not found".to_string())?;
+    let mut manager = manager.lock().unwrap();
+    let simulation = manager.get_mut(&simulation_id)?;
 
-    simulation
-        .step_until(until)
-        .map_err(|e| format!("Failed to step simulation until {}: {}", until, e))
+    Ok(api::step_until(simulation, until)?)
 }
 
 #[tauri::command]
 async fn reset_simulation(
-    manager: State<'_, Arc<SimulationManager>>,
+    manager: State<'_, Manager>,
     simulation_id: String,
 ) -> Result<(), String> {
-    let mut simulations = manager.simulations.lock().unwrap();
+    let mut manager = manager.lock().unwrap();
+    let simulation = manager.get_mut(&simulation_id)?;
 
-    let simulation = simulations
-        .get_mut(&simulation_id)
-        .ok_or_else(|| "Simulation not found".to_string())?;
-
-    simulation
-        .reset()
-        .map_err(|e| format!("Failed to reset simulation: {}", e))
+    Ok(api::reset(simulation)?)
 }
 
 #[tauri::command]
 async fn get_simulation_state(
-    manager: State<'_, Arc<SimulationManager>>,
+    manager: State<'_, Manager>,
     simulation_id: String,
 ) -> Result<SimulationState, String> {
-    let simulations = manager.simulations.lock().unwrap();
-
-    let simulation = simulations
-        .get(&simulation_id)
-        .ok_or_else(|| "Simulation not found".to_string())?;
+    let manager = manager.lock().unwrap();
+    let simulation = manager.get(&simulation_id)?;
 
-    Ok(simulation.get_simulation_state())
+    Ok(api::state(simulation))
 }
 
 #[tauri::command]
 async fn add_process(
-    manager: State<'_, Arc<SimulationManager>>,
+    manager: State<'_, Manager>,
     simulation_id: String,
     process: Process,
 ) -> Result<(), String> {
-    let mut simulations = manager.simulations.lock().unwrap();
+    let mut manager = manager.lock().unwrap();
+    let simulation = manager.get_mut(&simulation_id)?;
 
-    let simulation = simulations
-        .get_mut(&simulation_id)
-        .ok_or_else(|| "Simulation not found".to_string())?;
-
-    simulation
-        .add_process(process)
-        .map_err(|e| format!("Failed to add process: {}", e))
+    Ok(api::add_process(simulation, process)?)
 }
 
 #[tauri::command]
 async fn remove_process(
-    manager: State<'_, Arc<SimulationManager>>,
+    manager: State<'_, Manager>,
     simulation_id: String,
     process_id: String,
 ) -> Result<(), String> {
-    let mut simulations = manager.simulations.lock().unwrap();
-
-    let simulation = simulations
-        .get_mut(&simulation_id)
-        .ok_or_else(|| "Simulation not found".to_string())?;
+    let mut manager = manager.lock().unwrap();
+    let simulation = manager.get_mut(&simulation_id)?;
 
-    simulation
-        .remove_process(&process_id)
-        .map_err(|e| format!("Failed to remove process: {}", e))?;
-
-    Ok(())
+    Ok(api::remove_process(simulation, &process_id)?)
 }
 
 #[tauri::command]
 async fn update_process(
-    manager: State<'_, Arc<SimulationManager>>,
+    manager: State<'_, Manager>,
     simulation_id: String,
     process_id: String,
     process: Process,
 ) -> Result<(), String> {
-    let mut simulations = manager.simulations.lock().unwrap();
+    let mut manager = manager.lock().unwrap();
+    let simulation = manager.get_mut(&simulation_id)?;
 
-    let simulation = simulations
-        .get_mut(&simulation_id)
-        .ok_or_else(|| "Simulation not found".to_string())?;
-
-    simulation
-        .update_process(&process_id, process)
-        .map_err(|e| format!("Failed to update process: {}", e))
+    Ok(api::update_process(simulation, &process_id, process)?)
 }
 
 #[tauri::command]
 async fn get_processes(
-    manager: State<'_, Arc<SimulationManager>>,
+    manager: State<'_, Manager>,
     simulation_id: String,
 ) -> Result<Vec<Process>, String> {
-    let simulations = manager.simulations.lock().unwrap();
+    let manager = manager.lock().unwrap();
+    let simulation = manager.get(&simulation_id)?;
+
+    Ok(api::processes(simulation))
+}
 
-    let simulation = simulations
-        .get(&simulation_id)
-        .ok_or_else(|| "Simulation not found".to_string())?;
+#[tauri::command]
+async fn get_connections(
+    manager: State<'_, Manager>,
+    simulation_id: String,
+) -> Result<Vec<Connection>, String> {
+    let manager = manager.lock().unwrap();
+    let simulation = manager.get(&simulation_id)?;
 
-    Ok(simulation.processes().values().cloned().collect())
+    Ok(api::connections(simulation))
 }
 
 #[tauri::command]
 async fn add_connection(
-    manager: State<'_, Arc<SimulationManager>>,
+    manager: State<'_, Manager>,
     simulation_id: String,
     connection: Connection,
 ) -> Result<(), String> {
-    let mut simulations = manager.simulations.lock().unwrap();
-
-    let simulation = simulations
-        .get_mut(&simulation_id)
-        .ok_or_else(|| "Simulation not found".to_string())?;
+    let mut manager = manager.lock().unwrap();
+    let simulation = manager.get_mut(&simulation_id)?;
 
-    simulation
-        .add_connection(connection)
-        .map_err(|e| format!("Failed to add connection: {}", e))
+    Ok(api::add_connection(simulation, connection)?)
 }
 
 #[tauri::command]
 async fn remove_connection(
-    manager: State<'_, Arc<SimulationManager>>,
+    manager: State<'_, Manager>,
     simulation_id: String,
     connection_id: String,
 ) -> Result<(), String> {
-    let mut simulations = manager.simulations.lock().unwrap();
+    let mut manager = manager.lock().unwrap();
+    let simulation = manager.get_mut(&simulation_id)?;
 
-    let simulation = simulations
-        .get_mut(&simulation_id)
-        .ok_or_else(|| "Simulation not found".to_string())?;
-
-    simulation
-        .remove_connection(&connection_id)
-        .map_err(|e| format!("Failed to remove connection: {}", e))
+    Ok(api::remove_connection(simulation, &connection_id)?)
 }
 
 #[tauri::command]
 async fn update_connection(
-    manager: State<'_, Arc<SimulationManager>>,
+    manager: State<'_, Manager>,
     simulation_id: String,
     connection_id: String,
     connection: Connection,
 ) -> Result<(), String> {
-    let mut simulations = manager.simulations.lock().unwrap();
-
-    let simulation = simulations
-        .get_mut(&simulation_id)
-        .ok_or_else(|| "Simulation not found".to_string())?;
+    let mut manager = manager.lock().unwrap();
+    let simulation = manager.get_mut(&simulation_id)?;
+
+    Ok(api::update_connection(
+        simulation,
+        &connection_id,
+        connection,
+    )?)
+}
 
-    simulation
-        .update_connection(&connection_id, connection)
-        .map_err(|e| format!("Failed to update connection: {}", e))
+#[tauri::command]
+async fn set_connection_flow_rate(
+    manager: State<'_, Manager>,
+    simulation_id: String,
+    connection_id: String,
+    flow_rate: Option<f64>,
+) -> Result<(), String> {
+    let mut manager = manager.lock().unwrap();
+    let simulation = manager.get_mut(&simulation_id)?;
+
+    Ok(api::set_connection_flow_rate(
+        simulation,
+        &connection_id,
+        flow_rate,
+    )?)
 }
 
 #[tauri::command]
 async fn get_current_step(
-    manager: State<'_, Arc<SimulationManager>>,
+    manager: State<'_, Manager>,
     simulation_id: String,
 ) -> Result<u64, String> {
-    let simulations = manager.simulations.lock().unwrap();
-
-    let simulation = simulations
-        .get(&simulation_id)
-        .ok_or_else(|| "Simulation not found".to_string())?;
+    let manager = manager.lock().unwrap();
+    let simulation = manager.get(&simulation_id)?;
 
     Ok(simulation.current_step())
 }
 
 #[tauri::command]
 async fn get_current_time(
-    manager: State<'_, Arc<SimulationManager>>,
+    manager: State<'_, Manager>,
     simulation_id: String,
 ) -> Result<f64, String> {
-    let simulations = manager.simulations.lock().unwrap();
-
-    let simulation = simulations
-        .get(&simulation_id)
-        .ok_or_else(|| "Simulation not found".to_string())?;
+    let manager = manager.lock().unwrap();
+    let simulation = manager.get(&simulation_id)?;
 
     Ok(simulation.current_time())
 }
 
+#[tauri::command]
+async fn set_simulation_metadata(
+    manager: State<'_, Manager>,
+    simulation_id: String,
+    key: String,
+    value: Value,
+) -> Result<(), String> {
+    let mut manager = manager.lock().unwrap();
+    let simulation = manager.get_mut(&simulation_id)?;
+
+    simulation.set_metadata(key, value);
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_simulation_metadata(
+    manager: State<'_, Manager>,
+    simulation_id: String,
+) -> Result<Map<String, Value>, String> {
+    let manager = manager.lock().unwrap();
+    let simulation = manager.get(&simulation_id)?;
+
+    Ok(simulation.metadata().clone())
+}
+
+#[tauri::command]
+async fn capture_simulation_logs(
+    manager: State<'_, Manager>,
+    simulation_id: String,
+    min_level: String,
+) -> Result<(), String> {
+    let mut manager = manager.lock().unwrap();
+    let simulation = manager.get_mut(&simulation_id)?;
+
+    Ok(api::enable_log_capture(simulation, &min_level)?)
+}
+
+#[tauri::command]
+async fn get_simulation_logs(
+    manager: State<'_, Manager>,
+    simulation_id: String,
+) -> Result<Vec<LogRecord>, String> {
+    let manager = manager.lock().unwrap();
+    let simulation = manager.get(&simulation_id)?;
+
+    Ok(simulation.logs())
+}
+
+#[tauri::command]
+async fn export_simulation(
+    manager: State<'_, Manager>,
+    simulation_id: String,
+) -> Result<Vec<u8>, String> {
+    export(&manager.lock().unwrap(), &simulation_id)
+}
+
+#[tauri::command]
+async fn import_simulation(manager: State<'_, Manager>, blob: Vec<u8>) -> Result<String, String> {
+    import(&mut manager.lock().unwrap(), &blob)
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
-    let simulation_manager = Arc::new(SimulationManager::default());
+    let simulation_manager: Manager = Arc::new(Mutex::new(SimulationManager::new()));
 
     tauri::Builder::default()
         .manage(simulation_manager)
@@ -300,7 +332,9 @@ pub fn run() {
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
+            rpc,
             create_simulation,
+            load_simulation,
             simulation_step,
             simulation_step_n,
             destroy_simulation,
@@ -311,12 +345,64 @@ pub fn run() {
             remove_process,
             update_process,
             get_processes,
+            get_connections,
             add_connection,
             remove_connection,
             update_connection,
+            set_connection_flow_rate,
             get_current_step,
-            get_current_time
+            get_current_time,
+            set_simulation_metadata,
+            get_simulation_metadata,
+            capture_simulation_logs,
+            get_simulation_logs,
+            export_simulation,
+            import_simulation
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simcraft::model::nodes::{Pool, Source, Stepper};
+    use simcraft::simulator::{Simulate, Simulation, StatefulSimulation};
+
+    #[test]
+    fn test_export_import_simulation() {
+        let mut manager = SimulationManager::new();
+
+        let processes = vec![
+            Process::new(Box::new(Stepper::builder().id("stepper").build().unwrap())),
+            Process::new(Box::new(Source::new("source"))),
+            Process::new(Box::new(Pool::new("pool"))),
+        ];
+        let connections = vec![Connection::new(
+            "conn".to_string(),
+            "source".to_string(),
+            Some("out".to_string()),
+            "pool".to_string(),
+            Some("in".to_string()),
+            Some(1.0),
+        )];
+        let mut simulation = Simulation::new(processes, connections).unwrap();
+        simulation.step_n(3).unwrap();
+        let expected = simulation.get_simulation_state().stable_hash();
+
+        let id = manager.insert(simulation);
+
+        let blob = export(&manager, &id).unwrap();
+        let imported_id = import(&mut manager, &blob).unwrap();
+        assert_ne!(imported_id, id);
+
+        assert_eq!(
+            manager
+                .get(&imported_id)
+                .unwrap()
+                .get_simulation_state()
+                .stable_hash(),
+            expected
+        );
+    }
+}
@@ -2,6 +2,9 @@ use std::fmt;
 
 use serde::Serialize;
 use serde_wasm_bindgen::to_value;
+use simcraft::api::ApiError;
+use simcraft::simulator::Simulation;
+use simcraft::utils::errors::SimulationError;
 use wasm_bindgen::JsValue;
 
 #[derive(Serialize)]
@@ -11,11 +14,41 @@ pub struct CustomJsError {
 }
 
 pub(crate) fn wasm_error<E: fmt::Debug + fmt::Display>(err: E) -> JsValue {
-    let js_error = CustomJsError {
+    to_js_error(CustomJsError {
         error: format!("{:?}", err),
         message: err.to_string(),
-    };
+    })
+}
+
+/// Like `wasm_error`, with the message naming the type and settings of the process involved
+pub(crate) fn simulation_error(simulation: &Simulation, err: SimulationError) -> JsValue {
+    to_js_error(CustomJsError {
+        error: format!("{:?}", err),
+        message: simulation.describe_error(&err),
+    })
+}
+
+/// An error from the shared `simcraft::api` operations, reported like `simulation_error`
+pub(crate) fn api_error(err: ApiError) -> JsValue {
+    to_js_error(CustomJsError {
+        error: format!("{:?}", err.error),
+        message: err.description,
+    })
+}
+
+/// All problems found in a rejected model, reported together as one error
+pub(crate) fn validation_errors(errors: Vec<SimulationError>) -> JsValue {
+    to_js_error(CustomJsError {
+        error: format!("{:?}", errors),
+        message: errors
+            .iter()
+            .map(|e| e.to_string())
+            .collect::<Vec<_>>()
+            .join("; "),
+    })
+}
 
+fn to_js_error(js_error: CustomJsError) -> JsValue {
     to_value(&js_error).unwrap_or_else(|_| {
         JsValue::from_str("{\"error\": \"Unknown\", \"message\": \"Failed to convert error\"}")
     })
@@ -2,9 +2,12 @@ use js_sys::Array;
 use log::debug;
 use serde::{Deserialize, Serialize};
 use serde_wasm_bindgen::to_value;
+use simcraft::api;
+use simcraft::model::process_factory;
 use simcraft::model::Connection;
 use simcraft::model::Process;
 use simcraft::model::ProcessState;
+use simcraft::simulator::RunConfiguration;
 use simcraft::simulator::SimulationState;
 use wasm_bindgen::prelude::*;
 
@@ -15,9 +18,19 @@ use simcraft::simulator::StatefulSimulation;
 pub mod errors;
 pub mod logging;
 
-use errors::wasm_error;
+use errors::{api_error, simulation_error, validation_errors, wasm_error};
 use logging::init_logging;
 
+/// Fields accepted by a process type, with their JSON types and defaults
+#[wasm_bindgen]
+pub fn type_schema(process_type: &str) -> Result<JsValue, JsValue> {
+    let schema = process_factory::type_schema(process_type)
+        .ok_or_else(|| wasm_error(format!("Unknown process type '{}'", process_type)))?;
+    schema
+        .serialize(&serde_wasm_bindgen::Serializer::json_compatible())
+        .map_err(wasm_error)
+}
+
 #[wasm_bindgen]
 #[derive(Debug, Default, Serialize, Deserialize)]
 pub struct Simulation {
@@ -39,6 +52,32 @@ impl Simulation {
         Ok(simulation)
     }
 
+    pub fn with_config(processes: &str, connections: &str, config: &str) -> Result<Self, JsValue> {
+        init_logging();
+        debug!("Creating new simulation from run configuration");
+
+        let processes: Vec<Process> = serde_json::from_str(processes).map_err(wasm_error)?;
+        let connections: Vec<Connection> = serde_json::from_str(connections).map_err(wasm_error)?;
+        let config: RunConfiguration = serde_json::from_str(config).map_err(wasm_error)?;
+        let simulation = Self {
+            inner: CoreSimulation::with_config(processes, connections, config)
+                .map_err(wasm_error)?,
+        };
+
+        Ok(simulation)
+    }
+
+    /// Builds a simulation from a JSON model of `processes` and `connections`, filling in
+    /// missing connection ids and flow rates and reporting every problem at once
+    pub fn load(model: &str) -> Result<Self, JsValue> {
+        init_logging();
+        debug!("Loading simulation from model");
+
+        Ok(Self {
+            inner: CoreSimulation::load_and_validate(model).map_err(validation_errors)?,
+        })
+    }
+
     pub fn current_step(&self) -> u64 {
         self.inner.current_step()
     }
@@ -48,78 +87,117 @@ impl Simulation {
     }
 
     pub fn step(&mut self) -> Result<(), JsValue> {
-        self.inner.step().map_err(wasm_error)?;
+        api::step(&mut self.inner).map_err(api_error)?;
         Ok(())
     }
 
     pub fn step_until(&mut self, until: f64) -> Result<(), JsValue> {
-        self.inner.step_until(until).map_err(wasm_error)?;
+        api::step_until(&mut self.inner, until).map_err(api_error)?;
         Ok(())
     }
 
     pub fn step_n(&mut self, n: usize) -> Result<(), JsValue> {
-        self.inner.step_n(n).map_err(wasm_error)?;
+        api::step_n(&mut self.inner, n).map_err(api_error)?;
         Ok(())
     }
 
+    /// Runs `steps` steps, returning the state before the first step followed by the state
+    /// after each one
+    pub fn run_states(&mut self, steps: usize) -> Result<Array, JsValue> {
+        let states = Array::new();
+        states.push(&to_value(&self.inner.get_simulation_state()).map_err(wasm_error)?);
+        for _ in 0..steps {
+            api::step(&mut self.inner).map_err(api_error)?;
+            states.push(&to_value(&self.inner.get_simulation_state()).map_err(wasm_error)?);
+        }
+        Ok(states)
+    }
+
+    /// Steps once, returning only the processes whose state changed
+    pub fn step_delta(&mut self) -> Result<JsValue, JsValue> {
+        let delta = self
+            .inner
+            .step_delta()
+            .map_err(|e| simulation_error(&self.inner, e))?;
+        to_value(&delta).map_err(wasm_error)
+    }
+
+    /// Keeps engine log messages at `min_level` ("ERROR", "WARN", "INFO", "DEBUG" or "TRACE")
+    /// and above, for `logs()`
+    pub fn enable_log_capture(&mut self, min_level: &str) -> Result<(), JsValue> {
+        api::enable_log_capture(&mut self.inner, min_level).map_err(api_error)
+    }
+
+    pub fn disable_log_capture(&mut self) {
+        self.inner.disable_log_capture();
+    }
+
+    pub fn logs(&self) -> Result<JsValue, JsValue> {
+        to_value(&self.inner.logs()).map_err(wasm_error)
+    }
+
+    pub fn clear_logs(&mut self) {
+        self.inner.clear_logs();
+    }
+
     pub fn get_simulation_state(&self) -> JsValue {
-        let state: SimulationState = self.inner.get_simulation_state();
-        let js_state = to_value(&state).unwrap_or(JsValue::NULL);
-        js_state
+        let state: SimulationState = api::state(&self.inner);
+        to_value(&state).unwrap_or(JsValue::NULL)
     }
 
     pub fn get_process_state(&self, process_id: &str) -> Result<JsValue, JsValue> {
-        let state: ProcessState = self
-            .inner
-            .get_process_state(process_id)
-            .map_err(wasm_error)?;
+        let state: ProcessState = api::process_state(&self.inner, process_id).map_err(api_error)?;
         let js_state = to_value(&state).unwrap_or(JsValue::NULL);
         Ok(js_state)
     }
 
     pub fn reset(&mut self) -> Result<(), JsValue> {
-        self.inner.reset().map_err(wasm_error)?;
+        api::reset(&mut self.inner).map_err(api_error)?;
         Ok(())
     }
 
     pub fn add_process(&mut self, process: &str) -> Result<(), JsValue> {
         let process: Process = serde_json::from_str(process).map_err(wasm_error)?;
-        self.inner.add_process(process).map_err(wasm_error)?;
+        api::add_process(&mut self.inner, process).map_err(api_error)?;
         Ok(())
     }
 
     pub fn remove_process(&mut self, process_id: &str) -> Result<(), JsValue> {
-        self.inner.remove_process(process_id).map_err(wasm_error)?;
+        api::remove_process(&mut self.inner, process_id).map_err(api_error)?;
         Ok(())
     }
 
     pub fn get_processes(&self) -> Result<Array, JsValue> {
-        let processes = self.inner.processes();
-        let js_processes = processes
-            .values()
-            .map(|p| to_value(&p).unwrap_or(JsValue::NULL))
+        let js_processes = api::processes(&self.inner)
+            .iter()
+            .map(|p| to_value(p).unwrap_or(JsValue::NULL))
             .collect();
         Ok(js_processes)
     }
 
+    /// Every connection, in the order they were added
+    pub fn get_connections(&self) -> Result<Array, JsValue> {
+        let js_connections = api::connections(&self.inner)
+            .iter()
+            .map(|c| to_value(c).unwrap_or(JsValue::NULL))
+            .collect();
+        Ok(js_connections)
+    }
+
     pub fn update_process(&mut self, process_id: &str, process: &str) -> Result<(), JsValue> {
         let process: Process = serde_json::from_str(process).map_err(wasm_error)?;
-        self.inner
-            .update_process(process_id, process)
-            .map_err(wasm_error)?;
+        api::update_process(&mut self.inner, process_id, process).map_err(api_error)?;
         Ok(())
     }
 
     pub fn add_connection(&mut self, connection: &str) -> Result<(), JsValue> {
         let connection: Connection = serde_json::from_str(connection).map_err(wasm_error)?;
-        self.inner.add_connection(connection).map_err(wasm_error)?;
+        api::add_connection(&mut self.inner, connection).map_err(api_error)?;
         Ok(())
     }
 
     pub fn remove_connection(&mut self, connection_id: &str) -> Result<(), JsValue> {
-        self.inner
-            .remove_connection(connection_id)
-            .map_err(wasm_error)?;
+        api::remove_connection(&mut self.inner, connection_id).map_err(api_error)?;
         Ok(())
     }
 
@@ -129,9 +207,17 @@ impl Simulation {
         connection: &str,
     ) -> Result<(), JsValue> {
         let connection: Connection = serde_json::from_str(connection).map_err(wasm_error)?;
-        self.inner
-            .update_connection(connection_id, connection)
-            .map_err(wasm_error)?;
+        api::update_connection(&mut self.inner, connection_id, connection).map_err(api_error)?;
+        Ok(())
+    }
+
+    pub fn set_connection_flow_rate(
+        &mut self,
+        connection_id: &str,
+        flow_rate: Option<f64>,
+    ) -> Result<(), JsValue> {
+        api::set_connection_flow_rate(&mut self.inner, connection_id, flow_rate)
+            .map_err(api_error)?;
         Ok(())
     }
 }
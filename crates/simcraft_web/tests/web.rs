@@ -77,7 +77,7 @@ fn simulation_with_duplicate_processes() {
 fn simulation_step() {
     let mut simulation = Simulation::new(TEST_PROCESSES, TEST_CONNECTIONS).unwrap();
 
-    let results = simulation.step().unwrap();
+    let results = simulation.run_states(1).unwrap();
     debug!("Step results length: {}", results.length());
     assert!(
         results.length() > 0,
@@ -132,3 +132,66 @@ fn simulation_step() {
         "Source should have produced 1.0 resources"
     );
 }
+
+#[test]
+#[wasm_bindgen_test]
+fn simulation_run_states() {
+    let mut simulation = Simulation::new(TEST_PROCESSES, TEST_CONNECTIONS).unwrap();
+
+    let steps = 5;
+    let states = simulation.run_states(steps).unwrap();
+    assert_eq!(
+        states.length() as usize,
+        steps + 1,
+        "Expected the initial state followed by one state per step."
+    );
+
+    let last_state: Value =
+        from_value(states.get(states.length() - 1)).expect("Failed to convert state to JSON");
+    assert_eq!(last_state["step"].as_u64(), Some(steps as u64));
+
+    let pool_resources = last_state["process_states"]["pool-1"]["Pool"]["resources"]
+        .as_f64()
+        .expect("Resources not a number");
+    assert_eq!(
+        pool_resources, steps as f64,
+        "Pool should have received 1.0 resources per step from source"
+    );
+}
+
+#[test]
+#[wasm_bindgen_test]
+fn simulation_step_delta() {
+    let mut simulation = Simulation::new(TEST_PROCESSES, TEST_CONNECTIONS).unwrap();
+
+    let delta: Value =
+        from_value(simulation.step_delta().unwrap()).expect("Failed to convert delta to JSON");
+    assert_eq!(delta["step"].as_u64(), Some(1));
+
+    let changed = delta["changed"].as_object().expect("Changed not an object");
+    assert!(changed.contains_key("pool-1"));
+    assert!(
+        !changed.contains_key("stepper"),
+        "Steppers advance every step and are left out of deltas"
+    );
+}
+
+#[test]
+#[wasm_bindgen_test]
+fn simulation_load() {
+    let model = format!(
+        r#"{{ "processes": {}, "connections": [{{ "sourceID": "source-1", "targetID": "pool-1" }}] }}"#,
+        TEST_PROCESSES
+    );
+    let mut simulation = Simulation::load(&model).unwrap();
+    simulation.step().unwrap();
+    assert_eq!(simulation.current_step(), 1);
+
+    assert!(
+        Simulation::load(
+            r#"{ "processes": [], "connections": [{ "sourceID": "a", "targetID": "b" }] }"#
+        )
+        .is_err(),
+        "Expected connections to undefined processes to be rejected"
+    );
+}
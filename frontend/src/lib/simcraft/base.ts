@@ -85,15 +85,21 @@ export enum Action {
 	PullAny = 'PullAny',
 	PullAll = 'PullAll',
 	PushAny = 'PushAny',
-	PushAll = 'PushAll'
+	PushAll = 'PushAll',
+	PushLottery = 'PushLottery',
+	PushProportional = 'PushProportional',
+	PushIfRoom = 'PushIfRoom'
 }
 
 // Node-specific action constraints based on backend implementation
-export const SourceActions = [Action.PushAny] as const; // PushAll is unimplemented
+export const SourceActions = [Action.PushAny, Action.PushAll] as const;
 export const DrainActions = [Action.PullAny, Action.PullAll] as const;
 export const PoolActions = [
 	Action.PushAny,
 	Action.PushAll,
+	Action.PushLottery,
+	Action.PushProportional,
+	Action.PushIfRoom,
 	Action.PullAny,
 	Action.PullAll
 ] as const;